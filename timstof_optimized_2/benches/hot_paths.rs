@@ -0,0 +1,151 @@
+// Micro-benchmarks of the cache hot paths on deterministic synthetic data:
+//
+//   cargo bench --features bench --bench hot_paths -- --save-baseline release   # record a baseline
+//   BENCH_GATE_BASELINE=release cargo bench --features bench --bench hot_paths  # compare, fail on regressions
+//
+// With BENCH_GATE_BASELINE set, every benchmark's mean is compared with the named
+// baseline after the run and the process exits non-zero if any got slower by more than
+// BENCH_GATE_TOLERANCE (a fraction, default 0.10), so CI can gate releases on it.
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+
+use timstof_cache_ffi::bench::hot_paths::{self, IndexedTimsTOFData};
+use timstof_cache_ffi::bench::{synthetic_data, BenchCodec, SyntheticSpec};
+
+const MS1_POINTS: usize = 2_000_000;
+const POINTS_PER_SHARD: usize = 250_000;
+const CODECS: [BenchCodec; 3] = [BenchCodec::Raw, BenchCodec::Lz4, BenchCodec::Zstd { level: 3 }];
+
+fn ms1() -> IndexedTimsTOFData {
+    let spec = SyntheticSpec { ms1_points: MS1_POINTS, ms2_windows: 0, ..SyntheticSpec::default() };
+    synthetic_data(&spec).0
+}
+
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("timstof-hot-paths-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn shards(c: &mut Criterion) {
+    let data = ms1();
+    let dir = scratch_dir();
+    let mut group = c.benchmark_group("shards");
+    group.throughput(Throughput::Bytes(data.mz_values.len() as u64 * 24));
+    for codec in CODECS {
+        let path = dir.join(format!("{}.cache", codec.label()));
+        group.bench_with_input(BenchmarkId::new("split_into_shards", codec.label()), &codec, |b, &codec| {
+            b.iter(|| hot_paths::split_into_shards(&path, &data, POINTS_PER_SHARD, codec).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("merge_shards", codec.label()), &path, |b, path| {
+            b.iter(|| hot_paths::merge_shards(path).unwrap())
+        });
+    }
+    group.finish();
+
+    // Two halves interleaved over the whole m/z range, as after an incremental update
+    let halves: Vec<IndexedTimsTOFData> = (0..2).map(|half| pick(&data, |i| i % 2 == half)).collect();
+    c.bench_function("merge_by_mz/overlapping", |b| {
+        b.iter(|| hot_paths::merge_by_mz(black_box(halves.clone())))
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn codecs(c: &mut Criterion) {
+    let data = ms1();
+    let mut group = c.benchmark_group("codec");
+    for column in ["mz_values", "intensity_values"] {
+        let raw = hot_paths::column_bytes(&data, column);
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        for codec in CODECS.into_iter().filter(|codec| *codec != BenchCodec::Raw) {
+            let id = format!("{}/{}", column, codec.label());
+            group.bench_with_input(BenchmarkId::new("compress", &id), &raw, |b, raw| {
+                b.iter(|| hot_paths::compress(codec, raw.clone()).unwrap())
+            });
+            let stored = hot_paths::compress(codec, raw.clone()).unwrap();
+            group.bench_with_input(BenchmarkId::new("decompress", &id), &stored, |b, stored| {
+                b.iter(|| hot_paths::decompress(codec, stored.clone(), raw.len()).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn query_pruning(c: &mut Criterion) {
+    let data = ms1();
+    let dir = scratch_dir();
+    let path = dir.join("query.cache");
+    hot_paths::split_into_shards(&path, &data, POINTS_PER_SHARD, BenchCodec::Raw).unwrap();
+    let (first, last) = (data.mz_values[0], data.mz_values[data.mz_values.len() - 1]);
+    let middle = (first + last) / 2.0;
+    let mut group = c.benchmark_group("query_pruning");
+    for (name, range) in [("narrow", (middle, middle + 10.0)), ("full", (first, last))] {
+        group.bench_with_input(BenchmarkId::new("mz_range", name), &range, |b, &(mz_min, mz_max)| {
+            b.iter(|| hot_paths::query_mz_range(&path, mz_min, mz_max).unwrap())
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn pick(data: &IndexedTimsTOFData, keep: impl Fn(usize) -> bool) -> IndexedTimsTOFData {
+    let rows: Vec<usize> = (0..data.mz_values.len()).filter(|&i| keep(i)).collect();
+    IndexedTimsTOFData {
+        rt_values_min: rows.iter().map(|&i| data.rt_values_min[i]).collect(),
+        mobility_values: rows.iter().map(|&i| data.mobility_values[i]).collect(),
+        mz_values: rows.iter().map(|&i| data.mz_values[i]).collect(),
+        intensity_values: rows.iter().map(|&i| data.intensity_values[i]).collect(),
+        frame_indices: rows.iter().map(|&i| data.frame_indices[i]).collect(),
+        scan_indices: rows.iter().map(|&i| data.scan_indices[i]).collect(),
+    }
+}
+
+criterion_group! {
+    name = hot_paths;
+    config = Criterion::default().sample_size(10).measurement_time(Duration::from_secs(5));
+    targets = shards, codecs, query_pruning
+}
+
+// Benchmarks whose mean got slower than the baseline's by more than `tolerance`
+fn regressions(dir: &Path, baseline: &str, tolerance: f64, found: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let mean = |run: &str| -> Option<f64> {
+            let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path.join(run).join("estimates.json")).ok()?).ok()?;
+            json["mean"]["point_estimate"].as_f64()
+        };
+        if let (Some(new), Some(old)) = (mean("new"), mean(baseline)) {
+            if new > old * (1.0 + tolerance) {
+                found.push(format!("{}: {:.3} ms → {:.3} ms (+{:.0}%)",
+                                   path.strip_prefix(dir).unwrap_or(&path).display(), old / 1e6, new / 1e6, (new / old - 1.0) * 100.0));
+            }
+        }
+        regressions(&path, baseline, tolerance, found);
+    }
+}
+
+fn main() {
+    hot_paths();
+    Criterion::default().configure_from_args().final_summary();
+
+    let Ok(baseline) = std::env::var("BENCH_GATE_BASELINE") else { return };
+    let tolerance = std::env::var("BENCH_GATE_TOLERANCE").ok().and_then(|t| t.parse().ok()).unwrap_or(0.10);
+    let criterion_dir = std::env::var_os("CRITERION_HOME").map(PathBuf::from).unwrap_or_else(|| {
+        std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("target")).join("criterion")
+    });
+    let mut found = Vec::new();
+    regressions(&criterion_dir, &baseline, tolerance, &mut found);
+    if !found.is_empty() {
+        eprintln!("✗ {} benchmark(s) regressed by more than {:.0}% against baseline {}:", found.len(), tolerance * 100.0, baseline);
+        for regression in &found {
+            eprintln!("   {}", regression);
+        }
+        std::process::exit(1);
+    }
+    println!("✅ No regressions beyond {:.0}% against baseline {}", tolerance * 100.0, baseline);
+}
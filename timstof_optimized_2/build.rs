@@ -0,0 +1,88 @@
+// Passes the git commit the crate is built from to the provenance record (src/provenance.rs),
+// and regenerates the C header for the cabi module on request. include/timstof_cache.h is
+// committed: builds don't rewrite files in the source tree unless asked to with
+// TIMSTOF_CACHE_REGENERATE_HEADER=1, after a change to src/cabi.rs.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=TIMSTOF_CACHE_REGENERATE_HEADER");
+    if std::env::var_os("TIMSTOF_CACHE_REGENERATE_HEADER").is_some() {
+        println!("cargo:rerun-if-changed=src/cabi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        generate_header(&crate_dir);
+    }
+    git_commit(&crate_dir);
+}
+
+fn generate_header(crate_dir: &Path) {
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("invalid cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/cabi.rs"))
+        .generate()
+        .expect("failed to generate C bindings for src/cabi.rs")
+        .write_to_file(crate_dir.join("include/timstof_cache.h"));
+}
+
+// TIMSTOF_CACHE_GIT_COMMIT as set by the caller (release builds from a source tarball), or
+// else the commit HEAD points to, read from the repository's files rather than by running
+// git; nothing outside a checkout. Rebuilds follow commits and checkouts only.
+fn git_commit(crate_dir: &Path) {
+    println!("cargo:rerun-if-env-changed=TIMSTOF_CACHE_GIT_COMMIT");
+    if let Ok(commit) = std::env::var("TIMSTOF_CACHE_GIT_COMMIT") {
+        println!("cargo:rustc-env=TIMSTOF_CACHE_GIT_COMMIT={}", commit);
+        return;
+    }
+    let Some(git_dir) = crate_dir.ancestors().find_map(|dir| git_dir(&dir.join(".git"))) else { return };
+    let head_path = git_dir.join("HEAD");
+    println!("cargo:rerun-if-changed={}", head_path.display());
+    let Ok(head) = fs::read_to_string(&head_path) else { return };
+    let commit = match head.trim().strip_prefix("ref: ") {
+        Some(reference) => {
+            // Refs are shared between worktrees, in the main .git directory
+            let common_dir = fs::read_to_string(git_dir.join("commondir"))
+                .map(|common| git_dir.join(common.trim()))
+                .unwrap_or_else(|_| git_dir.clone());
+            let ref_path = common_dir.join(reference);
+            let packed_refs = common_dir.join("packed-refs");
+            // Only existing paths: cargo reruns on every build for a missing one. Watching the
+            // ref's directory also catches a commit writing the ref again after a gc packed it.
+            for path in [ref_path.parent(), Some(packed_refs.as_path())].into_iter().flatten().filter(|path| path.exists()) {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+            match fs::read_to_string(&ref_path) {
+                Ok(commit) => commit.trim().to_string(),
+                Err(_) => match packed_ref(&packed_refs, reference) {
+                    Some(commit) => commit,
+                    None => return, // No commits yet
+                },
+            }
+        }
+        None => head.trim().to_string(), // Detached HEAD
+    };
+    println!("cargo:rustc-env=TIMSTOF_CACHE_GIT_COMMIT={}", commit);
+}
+
+// A .git directory, or the directory a worktree's .git file points to
+fn git_dir(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+    let pointer = fs::read_to_string(dot_git).ok()?;
+    let target = PathBuf::from(pointer.trim().strip_prefix("gitdir: ")?);
+    Some(dot_git.parent()?.join(target))
+}
+
+// "<commit> <ref>" lines, after a "#" header; "^" lines peel annotated tags
+fn packed_ref(packed_refs: &Path, reference: &str) -> Option<String> {
+    fs::read_to_string(packed_refs).ok()?
+        .lines()
+        .find_map(|line| {
+            let (commit, name) = line.split_once(' ')?;
+            (name == reference).then(|| commit.to_string())
+        })
+}
@@ -0,0 +1,103 @@
+#ifndef TIMSTOF_CACHE_H
+#define TIMSTOF_CACHE_H
+
+/* Generated by cbindgen from src/cabi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct Option_CacheLogCallback Option_CacheLogCallback;
+
+/*
+ Opaque handle for one cached source (.d folder or alias).
+ */
+typedef struct TimstofCache TimstofCache;
+
+/*
+ Points returned by cache_load_range (MS1) or cache_load_ms2_window, in m/z order. All arrays have `len` entries
+ and stay valid until the range is passed to cache_free_range.
+ */
+typedef struct CacheRange {
+  size_t len;
+  float *rt;
+  float *mobility;
+  float *mz;
+  uint32_t *intensity;
+  uint32_t *frame;
+  uint32_t *scan;
+} CacheRange;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Message of the last failed call on this thread, or NULL. Owned by the library and
+ valid until the next failing call on the same thread.
+ */
+const char *cache_last_error(void);
+
+/*
+ Pass the library's progress and warning messages to `callback`, from whichever thread
+ logs them; they are dropped until a callback is set, and again after one is cleared
+ with NULL.
+ */
+void cache_set_log_callback(struct Option_CacheLogCallback callback);
+
+/*
+ Open the cache of a source given as a .d path or a registered alias (UTF-8; on Unix
+ any bytes, as paths there need not be UTF-8).
+ Returns NULL if the path is invalid or the source has no valid cache.
+ The cache is configured by the TIMSTOF_CACHE_* environment variables, keys included.
+
+ # Safety
+ `source_path` must be NULL or a valid NUL-terminated string.
+ */
+struct TimstofCache *cache_open(const char *source_path);
+
+/*
+ Load the MS1 points with m/z in [mz_min, mz_max]. Only the matching rows are read
+ from disk. Returns NULL on failure; an empty range is not a failure.
+
+ # Safety
+ `cache` must be a handle from cache_open that has not been freed.
+ */
+struct CacheRange *cache_load_range(const struct TimstofCache *cache, float mz_min, float mz_max);
+
+/*
+ Load the MS2 window with the given isolation range (matched to 0.001 Th), reading only
+ that window. Returns NULL on failure or if the cache has no such window.
+
+ # Safety
+ `cache` must be a handle from cache_open that has not been freed.
+ */
+struct CacheRange *cache_load_ms2_window(const struct TimstofCache *cache,
+                                         float isolation_low,
+                                         float isolation_high);
+
+/*
+ Release a range from cache_load_range or cache_load_ms2_window. NULL is ignored.
+
+ # Safety
+ `range` must be NULL or a range from cache_load_range or cache_load_ms2_window that
+ has not been freed.
+ */
+void cache_free_range(struct CacheRange *range);
+
+/*
+ Close a handle from cache_open. NULL is ignored. Ranges loaded through the handle
+ stay valid and must still be freed with cache_free_range.
+
+ # Safety
+ `cache` must be NULL or a handle from cache_open that has not been freed.
+ */
+void cache_free(struct TimstofCache *cache);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* TIMSTOF_CACHE_H */
@@ -0,0 +1,68 @@
+// File: src/aliases.rs
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::fdlimit;
+
+// Human-friendly names for source folders (e.g. "plasma_rep3" → /data/.../run_4382.d),
+// persisted in the cache dir so every entry point can accept either form
+pub struct AliasRegistry {
+    path: PathBuf,
+    aliases: BTreeMap<String, PathBuf>,
+}
+
+impl AliasRegistry {
+    const FILE_NAME: &'static str = "aliases.json";
+
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(Self::FILE_NAME);
+        let aliases = fdlimit::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, aliases }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(&self.aliases)
+            .map_err(io::Error::other)?;
+        // Write-then-rename so concurrent readers never see a truncated registry
+        let partial = self.path.with_extension("partial");
+        fdlimit::write(&partial, text)?;
+        fs::rename(partial, &self.path)
+    }
+
+    // Returns the path the alias pointed to before, if it was already registered
+    pub fn register(&mut self, alias: &str, source_path: &Path) -> io::Result<Option<PathBuf>> {
+        if alias.is_empty() || alias.contains(['/', '\\']) || alias.ends_with(".d") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid alias {:?}: must be a plain name, not a path or .d folder", alias),
+            ));
+        }
+        // Store absolute paths so the alias works from any working directory
+        let source_path = fs::canonicalize(source_path).unwrap_or_else(|_| source_path.to_path_buf());
+        Ok(self.aliases.insert(alias.to_string(), source_path))
+    }
+
+    pub fn remove(&mut self, alias: &str) -> Option<PathBuf> {
+        self.aliases.remove(alias)
+    }
+
+    pub fn get(&self, alias: &str) -> Option<&Path> {
+        self.aliases.get(alias).map(|p| p.as_path())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+        self.aliases.iter().map(|(alias, path)| (alias.as_str(), path.as_path()))
+    }
+
+    // A registered alias resolves to its source; anything else is taken as a path
+    pub fn resolve(&self, alias_or_path: &Path) -> PathBuf {
+        alias_or_path.to_str()
+            .and_then(|name| self.get(name))
+            .unwrap_or(alias_or_path)
+            .to_path_buf()
+    }
+}
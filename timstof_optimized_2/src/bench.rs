@@ -0,0 +1,419 @@
+// File: src/bench.rs
+//
+// Benchmark matrix for picking a cache configuration per machine. One dataset (synthetic
+// with realistic distributions, or decoded from a real cache) is saved and loaded under
+// every combination of backend, codec and thread count, each in its own scratch cache
+// directory, and the timings and sizes are reported as a table and optionally as CSV/JSON.
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use serde::Serialize;
+
+use crate::cache::{CacheConfig, CacheFormat, CacheManager};
+use crate::compression::CompressionType;
+use crate::hot::LoadedData;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+use crate::telemetry;
+
+// Size of a synthetic dataset
+#[derive(Debug, Clone)]
+pub struct SyntheticSpec {
+    pub ms1_points: usize,
+    pub ms2_windows: usize,
+    pub ms2_points_per_window: usize,
+    pub seed: u64,
+}
+
+impl Default for SyntheticSpec {
+    fn default() -> Self {
+        Self { ms1_points: 5_000_000, ms2_windows: 32, ms2_points_per_window: 250_000, seed: 42 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BenchCodec {
+    Raw,
+    Lz4,
+    Zstd { level: i32 },
+}
+
+impl BenchCodec {
+    // "raw", "lz4", "zstd" or "zstd-<level>"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "raw" | "none" => Ok(BenchCodec::Raw),
+            "lz4" => Ok(BenchCodec::Lz4),
+            "zstd" => Ok(BenchCodec::Zstd { level: 3 }),
+            _ => name.strip_prefix("zstd-")
+                .and_then(|level| level.parse().ok())
+                .filter(|level| (1..=22).contains(level))
+                .map(|level| BenchCodec::Zstd { level })
+                .ok_or_else(|| format!("unknown codec {} (raw, lz4, zstd or zstd-<1..22>)", name)),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            BenchCodec::Raw => "raw".to_string(),
+            BenchCodec::Lz4 => "lz4".to_string(),
+            BenchCodec::Zstd { level } => format!("zstd-{}", level),
+        }
+    }
+}
+
+pub fn format_label(format: &CacheFormat) -> &'static str {
+    match format {
+        CacheFormat::Columnar => "columnar",
+        CacheFormat::SingleFile => "single-file",
+        CacheFormat::Parquet { .. } => "parquet",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchMatrix {
+    pub formats: Vec<CacheFormat>,
+    pub codecs: Vec<BenchCodec>,
+    pub threads: Vec<usize>,
+    pub iterations: usize, // Full loads per cell; the fastest and the mean are reported
+    pub mz_width: f32,     // Width of the MS1 range queries
+}
+
+impl Default for BenchMatrix {
+    fn default() -> Self {
+        let cores = num_cpus::get();
+        Self {
+            formats: vec![CacheFormat::Columnar, CacheFormat::SingleFile, CacheFormat::Parquet { row_group_size: 1024 * 1024 }],
+            codecs: vec![BenchCodec::Raw, BenchCodec::Lz4, BenchCodec::Zstd { level: 3 }],
+            threads: if cores > 1 { vec![1, cores] } else { vec![1] },
+            iterations: 3,
+            mz_width: 10.0,
+        }
+    }
+}
+
+// One cell of the matrix
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchRow {
+    pub format: String,
+    pub codec: String,
+    pub threads: usize,
+    pub points: u64,
+    pub bytes: u64,
+    pub save_secs: f64,
+    pub load_best_secs: f64,
+    pub load_mean_secs: f64,
+    pub range_query_mean_secs: f64,
+    pub load_points_per_sec: f64, // From the best load
+}
+
+// Save and load the dataset under every combination in `matrix`. Each cell gets a scratch
+// cache under `work_dir`, removed afterwards.
+pub fn run_matrix(
+    work_dir: &Path,
+    ms1_indexed: &IndexedTimsTOFData,
+    ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    matrix: &BenchMatrix,
+) -> Result<Vec<BenchRow>, Box<dyn std::error::Error>> {
+    let points = (ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>()) as u64;
+    let mz_bounds = (
+        ms1_indexed.mz_values.first().copied().unwrap_or(0.0),
+        ms1_indexed.mz_values.last().copied().unwrap_or(0.0),
+    );
+    const RANGE_QUERIES: usize = 10;
+    let mut rows = Vec::new();
+    for format in &matrix.formats {
+        for codec in &matrix.codecs {
+            for &threads in &matrix.threads {
+                let label = format!("{}-{}-{}t", format_label(format), codec.label(), threads);
+                telemetry::info(&format!("🔬 Benchmark cell {}", label));
+                let cell_dir = work_dir.join(&label);
+                let source_path = cell_dir.join("bench.d");
+                fs::create_dir_all(&source_path)?;
+                let config = CacheConfig {
+                    format: *format,
+                    auto_compression: false,
+                    enable_compression: *codec != BenchCodec::Raw,
+                    compression: match *codec {
+                        BenchCodec::Zstd { level } => CompressionType::zstd(level),
+                        _ => CompressionType::Lz4,
+                    },
+                    content_addressed: false,
+                    hot_cache_entries: 0,
+                    parallel_threads: Some(threads.max(1)),
+                    ..CacheConfig::default()
+                };
+                let cache_dir = cell_dir.join("cache");
+                let manager = CacheManager::with_cache_dir(&cache_dir, config);
+
+                let start = Instant::now();
+                manager.save_indexed_data(&source_path, ms1_indexed, ms2_indexed_pairs)?;
+                let save_secs = start.elapsed().as_secs_f64();
+                let bytes = fs::read_dir(&cache_dir)?
+                    .flatten()
+                    .filter(|entry| entry.file_name().to_string_lossy().starts_with("bench.d-"))
+                    .filter_map(|entry| entry.metadata().ok())
+                    .map(|metadata| metadata.len())
+                    .sum();
+
+                let mut load_times = Vec::new();
+                for _ in 0..matrix.iterations.max(1) {
+                    let start = Instant::now();
+                    manager.load_indexed_data(&source_path)?;
+                    load_times.push(start.elapsed().as_secs_f64());
+                }
+                let mut query_secs = 0.0;
+                for i in 0..RANGE_QUERIES {
+                    let mz_min = mz_bounds.0 + (mz_bounds.1 - mz_bounds.0) * i as f32 / RANGE_QUERIES as f32;
+                    let start = Instant::now();
+                    manager.load_ms1_mz_range(&source_path, mz_min, mz_min + matrix.mz_width)?;
+                    query_secs += start.elapsed().as_secs_f64();
+                }
+                drop(manager);
+                fs::remove_dir_all(&cell_dir)?;
+
+                let load_best_secs = load_times.iter().copied().fold(f64::INFINITY, f64::min);
+                rows.push(BenchRow {
+                    format: format_label(format).to_string(),
+                    codec: codec.label(),
+                    threads,
+                    points,
+                    bytes,
+                    save_secs,
+                    load_best_secs,
+                    load_mean_secs: load_times.iter().sum::<f64>() / load_times.len() as f64,
+                    range_query_mean_secs: query_secs / RANGE_QUERIES as f64,
+                    load_points_per_sec: points as f64 / load_best_secs.max(1e-9),
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
+
+pub fn print_table(rows: &[BenchRow]) {
+    telemetry::info(&format!("📊 Benchmark matrix ({} points):", rows.first().map(|row| row.points).unwrap_or(0)));
+    telemetry::info(&format!("   {:<12} {:<8} {:>7} {:>10} {:>9} {:>9} {:>9} {:>11} {:>9}",
+             "format", "codec", "threads", "size MB", "save s", "load s", "mean s", "query ms", "M pts/s"));
+    for row in rows {
+        telemetry::info(&format!("   {:<12} {:<8} {:>7} {:>10.1} {:>9.3} {:>9.3} {:>9.3} {:>11.2} {:>9.1}",
+                 row.format, row.codec, row.threads, row.bytes as f64 / 1024.0 / 1024.0, row.save_secs,
+                 row.load_best_secs, row.load_mean_secs, row.range_query_mean_secs * 1000.0, row.load_points_per_sec / 1e6));
+    }
+}
+
+// JSON if the file name ends in .json, CSV otherwise
+pub fn write_report(rows: &[BenchRow], path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        fs::write(path, serde_json::to_string_pretty(rows)?)?;
+    } else {
+        let mut writer = csv::Writer::from_path(path)?;
+        for row in rows {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+    }
+    telemetry::info(&format!("Benchmark report written to {}", path.display()));
+    Ok(())
+}
+
+// Seconds per TIMS cycle (one MS1 frame plus its PASEF frames) and gradient length
+const CYCLE_MIN: f32 = 1.1 / 60.0;
+const GRADIENT_MIN: f32 = 60.0;
+const MIN_MOBILITY: f32 = 0.6;
+const MAX_MOBILITY: f32 = 1.6;
+const SCANS: f32 = 927.0;
+
+// A dataset shaped like a DIA-PASEF run: peptide features (isotope envelopes eluting over
+// a Gaussian peak, with charge-dependent ion mobility and log-normal intensities) on top
+// of uniform noise, frames and scans quantized as the instrument does. MS1 covers
+// 300-1500 m/z; MS2 windows tile 400-1200 and hold fragments over 100-1700.
+pub fn synthetic_data(spec: &SyntheticSpec) -> LoadedData {
+    let mut rng = SplitMix::new(spec.seed);
+    let ms1_indexed = synthetic_spectrum(&mut rng, spec.ms1_points, (300.0, 1500.0));
+    let windows = spec.ms2_windows.max(1);
+    let width = 800.0 / windows as f32;
+    let ms2_indexed_pairs = (0..spec.ms2_windows)
+        .map(|i| {
+            let low = 400.0 + width * i as f32;
+            (IsolationWindow::new(low, low + width, i), synthetic_spectrum(&mut rng, spec.ms2_points_per_window, (100.0, 1700.0)))
+        })
+        .collect();
+    (ms1_indexed, ms2_indexed_pairs)
+}
+
+fn synthetic_spectrum(rng: &mut SplitMix, points: usize, (mz_low, mz_high): (f32, f32)) -> IndexedTimsTOFData {
+    let mut data = IndexedTimsTOFData::new();
+    let push = |data: &mut IndexedTimsTOFData, mz: f32, rt: f32, mobility: f32, intensity: f32| {
+        let frame = (rt.clamp(0.0, GRADIENT_MIN) / CYCLE_MIN) as u32;
+        let mobility = mobility.clamp(MIN_MOBILITY, MAX_MOBILITY);
+        data.rt_values_min.push(frame as f32 * CYCLE_MIN);
+        data.mobility_values.push(mobility);
+        data.mz_values.push(mz.clamp(mz_low, mz_high));
+        data.intensity_values.push(intensity.max(1.0) as u32);
+        data.frame_indices.push(frame);
+        data.scan_indices.push(((MAX_MOBILITY - mobility) / (MAX_MOBILITY - MIN_MOBILITY) * SCANS) as u32);
+    };
+
+    // About 70% of the points belong to features, the rest is noise
+    let feature_points = points * 7 / 10;
+    while data.mz_values.len() < feature_points {
+        // Peptide masses cluster mid-range: average of two uniforms
+        let mono = mz_low + (mz_high - mz_low) * (rng.uniform() + rng.uniform()) / 2.0;
+        let charge = match rng.uniform() {
+            u if u < 0.15 => 1.0,
+            u if u < 0.75 => 2.0,
+            u if u < 0.95 => 3.0,
+            _ => 4.0,
+        };
+        let apex = rng.uniform() * GRADIENT_MIN;
+        let elution_width = 0.05 + rng.uniform() * 0.15;
+        let mobility = 0.45 + mono * 0.0006 * (1.0 + 0.2 * (charge - 2.0)) + rng.normal() * 0.03;
+        let height = (7.0 + 1.2 * rng.normal()).exp();
+        let peaks = (20 + (rng.uniform() * 60.0) as usize).min(feature_points - data.mz_values.len());
+        for _ in 0..peaks {
+            let isotope = match rng.uniform() {
+                u if u < 0.45 => 0.0,
+                u if u < 0.80 => 1.0,
+                u if u < 0.95 => 2.0,
+                _ => 3.0,
+            };
+            let offset = rng.normal();
+            let mz = mono + isotope * 1.003_355 / charge + rng.normal() * mono * 5e-6;
+            let intensity = height * (-0.5 * offset * offset).exp() * (-0.6 * isotope).exp();
+            push(&mut data, mz, apex + offset * elution_width, mobility + rng.normal() * 0.005, intensity);
+        }
+    }
+    while data.mz_values.len() < points {
+        let mz = mz_low + (mz_high - mz_low) * rng.uniform();
+        let mobility = MIN_MOBILITY + (MAX_MOBILITY - MIN_MOBILITY) * rng.uniform();
+        push(&mut data, mz, rng.uniform() * GRADIENT_MIN, mobility, (4.0 + 0.5 * rng.normal()).exp());
+    }
+
+    // Indexed data is sorted by m/z
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.sort_unstable_by(|&a, &b| data.mz_values[a].total_cmp(&data.mz_values[b]));
+    IndexedTimsTOFData {
+        rt_values_min: order.iter().map(|&i| data.rt_values_min[i]).collect(),
+        mobility_values: order.iter().map(|&i| data.mobility_values[i]).collect(),
+        mz_values: order.iter().map(|&i| data.mz_values[i]).collect(),
+        intensity_values: order.iter().map(|&i| data.intensity_values[i]).collect(),
+        frame_indices: order.iter().map(|&i| data.frame_indices[i]).collect(),
+        scan_indices: order.iter().map(|&i| data.scan_indices[i]).collect(),
+    }
+}
+
+// Small deterministic generator, so a seed always gives the same dataset
+pub(crate) struct SplitMix(u64);
+
+impl SplitMix {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // In [0, 1)
+    pub(crate) fn uniform(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Standard normal (Box-Muller)
+    pub(crate) fn normal(&mut self) -> f32 {
+        let u = self.uniform().max(f32::MIN_POSITIVE);
+        let v = self.uniform();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos()
+    }
+}
+
+// Entry points into the save/load hot paths, for the criterion benches in
+// benches/hot_paths.rs (cargo bench --features bench). Not a stable API.
+#[cfg(feature = "bench")]
+pub mod hot_paths {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    use super::BenchCodec;
+    use crate::cache::ms1_segments;
+    use crate::columnar::{self, ContainerReader, ShardPlan, WriteOptions, INDEXED_COLUMNS};
+    use crate::durability::Durability;
+    use crate::precision::Precision;
+    use crate::compression::{self, ColumnCodec};
+    pub use crate::utils::IndexedTimsTOFData;
+
+    fn column_codec(codec: BenchCodec) -> ColumnCodec {
+        match codec {
+            BenchCodec::Raw => ColumnCodec::Raw,
+            BenchCodec::Lz4 => ColumnCodec::Lz4,
+            BenchCodec::Zstd { level } => ColumnCodec::zstd(level),
+        }
+    }
+
+    // Split MS1 into shards of `points_per_shard`, encode and write them as a container
+    pub fn split_into_shards(path: &Path, ms1_indexed: &IndexedTimsTOFData, points_per_shard: usize, codec: BenchCodec) -> io::Result<usize> {
+        let shard_plan = ShardPlan::new(ms1_indexed.mz_values.len(), None, Some(points_per_shard), 1);
+        let options = WriteOptions {
+            column_order: INDEXED_COLUMNS.iter().map(|(name, _)| *name).collect(),
+            codecs: compression::uniform_codecs(column_codec(codec)),
+            buffer_size: 32 * 1024 * 1024,
+            encryption_key: None,
+            cancel: None,
+            lossy: None,
+            precision: Precision::F32,
+            block_rows: None,
+            byte_shuffle: false,
+            durability: Durability::None,
+            deterministic: false,
+        };
+        let header = columnar::write_container(path, &ms1_segments(ms1_indexed, &shard_plan), &options)?;
+        Ok(header.segments.len())
+    }
+
+    // Decode every MS1 shard of a container into one m/z-sorted dataset
+    pub fn merge_shards(path: &Path) -> io::Result<IndexedTimsTOFData> {
+        let file = File::open(path)?;
+        let reader = ContainerReader::open(columnar::file_reader(&file), file.metadata()?.len(), None)?;
+        let shards: Vec<usize> = (0..reader.header.segments.len()).collect();
+        reader.merged_segments(&shards, None)
+    }
+
+    // k-way merge of parts that overlap in m/z (incrementally updated caches)
+    pub fn merge_by_mz(parts: Vec<IndexedTimsTOFData>) -> IndexedTimsTOFData {
+        columnar::merge_by_mz(parts)
+    }
+
+    // MS1 points in [mz_min, mz_max], reading only the shards the range overlaps; also
+    // returns how many shards were read
+    pub fn query_mz_range(path: &Path, mz_min: f32, mz_max: f32) -> io::Result<(IndexedTimsTOFData, usize)> {
+        let file = File::open(path)?;
+        let reader = ContainerReader::open(columnar::file_reader(&file), file.metadata()?.len(), None)?;
+        reader.mz_range(mz_min, mz_max)
+    }
+
+    // Stored bytes of one column, as compress() gets them
+    pub fn column_bytes(data: &IndexedTimsTOFData, name: &str) -> Vec<u8> {
+        match name {
+            "rt_values_min" => data.rt_values_min.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            "mobility_values" => data.mobility_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            "mz_values" => data.mz_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            "intensity_values" => data.intensity_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            "frame_indices" => data.frame_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            _ => data.scan_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        }
+    }
+
+    pub fn compress(codec: BenchCodec, raw: Vec<u8>) -> io::Result<Vec<u8>> {
+        column_codec(codec).compress(raw)
+    }
+
+    pub fn decompress(codec: BenchCodec, stored: Vec<u8>, raw_len: usize) -> io::Result<Vec<u8>> {
+        column_codec(codec).decompress(stored, raw_len)
+    }
+}
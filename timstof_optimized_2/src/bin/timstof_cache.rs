@@ -0,0 +1,728 @@
+// File: src/bin/timstof_cache.rs
+// Cache management front-end over the cache library (src/lib.rs)
+use timstof_cache_ffi::cache::{CacheManager, CacheConfig, CacheFormat, GcOptions};
+use timstof_cache_ffi::daemon::{CacheDaemon, DaemonConfig};
+use timstof_cache_ffi::scrub::ScrubBudget;
+use timstof_cache_ffi::diff::DataComparison;
+use timstof_cache_ffi::health::HealthStatus;
+use timstof_cache_ffi::parquet_backend::RegionFilter;
+use timstof_cache_ffi::mzml::{ArrayCompression, MzmlOptions, SpectrumGrouping};
+use timstof_cache_ffi::compression::CompressionType;
+use timstof_cache_ffi::bench::{self, BenchCodec, BenchMatrix, SyntheticSpec};
+use timstof_cache_ffi::telemetry;
+use timstof_cache_ffi::utils::{read_timstof_data, build_indexed_data};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{error::Error, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+/// Manage the timsTOF data cache: build, inspect, verify, export and clean up caches.
+/// Wherever a source is expected, a registered alias works too.
+#[derive(Parser)]
+#[command(name = "timstof-cache", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read a .d folder and write its cache
+    Build(BuildArgs),
+    /// List cached sources, or show the metadata of one source
+    #[command(alias = "status")]
+    Info {
+        source: Option<PathBuf>,
+    },
+    /// Check one source's signature and every column checksum
+    Verify {
+        source: PathBuf,
+    },
+    /// Check that MS1 shards are m/z-sorted and non-overlapping and MS2 windows match the metadata
+    Validate {
+        source: PathBuf,
+    },
+    /// Check the cache directory: writable, free space, manifest lock and manifest; exits
+    /// with an error if it isn't ready
+    Health,
+    /// Compare the caches of two sources (counts, coverage, checksums, settings)
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+    },
+    /// Cache the union of several cached sources (e.g. fractions) under a new alias
+    Merge {
+        output_key: String,
+        #[arg(required = true, num_args = 2..)]
+        sources: Vec<PathBuf>,
+    },
+    /// Pack a source's cache into one .tar.zst file, e.g. to send it to another machine
+    Bundle {
+        source: PathBuf,
+        out: PathBuf,
+    },
+    /// Unpack a cache bundle into the cache directory
+    Import {
+        bundle: PathBuf,
+        /// Also register this alias for the imported source
+        #[arg(long)]
+        alias: Option<String>,
+    },
+    /// Move one source's cache to the trash, or delete the whole cache directory
+    Clear {
+        /// Only clear this source (restorable with `restore`)
+        #[arg(long)]
+        source: Option<PathBuf>,
+    },
+    /// Duplicate a source's cache into another cache directory (reflink, hard link or copy)
+    Clone {
+        source: PathBuf,
+        cache_dir: PathBuf,
+    },
+    /// Append frames acquired since the cache was built (acquisitions still in progress)
+    Update {
+        source: PathBuf,
+    },
+    /// Regenerate missing or corrupt shards of a source's cache from the raw data
+    Repair {
+        source: PathBuf,
+    },
+    /// Repack a source's cache into a single zstd-19 archive for long-term storage
+    Compact {
+        source: PathBuf,
+    },
+    /// Turn an archived cache back into regular (fast) cache files
+    Unpack {
+        source: PathBuf,
+    },
+    /// Restore the most recently trashed cache of a source
+    Restore {
+        source: PathBuf,
+    },
+    /// List trashed caches, or delete them permanently
+    Trash {
+        #[arg(long)]
+        empty: bool,
+    },
+    /// Write the cached data in an interchange format
+    Export {
+        source: PathBuf,
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Arrow)]
+        format: ExportFormat,
+        /// mzML binary array encoding
+        #[arg(long, value_enum, default_value_t = MzmlEncoding::Zlib)]
+        mzml_encoding: MzmlEncoding,
+        /// mzML: one spectrum per frame and TIMS scan instead of per frame
+        #[arg(long)]
+        per_scan: bool,
+        /// mzML: leave out the ion mobility array
+        #[arg(long)]
+        no_mobility: bool,
+    },
+    /// Time full loads and MS1 m/z range queries of a cached source, or the raw
+    /// save/load speed of the cache directory without a source
+    Bench {
+        source: Option<PathBuf>,
+        #[arg(long, default_value_t = 3)]
+        iterations: usize,
+        /// Width of the MS1 range queries in m/z
+        #[arg(long, default_value_t = 10.0)]
+        mz_width: f32,
+        /// Save and load the source's data (or synthetic data) under every combination
+        /// of --formats, --codecs and --threads
+        #[arg(long)]
+        matrix: bool,
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "columnar,single-file,parquet")]
+        formats: Vec<BuildFormat>,
+        /// raw, lz4, zstd or zstd-<level>
+        #[arg(long, value_delimiter = ',', default_value = "raw,lz4,zstd-3", value_parser = BenchCodec::parse)]
+        codecs: Vec<BenchCodec>,
+        /// Thread counts (default: 1 and all cores)
+        #[arg(long, value_delimiter = ',')]
+        threads: Vec<usize>,
+        /// MS1 points of the synthetic dataset used without a source
+        #[arg(long, default_value_t = 5_000_000)]
+        synthetic_points: usize,
+        /// Write the matrix to this CSV file, or JSON if it ends in .json
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Load an MS1 region
+    Query {
+        source: PathBuf,
+        #[arg(long, num_args = 2, value_names = ["MIN", "MAX"])]
+        mz: Option<Vec<f32>>,
+        #[arg(long, num_args = 2, value_names = ["MIN", "MAX"])]
+        rt: Option<Vec<f32>>,
+        /// Print the read profile of the query
+        #[arg(long)]
+        explain: bool,
+    },
+    /// Per-dataset read amplification over all queries
+    Profile {
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Remove orphaned cache files (interrupted saves, old format versions)
+    Gc {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+        /// Leave files younger than this alone (saves in progress)
+        #[arg(long, default_value_t = 60)]
+        min_age_minutes: u64,
+    },
+    /// Verify the next slice of cache data, continuing where the last run stopped
+    Scrub {
+        #[arg(long, default_value_t = 1024.0)]
+        budget_mb: f64,
+        /// Limit verification reads to this many MB/s
+        #[arg(long)]
+        rate_mb: Option<f64>,
+    },
+    /// Register a short name for a source, or list aliases without arguments
+    Alias {
+        #[arg(requires = "source")]
+        name: Option<String>,
+        source: Option<PathBuf>,
+    },
+    /// Remove an alias
+    Unalias {
+        name: String,
+    },
+    /// Watch a folder and cache new .d acquisitions
+    Daemon {
+        #[arg(long)]
+        watch: PathBuf,
+        /// Poll interval in seconds
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+        /// Seconds a source must stay unchanged before it is cached
+        #[arg(long, default_value_t = 120)]
+        settle: u64,
+        /// Evict the oldest caches above this size
+        #[arg(long)]
+        quota_gb: Option<f64>,
+        /// Serve JSON status over HTTP, e.g. 127.0.0.1:8787
+        #[arg(long)]
+        status: Option<String>,
+        /// Threads per build (default: half the cores)
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Only poll, ignoring filesystem events (e.g. for network shares)
+        #[arg(long)]
+        no_notify: bool,
+        /// Build at normal instead of reduced CPU priority
+        #[arg(long)]
+        normal_priority: bool,
+    },
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    source: PathBuf,
+    /// Rebuild even if a valid cache exists
+    #[arg(long)]
+    force: bool,
+    #[arg(long, value_enum, default_value_t = BuildFormat::Columnar)]
+    format: BuildFormat,
+    /// Worker threads for reading and indexing (default: all cores)
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Target MS1 shard size in MB (default: auto-tuned)
+    #[arg(long)]
+    shard_mb: Option<u64>,
+    /// Target MS1 points per shard, overrides --shard-mb
+    #[arg(long)]
+    shard_points: Option<usize>,
+    /// Codec for the files that get compressed
+    #[arg(long, value_enum, default_value_t = BuildCompression::Lz4)]
+    compression: BuildCompression,
+    /// zstd level (1-22)
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(i32).range(1..=22))]
+    zstd_level: i32,
+    /// zstd long-distance matching (128 MB window), for archival caches
+    #[arg(long)]
+    zstd_long: bool,
+    /// zstd compression threads per column, for large unblocked shards (0: none)
+    #[arg(long, default_value_t = 0)]
+    zstd_workers: u32,
+    /// log2 of the zstd match window (default: the level's, 27 with --zstd-long)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(10..=30))]
+    zstd_window_log: Option<u32>,
+    /// Pick lz4, zstd or no compression per column by sampling (columnar only)
+    #[arg(long)]
+    adaptive_compression: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BuildCompression {
+    Lz4,
+    Zstd,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BuildFormat {
+    Columnar,
+    /// Columnar, with MS1 and MS2 in one file per source
+    SingleFile,
+    Parquet,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Arrow,
+    Parquet,
+    Mzml,
+    #[cfg(feature = "hdf5")]
+    Hdf5,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MzmlEncoding {
+    None,
+    Zlib,
+    Numpress,
+}
+
+fn cache_format(format: BuildFormat) -> CacheFormat {
+    match format {
+        BuildFormat::Columnar => CacheFormat::Columnar,
+        BuildFormat::SingleFile => CacheFormat::SingleFile,
+        BuildFormat::Parquet => CacheFormat::Parquet { row_group_size: 1024 * 1024 },
+    }
+}
+
+fn run_build(args: BuildArgs) -> Result<(), Box<dyn Error>> {
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()?;
+
+    let config = CacheConfig {
+        format: cache_format(args.format),
+        target_shard_bytes: args.shard_mb.map(|mb| mb * 1024 * 1024),
+        target_shard_points: args.shard_points,
+        compression: match args.compression {
+            BuildCompression::Lz4 => CompressionType::Lz4,
+            BuildCompression::Zstd => CompressionType::Zstd {
+                level: args.zstd_level,
+                long_mode: args.zstd_long,
+                workers: args.zstd_workers,
+                window_log: args.zstd_window_log,
+            },
+        },
+        adaptive_compression: args.adaptive_compression,
+        ..CacheConfig::from_env()?
+    };
+    let cache_manager = CacheManager::with_config(config).configure_for_threads(threads);
+    let source_path = cache_manager.resolve_source(&args.source);
+    if !args.force && cache_manager.is_cache_valid(&source_path) {
+        println!("{}: valid cache exists (use --force to rebuild)", source_path.display());
+        return Ok(());
+    }
+
+    let start_time = Instant::now();
+    println!("Building cache for {} with {} threads...", source_path.display(), threads);
+    let raw_data = read_timstof_data(&source_path)?;
+    let (ms1_indexed, ms2_indexed_pairs) = build_indexed_data(raw_data)?;
+    cache_manager.save_indexed_data(&source_path, &ms1_indexed, &ms2_indexed_pairs)?;
+    println!("✅ Cache built in {:.2}s", start_time.elapsed().as_secs_f32());
+    Ok(())
+}
+
+fn run_update(source: &Path) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    let source_path = cache_manager.resolve_source(source);
+    let start_time = Instant::now();
+    let raw_data = read_timstof_data(&source_path)?;
+    let (ms1_indexed, ms2_indexed_pairs) = build_indexed_data(raw_data)?;
+    cache_manager.update_cache(&source_path, &ms1_indexed, &ms2_indexed_pairs)?;
+    println!("✅ Cache updated in {:.2}s", start_time.elapsed().as_secs_f32());
+    Ok(())
+}
+
+fn run_info(source: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    if let Some(source) = source {
+        let source_path = cache_manager.resolve_source(source);
+        let state = if cache_manager.is_cache_valid(&source_path) { "valid cache" } else { "no valid cache" };
+        println!("{}: {}", source_path.display(), state);
+        if let Ok(metadata) = cache_manager.metadata(&source_path) {
+            // The shard table has a line per shard; `validate` checks it against the files
+            let shards = metadata.iter().filter(|(key, _)| key == "shard").count();
+            for (key, value) in metadata.iter().filter(|(key, _)| !["shard", "summary", "instrument", "window_scheme", "provenance"].contains(&key.as_str())) {
+                println!("   {}: {}", key, value);
+            }
+            if shards > 0 {
+                println!("   shard table: {} entries", shards);
+            }
+        }
+        if let Ok(Some(instrument)) = cache_manager.instrument(&source_path) {
+            let fields = [
+                ("instrument", &instrument.instrument), ("serial", &instrument.serial), ("acquired", &instrument.acquired),
+                ("method", &instrument.method), ("software", &instrument.software), ("sample", &instrument.sample),
+                ("operator", &instrument.operator),
+            ];
+            for (name, value) in fields.iter().filter_map(|(name, value)| Some((name, value.as_ref()?))) {
+                println!("   {}: {}", name, value);
+            }
+        }
+        if let Ok(Some(provenance)) = cache_manager.provenance(&source_path) {
+            println!("   written by: timstof_cache {}", provenance.describe());
+            if let Some(seconds) = provenance.wall_seconds {
+                println!("   write time: {:.2}s", seconds);
+            }
+        }
+        if let Ok(Some(scheme)) = cache_manager.window_scheme(&source_path) {
+            let overlapping = scheme.overlapping_pairs().len();
+            println!("   window scheme: {} windows per cycle, {} overlapping pairs", scheme.windows.len(), overlapping);
+        }
+        if let Ok(summary) = cache_manager.describe(&source_path) {
+            println!("   points: {} MS1, {} MS2 in {} windows", summary.ms1_points, summary.ms2_points, summary.windows.len());
+            for (name, range) in [("rt", summary.rt_range), ("m/z", summary.mz_range), ("mobility", summary.mobility_range)] {
+                if let Some((min, max)) = range {
+                    println!("   {} range: {:.4} - {:.4}", name, min, max);
+                }
+            }
+            if let (Some(p50), Some(p99)) = (summary.intensity_percentile(50.0), summary.intensity_percentile(99.0)) {
+                println!("   intensity: median ~{}, 99th percentile ~{}", p50, p99);
+            }
+        }
+        return Ok(());
+    }
+
+    let sources = cache_manager.cached_sources()?;
+    if sources.is_empty() {
+        println!("No cached sources in {}", cache_manager.cache_dir().display());
+    }
+    let total: u64 = sources.iter().map(|s| s.size_bytes).sum();
+    for source in &sources {
+        let cached_at: chrono::DateTime<chrono::Local> = source.cached_at.into();
+        println!("  {} - {:.2} MB, cached {}", source.source_name,
+                 source.size_bytes as f32 / 1024.0 / 1024.0, cached_at.format("%Y-%m-%d %H:%M"));
+    }
+    println!("{} source(s), {:.2} MB total", sources.len(), total as f32 / 1024.0 / 1024.0);
+    let orphans = cache_manager.orphaned_files()?;
+    if !orphans.is_empty() {
+        println!("{} orphaned cache file(s) not listed in the manifest", orphans.len());
+    }
+    Ok(())
+}
+
+fn run_verify(source: &Path) -> Result<(), Box<dyn Error>> {
+    let report = CacheManager::from_env()?.verify(source)?;
+    for problem in &report.corrupt {
+        eprintln!("✗ Corrupt cache data: {}", problem);
+    }
+    for file in &report.skipped {
+        println!("   {} has no checksums (legacy format), not verified", file);
+    }
+    if !report.corrupt.is_empty() {
+        return Err(format!("{} corrupt chunk(s) found", report.corrupt.len()).into());
+    }
+    println!("✅ Verified {:.2} MB in {} chunk(s)", report.bytes_verified as f32 / 1024.0 / 1024.0, report.units_verified);
+    Ok(())
+}
+
+fn run_validate(source: &Path) -> Result<(), Box<dyn Error>> {
+    let report = CacheManager::from_env()?.validate_layout(source)?;
+    for issue in &report.issues {
+        eprintln!("✗ Layout: {}", issue);
+    }
+    if !report.is_valid() {
+        return Err(format!("{} layout problem(s) found", report.issues.len()).into());
+    }
+    println!("✅ Layout valid: {} MS1 shard(s) in {} run(s), {} MS2 window(s), {} points",
+             report.ms1_shards, report.ms1_runs, report.ms2_windows, report.points);
+    Ok(())
+}
+
+fn run_health() -> Result<(), Box<dyn Error>> {
+    let report = CacheManager::from_env()?.health();
+    for check in &report.checks {
+        let mark = match check.status {
+            HealthStatus::Ok => "✅",
+            HealthStatus::Degraded => "⚠️ ",
+            HealthStatus::Failed => "✗",
+        };
+        println!("{} {}: {}", mark, check.name, check.detail);
+    }
+    if !report.is_ready() {
+        return Err(format!("cache directory {} is not ready", report.cache_dir.display()).into());
+    }
+    Ok(())
+}
+
+fn run_diff(a: &Path, b: &Path) -> Result<(), Box<dyn Error>> {
+    let diff = CacheManager::from_env()?.diff_caches(a, b)?;
+    let show = |what: &str, values: (String, String)| {
+        if values.0 != values.1 {
+            println!("   {}: {} -> {}", what, values.0, values.1);
+        }
+    };
+    show("MS1 points", (format!("{:?}", diff.ms1_points.0), format!("{:?}", diff.ms1_points.1)));
+    show("MS2 points", (format!("{:?}", diff.ms2_points.0), format!("{:?}", diff.ms2_points.1)));
+    show("rt range", (format!("{:?}", diff.rt_range.0), format!("{:?}", diff.rt_range.1)));
+    show("m/z range", (format!("{:?}", diff.mz_range.0), format!("{:?}", diff.mz_range.1)));
+    for window in &diff.windows {
+        show(&format!("window {:.3}-{:.3} points", window.isolation.0, window.isolation.1),
+             (format!("{:?}", window.points.0), format!("{:?}", window.points.1)));
+    }
+    for entry in &diff.metadata {
+        show(&entry.key, (entry.values.0.clone().unwrap_or_else(|| "-".to_string()), entry.values.1.clone().unwrap_or_else(|| "-".to_string())));
+    }
+    match &diff.data {
+        DataComparison::Identical => println!("   data: identical checksums"),
+        DataComparison::Different { segments } => println!("   data: {} segment(s) differ", segments),
+        DataComparison::NotComparable(reason) => println!("   data: not comparable ({})", reason),
+    }
+    if diff.is_identical() {
+        println!("✅ Identical caches");
+    } else if diff.same_data() {
+        println!("✅ Same data, different settings");
+    } else {
+        println!("✗ The data differs");
+    }
+    Ok(())
+}
+
+fn run_export(source: &Path, out: &Path, format: ExportFormat, options: MzmlOptions) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    match format {
+        ExportFormat::Arrow => cache_manager.export_arrow(source, out)?,
+        ExportFormat::Parquet => cache_manager.export_parquet(source, out)?,
+        ExportFormat::Mzml => cache_manager.export_mzml(source, out, &options)?,
+        #[cfg(feature = "hdf5")]
+        ExportFormat::Hdf5 => cache_manager.export_hdf5(source, out)?,
+    };
+    Ok(())
+}
+
+fn run_alias(name: Option<&str>, source: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    if let (Some(alias), Some(source)) = (name, source) {
+        return cache_manager.register_alias(alias, source);
+    }
+
+    let aliases = cache_manager.aliases();
+    if aliases.is_empty() {
+        println!("No aliases registered");
+    }
+    for (alias, path) in aliases {
+        let cached = if cache_manager.is_cache_valid(&path) { "cached" } else { "not cached" };
+        println!("  {} → {} ({})", alias, path.display(), cached);
+    }
+    Ok(())
+}
+
+fn run_trash(empty: bool) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    if empty {
+        return cache_manager.empty_trash();
+    }
+    cache_manager.purge_trash()?;
+    let entries = cache_manager.trash_entries()?;
+    if entries.is_empty() {
+        println!("Trash is empty");
+    }
+    for entry in entries {
+        let trashed_at: chrono::DateTime<chrono::Local> = entry.trashed_at.into();
+        println!("  {} - {:.2} MB, trashed {}", entry.source_name,
+                 entry.size_bytes as f32 / 1024.0 / 1024.0, trashed_at.format("%Y-%m-%d %H:%M"));
+    }
+    Ok(())
+}
+
+fn run_query(source: &Path, filter: RegionFilter, explain: bool) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    let data = cache_manager.load_ms1_region(source, filter)?;
+    println!("{} MS1 points", data.mz_values.len());
+    if explain {
+        if let Some(profile) = cache_manager.profiler().last() {
+            println!("{}", profile.explain());
+        }
+    }
+    Ok(())
+}
+
+fn run_bench_matrix(source: Option<&Path>, matrix: &BenchMatrix, synthetic_points: usize, report: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    let (ms1_indexed, ms2_indexed_pairs) = match source {
+        Some(source) => cache_manager.load_indexed_data(source)?,
+        None => {
+            let spec = SyntheticSpec { ms1_points: synthetic_points, ..SyntheticSpec::default() };
+            println!("Generating synthetic data ({} MS1 points, {} MS2 windows)...", spec.ms1_points, spec.ms2_windows);
+            bench::synthetic_data(&spec)
+        }
+    };
+    let work_dir = std::env::temp_dir().join(format!("timstof-bench-{}", std::process::id()));
+    let rows = bench::run_matrix(&work_dir, &ms1_indexed, &ms2_indexed_pairs, matrix);
+    let _ = std::fs::remove_dir_all(&work_dir);
+    let rows = rows?;
+    bench::print_table(&rows);
+    if let Some(path) = report {
+        bench::write_report(&rows, path)?;
+    }
+    Ok(())
+}
+
+fn run_profile(reset: bool) -> Result<(), Box<dyn Error>> {
+    let cache_manager = CacheManager::from_env()?;
+    if reset {
+        cache_manager.profiler().reset()?;
+        println!("Query profile reset");
+        return Ok(());
+    }
+
+    let report = cache_manager.profiler().report();
+    if report.is_empty() {
+        println!("No queries recorded yet");
+    }
+    for (source, profile) in report {
+        println!("{}: {} queries, {:.2} MB read, {} points returned, amplification {:.1}x",
+                 source, profile.queries, profile.bytes_read as f64 / 1024.0 / 1024.0,
+                 profile.rows_returned, profile.amplification());
+        for (strategy, count) in &profile.by_strategy {
+            println!("   └── {}: {} queries", strategy, count);
+        }
+    }
+    Ok(())
+}
+
+fn run_scrub(budget: ScrubBudget) -> Result<(), Box<dyn Error>> {
+    let report = CacheManager::from_env()?.scrub(budget)?;
+    if !report.corrupt.is_empty() {
+        return Err(format!("{} corrupt chunk(s) found", report.corrupt.len()).into());
+    }
+    Ok(())
+}
+
+fn run_daemon(config: DaemonConfig) -> Result<(), Box<dyn Error>> {
+    let threads = num_cpus::get();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .unwrap();
+
+    let cache_manager = CacheManager::from_env()?.configure_for_threads(threads);
+    let mut daemon = CacheDaemon::new(config, cache_manager);
+    
+    // First Ctrl-C/SIGTERM: finish the current build and persist the queue; second: exit now
+    let shutdown = daemon.shutdown_handle();
+    ctrlc::set_handler(move || {
+        if shutdown.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            eprintln!("Forced exit; the interrupted build resumes from its last committed file");
+            std::process::exit(130);
+        }
+        eprintln!("Shutdown requested, stopping after the current build (Ctrl-C again to force)");
+    })?;
+    
+    daemon.run()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    telemetry::set_log_hook(telemetry::print_to_terminal);
+    let bounds = |values: Option<Vec<f32>>| values.map(|v| (v[0], v[1]));
+
+    match Cli::parse().command {
+        Command::Build(args) => run_build(args),
+        Command::Info { source } => run_info(source.as_deref()),
+        Command::Verify { source } => run_verify(&source),
+        Command::Validate { source } => run_validate(&source),
+        Command::Health => run_health(),
+        Command::Diff { a, b } => run_diff(&a, &b),
+        Command::Merge { output_key, sources } => {
+            let sources: Vec<&Path> = sources.iter().map(PathBuf::as_path).collect();
+            CacheManager::from_env()?.merge_sources(&sources, &output_key)?;
+            Ok(())
+        }
+        Command::Bundle { source, out } => {
+            CacheManager::from_env()?.export_bundle(&source, &out)?;
+            Ok(())
+        }
+        Command::Import { bundle, alias } => {
+            let cache_manager = CacheManager::from_env()?;
+            let source_path = cache_manager.import_bundle(&bundle)?;
+            if let Some(alias) = alias {
+                cache_manager.register_alias(&alias, &source_path)?;
+                println!("{} -> {}", alias, source_path.display());
+            }
+            Ok(())
+        }
+        Command::Clear { source: Some(source) } => CacheManager::from_env()?.clear_cache_for(&source),
+        Command::Clear { source: None } => CacheManager::from_env()?.clear_cache(),
+        Command::Clone { source, cache_dir } => {
+            CacheManager::from_env()?.clone_cache(&source, &cache_dir)?;
+            Ok(())
+        }
+        Command::Update { source } => run_update(&source),
+        Command::Repair { source } => {
+            CacheManager::from_env()?.repair_cache(&source, |source_path| build_indexed_data(read_timstof_data(source_path)?))?;
+            Ok(())
+        }
+        Command::Compact { source } => {
+            CacheManager::from_env()?.compact_cache(&source)?;
+            Ok(())
+        }
+        Command::Unpack { source } => CacheManager::from_env()?.unpack_cache(&source),
+        Command::Restore { source } => CacheManager::from_env()?.restore(&source),
+        Command::Trash { empty } => run_trash(empty),
+        Command::Export { source, out, format, mzml_encoding, per_scan, no_mobility } => {
+            let options = MzmlOptions {
+                grouping: if per_scan { SpectrumGrouping::FrameScan } else { SpectrumGrouping::Frame },
+                compression: match mzml_encoding {
+                    MzmlEncoding::None => ArrayCompression::None,
+                    MzmlEncoding::Zlib => ArrayCompression::Zlib,
+                    MzmlEncoding::Numpress => ArrayCompression::Numpress,
+                },
+                include_mobility: !no_mobility,
+            };
+            run_export(&source, &out, format, options)
+        }
+        Command::Bench { source, iterations, mz_width, matrix: true, formats, codecs, threads, synthetic_points, report } => {
+            let mut matrix = BenchMatrix {
+                formats: formats.into_iter().map(cache_format).collect(),
+                codecs,
+                iterations,
+                mz_width,
+                ..BenchMatrix::default()
+            };
+            if !threads.is_empty() {
+                matrix.threads = threads;
+            }
+            run_bench_matrix(source.as_deref(), &matrix, synthetic_points, report.as_deref())
+        }
+        Command::Bench { source: Some(source), iterations, mz_width, .. } => {
+            CacheManager::from_env()?.benchmark_source(&source, iterations, mz_width)
+        }
+        Command::Bench { source: None, .. } => CacheManager::from_env()?.benchmark_cache(10 * 1024 * 1024), // 10MB test
+        Command::Query { source, mz, rt, explain } => {
+            run_query(&source, RegionFilter { mz: bounds(mz), rt: bounds(rt) }, explain)
+        }
+        Command::Profile { reset } => run_profile(reset),
+        Command::Gc { dry_run, min_age_minutes } => {
+            CacheManager::from_env()?.gc(GcOptions { dry_run, min_age: Duration::from_secs(min_age_minutes * 60) })?;
+            Ok(())
+        }
+        Command::Scrub { budget_mb, rate_mb } => run_scrub(ScrubBudget {
+            bytes: (budget_mb * 1024.0 * 1024.0) as u64,
+            bytes_per_sec: rate_mb.map(|mb| (mb * 1024.0 * 1024.0) as u64),
+        }),
+        Command::Alias { name, source } => run_alias(name.as_deref(), source.as_deref()),
+        Command::Unalias { name } => CacheManager::from_env()?.remove_alias(&name),
+        Command::Daemon { watch, interval, settle, quota_gb, status, threads, no_notify, normal_priority } => {
+            let mut config = DaemonConfig::new(watch);
+            config.poll_interval = Duration::from_secs(interval);
+            config.settle_time = Duration::from_secs(settle);
+            config.quota_bytes = quota_gb.map(|gb| (gb * 1024.0 * 1024.0 * 1024.0) as u64);
+            config.status_addr = status;
+            config.use_notify = !no_notify;
+            config.low_priority = !normal_priority;
+            if let Some(threads) = threads {
+                config.build_threads = threads;
+            }
+            run_daemon(config)
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// File: src/bundle.rs
+//
+// A source's cache as one portable file (export_bundle / import_bundle), so an analyst can
+// ship a cache to a collaborator instead of the much larger raw folder. A bundle is a
+// zstd-compressed tar: an index first (bundle.json: the source as recorded, and each file's
+// size and SHA-256), then the cache files as stored, so encrypted and signed caches stay so.
+// Files are named by their suffix after the cache key, since the importing machine may key
+// the source differently. Import stages every file as .partial and checks it against the
+// index before any of them replaces what is in the cache directory.
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::fdlimit;
+use crate::signing;
+
+pub const FORMAT_VERSION: u32 = 1;
+const INDEX_NAME: &str = "bundle.json";
+const FILES_DIR: &str = "files/";
+const ZSTD_LEVEL: i32 = 3; // Shards are mostly compressed already
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub format: u32,
+    pub source: String, // Source path as recorded on the exporting machine
+    pub created_secs: u64,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub suffix: String, // File name after the cache key, e.g. ".ms1_indexed.cache.bin"
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+// Pack `files`, all named "<cache_name><suffix>"; returns the bundle's size in bytes
+pub fn write(bundle_path: &Path, source: &str, cache_name: &str, files: &[PathBuf]) -> io::Result<u64> {
+    let mut entries = Vec::new();
+    for path in files {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let suffix = name.strip_prefix(cache_name).filter(|suffix| valid_suffix(suffix)).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a cache file of {}", path.display(), cache_name),
+        ))?;
+        entries.push(BundleFile { suffix: suffix.to_string(), bytes: fs::metadata(path)?.len(), sha256: signing::sha256_file(path)? });
+    }
+    let index = BundleIndex {
+        format: FORMAT_VERSION,
+        source: source.to_string(),
+        created_secs: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        files: entries,
+    };
+
+    // Written under a temporary name and renamed, like cache files
+    let partial_path = bundle_path.with_extension("partial");
+    let mut builder = tar::Builder::new(zstd::Encoder::new(fdlimit::create(&partial_path)?, ZSTD_LEVEL)?);
+    let json = serde_json::to_vec_pretty(&index).map_err(io::Error::other)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(index.created_secs);
+    header.set_cksum();
+    builder.append_data(&mut header, INDEX_NAME, json.as_slice())?;
+    for (path, entry) in files.iter().zip(&index.files) {
+        let (mut file, _permit) = fdlimit::open(path)?.into_parts();
+        builder.append_file(format!("{}{}", FILES_DIR, entry.suffix), &mut file)?;
+    }
+    builder.into_inner()?.finish()?;
+    fs::rename(&partial_path, bundle_path)?;
+    fs::metadata(bundle_path).map(|m| m.len())
+}
+
+// Stage the files of a bundle in `dir` as "<name_for(index)><suffix>.partial", each
+// checked against the index. Returns the index and (staged, final) paths; on any error
+// the staged files are removed again.
+pub fn unpack<F>(bundle_path: &Path, dir: &Path, name_for: F) -> io::Result<(BundleIndex, Vec<(PathBuf, PathBuf)>)>
+where
+    F: FnOnce(&BundleIndex) -> String,
+{
+    let mut staged = Vec::new();
+    let result = stage(bundle_path, dir, name_for, &mut staged);
+    if result.is_err() {
+        for (partial_path, _) in &staged {
+            let _ = fs::remove_file(partial_path);
+        }
+    }
+    result.map(|index| (index, staged))
+}
+
+fn stage<F>(bundle_path: &Path, dir: &Path, name_for: F, staged: &mut Vec<(PathBuf, PathBuf)>) -> io::Result<BundleIndex>
+where
+    F: FnOnce(&BundleIndex) -> String,
+{
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", bundle_path.display(), message));
+    let mut archive = tar::Archive::new(zstd::Decoder::new(fdlimit::open(bundle_path)?)?);
+    let mut entries = archive.entries()?;
+
+    let mut first = match entries.next() {
+        Some(Ok(entry)) => entry,
+        Some(Err(e)) => return Err(invalid(format!("not a cache bundle ({})", e))),
+        None => return Err(invalid("empty bundle".to_string())),
+    };
+    if first.path()?.to_str() != Some(INDEX_NAME) {
+        return Err(invalid(format!("not a cache bundle (no {} first)", INDEX_NAME)));
+    }
+    let mut json = Vec::new();
+    first.read_to_end(&mut json)?;
+    let index: BundleIndex = serde_json::from_slice(&json).map_err(|e| invalid(format!("unreadable index: {}", e)))?;
+    if index.format != FORMAT_VERSION {
+        return Err(invalid(format!("bundle format {} (this build reads {})", index.format, FORMAT_VERSION)));
+    }
+    let cache_name = name_for(&index);
+
+    let mut seen = Vec::new();
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.to_str().map(str::to_string).unwrap_or_default();
+        let listed = path.strip_prefix(FILES_DIR)
+            .and_then(|suffix| index.files.iter().find(|file| file.suffix == suffix))
+            .filter(|file| valid_suffix(&file.suffix))
+            .ok_or_else(|| invalid(format!("{} is not listed in the index", path)))?;
+        let final_path = dir.join(format!("{}{}", cache_name, listed.suffix));
+        let partial_path = dir.join(format!("{}{}.partial", cache_name, listed.suffix));
+        staged.push((partial_path.clone(), final_path));
+
+        let mut hashing = HashingReader { inner: &mut entry, hasher: Sha256::new(), bytes: 0 };
+        io::copy(&mut hashing, &mut fdlimit::create(&partial_path)?)?;
+        let sha256 = hex::encode(hashing.hasher.finalize());
+        if hashing.bytes != listed.bytes || sha256 != listed.sha256 {
+            return Err(invalid(format!("{} does not match its checksum; the bundle is damaged", path)));
+        }
+        seen.push(listed.suffix.clone());
+    }
+    if let Some(missing) = index.files.iter().find(|file| !seen.contains(&file.suffix)) {
+        return Err(invalid(format!("{} is missing; the bundle is truncated", missing.suffix)));
+    }
+    Ok(index)
+}
+
+// ".meta" or ".<kind>.cache.<ext>": one file name component, nothing else
+fn valid_suffix(suffix: &str) -> bool {
+    suffix.starts_with('.')
+        && !suffix.contains(['/', '\\'])
+        && !suffix.contains("..")
+        && (suffix == ".meta" || suffix.contains(".cache."))
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
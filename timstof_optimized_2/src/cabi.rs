@@ -0,0 +1,219 @@
+// File: src/cabi.rs
+//
+// C ABI over the cache so C/C++ services (e.g. a vendor acquisition-processing pipeline)
+// can read cached data without going through Rust. The header include/timstof_cache.h is
+// generated from this file by cbindgen; after changing it, build with
+// TIMSTOF_CACHE_REGENERATE_HEADER=1 to update the header (build.rs).
+//
+// Conventions: functions returning pointers return NULL on failure and leave a message
+// for cache_last_error(); everything returned must be released with the matching free
+// function. Handles are not thread-safe; open one per thread. Caches are looked up in
+// TIMSTOF_CACHE_CACHE_DIR, or else ./.timstof_cache relative to the process working
+// directory, as in the CLI tools. Covered by tests/cabi.rs, header included.
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::cache::{CacheManager, WindowKey};
+use crate::telemetry::{self, Level};
+
+#[cfg(unix)]
+fn path_from_c(path: &CStr) -> Result<PathBuf, String> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn path_from_c(path: &CStr) -> Result<PathBuf, String> {
+    path.to_str().map(PathBuf::from).map_err(|_| "source_path is not valid UTF-8".to_string())
+}
+
+/// Opaque handle for one cached source (.d folder or alias).
+pub struct TimstofCache {
+    manager: CacheManager,
+    source_path: PathBuf,
+}
+
+/// Points returned by cache_load_range (MS1) or cache_load_ms2_window, in m/z order. All arrays have `len` entries
+/// and stay valid until the range is passed to cache_free_range.
+#[repr(C)]
+pub struct CacheRange {
+    pub len: usize,
+    pub rt: *mut f32,        // Retention time in minutes
+    pub mobility: *mut f32,  // 1/K0
+    pub mz: *mut f32,
+    pub intensity: *mut u32,
+    pub frame: *mut u32,
+    pub scan: *mut u32,
+}
+
+/// Receives the library's messages: `level` is 0 for progress, 1 for warnings and 2 for
+/// errors; `message` is valid for the duration of the call.
+pub type CacheLogCallback = extern "C" fn(level: c_int, message: *const c_char);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message on the C side anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// Run `body`, turning errors and panics into NULL plus a last-error message, so neither
+// ever unwinds into C
+fn guarded<T>(body: impl FnOnce() -> Result<*mut T, String>) -> *mut T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("internal panic in timstof cache".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+fn leak<T>(values: Vec<T>) -> *mut T {
+    Box::into_raw(values.into_boxed_slice()) as *mut T
+}
+
+unsafe fn unleak<T>(values: *mut T, len: usize) {
+    if !values.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(values, len)));
+    }
+}
+
+/// Message of the last failed call on this thread, or NULL. Owned by the library and
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn cache_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Pass the library's progress and warning messages to `callback`, from whichever thread
+/// logs them; they are dropped until a callback is set, and again after one is cleared
+/// with NULL.
+#[no_mangle]
+pub extern "C" fn cache_set_log_callback(callback: Option<CacheLogCallback>) {
+    match callback {
+        Some(callback) => telemetry::set_log_hook(move |level, message| {
+            let level = match level {
+                Level::Info => 0,
+                Level::Warn => 1,
+                Level::Error => 2,
+            };
+            let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+            callback(level, message.as_ptr());
+        }),
+        None => telemetry::set_log_hook(|_, _| {}),
+    }
+}
+
+/// Open the cache of a source given as a .d path or a registered alias (UTF-8; on Unix
+/// any bytes, as paths there need not be UTF-8).
+/// Returns NULL if the path is invalid or the source has no valid cache.
+/// The cache is configured by the TIMSTOF_CACHE_* environment variables, keys included.
+///
+/// # Safety
+/// `source_path` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cache_open(source_path: *const c_char) -> *mut TimstofCache {
+    guarded(|| {
+        if source_path.is_null() {
+            return Err("source_path is NULL".to_string());
+        }
+        let source = path_from_c(CStr::from_ptr(source_path))?;
+        let manager = CacheManager::from_env().map_err(|e| e.to_string())?;
+        let source_path = manager.resolve_source(&source);
+        if !manager.is_cache_valid(&source_path) {
+            return Err(format!("no valid cache for {}", source_path.display()));
+        }
+        Ok(Box::into_raw(Box::new(TimstofCache { manager, source_path })))
+    })
+}
+
+/// Load the MS1 points with m/z in [mz_min, mz_max]. Only the matching rows are read
+/// from disk. Returns NULL on failure; an empty range is not a failure.
+///
+/// # Safety
+/// `cache` must be a handle from cache_open that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cache_load_range(cache: *const TimstofCache, mz_min: f32, mz_max: f32) -> *mut CacheRange {
+    guarded(|| {
+        let cache = cache.as_ref().ok_or("cache handle is NULL")?;
+        let data = cache.manager
+            .load_ms1_mz_range(&cache.source_path, mz_min, mz_max)
+            .map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(CacheRange {
+            len: data.mz_values.len(),
+            rt: leak(data.rt_values_min),
+            mobility: leak(data.mobility_values),
+            mz: leak(data.mz_values),
+            intensity: leak(data.intensity_values),
+            frame: leak(data.frame_indices),
+            scan: leak(data.scan_indices),
+        })))
+    })
+}
+
+/// Load the MS2 window with the given isolation range (matched to 0.001 Th), reading only
+/// that window. Returns NULL on failure or if the cache has no such window.
+///
+/// # Safety
+/// `cache` must be a handle from cache_open that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cache_load_ms2_window(cache: *const TimstofCache, isolation_low: f32, isolation_high: f32) -> *mut CacheRange {
+    guarded(|| {
+        let cache = cache.as_ref().ok_or("cache handle is NULL")?;
+        let data = cache.manager
+            .load_ms2_window_by_range(&cache.source_path, WindowKey::new(isolation_low, isolation_high))
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("no MS2 window {}-{} in the cache", isolation_low, isolation_high))?;
+        Ok(Box::into_raw(Box::new(CacheRange {
+            len: data.mz_values.len(),
+            rt: leak(data.rt_values_min),
+            mobility: leak(data.mobility_values),
+            mz: leak(data.mz_values),
+            intensity: leak(data.intensity_values),
+            frame: leak(data.frame_indices),
+            scan: leak(data.scan_indices),
+        })))
+    })
+}
+
+/// Release a range from cache_load_range or cache_load_ms2_window. NULL is ignored.
+///
+/// # Safety
+/// `range` must be NULL or a range from cache_load_range or cache_load_ms2_window that
+/// has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cache_free_range(range: *mut CacheRange) {
+    if range.is_null() {
+        return;
+    }
+    let range = Box::from_raw(range);
+    unleak(range.rt, range.len);
+    unleak(range.mobility, range.len);
+    unleak(range.mz, range.len);
+    unleak(range.intensity, range.len);
+    unleak(range.frame, range.len);
+    unleak(range.scan, range.len);
+}
+
+/// Close a handle from cache_open. NULL is ignored. Ranges loaded through the handle
+/// stay valid and must still be freed with cache_free_range.
+///
+/// # Safety
+/// `cache` must be NULL or a handle from cache_open that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cache_free(cache: *mut TimstofCache) {
+    if !cache.is_null() {
+        drop(Box::from_raw(cache));
+    }
+}
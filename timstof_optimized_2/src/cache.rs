@@ -1,17 +1,138 @@
 // File: src/cache.rs
 use std::path::{Path, PathBuf};
-use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
-use bincode;
-use std::time::SystemTime;
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use memmap2::Mmap;
 
+use crate::fdlimit;
 use crate::utils::{TimsTOFRawData, IndexedTimsTOFData};
+use crate::window::{self, IsolationWindow};
+use crate::chunked::ChunkedIndexedData;
+use crate::shm::{self, SharedIndexedData};
+use crate::mapped::{self, DataShard, MappedShard};
+use crate::frames::{self, FrameSpectrum};
+use crate::chromatogram::{Chromatogram, FrameSummary};
+use crate::heatmap::{Heatmap, HeatmapPyramid, Viewport};
+use crate::summary::DatasetSummary;
+use crate::diff::{CacheDiff, CacheSide};
+use crate::instrument::InstrumentInfo;
+use crate::provenance::Provenance;
+use crate::telemetry;
+use crate::report::{OperationReport, ReportWriter};
+use crate::health::HealthReport;
+use crate::longpath;
+use crate::keys::{self, CacheKeyStrategy, PathHashKey};
+use crate::sidecar;
+use crate::bundle;
+use crate::hot::{HotCache, LoadedData, Ms2Windows};
+use crate::cancel::CancellationToken;
+use crate::remote::{RemoteStore, MirrorIndex};
+use crate::columnar::{self, ContainerHeader, ContainerReader, ContainerWriter, SegmentInput, SegmentDescriptor, ShardPlan, WriteOptions, INDEXED_COLUMNS};
+use crate::encryption::EncryptionKey;
+use crate::signing::{self, SigningKey};
+use crate::aliases::AliasRegistry;
+use crate::calibration::{Calibration, CalibrationRegistry};
+use crate::parquet_backend::{self, ParquetBackend, RegionFilter};
+use crate::scrub::{self, ScrubBudget, ScrubReport};
+use crate::profile::{self, QueryProfile, QueryProfiler};
+use crate::space::{self, InsufficientSpace, LowSpacePolicy};
+use crate::retry::{RetryPolicy, RetryingFile};
+use crate::quantize::LossyOptions;
+use crate::precision::{Float, Precision};
+use crate::offload::{self, Offloaded};
+use crate::mzml::{self, MzmlOptions};
+use crate::content::{self, ContentIndex};
+use crate::freshness::{self, ValidationPolicy};
+use crate::reflink::{self, CloneMethod};
+use crate::compression::{self, CodecMap, ColumnCodec, CompressionType};
+use crate::manifest::{self, Manifest, ManifestEntry};
+use crate::settings::CacheSettings;
+use crate::prefetch;
+use crate::iomode::{self, IoMode};
+use crate::input::{self, NonFinitePolicy, SortPolicy};
+use crate::durability::{self, Durability};
+use crate::layout::{self, Layout};
+use crate::scheme::WindowScheme;
+use crate::snapshot::{self, Generation, Snapshot};
+use crate::stream::{MergedRuns, SpillDir, StreamBuffer, TimsTOFPoint};
+use polars::prelude::{DataFrame, IpcWriter, NamedFrom, ParquetWriter, SerWriter, Series};
+
+// A container load: the MS1 segments, the MS2 windows and the number of segments decoded
+type LoadedSegments<F> = (Vec<IndexedTimsTOFData<F>>, Ms2Windows<F>, usize);
+// One MS2 window read by range: its data (None if the cache has no such window), bytes
+// read, strategy and segments read, for the query profile
+type WindowRead = (Option<IndexedTimsTOFData>, u64, String, usize);
+// How a range query got its data, for its query profile (record_query)
+struct RangeRead {
+    strategy: String,
+    segments_read: usize,
+    bytes_read: u64,
+}
+
+// A cache file in get_cache_info(): name, size in bytes, size for display
+pub type CacheFileInfo = (String, u32, String);
+
+// On-disk layout of the MS1/MS2 cache files. Both native layouts are the versioned,
+// self-describing container, whose columns are found by name and type, so caches survive
+// changes to the structs; bincode is only read, for caches from before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheFormat {
+    Columnar,                          // Native container (columnar.rs)
+    SingleFile,                        // Native container, MS1 and MS2 in one file per source
+    Parquet { row_group_size: usize }, // Readable by DuckDB/Spark/polars, see parquet_backend.rs
+}
 
 #[derive(Clone)]
 pub struct CacheConfig {
     pub enable_compression: bool,
     pub buffer_size: usize,
     pub auto_compression: bool, // Automatically decide based on file size
+    pub compression: CompressionType, // Codec (and level) for files that are compressed
+    pub adaptive_compression: bool, // Columnar only: pick per-column codecs by sampling (compression.rs)
+    pub byte_shuffle: bool,         // Columnar only: byte-shuffle compressed float columns first (compression::byte_shuffle); older readers reject them
+    pub local_mirror_bytes: Option<u64>, // Bound on the local copy of a remote cache (LRU)
+    pub column_order: Vec<String>,       // Physical column order inside cache files
+    pub encryption_key: Option<EncryptionKey>, // AES-256-GCM at rest, applied after compression
+    pub signing_key: Option<SigningKey>,       // HMAC-signed metadata, verified on load
+    pub trash_retention: Duration,             // How long cleared caches stay restorable
+    pub history_generations: usize,            // Committed generations per source kept for rollback(); 0 keeps none
+    pub format: CacheFormat,
+    pub async_threads: usize,                  // Dedicated pool for the *_async methods
+    pub content_addressed: bool,               // Key caches by source content, sharing copies (content.rs)
+    pub target_shard_bytes: Option<u64>,       // MS1 shard size; None with no point target auto-tunes
+    pub target_shard_points: Option<usize>,    // MS1 points per shard, takes precedence over bytes
+    pub stream_buffer_points: usize,           // Points save_from_stream() holds (24 bytes each) before spilling them to disk as a sorted run (stream.rs)
+    pub shared_memory_dir: Option<PathBuf>,    // Where load_shared() puts decoded copies; /dev/shm if None
+    pub hot_cache_entries: usize,              // Sources load_indexed_data_hot() keeps in memory; 0 keeps none
+    pub parallel_threads: Option<usize>,       // Own pool for shard encoding/decoding; None shares the caller's
+    pub validation: ValidationPolicy,          // How is_cache_valid() decides the source is unchanged (freshness.rs)
+    pub read_only: bool,                       // Never write to the cache directory, see open_read_only()
+    pub on_low_space: LowSpacePolicy,          // When a save's estimated size exceeds the free space (space.rs)
+    pub retry: RetryPolicy,                    // Retries of shard reads and container writes on transient errors (retry.rs)
+    pub window_compression_min_bytes: u64,     // MS2 windows smaller than this (24 bytes per point) are stored raw; containers only
+    pub lossy: Option<LossyOptions>,           // Opt-in quantized m/z and intensities, MS1 compressed too, for QC/visualization caches (quantize.rs); containers only
+    pub precision: Precision,                  // Stored width of rt, mobility and m/z (precision.rs); containers only, other formats store f32
+    pub frame_index: bool,                     // Also write the points sorted by frame, for load_frame() (frames.rs)
+    pub rt_major: bool,                        // Also write a copy sorted by frame and scan, for Layout::RtMajor loads (layout.rs)
+    pub heatmap: bool,                         // Also build rt × m/z and rt × mobility grids, for load_heatmap() (heatmap.rs)
+    pub cache_dir: Option<PathBuf>,            // Where with_config() keeps caches; .timstof_cache if None
+    pub params_fingerprint: Option<u64>,       // Caller's hash of its indexing parameters, part of the cache key and checked on load
+    pub prefetch: bool,                        // Read-ahead hints (fadvise/madvise WILLNEED) for shard data before decoding or mapping (prefetch.rs)
+    pub io_mode: IoMode,                       // Buffered reads or a mapping per container load; Auto decides by size, compression and free memory (iomode.rs)
+    pub compression_block_bytes: Option<usize>, // Compressed columns are stored in independent blocks of about this many bytes, so range queries decompress only what they need; None compresses whole columns
+    pub sort: SortPolicy,                      // MS1 not sorted by m/z on save: rejected, or sorted first (input.rs)
+    pub non_finite: NonFinitePolicy,           // NaN / infinite rt, mobility and m/z on save: kept, rejected, rows dropped or values replaced (input.rs)
+    pub durability: Durability,                // Syncing of data and metadata files on save, in the background while shards are written (durability.rs)
+    pub deterministic: bool,                   // Byte-identical cache files for the same input and config: fixed shard plan and codecs, synthetic nonces, timestamps from SOURCE_DATE_EPOCH or zero
 }
 
 impl Default for CacheConfig {
@@ -20,13 +141,774 @@ impl Default for CacheConfig {
             enable_compression: false,  // Disabled by default for speed
             buffer_size: 1024 * 1024 * 32, // Smaller, more efficient buffer
             auto_compression: true,     // Smart compression decisions
+            compression: CompressionType::Lz4,
+            adaptive_compression: false,
+            byte_shuffle: false,
+            local_mirror_bytes: None,   // Unbounded local mirror
+            column_order: INDEXED_COLUMNS.iter().map(|(name, _)| name.to_string()).collect(),
+            encryption_key: None,
+            signing_key: None,
+            trash_retention: Duration::from_secs(7 * 24 * 3600), // One week
+            history_generations: 0,     // Each kept generation holds on to the data a rebuild replaces
+            format: CacheFormat::Columnar,
+            async_threads: (num_cpus::get() / 2).max(1), // Leave cores for the async runtime
+            content_addressed: false,
+            target_shard_bytes: None,   // Auto-tune from data size and parallelism
+            target_shard_points: None,
+            stream_buffer_points: 16 * 1024 * 1024, // About 400 MB
+            shared_memory_dir: None,   // /dev/shm
+            hot_cache_entries: 2,
+            parallel_threads: None,     // Global rayon pool, or the one the caller installed
+            validation: ValidationPolicy::default(), // DeepMtime
+            read_only: false,
+            on_low_space: LowSpacePolicy::Fail,
+            retry: RetryPolicy::default(),              // 3 tries
+            window_compression_min_bytes: 64 * 1024,
+            lossy: None,
+            precision: Precision::F32,
+            frame_index: false,         // Roughly doubles the size of a compressed cache
+            rt_major: false,            // Likewise
+            heatmap: false,
+            cache_dir: None,
+            params_fingerprint: None,
+            prefetch: false,
+            io_mode: IoMode::default(),                 // Auto
+            compression_block_bytes: Some(2 * 1024 * 1024),
+            sort: SortPolicy::default(),                // Verify
+            non_finite: NonFinitePolicy::default(),     // Keep
+            durability: Durability::default(),          // None
+            deterministic: false,
+        }
+    }
+}
+
+impl CacheConfig {
+    // Defaults overridden by the environment: TIMSTOF_CACHE_<SETTING> variables (settings.rs),
+    // the encryption and signing keys (TIMSTOF_CACHE_KEY, TIMSTOF_CACHE_SIGNING_KEY and their
+    // _ID variables), TIMSTOF_CACHE_CONTENT_ADDRESSED (set at all) and TIMSTOF_CACHE_SHM_DIR.
+    // The one place configuration comes from the environment; Default and from_file never
+    // read it, so a library caller gets only what it asked for.
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Self {
+            encryption_key: EncryptionKey::from_env(),
+            signing_key: SigningKey::from_env(),
+            content_addressed: std::env::var_os("TIMSTOF_CACHE_CONTENT_ADDRESSED").is_some(),
+            shared_memory_dir: std::env::var_os("TIMSTOF_CACHE_SHM_DIR").map(PathBuf::from),
+            ..Self::default()
+        };
+        CacheSettings::from_env()?.apply(&mut config)?;
+        Ok(config)
+    }
+
+    // Defaults overridden by a TOML file, e.g. cache.toml; the environment is not consulted
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let text = fdlimit::read_to_string(path)?;
+        let settings = CacheSettings::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut config = Self::default();
+        settings.apply(&mut config).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+}
+
+// Units of an in-progress save that are already safely on disk, and the segments of the
+// columnar units being written that are: an interrupted save continues after them
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BuildProgress {
+    source_modified_secs: u64,
+    #[serde(default)]
+    write_fingerprint: String, // CacheManager::write_fingerprint() of the save
+    committed: Vec<String>,
+    #[serde(default)]
+    segments: BTreeMap<String, ContainerHeader>, // Unit -> the segments its .partial file holds
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    durability: Durability,
+}
+
+impl BuildProgress {
+    // Progress left behind by a different version of the source, or by a save with other
+    // settings or input, describes files this save would not write and is discarded
+    fn load(progress_path: &Path, source_path: &Path, write_fingerprint: String, durability: Durability) -> Self {
+        let source_modified_secs = source_modified_secs(source_path);
+        let progress = fdlimit::read_to_string(progress_path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<BuildProgress>(&text).ok())
+            .filter(|progress| progress.source_modified_secs == source_modified_secs && progress.write_fingerprint == write_fingerprint)
+            .unwrap_or(BuildProgress { source_modified_secs, write_fingerprint, ..BuildProgress::default() });
+        BuildProgress { path: progress_path.to_path_buf(), durability, ..progress }
+    }
+
+    fn is_committed(&self, unit: &str, unit_path: &Path) -> bool {
+        self.committed.iter().any(|c| c == unit) && unit_path.exists()
+    }
+
+    fn commit(&mut self, unit: &str) -> Result<(), std::io::Error> {
+        self.committed.push(unit.to_string());
+        self.segments.remove(unit);
+        self.write()
+    }
+
+    // `header` lists the segments of `unit` written and synced so far
+    fn commit_segments(&mut self, unit: &str, header: &ContainerHeader) -> Result<(), std::io::Error> {
+        self.segments.insert(unit.to_string(), header.clone());
+        self.write()
+    }
+
+    fn write(&self) -> Result<(), std::io::Error> {
+        let text = serde_json::to_string(self)
+            .map_err(std::io::Error::other)?;
+        durability::write_file(&self.path, text, self.durability)
+    }
+}
+
+fn source_modified_secs(source_path: &Path) -> u64 {
+    fs::metadata(source_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// The "source" metadata line: the canonical path, on one line
+fn recorded_source(source_path: &Path) -> String {
+    let source_path = fs::canonicalize(source_path).unwrap_or_else(|_| source_path.to_path_buf());
+    source_path.display().to_string().replace('\n', " ")
+}
+
+// Cache file names are always UTF-8 (see content::path_key); remote stores key by them
+fn file_name_of(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn is_lz4_path(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("lz4")
+}
+
+// Stable identity of an MS2 isolation window: its bounds quantized to 0.001 Th. Windows
+// are looked up by this key, never by position or exact float equality, so neither the
+// order of the input Vec nor f32/f64 round trips change which data a window maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowKey {
+    low_mth: i64, // milli-Thomson
+    high_mth: i64,
+}
+
+impl WindowKey {
+    pub fn new(low: f32, high: f32) -> Self {
+        let quantize = |mz: f32| (mz as f64 * 1000.0).round() as i64;
+        Self { low_mth: quantize(low), high_mth: quantize(high) }
+    }
+    
+    fn of(segment: &SegmentDescriptor) -> Self {
+        let (low, high) = isolation_range(segment);
+        Self::new(low, high)
+    }
+}
+
+// "400.000-425.000", as listed in the metadata (ms2_window_ids)
+impl fmt::Display for WindowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}-{:.3}", self.low_mth as f64 / 1000.0, self.high_mth as f64 / 1000.0)
+    }
+}
+
+// Which MS2 isolation windows load_ms2_windows returns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFilter {
+    All,
+    Overlapping(f32, f32), // Isolation range shares more than an edge with [low, high]
+    Containing(f32),       // Isolation range holds this precursor m/z
+    Exact(f32, f32),       // The window with these bounds (compared as WindowKeys)
+}
+
+impl WindowFilter {
+    pub fn matches(&self, window: &IsolationWindow) -> bool {
+        let (low, high) = window.bounds();
+        match *self {
+            WindowFilter::All => true,
+            WindowFilter::Overlapping(from, to) => low < to && high > from,
+            WindowFilter::Containing(mz) => low <= mz && mz <= high,
+            WindowFilter::Exact(from, to) => WindowKey::new(low, high) == WindowKey::new(from, to),
+        }
+    }
+}
+
+// What a container load decodes: the MS1 shards, the MS2 windows passing a filter, or both
+#[derive(Debug, Clone, Copy)]
+struct LoadParts {
+    ms1: bool,
+    windows: Option<WindowFilter>,
+}
+
+impl LoadParts {
+    const ALL: Self = Self { ms1: true, windows: Some(WindowFilter::All) };
+    const MS1: Self = Self { ms1: true, windows: None };
+
+    fn ms2(filter: WindowFilter) -> Self {
+        Self { ms1: false, windows: Some(filter) }
+    }
+}
+
+// One MS1 shard or MS2 window as listed in the metadata ("shard" lines). With the table
+// signed alongside the rest of the metadata, queries can be pruned and data files checked
+// without opening their footers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardInfo {
+    pub file: String,           // Data file name
+    pub segment: usize,         // Index in the container
+    pub name: String,           // "ms1" | "ms2_window"
+    pub mz_range: (f32, f32),   // m/z bounds of an MS1 shard, isolation window of an MS2 window
+    pub data_points: u64,
+    pub compressed_bytes: u64,  // Stored size of all columns
+    pub uncompressed_bytes: u64, // Of all columns once decompressed, at their stored width
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,  // CRC32 of the column CRC32s; None for columns without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codecs: Option<String>, // Codecs of its columns (describe_codecs); None in tables written before they were listed
+}
+
+impl ShardInfo {
+    fn of(file: &str, segment_index: usize, segment: &SegmentDescriptor) -> Self {
+        let mz_range = match segment.name.as_str() {
+            "ms2_window" => isolation_range(segment),
+            _ => {
+                let attr = |name: &str| segment.attrs.get(name).copied().unwrap_or(0.0) as f32;
+                (attr("mz_min"), attr("mz_max"))
+            }
+        };
+        let checksum = segment.columns.iter()
+            .map(|column| column.crc32)
+            .collect::<Option<Vec<u32>>>()
+            .map(|crcs| crc32fast::hash(&crcs.iter().flat_map(|crc| crc.to_le_bytes()).collect::<Vec<_>>()));
+        Self {
+            file: file.to_string(),
+            segment: segment_index,
+            name: segment.name.clone(),
+            mz_range,
+            data_points: segment.rows,
+            compressed_bytes: segment.columns.iter().map(|column| column.stored_len).sum(),
+            uncompressed_bytes: segment.columns.iter().map(|column| column.rows * column.stored_width() as u64).sum(),
+            checksum,
+            codecs: Some(describe_columns(segment)),
+        }
+    }
+
+    fn overlaps(&self, mz_min: f32, mz_max: f32) -> bool {
+        self.data_points > 0 && self.mz_range.0 <= mz_max && self.mz_range.1 >= mz_min
+    }
+}
+
+// Shard table of a cache's container files, read from their footers
+fn shard_table(data_files: &[(&str, PathBuf)]) -> std::io::Result<Vec<ShardInfo>> {
+    let mut table = Vec::new();
+    for (_, path) in data_files {
+        let file = fdlimit::open(path)?;
+        let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        table.extend(header.segments.iter().enumerate().map(|(segment_index, segment)| ShardInfo::of(name, segment_index, segment)));
+    }
+    Ok(table)
+}
+
+fn shard_lines(table: &[ShardInfo]) -> Result<String, std::io::Error> {
+    let mut lines = String::new();
+    for shard in table {
+        let json = serde_json::to_string(shard)
+            .map_err(std::io::Error::other)?;
+        lines.push_str(&format!("shard: {}\n", json));
+    }
+    Ok(lines)
+}
+
+// "key: value" lines of metadata text, in order
+fn metadata_lines(metadata: &str) -> Vec<(String, String)> {
+    metadata.lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+// The shard table listed in metadata text, None if it has none (Parquet, or written
+// before shards were listed)
+fn recorded_shards(metadata: &str) -> Option<Vec<ShardInfo>> {
+    let table: Vec<ShardInfo> = metadata.lines()
+        .filter_map(|line| line.strip_prefix("shard: "))
+        .filter_map(|json| serde_json::from_str(json).ok())
+        .collect();
+    (!table.is_empty()).then_some(table)
+}
+
+fn summary_line(summary: &DatasetSummary) -> Result<String, std::io::Error> {
+    let json = serde_json::to_string(summary).map_err(std::io::Error::other)?;
+    Ok(format!("summary: {}\n", json))
+}
+
+// The dataset summary listed in metadata text, None for caches saved before summaries
+fn recorded_summary(metadata: &str) -> Option<DatasetSummary> {
+    let json = metadata.lines().find_map(|line| line.strip_prefix("summary: "))?;
+    serde_json::from_str(json).ok()
+}
+
+fn scheme_line(scheme: &WindowScheme) -> Result<String, std::io::Error> {
+    let json = serde_json::to_string(scheme).map_err(std::io::Error::other)?;
+    Ok(format!("window_scheme: {}\n", json))
+}
+
+// The DIA window scheme listed in metadata text (scheme.rs), None for caches saved before
+// schemes or without MS2 windows
+fn recorded_scheme(metadata: &str) -> Option<WindowScheme> {
+    let json = metadata.lines().find_map(|line| line.strip_prefix("window_scheme: "))?;
+    serde_json::from_str(json).ok()
+}
+
+// Acquisition metadata listed in metadata text, None if the source had none (instrument.rs)
+fn recorded_instrument(metadata: &str) -> Option<InstrumentInfo> {
+    let json = metadata.lines().find_map(|line| line.strip_prefix("instrument: "))?;
+    serde_json::from_str(json).ok()
+}
+
+// How the cache was last written (provenance.rs), None for caches from before provenance
+fn recorded_provenance(metadata: &str) -> Option<Provenance> {
+    let json = metadata.lines().find_map(|line| line.strip_prefix("provenance: "))?;
+    serde_json::from_str(json).ok()
+}
+
+// The "lossy" metadata value of a quantized cache, None for exact ones
+fn lossy_description(metadata: &str) -> Option<&str> {
+    metadata.lines()
+        .find_map(|line| line.strip_prefix("lossy: "))
+        .filter(|value| *value != "false")
+}
+
+// The recorded schema hash if it isn't this build's; caches from before it was recorded
+// have none and are trusted to their column tables
+fn schema_mismatch(metadata_path: &Path, metadata: &str) -> Option<columnar::SchemaMismatch> {
+    let recorded = metadata.lines().find_map(|line| line.strip_prefix("schema: "))?;
+    let expected = columnar::schema_hash();
+    (recorded != expected).then(|| columnar::SchemaMismatch {
+        metadata_path: metadata_path.to_path_buf(),
+        recorded: recorded.to_string(),
+        expected,
+    })
+}
+
+// The "params_fingerprint" metadata value, None for caches saved without one
+fn recorded_params_fingerprint(metadata: &str) -> Option<u64> {
+    metadata.lines()
+        .find_map(|line| line.strip_prefix("params_fingerprint: "))
+        .and_then(|value| u64::from_str_radix(value, 16).ok())
+}
+
+// The "calibration_fingerprint" metadata value, None for caches saved uncalibrated
+fn recorded_calibration_fingerprint(metadata: &str) -> Option<u64> {
+    metadata.lines()
+        .find_map(|line| line.strip_prefix("calibration_fingerprint: "))
+        .and_then(|value| u64::from_str_radix(value, 16).ok())
+}
+
+// Width the float columns were saved at; caches from before the setting are f32
+fn recorded_precision(metadata: &str) -> Precision {
+    metadata.lines()
+        .find_map(|line| line.strip_prefix("precision: "))
+        .and_then(Precision::from_dtype)
+        .unwrap_or_default()
+}
+
+fn window_ids(keys: impl Iterator<Item = WindowKey>) -> String {
+    keys.map(|key| key.to_string()).collect::<Vec<_>>().join(",")
+}
+
+// All segments of one MS2 window in a container, merged; None if the window isn't stored.
+// Returns the data and the number of segments read.
+fn read_window<F>(reader: &ContainerReader<F>, key: WindowKey) -> std::io::Result<(Option<IndexedTimsTOFData>, usize)>
+where
+    F: Fn(u64, usize) -> std::io::Result<Vec<u8>> + Sync,
+{
+    let matching: Vec<usize> = reader.header.segments.iter()
+        .enumerate()
+        .filter(|(_, segment)| segment.name == "ms2_window" && WindowKey::of(segment) == key)
+        .map(|(segment_index, _)| segment_index)
+        .collect();
+    if matching.is_empty() {
+        return Ok((None, 0));
+    }
+    let parts = matching.par_iter()
+        .map(|&segment_index| reader.segment(segment_index))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok((Some(columnar::merge_by_mz(parts)), matching.len()))
+}
+
+fn isolation_range(segment: &SegmentDescriptor) -> (f32, f32) {
+    let attr = |name: &str| segment.attrs.get(name).copied().unwrap_or(0.0) as f32;
+    (attr("isolation_low"), attr("isolation_high"))
+}
+
+fn isolation_window(segment: &SegmentDescriptor, index: usize) -> IsolationWindow {
+    let (low, high) = isolation_range(segment);
+    IsolationWindow::new(low, high, index)
+}
+
+// MS1 is sorted by m/z, so each shard covers an m/z interval recorded in its attrs and
+// range reads only touch the shards overlapping the query
+pub(crate) fn ms1_segments<'a, F: Float>(ms1_indexed: &'a IndexedTimsTOFData<F>, shard_plan: &ShardPlan) -> Vec<SegmentInput<'a, F>> {
+    shard_plan.ranges(ms1_indexed.mz_values.len()).into_iter()
+        .map(|rows| {
+            let mut attrs = BTreeMap::new();
+            if !rows.is_empty() {
+                attrs.insert("mz_min".to_string(), ms1_indexed.mz_values[rows.start].to_f64());
+                attrs.insert("mz_max".to_string(), ms1_indexed.mz_values[rows.end - 1].to_f64());
+            }
+            SegmentInput { name: "ms1".to_string(), attrs, data: ms1_indexed, rows, codecs: None }
+        })
+        .collect()
+}
+
+// No codecs: every column raw
+static RAW_CODECS: CodecMap = CodecMap::new();
+
+// Windows under `compression_min_bytes` are stored raw, since a few KB barely compress
+// and their codec frames cost more than they save; the others get the file's codecs
+// (codecs None) unless the caller sets them
+fn ms2_segments<F: Float>(ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)], compression_min_bytes: u64) -> Vec<SegmentInput<'_, F>> {
+    ms2_indexed_pairs.iter()
+        .map(|(window, data)| SegmentInput {
+            name: "ms2_window".to_string(),
+            attrs: BTreeMap::from([
+                ("isolation_low".to_string(), window.mz_low as f64),
+                ("isolation_high".to_string(), window.mz_high as f64),
+            ]),
+            data,
+            rows: 0..data.mz_values.len(),
+            codecs: (data.mz_values.len() as u64 * columnar::POINT_BYTES < compression_min_bytes).then_some(&RAW_CODECS),
+        })
+        .collect()
+}
+
+// describe_codecs of a stored segment, with SHUFFLED after the codec of shuffled columns
+fn describe_columns(segment: &SegmentDescriptor) -> String {
+    let codecs = segment_codecs(segment);
+    let shuffled = |name: &str| segment.column(name).is_some_and(|column| column.is_shuffled());
+    codecs.iter()
+        .map(|(column, codec)| format!("{}={}{}", column, codec.label(), if shuffled(column) { columnar::SHUFFLED } else { "" }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn segment_codecs(segment: &SegmentDescriptor) -> CodecMap {
+    INDEXED_COLUMNS.iter()
+        .filter_map(|(name, _)| {
+            let column = segment.column(name)?;
+            Some((*name, column.codec()?))
+        })
+        .collect()
+}
+
+// Segment indices of a container's MS1 shards, and of each MS2 window (in order of first
+// appearance, which numbers them) with its isolation range
+fn group_segments(header: &ContainerHeader) -> (Vec<usize>, Vec<(IsolationWindow, Vec<usize>)>) {
+    let mut ms1_segments = Vec::new();
+    let mut windows: Vec<(IsolationWindow, Vec<usize>)> = Vec::new();
+    for (segment_index, segment) in header.segments.iter().enumerate() {
+        if segment.name != "ms2_window" {
+            ms1_segments.push(segment_index);
+            continue;
+        }
+        let window = isolation_window(segment, windows.len());
+        match windows.iter_mut().find(|(known, _)| *known == window) {
+            Some((_, segments)) => segments.push(segment_index),
+            None => windows.push((window, vec![segment_index])),
+        }
+    }
+    (ms1_segments, windows)
+}
+
+// Rows of freshly indexed data that a damaged segment held: the MS1 shards of one save are
+// consecutive row ranges of the m/z-sorted data, an MS2 segment is one whole window
+fn regenerated_rows<'a>(
+    header: &ContainerHeader,
+    segment_index: usize,
+    ms1_indexed: &'a IndexedTimsTOFData,
+    ms2_indexed_pairs: &'a [(IsolationWindow, IndexedTimsTOFData)],
+) -> Result<(&'a IndexedTimsTOFData, Range<usize>), Box<dyn std::error::Error>> {
+    let segment = &header.segments[segment_index];
+    let mismatch = || format!("raw data does not match {} segment {} of the cache; rebuild it", segment.name, segment_index);
+    
+    if segment.name == "ms2_window" {
+        let window = WindowKey::of(segment);
+        if header.segments.iter().filter(|s| s.name == "ms2_window" && WindowKey::of(s) == window).count() > 1 {
+            return Err("MS2 window was extended by incremental updates and can't be repaired alone; rebuild the cache".into());
+        }
+        let (_, data) = ms2_indexed_pairs.iter()
+            .find(|(known, _)| known.key() == window)
+            .ok_or_else(mismatch)?;
+        if data.mz_values.len() as u64 != segment.rows {
+            return Err(mismatch().into());
+        }
+        return Ok((data, 0..data.mz_values.len()));
+    }
+    
+    let shards: Vec<&SegmentDescriptor> = header.segments.iter().filter(|s| s.name == "ms1").collect();
+    let bound = |segment: &SegmentDescriptor, key: &str| segment.attrs.get(key).copied();
+    if shards.windows(2).any(|pair| bound(pair[0], "mz_max") > bound(pair[1], "mz_min")) {
+        return Err("MS1 shards overlap after incremental updates and can't be repaired alone; rebuild the cache".into());
+    }
+    if shards.iter().map(|s| s.rows).sum::<u64>() != ms1_indexed.mz_values.len() as u64 {
+        return Err(mismatch().into());
+    }
+    let start = header.segments[..segment_index].iter().filter(|s| s.name == "ms1").map(|s| s.rows).sum::<u64>() as usize;
+    let rows = start..start + segment.rows as usize;
+    if let (Some(mz_min), Some(mz_max), false) = (bound(segment, "mz_min"), bound(segment, "mz_max"), rows.is_empty()) {
+        if ms1_indexed.mz_values[rows.start] as f64 != mz_min || ms1_indexed.mz_values[rows.end - 1] as f64 != mz_max {
+            return Err(mismatch().into());
+        }
+    }
+    Ok((ms1_indexed, rows))
+}
+
+// Incremental updates append a new segment per window, so one isolation window can be
+// stored in several segments; merge them, keeping the windows in first-stored order and
+// numbering them in it
+fn group_windows(pairs: Vec<(IsolationWindow, IndexedTimsTOFData)>) -> Vec<(IsolationWindow, IndexedTimsTOFData)> {
+    let mut windows: Vec<(IsolationWindow, Vec<IndexedTimsTOFData>)> = Vec::new();
+    for (window, data) in pairs {
+        match windows.iter_mut().find(|(known, _)| *known == window) {
+            Some((_, parts)) => parts.push(data),
+            None => windows.push((window.at(windows.len()), vec![data])),
+        }
+    }
+    windows.into_iter().map(|(window, parts)| (window, columnar::merge_by_mz(parts))).collect()
+}
+
+// What a save wrote, for the metadata that marks it complete (CacheManager::commit_save)
+struct SavedData {
+    ms1_codecs: CodecMap,
+    ms2_codecs: CodecMap,
+    shard_plan: ShardPlan,
+    windows: Vec<WindowKey>,    // In save order
+    last_frame: Option<u32>,
+    non_finite_rows: usize,
+    sorted_on_save: bool,
+    summary: DatasetSummary,
+    scheme: Option<WindowScheme>, // None without MS2
+    frame_index: bool,          // Sidecars written
+    rt_major: bool,
+}
+
+// Highest frame index in the data, recorded as last_frame so updates know where to resume
+fn max_frame<F>(ms1_indexed: &IndexedTimsTOFData<F>, ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)]) -> Option<u32> {
+    std::iter::once(ms1_indexed)
+        .chain(ms2_indexed_pairs.iter().map(|(_, data)| data))
+        .filter_map(|data| data.frame_indices.iter().max().copied())
+        .max()
+}
+
+// The points of frames after `last_frame`, still in m/z order
+fn frames_after(data: &IndexedTimsTOFData, last_frame: u32) -> IndexedTimsTOFData {
+    let keep: Vec<usize> = (0..data.frame_indices.len()).filter(|&i| data.frame_indices[i] > last_frame).collect();
+    IndexedTimsTOFData {
+        rt_values_min: keep.iter().map(|&i| data.rt_values_min[i]).collect(),
+        mobility_values: keep.iter().map(|&i| data.mobility_values[i]).collect(),
+        mz_values: keep.iter().map(|&i| data.mz_values[i]).collect(),
+        intensity_values: keep.iter().map(|&i| data.intensity_values[i]).collect(),
+        frame_indices: keep.iter().map(|&i| data.frame_indices[i]).collect(),
+        scan_indices: keep.iter().map(|&i| data.scan_indices[i]).collect(),
+    }
+}
+
+// The points with rt in [rt_min, rt_max), still in m/z order
+fn rt_slice(data: &IndexedTimsTOFData, rt_min: f32, rt_max: f32) -> IndexedTimsTOFData {
+    let keep: Vec<usize> = (0..data.rt_values_min.len())
+        .filter(|&i| data.rt_values_min[i] >= rt_min && data.rt_values_min[i] < rt_max)
+        .collect();
+    IndexedTimsTOFData {
+        rt_values_min: keep.iter().map(|&i| data.rt_values_min[i]).collect(),
+        mobility_values: keep.iter().map(|&i| data.mobility_values[i]).collect(),
+        mz_values: keep.iter().map(|&i| data.mz_values[i]).collect(),
+        intensity_values: keep.iter().map(|&i| data.intensity_values[i]).collect(),
+        frame_indices: keep.iter().map(|&i| data.frame_indices[i]).collect(),
+        scan_indices: keep.iter().map(|&i| data.scan_indices[i]).collect(),
+    }
+}
+
+// Encoding of the first MS1 column, for range-read descriptions; an MS1 container with no
+// MS1 shard, or a shard without columns, is corrupt
+fn ms1_encoding(header: &ContainerHeader, path: &Path) -> Result<String, std::io::Error> {
+    header.segments.iter()
+        .find(|segment| segment.name == "ms1")
+        .and_then(|segment| segment.columns.first())
+        .map(|column| column.encoding.clone())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} holds no MS1 shard with columns", path.display())))
+}
+
+// `path` against its checksum `field` in the signed part of the metadata
+fn check_signed_checksum(body: &str, field: &str, path: &Path) -> Result<(), std::io::Error> {
+    let expected = body.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(": "))
+        .ok_or_else(|| signing::tampered(format!("{} missing from signed metadata", field)))?;
+    if signing::sha256_file(path)? != expected.trim() {
+        return Err(signing::tampered(format!("{} was modified after it was signed", path.display())));
+    }
+    Ok(())
+}
+
+// Unsigned metadata lines minus the given keys and the data and sidecar checksums
+fn carried_metadata(previous: &str, replaced: &[&str]) -> String {
+    let body = signing::split_signature(previous).map(|(body, _)| body).unwrap_or(previous);
+    body.lines()
+        .filter(|line| {
+            let key = line.split(": ").next().unwrap_or("");
+            !replaced.contains(&key) && !key.ends_with("_sha256")
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+// Data file of the raw frame tier (sidecar.rs envelope around TimsTOFRawData), signed as
+// raw_sha256 in the tier's metadata
+const RAW_TIER_MAGIC: &[u8; 8] = b"TSRAWFR1";
+
+// Metadata lines describing the data files, replaced when the cache changes form
+const FORM_SPECIFIC_METADATA: [&str; 10] = [
+    "form", "archive_codec", "ms1_compression", "ms2_compression", "ms1_codecs", "ms2_codecs",
+    "ms1_shards", "shard", "signing_key_id", "signature",
+];
+
+fn mz_range_of(data: &IndexedTimsTOFData, mz_min: f32, mz_max: f32) -> IndexedTimsTOFData {
+    let start = data.mz_values.partition_point(|&x| x < mz_min);
+    let end = data.mz_values.partition_point(|&x| x <= mz_max).max(start);
+    IndexedTimsTOFData {
+        rt_values_min: data.rt_values_min[start..end].to_vec(),
+        mobility_values: data.mobility_values[start..end].to_vec(),
+        mz_values: data.mz_values[start..end].to_vec(),
+        intensity_values: data.intensity_values[start..end].to_vec(),
+        frame_indices: data.frame_indices[start..end].to_vec(),
+        scan_indices: data.scan_indices[start..end].to_vec(),
+    }
+}
+
+// Summary of everything cached for one source folder
+#[derive(Debug, Clone)]
+pub struct CachedSource {
+    pub source_name: String,
+    pub size_bytes: u64,
+    pub cached_at: SystemTime,
+}
+
+// A cleared cache waiting in the trash, restorable until its retention runs out
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    pub source_name: String,
+    pub trashed_at: SystemTime,
+    pub size_bytes: u64,
+    path: PathBuf,
+}
+
+// A committed generation of a cache kept for rollback() (CacheConfig::history_generations)
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub generation: u64,
+    pub size_bytes: u64, // Of its files, which share their data with the live cache while unchanged
+    path: PathBuf,
+}
+
+// An MS1 shard or MS2 window that could not be read back intact
+#[derive(Debug, Clone)]
+pub struct ShardError {
+    pub file: PathBuf,
+    pub segment: Option<usize>,       // None when the whole file is missing or unreadable
+    pub name: String,                 // "ms1" | "ms2_window", empty for whole files
+    pub attrs: BTreeMap<String, f64>, // m/z bounds or isolation window of the segment
+    pub error: String,
+}
+
+impl fmt::Display for ShardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        match self.segment {
+            Some(segment) => write!(f, "{} segment {} ({}): {}", file, segment, self.name, self.error),
+            None => write!(f, "{}: {}", file, self.error),
+        }
+    }
+}
+
+// A departure from the shard layout the loaders rely on, found by validate_layout
+#[derive(Debug, Clone)]
+pub struct LayoutIssue {
+    pub file: PathBuf,
+    pub segment: Option<usize>, // None for problems of a whole file or the metadata
+    pub problem: String,
+}
+
+impl fmt::Display for LayoutIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        match self.segment {
+            Some(segment) => write!(f, "{} segment {}: {}", file, segment, self.problem),
+            None => write!(f, "{}: {}", file, self.problem),
         }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct LayoutReport {
+    pub ms1_shards: usize,
+    pub ms1_runs: usize,    // m/z-ordered runs of MS1 shards: one per save, plus one per update
+    pub ms2_windows: usize, // Distinct isolation windows
+    pub points: u64,
+    pub issues: Vec<LayoutIssue>,
+}
+
+impl LayoutReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+// What gc() may collect; files younger than min_age could belong to a save in progress
+#[derive(Debug, Clone, Copy)]
+pub struct GcOptions {
+    pub dry_run: bool, // Only report
+    pub min_age: Duration,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self { dry_run: false, min_age: Duration::from_secs(3600) }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    pub orphans: Vec<(PathBuf, u64)>,  // Collected files and their sizes
+    pub bytes: u64,
+    pub dropped_entries: Vec<String>,  // Manifest entries whose files were gone
+    pub adopted_entries: Vec<String>,  // Complete caches missing from the manifest
+    pub dry_run: bool,
+}
+
+// Behind CacheManager::global(); CacheManager methods take &self, so one instance is shared
+static GLOBAL_MANAGER: OnceLock<CacheManager> = OnceLock::new();
+
 pub struct CacheManager {
     cache_dir: PathBuf,
     config: CacheConfig,
+    remote: Option<Arc<dyn RemoteStore>>,
+    key_strategy: Arc<dyn CacheKeyStrategy>,
+    profiler: QueryProfiler,
+    async_pool: OnceLock<rayon::ThreadPool>,
+    hot: HotCache,
+    pool: Option<Arc<rayon::ThreadPool>>,
+    reports: Option<ReportWriter>,
+    content_keys: Mutex<HashMap<PathBuf, (content::Fingerprint, String)>>, // Content-addressed keys resolved so far
+}
+
+impl Default for CacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CacheManager {
@@ -34,220 +916,4456 @@ impl CacheManager {
         Self::with_config(CacheConfig::default())
     }
     
-    pub fn with_config(config: CacheConfig) -> Self {
-        let cache_dir = PathBuf::from(".timstof_cache");
-        fs::create_dir_all(&cache_dir).unwrap();
-        Self { cache_dir, config }
+    // Configured by the environment (CacheConfig::from_env), as the command-line tools are
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::with_config(CacheConfig::from_env()?))
+    }
+    
+    pub fn with_config(config: CacheConfig) -> Self {
+        let cache_dir = config.cache_dir.clone().unwrap_or_else(|| PathBuf::from(".timstof_cache"));
+        Self::with_cache_dir(cache_dir, config)
+    }
+    
+    // Cache in another directory, e.g. a per-job sandbox filled by clone_cache()
+    pub fn with_cache_dir(cache_dir: impl Into<PathBuf>, mut config: CacheConfig) -> Self {
+        // Extended-length on Windows: deep cache trees aren't held to MAX_PATH
+        let cache_dir = longpath::extended(&cache_dir.into());
+        if config.deterministic {
+            config.encryption_key = config.encryption_key.map(EncryptionKey::with_synthetic_nonces);
+        }
+        let profiler = match config.read_only {
+            true => QueryProfiler::in_memory(),
+            false => {
+                fs::create_dir_all(&cache_dir).unwrap();
+                QueryProfiler::new(&cache_dir)
+            }
+        };
+        let hot = HotCache::new(config.hot_cache_entries);
+        let pool = config.parallel_threads.map(|threads| {
+            Arc::new(rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(|i| format!("timstof-io-{}", i))
+                .build()
+                .expect("failed to build cache I/O pool"))
+        });
+        Self { cache_dir, config, remote: None, key_strategy: Arc::new(PathHashKey), profiler, async_pool: OnceLock::new(), hot, pool, reports: None, content_keys: Mutex::default() }
+    }
+    
+    // Cache on read-only storage (a shared network volume, a container image): loads and
+    // queries work and nothing is written to the directory, not even the bookkeeping
+    // (query profile, content index, legacy-name migration, remote mirror). Operations
+    // that modify the cache fail with PermissionDenied.
+    pub fn open_read_only(cache_dir: impl Into<PathBuf>) -> Self {
+        Self::with_cache_dir(cache_dir, CacheConfig { read_only: true, ..CacheConfig::default() })
+    }
+    
+    // Run shard encoding/decoding on `pool` (e.g. one the application sized for I/O)
+    // instead of whatever pool the caller is on
+    pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+    
+    // Name caches by `strategy` (keys.rs) instead of by location, e.g. by sample IDs the
+    // pipeline already has, so the same sample hits its cache on every machine
+    pub fn with_key_strategy(mut self, strategy: Arc<dyn CacheKeyStrategy>) -> Self {
+        self.key_strategy = strategy;
+        self
+    }
+    
+    // Caches keyed to indexing parameters (m/z binning, window scheme, ...): `fingerprint` is
+    // any hash of them the caller chooses; caches saved with other parameters never load
+    pub fn with_params_fingerprint(mut self, fingerprint: u64) -> Self {
+        self.config.params_fingerprint = Some(fingerprint);
+        self
+    }
+    
+    // Summaries of saves, updates and loads as JSON lines on `writer` instead of text on
+    // stdout (report.rs), e.g. a log file or pipe an orchestrator reads
+    pub fn with_json_reports(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.reports = Some(Arc::new(Mutex::new(writer)));
+        self
+    }
+    
+    // Two-tier mode: the remote store is the primary cache, cache_dir a bounded local mirror
+    pub fn with_remote(mut self, remote: Arc<dyn RemoteStore>) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+    
+    // Process-wide manager for code deep in call stacks that has none passed in. The first
+    // call initializes it with the defaults (CacheManager::new) unless set_global ran first.
+    pub fn global() -> &'static CacheManager {
+        GLOBAL_MANAGER.get_or_init(CacheManager::new)
+    }
+    
+    // Configure the global manager; only possible once, before its first use
+    pub fn set_global(manager: CacheManager) -> Result<&'static CacheManager, Box<dyn std::error::Error>> {
+        GLOBAL_MANAGER.set(manager)
+            .map_err(|_| "the global cache manager is already initialized; set_global must run before its first use")?;
+        Ok(Self::global())
+    }
+    
+    const CACHE_EXTENSIONS: [&'static str; 3] = ["cache.bin", "cache.lz4", "cache.parquet"];
+    // Optional files next to the data files, named "<source>.<file>", with their checksum
+    // field in signed metadata
+    const SIDECAR_FILES: [(&'static str, &'static str); 4] = [
+        ("frames.cache.bin", "frames_sha256"),
+        ("rt.cache.bin", "rt_sha256"),
+        ("chromatograms.cache.bin", "chromatograms_sha256"),
+        ("heatmap.cache.bin", "heatmap_sha256"),
+    ];
+    
+    fn ensure_writable(&self) -> Result<(), std::io::Error> {
+        match self.config.read_only {
+            true => Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("cache directory {} is opened read-only", self.cache_dir.display()),
+            )),
+            false => Ok(()),
+        }
+    }
+    
+    // Name the cache files of a source start with: its source key, plus the indexing
+    // parameter fingerprint if there is one, so caches for other parameters live side by side,
+    // plus the fingerprint of its registered calibration (calibration.rs). Resolving it reads
+    // the calibration registry, so operations resolve it once and hand it to the path
+    // helpers (get_cache_path, get_metadata_path, sidecar_path, ...) as `source_name`
+    fn cache_name(&self, source_path: &Path) -> String {
+        let source_key = self.source_key(source_path);
+        let name = match self.config.params_fingerprint {
+            Some(fingerprint) => format!("{}-p{:016x}", source_key, fingerprint),
+            None => source_key.clone(),
+        };
+        match CalibrationRegistry::load(&self.cache_dir).get(&source_key) {
+            Some(calibration) => format!("{}-c{:016x}", name, calibration.fingerprint()),
+            None => name,
+        }
+    }
+    
+    // The key strategy's key (by default the folder name plus a hash of its location,
+    // content::path_key), or in content-addressed mode a hash of its content
+    fn source_key(&self, source_path: &Path) -> String {
+        if !self.config.content_addressed {
+            return match self.key_strategy.key(source_path).and_then(keys::checked) {
+                Ok(key) => key,
+                // Sources a user-supplied strategy has no key for are cached by location
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => content::path_key(source_path),
+                Err(e) => {
+                    telemetry::error(&format!("✗ No {} key for {} ({}), caching it by location", self.key_strategy.describe(), source_path.display(), e));
+                    content::path_key(source_path)
+                }
+            };
+        }
+        // Known and unchanged since: no index read, no index write
+        let fingerprint = content::fingerprint(source_path).ok();
+        if let Some((known, key)) = self.content_keys.lock().unwrap().get(source_path) {
+            if fingerprint.as_ref() == Some(known) {
+                return key.clone();
+            }
+        }
+        let mut index = ContentIndex::load(&self.cache_dir);
+        let key = index.key_for(source_path).and_then(|key| match index.changed() && !self.config.read_only {
+            true => index.save().map(|_| key),
+            false => Ok(key),
+        });
+        match key {
+            Ok(key) => {
+                if let Some(fingerprint) = fingerprint {
+                    self.content_keys.lock().unwrap().insert(source_path.to_path_buf(), (fingerprint, key.clone()));
+                }
+                key
+            }
+            Err(e) => {
+                telemetry::error(&format!("✗ Could not hash {} ({}), caching it by location", source_path.display(), e));
+                content::path_key(source_path)
+            }
+        }
+    }
+    
+    // Caches written before keys included the location are named after the folder alone.
+    // Such a cache moves to the source's key unless its metadata or the manifest records
+    // it for another folder of the same name (same-named folders used to share one cache).
+    fn migrate_legacy_cache(&self, source_path: &Path) -> Result<(), std::io::Error> {
+        if self.config.content_addressed || self.config.read_only || self.config.params_fingerprint.is_some() {
+            return Ok(());
+        }
+        let legacy_name = match source_path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return Ok(()), // Never had a legacy name
+        };
+        let legacy_meta = self.cache_dir.join(format!("{}.meta", legacy_name));
+        if !legacy_meta.exists() {
+            return Ok(());
+        }
+        let source_name = &self.cache_name(source_path);
+        if self.get_metadata_path(source_name).exists() {
+            return Ok(());
+        }
+        let metadata = fdlimit::read_to_string(&legacy_meta)?;
+        let owner = metadata.lines().find_map(|line| line.strip_prefix("source: ")).map(str::to_string).or_else(|| {
+            let manifest = Manifest::read(&self.cache_dir)?;
+            Some(manifest.sources.get(legacy_name)?.source_path.as_ref()?.display().to_string())
+        });
+        if owner.is_some_and(|owner| owner != recorded_source(source_path)) {
+            return Ok(());
+        }
+        
+        // Metadata last: an interrupted migration leaves it behind and finishes next time
+        let mut files = self.source_files(legacy_name)?;
+        files.sort_by_key(|path| path.extension().is_some_and(|ext| ext == "meta"));
+        for path in files {
+            let suffix = file_name_of(&path)[legacy_name.len()..].to_string();
+            fs::rename(&path, self.cache_dir.join(format!("{}{}", source_name, suffix)))?;
+        }
+        self.drop_from_manifest(legacy_name)?;
+        self.record_in_manifest(source_path, source_name)?;
+        telemetry::info(&format!("Migrated cache {} to {}", legacy_name, source_name));
+        Ok(())
+    }
+    
+    // Content-addressed mode: record that `source_path` uses its (possibly shared) cache
+    fn add_content_ref(&self, source_path: &Path) -> Result<(), std::io::Error> {
+        if self.config.content_addressed && !self.config.read_only {
+            let mut index = ContentIndex::load(&self.cache_dir);
+            let key = index.key_for(source_path)?;
+            if index.add_ref(&key, source_path) {
+                index.save()?;
+            }
+        }
+        Ok(())
+    }
+    
+    // Where a save with the current configuration writes
+    fn configured_cache_path(&self, source_name: &str, cache_type: &str) -> PathBuf {
+        let extension = match self.config.format {
+            CacheFormat::Parquet { .. } => "cache.parquet",
+            CacheFormat::Columnar | CacheFormat::SingleFile if self.should_compress_file(cache_type) => "cache.lz4",
+            CacheFormat::Columnar | CacheFormat::SingleFile => "cache.bin",
+        };
+        let cache_name = format!("{}.{}.{}", source_name, cache_type, extension);
+        self.cache_dir.join(cache_name)
+    }
+    
+    // Where to read from: a cache written under a different format or compression
+    // setting is still found (the reader detects the format from the file itself). Like
+    // every cache path, joined onto cache_dir, already extended-length on Windows
+    fn get_cache_path(&self, source_name: &str, cache_type: &str) -> PathBuf {
+        let configured = self.configured_cache_path(source_name, cache_type);
+        if configured.exists() {
+            return configured;
+        }
+        Self::CACHE_EXTENSIONS.iter()
+            .map(|extension| self.cache_dir.join(format!("{}.{}.{}", source_name, cache_type, extension)))
+            .find(|path| path.exists())
+            .unwrap_or(configured)
+    }
+    
+    // Last committed generation of a cache's files (snapshot.rs)
+    fn get_generation_path(&self, source_name: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.generation", source_name))
+    }
+    
+    fn get_progress_path(&self, source_name: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.progress", source_name))
+    }
+    
+    // Accepts a registered alias or a source path, see AliasRegistry. A cache the source
+    // has under its pre-hash name is moved to its current key on the way.
+    pub fn resolve_source(&self, alias_or_path: &Path) -> PathBuf {
+        let source_path = AliasRegistry::load(&self.cache_dir).resolve(alias_or_path);
+        if let Err(e) = self.migrate_legacy_cache(&source_path) {
+            telemetry::error(&format!("✗ Could not migrate the cache of {}: {}", source_path.display(), e));
+        }
+        source_path
+    }
+    
+    pub fn register_alias(&self, alias: &str, source_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let mut registry = AliasRegistry::load(&self.cache_dir);
+        if let Some(previous) = registry.register(alias, source_path)? {
+            telemetry::info(&format!("Alias {} previously pointed to {}", alias, previous.display()));
+        }
+        registry.save()?;
+        telemetry::info(&format!("Alias {} → {}", alias, registry.get(alias).unwrap().display()));
+        Ok(())
+    }
+    
+    pub fn remove_alias(&self, alias: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let mut registry = AliasRegistry::load(&self.cache_dir);
+        registry.remove(alias).ok_or_else(|| format!("no alias named {}", alias))?;
+        registry.save()?;
+        telemetry::info(&format!("Alias {} removed", alias));
+        Ok(())
+    }
+    
+    // Make `calibration` part of the cache key of a source: caches indexed under another
+    // calibration are no longer served, and the one the source had until now is moved to
+    // the trash. Returns the calibration registered before, if any.
+    pub fn register_calibration(&self, source_path: &Path, calibration: Calibration) -> Result<Option<Calibration>, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let previous_name = self.cache_name(source_path);
+        let mut registry = CalibrationRegistry::load(&self.cache_dir);
+        let id = calibration.id.clone();
+        let previous = registry.register(&self.source_key(source_path), calibration)?;
+        registry.save()?;
+        if self.cache_name(source_path) != previous_name {
+            self.hot.remove(source_path);
+            self.trash_cache(&previous_name)?;
+        }
+        telemetry::info(&format!("Calibration {} registered for {}", id, source_path.display()));
+        Ok(previous)
+    }
+    
+    pub fn calibration(&self, source_path: &Path) -> Option<Calibration> {
+        self.registered_calibration(&self.resolve_source(source_path))
+    }
+    
+    fn registered_calibration(&self, source_path: &Path) -> Option<Calibration> {
+        CalibrationRegistry::load(&self.cache_dir).get(&self.source_key(source_path)).cloned()
+    }
+    
+    // Back to uncalibrated caching; the calibrated cache stays until cleared or evicted
+    pub fn remove_calibration(&self, source_path: &Path) -> Result<Option<Calibration>, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let mut registry = CalibrationRegistry::load(&self.cache_dir);
+        let removed = registry.remove(&self.source_key(source_path));
+        registry.save()?;
+        self.hot.remove(source_path);
+        Ok(removed)
+    }
+    
+    pub fn aliases(&self) -> Vec<(String, PathBuf)> {
+        AliasRegistry::load(&self.cache_dir).iter()
+            .map(|(alias, path)| (alias.to_string(), path.to_path_buf()))
+            .collect()
+    }
+    
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+    
+    fn get_metadata_path(&self, source_name: &str) -> PathBuf {
+        let meta_name = format!("{}.meta", source_name);
+        self.cache_dir.join(meta_name)
+    }
+    
+    // Bytes a save of this many points writes at most: the raw columns, or 80% of them
+    // where compressed (timsTOF data compresses far better), plus headers and metadata
+    fn estimated_save_bytes(&self, ms1_points: usize, ms2_points: usize) -> u64 {
+        // f64 widens the three float columns by 4 bytes each
+        let row_bytes = profile::ROW_BYTES + 3 * (self.config.precision.width() as u64 - 4);
+        let bytes = |points: usize, cache_type: &str| {
+            let raw = points as u64 * row_bytes;
+            if self.should_compress_file(cache_type) { raw / 5 * 4 } else { raw }
+        };
+        // The frame index and the RT-major copy each hold every point again, always compressed
+        let copies = u64::from(self.config.frame_index) + u64::from(self.config.rt_major);
+        let copies_bytes = copies * (ms1_points + ms2_points) as u64 * row_bytes / 5 * 4;
+        bytes(ms1_points, "ms1_indexed") + bytes(ms2_points, "ms2_indexed") + copies_bytes + 1024 * 1024
+    }
+    
+    // Check that `required` bytes fit into the cache directory before a save writes
+    // anything, evicting other sources first under LowSpacePolicy::EvictOldest. Filesystems
+    // that don't report their free space are not checked.
+    fn ensure_space(&self, source_name: &str, required: u64) -> Result<(), Box<dyn std::error::Error>> {
+        if self.config.on_low_space == LowSpacePolicy::Ignore {
+            return Ok(());
+        }
+        let mut available = match space::available_bytes(&self.cache_dir) {
+            Ok(available) => available,
+            Err(_) => return Ok(()),
+        };
+        if available >= required {
+            return Ok(());
+        }
+        if self.config.on_low_space == LowSpacePolicy::EvictOldest {
+            let mut sources: Vec<CachedSource> = self.cached_sources()?.into_iter().filter(|s| s.source_name != source_name).collect();
+            sources.sort_by_key(|s| s.cached_at);
+            // Evicting everything would not be enough: keep the caches
+            let evictable: u64 = sources.iter().map(|s| s.size_bytes).sum();
+            if available + evictable < required {
+                return Err(Box::new(InsufficientSpace { dir: self.cache_dir.clone(), required, available }));
+            }
+            for source in sources {
+                let freed = self.remove_source_files(&source.source_name)?;
+                telemetry::info(&format!("Evicted {} ({:.2} MB) to make room", source.source_name, freed as f32 / 1024.0 / 1024.0));
+                available = space::available_bytes(&self.cache_dir)?;
+                if available >= required {
+                    return Ok(());
+                }
+            }
+        }
+        Err(Box::new(InsufficientSpace { dir: self.cache_dir.clone(), required, available }))
+    }
+    
+    // Smart compression decision based on file type and size
+    fn should_compress_file(&self, cache_type: &str) -> bool {
+        if !self.config.auto_compression {
+            return self.config.enable_compression;
+        }
+        
+        // Only compress larger files where the CPU overhead is worth it
+        // MS2 data is typically much larger and benefits from compression
+        match cache_type {
+            "ms2_indexed" => true,  // Large, repetitive data - good compression ratio
+            "ms1_indexed" => false, // Smaller, less compressible - not worth the CPU cost
+            _ => false,
+        }
+    }
+    
+    fn source_file_paths(&self, source_name: &str) -> Vec<PathBuf> {
+        let mut paths = vec![
+            self.get_cache_path(source_name, "ms1_indexed"),
+            self.get_cache_path(source_name, "ms2_indexed"),
+            self.single_file_path(source_name),
+            self.archive_path(source_name),
+            self.get_metadata_path(source_name),
+        ];
+        paths.extend(Self::SIDECAR_FILES.iter().map(|(file, _)| self.sidecar_path(source_name, file)));
+        paths
+    }
+    
+    fn sidecar_path(&self, source_name: &str, file: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.{}", source_name, file))
+    }
+    
+    // Data file of the SingleFile format
+    fn single_file_path(&self, source_name: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.indexed.cache.bin", source_name))
+    }
+    
+    // Cold form written by compact_cache, also a single file
+    fn archive_path(&self, source_name: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.archive.cache.zst", source_name))
+    }
+    
+    // The container holding both MS1 and MS2 when the source has no separate MS1 file
+    fn combined_file(&self, source_name: &str) -> Option<PathBuf> {
+        if self.get_cache_path(source_name, "ms1_indexed").exists() {
+            return None;
+        }
+        [self.single_file_path(source_name), self.archive_path(source_name)].into_iter()
+            .find(|path| path.exists())
+    }
+    
+    fn is_archived(&self, source_name: &str) -> bool {
+        self.combined_file(source_name) == Some(self.archive_path(source_name))
+    }
+    
+    // Data files the cache of a source consists of, with their checksum field in signed
+    // metadata: the MS1/MS2 pair, the single file or the archive
+    fn data_files(&self, source_name: &str) -> Vec<(&'static str, PathBuf)> {
+        match self.combined_file(source_name) {
+            Some(path) if self.is_archived(source_name) => return vec![("archive_sha256", path)],
+            Some(path) => return vec![("data_sha256", path)],
+            None => {}
+        }
+        vec![
+            ("ms1_sha256", self.get_cache_path(source_name, "ms1_indexed")),
+            ("ms2_sha256", self.get_cache_path(source_name, "ms2_indexed")),
+        ]
+    }
+    
+    // Files signed metadata lists a checksum for: the data files and the sidecars present
+    fn signed_files(&self, source_name: &str) -> Vec<(&'static str, PathBuf)> {
+        let sidecars = Self::SIDECAR_FILES.iter()
+            .map(|(file, field)| (*field, self.sidecar_path(source_name, file)))
+            .filter(|(_, path)| path.exists());
+        self.data_files(source_name).into_iter().chain(sidecars).collect()
+    }
+    
+    // Two-tier read-through: pull missing files from the remote store into the local mirror
+    fn mirror_from_remote(&self, source_path: &Path, source_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let remote = match &self.remote {
+            Some(remote) if !self.config.read_only => remote,
+            _ => return Ok(()), // Read-only: range queries still go to the remote directly
+        };
+        
+        let mut fetched = 0u64;
+        for local_path in self.source_file_paths(source_name) {
+            let file_name = &file_name_of(&local_path);
+            if local_path.exists() || !remote.contains(file_name) {
+                continue;
+            }
+            let partial = local_path.with_extension("partial");
+            fetched += remote.fetch(file_name, &partial)?;
+            fs::rename(&partial, &local_path)?;
+        }
+        
+        if fetched > 0 {
+            telemetry::info(&format!("Mirrored {} from {} ({:.2} MB)", source_name, remote.describe(), fetched as f32 / 1024.0 / 1024.0));
+            if self.get_metadata_path(source_name).exists() {
+                self.record_in_manifest(source_path, source_name)?;
+            }
+        }
+        
+        let mut index = MirrorIndex::load(&self.cache_dir);
+        match fetched {
+            0 => index.touch(source_name),
+            _ => index.record(source_name),
+        }
+        if let Some(limit) = self.config.local_mirror_bytes {
+            self.evict_mirror_lru(&mut index, limit, source_name)?;
+        }
+        index.save()?;
+        
+        Ok(())
+    }
+    
+    // Drop least recently used mirrored sources until the mirror fits, never the one in use.
+    // Only sources the mirror fetched count: caches built here may exist nowhere else.
+    fn evict_mirror_lru(&self, index: &mut MirrorIndex, limit: u64, in_use: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sources = self.cached_sources()?;
+        sources.retain(|source| index.contains(&source.source_name));
+        let mut total: u64 = sources.iter().map(|s| s.size_bytes).sum();
+        
+        sources.sort_by_key(|s| index.last_access(&s.source_name));
+        for source in sources {
+            if total <= limit {
+                break;
+            }
+            if source.source_name == in_use {
+                continue;
+            }
+            total = total.saturating_sub(self.remove_source_files(&source.source_name)?);
+            index.remove(&source.source_name);
+        }
+        
+        Ok(())
+    }
+    
+    pub fn is_cache_valid(&self, source_path: &Path) -> bool {
+        let source_path = &self.resolve_source(source_path);
+        self.is_valid(source_path, &self.cache_name(source_path))
+    }
+    
+    // is_cache_valid of a resolved source; counted as a cache miss when false (telemetry.rs)
+    fn is_valid(&self, source_path: &Path, source_name: &str) -> bool {
+        let valid = self.cache_is_current(source_path, source_name);
+        if !valid {
+            telemetry::record_miss();
+        }
+        valid
+    }
+    
+    // Side-effect free: a cache only the remote store has is judged by its metadata there,
+    // and nothing is downloaded until a load needs the files (mirror_from_remote)
+    fn cache_is_current(&self, source_path: &Path, source_name: &str) -> bool {
+        let data_files = self.data_files(source_name);
+        let meta_path = self.get_metadata_path(source_name);
+        let local = meta_path.exists() && data_files.iter().all(|(_, path)| path.exists());
+        if !local && !self.remote_has_cache(source_name) {
+            return false;
+        }
+        let metadata = match self.read_metadata(source_name) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                telemetry::warn(&format!("Remote cache unavailable: {}", e));
+                return false;
+            }
+        };
+        
+        // Quantized data is only served to managers configured for it, never in place of exact data
+        if self.config.lossy.is_none() && lossy_description(&metadata).is_some() {
+            return false;
+        }
+        if schema_mismatch(&meta_path, &metadata).is_some() {
+            return false;
+        }
+        // The fingerprint is in the file names too; this catches renamed or copied caches
+        if recorded_params_fingerprint(&metadata) != self.config.params_fingerprint {
+            return false;
+        }
+        if recorded_calibration_fingerprint(&metadata) != self.registered_calibration(source_path).map(|calibration| calibration.fingerprint()) {
+            return false;
+        }
+        // Likewise f32 data for managers asking for f64 (Parquet caches are always f32)
+        if self.config.precision == Precision::F64
+            && !matches!(self.config.format, CacheFormat::Parquet { .. })
+            && recorded_precision(&metadata) == Precision::F32
+        {
+            return false;
+        }
+        
+        // An imported cache (import_bundle) of a source this machine doesn't have has
+        // nothing to be compared against
+        if !source_path.exists() && self.imported_marker_path(source_name).exists() {
+            return true;
+        }
+        
+        // Content-addressed names change whenever the content does, and a copied source is
+        // newer than the cache it shares, so modification times say nothing here
+        if self.config.content_addressed {
+            return true;
+        }
+        
+        // A remote cache counts as written now, as the copy mirroring it would be
+        let cache_modified = match local {
+            true => fs::metadata(&data_files[0].1).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH),
+            false => SystemTime::now(),
+        };
+        if let Some(fresh) = freshness::merged_is_fresh(&metadata, self.config.validation, cache_modified) {
+            return fresh;
+        }
+        freshness::is_fresh(source_path, self.config.validation, &metadata, cache_modified)
+    }
+    
+    // Whether the remote store (which a read-only manager doesn't mirror from) has the
+    // metadata and every data file of one form of the cache: the MS1/MS2 pair, the single
+    // file or the archive
+    fn remote_has_cache(&self, source_name: &str) -> bool {
+        let remote = match &self.remote {
+            Some(remote) if !self.config.read_only => remote,
+            _ => return false,
+        };
+        let has = |path: PathBuf| remote.contains(&file_name_of(&path));
+        has(self.get_metadata_path(source_name))
+            && ((has(self.get_cache_path(source_name, "ms1_indexed")) && has(self.get_cache_path(source_name, "ms2_indexed")))
+                || has(self.single_file_path(source_name))
+                || has(self.archive_path(source_name)))
+    }
+    
+    // The metadata of a source, from the remote store if it isn't mirrored (yet)
+    fn read_metadata(&self, source_name: &str) -> Result<String, std::io::Error> {
+        let meta_path = self.get_metadata_path(source_name);
+        match &self.remote {
+            Some(remote) if !meta_path.exists() && !self.config.read_only => {
+                String::from_utf8(remote.read(&file_name_of(&meta_path))?).map_err(std::io::Error::other)
+            }
+            _ => fdlimit::read_to_string(&meta_path),
+        }
+    }
+    
+    // OPTIMIZED: Sequential save with smart compression
+    // Empty input is a valid cache: no MS1 points is one empty shard, and MS2 windows
+    // without points (or no windows at all) load back as they were saved, on every format
+    pub fn save_indexed_data<F: Float>(
+        &self, 
+        source_path: &Path, 
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)]
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_indexed_data_cancellable(source_path, ms1_indexed, ms2_indexed_pairs, &CancellationToken::new())
+    }
+    
+    // save_indexed_data that stops at the next shard once `cancel` is cancelled, with an
+    // Interrupted error. The metadata is never written then, so the cache stays invalid;
+    // data files and shards already committed are kept for a later save of the same input
+    // with the same settings to resume from.
+    pub fn save_indexed_data_cancellable<F: Float>(
+        &self,
+        source_path: &Path,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        cancel: &CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_indexed(source_path, ms1_indexed, ms2_indexed_pairs, cancel, &[])
+    }
+    
+    // The save behind save_indexed_data*; `merged` lists the sources and frame offsets of a
+    // merged cache (merge_sources), empty for a cache of one source folder
+    fn save_indexed<F: Float>(
+        &self,
+        source_path: &Path,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        cancel: &CancellationToken,
+        merged: &[(PathBuf, u32)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        input::check_shapes(ms1_indexed, ms2_indexed_pairs)?;
+        let scrubbed = input::scrub_non_finite(ms1_indexed, ms2_indexed_pairs, self.config.non_finite)?;
+        let (ms1_sorted, sorted_on_save) = self.in_pool(|| input::sort_ms1(scrubbed.ms1, self.config.sort));
+        let (ms1_indexed, ms2_indexed_pairs) = (ms1_sorted.as_ref(), scrubbed.ms2.as_ref());
+        input::check_save_input(ms1_indexed, ms2_indexed_pairs)?;
+        if scrubbed.rows > 0 {
+            telemetry::warn(&format!("⚠️  {} row(s) with NaN or infinite values ({})", scrubbed.rows, self.config.non_finite.describe()));
+        }
+        if sorted_on_save {
+            telemetry::info(&format!("   MS1 was not sorted by m/z; sorted {} points before saving", ms1_indexed.mz_values.len()));
+        }
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        telemetry::info("Saving indexed data to optimized cache...");
+        let start_time = std::time::Instant::now();
+        let ms2_points = ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum();
+        self.ensure_space(source_name, self.estimated_save_bytes(ms1_indexed.mz_values.len(), ms2_points))?;
+        
+        // Metadata is written last and marks the cache complete, so drop the old one first:
+        // an interrupted save must never pair a previous metadata file with new data files
+        let meta_path = self.get_metadata_path(source_name);
+        if meta_path.exists() {
+            fs::remove_file(&meta_path)?;
+        }
+        
+        // Resume support: units and segments committed by an interrupted save of the same
+        // source, settings and input are kept
+        let shard_plan = self.shard_plan(ms1_indexed.mz_values.len());
+        let write_fingerprint = self.write_fingerprint(ms1_indexed, ms2_indexed_pairs, &shard_plan);
+        let mut progress = BuildProgress::load(&self.get_progress_path(source_name), source_path, write_fingerprint, self.config.durability);
+        
+        let (ms1_codecs, ms2_codecs) = match self.config.format {
+            CacheFormat::SingleFile => self.save_single_file(source_name, ms1_indexed, ms2_indexed_pairs, &shard_plan, &mut progress, cancel)?,
+            _ => self.save_split_files(source_name, ms1_indexed, ms2_indexed_pairs, &shard_plan, &mut progress, cancel)?,
+        };
+        cancel.check()?;
+        
+        // A frame index left by an earlier save would describe other data
+        let frame_index_path = self.sidecar_path(source_name, "frames.cache.bin");
+        if self.config.frame_index {
+            self.save_frame_index(&frame_index_path, ms1_indexed, ms2_indexed_pairs, Some(cancel))?;
+        } else if frame_index_path.exists() {
+            fs::remove_file(&frame_index_path)?;
+        }
+        let rt_major_path = self.sidecar_path(source_name, "rt.cache.bin");
+        if self.config.rt_major {
+            self.save_rt_major(&rt_major_path, ms1_indexed, ms2_indexed_pairs, Some(cancel))?;
+        } else if rt_major_path.exists() {
+            fs::remove_file(&rt_major_path)?;
+        }
+        let ms2_parts: Vec<&IndexedTimsTOFData<F>> = ms2_indexed_pairs.iter().map(|(_, data)| data).collect();
+        let summary = self.in_pool(|| FrameSummary::compute(ms1_indexed, &ms2_parts));
+        summary.write(&self.sidecar_path(source_name, "chromatograms.cache.bin"), self.config.encryption_key.as_ref())?;
+        let heatmap_path = self.sidecar_path(source_name, "heatmap.cache.bin");
+        if self.config.heatmap {
+            let start_time = std::time::Instant::now();
+            let pyramid = self.in_pool(|| HeatmapPyramid::build(ms1_indexed));
+            pyramid.write(&heatmap_path, self.config.encryption_key.as_ref())?;
+            telemetry::info(&format!("   ├── Heatmap: {:.3}s ({:.1} MB)", start_time.elapsed().as_secs_f32(),
+                     fs::metadata(&heatmap_path)?.len() as f32 / 1024.0 / 1024.0));
+        } else if heatmap_path.exists() {
+            fs::remove_file(&heatmap_path)?;
+        }
+        self.remove_shadowing_files(source_name)?;
+        
+        let saved = SavedData {
+            ms1_codecs,
+            ms2_codecs,
+            shard_plan,
+            windows: ms2_indexed_pairs.iter().map(|(window, _)| window.key()).collect(),
+            last_frame: max_frame(ms1_indexed, ms2_indexed_pairs),
+            non_finite_rows: scrubbed.rows,
+            sorted_on_save,
+            summary: self.in_pool(|| DatasetSummary::compute(ms1_indexed, ms2_indexed_pairs)),
+            scheme: (!ms2_indexed_pairs.is_empty()).then(|| WindowScheme::from_windows(ms2_indexed_pairs)),
+            frame_index: self.config.frame_index,
+            rt_major: self.config.rt_major,
+        };
+        self.commit_save(source_path, source_name, &saved, merged, start_time)
+    }
+    
+    // Save points as they come from `points`, e.g. a converter reading the raw data, holding
+    // at most CacheConfig::stream_buffer_points of them in memory: full buffers are spilled
+    // as sorted runs under cache_dir/spill, merged into the cache files at the end (stream.rs).
+    // `window_assigner` gives the MS2 window a point belongs to, None for MS1 points; windows
+    // are numbered in order of first appearance, whatever index the assigner gives them. Columnar format only. The frame index, RT-major copy and heatmap need
+    // all points at once and are not built; chromatograms, summary and window scheme are.
+    pub fn save_from_stream<I, A>(&self, source_path: &Path, points: I, mut window_assigner: A) -> Result<(), Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = TimsTOFPoint>,
+        A: FnMut(&TimsTOFPoint) -> Option<IsolationWindow>,
+    {
+        self.ensure_writable()?;
+        if self.config.format != CacheFormat::Columnar {
+            return Err("streaming saves are only supported by the columnar cache format".into());
+        }
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        telemetry::info("Saving streamed points to optimized cache...");
+        let start_time = std::time::Instant::now();
+        
+        // As in save_indexed; nothing of an interrupted save can be resumed, its points are gone
+        let meta_path = self.get_metadata_path(source_name);
+        if meta_path.exists() {
+            fs::remove_file(&meta_path)?;
+        }
+        let progress_path = self.get_progress_path(source_name);
+        if progress_path.exists() {
+            fs::remove_file(&progress_path)?;
+        }
+        
+        let ms1_cache_path = self.configured_cache_path(source_name, "ms1_indexed");
+        self.remove_stale_variants(&ms1_cache_path, source_name, "ms1_indexed")?;
+        let ms2_cache_path = self.configured_cache_path(source_name, "ms2_indexed");
+        self.remove_stale_variants(&ms2_cache_path, source_name, "ms2_indexed")?;
+        let ms1_compression = self.config.lossy.is_some();
+        let ms2_compression = self.should_compress_file("ms2_indexed") || self.config.lossy.is_some();
+        let mut spill = SpillDir::create(&self.cache_dir.join("spill").join(meta_path.file_name().unwrap_or_default()))?;
+        
+        // Decided on the first run, which is the only sample there is
+        let mut codecs: Option<(CodecMap, CodecMap)> = None;
+        let mut ms1_runs: Vec<PathBuf> = Vec::new();
+        let mut ms1_points = 0;
+        let mut window_runs: Vec<(IsolationWindow, Vec<PathBuf>)> = Vec::new();
+        let mut scheme = WindowScheme::default();
+        let mut summary: Option<DatasetSummary> = None;
+        let mut chromatograms = FrameSummary::default();
+        let mut last_frame: Option<u32> = None;
+        let mut non_finite_rows = 0;
+        let mut spill_run = |buffer: &mut StreamBuffer| -> Result<(), Box<dyn std::error::Error>> {
+            let run_start = std::time::Instant::now();
+            let points = buffer.len();
+            let (ms1, ms2) = self.in_pool(|| buffer.take_sorted());
+            let scrubbed = input::scrub_non_finite(&ms1, &ms2, self.config.non_finite)?;
+            let (ms1, ms2) = (scrubbed.ms1.as_ref(), scrubbed.ms2.as_ref());
+            input::check_save_input(ms1, ms2)?;
+            non_finite_rows += scrubbed.rows;
+            
+            if codecs.is_none() {
+                let sample = ms2.iter().map(|(_, data)| data).max_by_key(|data| data.mz_values.len());
+                codecs = Some((self.column_codecs("MS1", Some(ms1), ms1_compression)?, self.column_codecs("MS2", sample, ms2_compression)?));
+            }
+            if !ms1.mz_values.is_empty() {
+                ms1_runs.push(spill.write_run(ms1)?);
+                ms1_points += ms1.mz_values.len();
+            }
+            for (window, data) in ms2 {
+                let position = match window_runs.iter().position(|(known, _)| known == window) {
+                    Some(position) => position,
+                    None => {
+                        window_runs.push((window.at(window_runs.len()), Vec::new()));
+                        window_runs.len() - 1
+                    }
+                };
+                if !data.mz_values.is_empty() {
+                    window_runs[position].1.push(spill.write_run(data)?);
+                }
+            }
+            
+            scheme.add(ms2);
+            let run_summary = self.in_pool(|| DatasetSummary::compute(ms1, ms2));
+            summary = Some(match &summary {
+                Some(summary) => summary.merge(&run_summary),
+                None => run_summary,
+            });
+            let ms2_parts: Vec<&IndexedTimsTOFData> = ms2.iter().map(|(_, data)| data).collect();
+            chromatograms.merge(&self.in_pool(|| FrameSummary::compute(ms1, &ms2_parts)));
+            last_frame = last_frame.max(max_frame(ms1, ms2));
+            telemetry::info(&format!("   ├── Spilled run of {} points: {:.3}s", points, run_start.elapsed().as_secs_f32()));
+            Ok(())
+        };
+        
+        let mut buffer = StreamBuffer::default();
+        let mut spilled = false;
+        for point in points {
+            let window = window_assigner(&point);
+            buffer.push(point, window);
+            if buffer.len() >= self.config.stream_buffer_points.max(1) {
+                spill_run(&mut buffer)?;
+                spilled = true;
+            }
+        }
+        // An empty stream still gets its codecs, and empty files
+        if !buffer.is_empty() || !spilled {
+            spill_run(&mut buffer)?;
+        }
+        let (ms1_codecs, ms2_codecs) = codecs.take().unwrap_or_default();
+        let summary = summary.take().unwrap_or_else(|| DatasetSummary::compute::<f32>(&IndexedTimsTOFData::new(), &[]));
+        
+        // Merge the runs into the cache files: MS1 one shard at a time, so every shard is
+        // cut from the fully merged order as in save_indexed
+        let merge_start = std::time::Instant::now();
+        let shard_plan = self.shard_plan(ms1_points);
+        let ms1_options = WriteOptions { lossy: self.config.lossy, ..self.write_options(&ms1_codecs)? };
+        let mut ms1_writer = columnar::ContainerWriter::create(&ms1_cache_path, &ms1_options)?;
+        let mut ms1_merge = MergedRuns::open(&ms1_runs)?;
+        loop {
+            let shard = ms1_merge.next_chunk(shard_plan.points_per_shard)?;
+            // No points still get the single empty shard of an in-memory save
+            if shard.mz_values.is_empty() && !ms1_writer.segments().is_empty() {
+                break;
+            }
+            let segments = ms1_segments(&shard, &ShardPlan { shards: 1, ..shard_plan });
+            self.in_pool(|| ms1_writer.write(&segments, &ms1_options))?;
+            if shard.mz_values.len() < shard_plan.points_per_shard {
+                break;
+            }
+        }
+        let ms2_options = WriteOptions { lossy: self.config.lossy, ..self.write_options(&ms2_codecs)? };
+        let mut ms2_writer = columnar::ContainerWriter::create(&ms2_cache_path, &ms2_options)?;
+        // Windows in chunks of the stream buffer's size, each chunk a segment of the window
+        // as updates append them, so one dominant window is never held whole either
+        let chunk_points = self.config.stream_buffer_points.max(1);
+        for (window, runs) in &window_runs {
+            let mut window_merge = MergedRuns::open(runs)?;
+            let mut segments = 0;
+            loop {
+                let chunk = window_merge.next_chunk(chunk_points)?;
+                // A window without points still gets its one empty segment
+                if chunk.mz_values.is_empty() && segments > 0 {
+                    break;
+                }
+                let last = chunk.mz_values.len() < chunk_points;
+                let window = [(*window, chunk)];
+                self.in_pool(|| ms2_writer.write(&ms2_segments(&window, self.config.window_compression_min_bytes), &ms2_options))?;
+                segments += 1;
+                if last {
+                    break;
+                }
+            }
+        }
+        let ms1_shards = ms1_writer.segments().len();
+        ms1_writer.finish(&ms1_options)?;
+        ms2_writer.finish(&ms2_options)?;
+        drop(spill);
+        telemetry::info(&format!("   ├── Merged {} run(s) into {} MS1 shards and {} MS2 windows: {:.3}s",
+                 ms1_runs.len(), ms1_shards, window_runs.len(), merge_start.elapsed().as_secs_f32()));
+        
+        // Sidecars of an earlier save would describe other data
+        for file in ["frames.cache.bin", "rt.cache.bin", "heatmap.cache.bin"] {
+            let path = self.sidecar_path(source_name, file);
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        if self.config.frame_index || self.config.rt_major || self.config.heatmap {
+            telemetry::warn(&format!("⚠️  Streaming saves don't build the frame index, RT-major copy or heatmap of {}", source_path.display()));
+        }
+        chromatograms.write(&self.sidecar_path(source_name, "chromatograms.cache.bin"), self.config.encryption_key.as_ref())?;
+        self.remove_shadowing_files(source_name)?;
+        
+        let saved = SavedData {
+            ms1_codecs,
+            ms2_codecs,
+            shard_plan: ShardPlan { shards: ms1_shards, ..shard_plan },
+            windows: window_runs.iter().map(|(window, _)| window.key()).collect(),
+            last_frame,
+            non_finite_rows,
+            sorted_on_save: true,
+            summary,
+            scheme: (!scheme.windows.is_empty()).then_some(scheme),
+            frame_index: false,
+            rt_major: false,
+        };
+        self.commit_save(source_path, source_name, &saved, &[], start_time)
+    }
+    
+    // Data files of the other layout or an archive would shadow those of a new save
+    fn remove_shadowing_files(&self, source_name: &str) -> Result<(), std::io::Error> {
+        let stale: Vec<PathBuf> = match self.config.format {
+            CacheFormat::SingleFile => ["ms1_indexed", "ms2_indexed"].iter()
+                .map(|cache_type| self.get_cache_path(source_name, cache_type))
+                .collect(),
+            _ => vec![self.single_file_path(source_name)],
+        };
+        for path in stale.into_iter().chain([self.archive_path(source_name)]) {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+    
+    // Writes the metadata of a save whose data files are in place, marking the cache complete
+    fn commit_save(
+        &self,
+        source_path: &Path, source_name: &str,
+        saved: &SavedData,
+        merged: &[(PathBuf, u32)],
+        start_time: std::time::Instant,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let meta_path = self.get_metadata_path(source_name);
+        let progress_path = self.get_progress_path(source_name);
+        
+        // Save metadata. Parquet files are never quantized and always f32.
+        let parquet = matches!(self.config.format, CacheFormat::Parquet { .. });
+        let lossy = self.config.lossy
+            .filter(|_| !parquet)
+            .map(|lossy| lossy.describe());
+        let precision = if parquet { Precision::F32 } else { self.config.precision };
+        if let Some(lossy) = &lossy {
+            telemetry::warn(&format!("⚠️  Lossy cache ({}): for QC and visualization, not quantitation", lossy));
+        }
+        let mut metadata = format!(
+            "cached at: {:?}\nsource: {}\nms2_windows: {}\ntype: indexed\nform: fast\nms1_compression: {}\nms2_compression: {}\nformat: {}\nencryption: {}\nlossy: {}\nprecision: {}\nserialization: {}\nschema: {}\nframe_index: {}\nrt_major: {}\nversion: 3.0\n",
+            self.recorded_time(),
+            recorded_source(source_path),
+            saved.windows.len(),
+            saved.ms1_codecs.values().any(|codec| *codec != ColumnCodec::Raw),
+            saved.ms2_codecs.values().any(|codec| *codec != ColumnCodec::Raw),
+            match self.config.format {
+                CacheFormat::Columnar => "columnar",
+                CacheFormat::SingleFile => "single-file",
+                CacheFormat::Parquet { .. } => "parquet",
+            },
+            self.encryption_description(),
+            lossy.as_deref().unwrap_or("false"),
+            precision.dtype(),
+            if parquet { "parquet".to_string() } else { format!("container-v{}", columnar::FORMAT_VERSION) },
+            columnar::schema_hash(),
+            saved.frame_index,
+            saved.rt_major
+        );
+        metadata.push_str(&match merged.is_empty() {
+            true => freshness::source_metadata(source_path, self.config.validation)?,
+            false => freshness::merged_source_metadata(merged, self.config.validation)?,
+        });
+        metadata.push_str(&format!(
+            "ms2_window_ids: {}\n",
+            window_ids(saved.windows.iter().copied())
+        ));
+        if let Some(last_frame) = saved.last_frame {
+            metadata.push_str(&format!("last_frame: {}\n", last_frame));
+        }
+        if self.config.non_finite != NonFinitePolicy::Keep {
+            metadata.push_str(&format!("non_finite: {}\nnon_finite_rows: {}\n", self.config.non_finite.describe(), saved.non_finite_rows));
+        }
+        metadata.push_str(&format!("sort_order: mz-ascending\nsorted_on_save: {}\n", saved.sorted_on_save));
+        if self.config.deterministic {
+            metadata.push_str("deterministic: true\n");
+        }
+        metadata.push_str(&summary_line(&saved.summary)?);
+        if let Some(scheme) = &saved.scheme {
+            metadata.push_str(&scheme_line(scheme)?);
+        }
+        if let Some(fingerprint) = self.config.params_fingerprint {
+            metadata.push_str(&format!("params_fingerprint: {:016x}\n", fingerprint));
+        }
+        if let Some(calibration) = self.registered_calibration(source_path) {
+            let json = serde_json::to_string(&calibration).map_err(std::io::Error::other)?;
+            metadata.push_str(&format!("calibration: {}\ncalibration_fingerprint: {:016x}\n", json, calibration.fingerprint()));
+        }
+        if let Some(instrument) = InstrumentInfo::read(source_path).filter(|_| merged.is_empty()) {
+            let json = serde_json::to_string(&instrument).map_err(std::io::Error::other)?;
+            metadata.push_str(&format!("instrument: {}\n", json));
+        }
+        metadata.push_str(&self.provenance_line(start_time)?);
+        if !matches!(self.config.format, CacheFormat::Parquet { .. }) {
+            metadata.push_str(&format!(
+                "ms1_shards: {}\nshard_points: {}\nshard_tuning: {}\nms1_codecs: {}\nms2_codecs: {}\nbyte_shuffle: {}\n",
+                saved.shard_plan.shards, saved.shard_plan.points_per_shard, saved.shard_plan.tuning,
+                compression::describe_codecs(&saved.ms1_codecs), compression::describe_codecs(&saved.ms2_codecs), self.config.byte_shuffle
+            ));
+            metadata.push_str(&shard_lines(&shard_table(&self.data_files(source_name))?)?);
+        }
+        // Loaders binary-search shards and skip them by their recorded bounds, so a layout
+        // that breaks those assumptions must never be marked complete
+        if !matches!(self.config.format, CacheFormat::Parquet { .. }) {
+            let layout = self.check_layout(&self.data_files(source_name), &meta_path, &metadata);
+            if !layout.is_valid() {
+                for issue in &layout.issues {
+                    telemetry::error(&format!("✗ Layout: {}", issue));
+                }
+                let _ = fs::remove_file(&progress_path);
+                return Err(format!("saved cache has {} layout problem(s), not marking it complete", layout.issues.len()).into());
+            }
+        }
+        let metadata = match &self.config.signing_key {
+            Some(key) => self.sign_metadata(key, metadata, &self.signed_files(source_name))?,
+            None => metadata,
+        };
+        durability::write_file(&meta_path, metadata, self.config.durability)?;
+        self.commit_generation(source_name)?;
+        let _ = fs::remove_file(&progress_path);
+        self.add_content_ref(source_path)?;
+        self.record_in_manifest(source_path, source_name)?;
+        
+        // Publish to the primary (remote) cache in two-tier mode. Built here, the cache is no
+        // longer a mirrored copy the mirror's bound may evict.
+        if let Some(remote) = &self.remote {
+            for local_path in self.source_file_paths(source_name).into_iter().filter(|path| path.exists()) {
+                remote.put(&file_name_of(&local_path), &local_path)?;
+            }
+            telemetry::info(&format!("Published cache to {}", remote.describe()));
+            let mut index = MirrorIndex::load(&self.cache_dir);
+            if index.contains(source_name) {
+                index.remove(source_name);
+                index.save()?;
+            }
+        }
+        
+        let elapsed = start_time.elapsed();
+        let mut total_size = 0u64;
+        for (_, path) in self.data_files(source_name) {
+            total_size += fs::metadata(&path)?.len();
+        }
+        telemetry::record_write("save", total_size, elapsed);
+        
+        let points = saved.summary.ms1_points + saved.summary.ms2_points;
+        let shards = (!parquet).then_some(saved.shard_plan.shards);
+        self.report(OperationReport::new("save", source_path, total_size, points, elapsed).with_layout(shards, saved.windows.len()), || {
+            telemetry::info(&format!("✅ Optimized cache saved: {:.2} MB total", total_size as f32 / 1024.0 / 1024.0));
+            telemetry::info(&format!("   └── Total time: {:.3}s", elapsed.as_secs_f32()));
+        });
+        
+        Ok(())
+    }
+    
+    // The frame index sidecar (frames.rs): MS1 and MS2 points sorted by frame, compressed
+    // with the configured codec (the rt and frame columns are nearly constant)
+    fn save_frame_index<F: Float>(
+        &self,
+        path: &Path,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), std::io::Error> {
+        let start_time = std::time::Instant::now();
+        let ms2_parts: Vec<&IndexedTimsTOFData<F>> = ms2_indexed_pairs.iter().map(|(_, data)| data).collect();
+        let (ms1_sorted, ms2_sorted) = self.in_pool(|| (frames::frame_sorted(&[ms1_indexed]), frames::frame_sorted(&ms2_parts)));
+        let mut segments = frames::segments("ms1", &ms1_sorted);
+        segments.extend(frames::segments("ms2", &ms2_sorted));
+        let codecs = self.uniform_codecs(true);
+        self.config.retry.run(|| format!("writing {}", path.display()), |_| {
+            self.in_pool(|| columnar::write_container(path, &segments, &WriteOptions { cancel, ..self.write_options(&codecs)? })).map(drop)
+        })?;
+        telemetry::info(&format!("   ├── Frame index: {:.3}s ({:.1} MB, {} segments)", start_time.elapsed().as_secs_f32(),
+                 fs::metadata(path)?.len() as f32 / 1024.0 / 1024.0, segments.len()));
+        Ok(())
+    }
+    
+    // The RT-major copy (layout.rs), compressed like the frame index
+    fn save_rt_major<F: Float>(
+        &self,
+        path: &Path,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), std::io::Error> {
+        let start_time = std::time::Instant::now();
+        let (ms1_sorted, ms2_sorted) = self.in_pool(|| {
+            let ms2_sorted: Vec<_> = ms2_indexed_pairs.par_iter().map(|(window, data)| (*window, layout::rt_sorted(data))).collect();
+            (layout::rt_sorted(ms1_indexed), ms2_sorted)
+        });
+        let segments = layout::segments(&ms1_sorted, &ms2_sorted);
+        let codecs = self.uniform_codecs(true);
+        self.config.retry.run(|| format!("writing {}", path.display()), |_| {
+            self.in_pool(|| columnar::write_container(path, &segments, &WriteOptions { cancel, ..self.write_options(&codecs)? })).map(drop)
+        })?;
+        telemetry::info(&format!("   ├── RT-major copy: {:.3}s ({:.1} MB, {} segments)", start_time.elapsed().as_secs_f32(),
+                 fs::metadata(path)?.len() as f32 / 1024.0 / 1024.0, segments.len()));
+        Ok(())
+    }
+    
+    // MS1 and MS2 in separate files, each a resumable unit of the save; columnar files resume
+    // after their last committed shard or window
+    fn save_split_files<F: Float>(
+        &self,
+        source_name: &str,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        shard_plan: &ShardPlan,
+        progress: &mut BuildProgress,
+        cancel: &CancellationToken,
+    ) -> Result<(CodecMap, CodecMap), Box<dyn std::error::Error>> {
+        // Save MS1 data (fast, no compression unless lossy)
+        let ms1_start = std::time::Instant::now();
+        let ms1_cache_path = self.configured_cache_path(source_name, "ms1_indexed");
+        self.remove_stale_variants(&ms1_cache_path, source_name, "ms1_indexed")?;
+        let ms1_compression = self.config.lossy.is_some();
+        let ms1_codecs = if progress.is_committed("ms1_indexed", &ms1_cache_path) {
+            telemetry::info("   MS1 already committed by an interrupted build, skipping");
+            self.stored_codecs(&ms1_cache_path, ms1_compression)?
+        } else {
+            let codecs = self.column_codecs("MS1", Some(ms1_indexed), ms1_compression)?;
+            self.save_ms1_file(&ms1_cache_path, ms1_indexed, shard_plan, &codecs, Some(cancel), Some(&mut *progress))?;
+            progress.commit("ms1_indexed")?;
+            codecs
+        };
+        let ms1_time = ms1_start.elapsed();
+        cancel.check()?;
+        
+        // Save MS2 data (with smart compression)
+        let ms2_start = std::time::Instant::now();
+        let ms2_cache_path = self.configured_cache_path(source_name, "ms2_indexed");
+        self.remove_stale_variants(&ms2_cache_path, source_name, "ms2_indexed")?;
+        let use_compression = self.should_compress_file("ms2_indexed") || self.config.lossy.is_some();
+        let ms2_codecs = if progress.is_committed("ms2_indexed", &ms2_cache_path) {
+            telemetry::info("   MS2 already committed by an interrupted build, skipping");
+            self.stored_codecs(&ms2_cache_path, use_compression)?
+        } else {
+            // The largest window is the most representative sample
+            let sample = ms2_indexed_pairs.iter().map(|(_, data)| data).max_by_key(|data| data.mz_values.len());
+            let codecs = self.column_codecs("MS2", sample, use_compression)?;
+            self.save_ms2_file(&ms2_cache_path, ms2_indexed_pairs, &codecs, Some(cancel), Some(&mut *progress))?;
+            progress.commit("ms2_indexed")?;
+            codecs
+        };
+        let ms2_time = ms2_start.elapsed();
+        
+        let ms1_size = fs::metadata(&ms1_cache_path)?.len();
+        let ms2_size = fs::metadata(&ms2_cache_path)?.len();
+        telemetry::info(&format!("   ├── MS1: {:.3}s ({:.1} MB, {} shards)", ms1_time.as_secs_f32(), ms1_size as f32 / 1024.0 / 1024.0, shard_plan.shards));
+        telemetry::info(&format!("   ├── MS2: {:.3}s ({:.1} MB, compressed: {})", ms2_time.as_secs_f32(), ms2_size as f32 / 1024.0 / 1024.0,
+                 ms2_codecs.values().any(|codec| *codec != ColumnCodec::Raw)));
+        Ok((ms1_codecs, ms2_codecs))
+    }
+    
+    // MS1 shards and MS2 windows in one container and one resumable unit, resumed segment by
+    // segment, so a source has a single data file. Readers find segments through the footer index and pread them in
+    // parallel exactly as with separate files.
+    fn save_single_file<F: Float>(
+        &self,
+        source_name: &str,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        shard_plan: &ShardPlan,
+        progress: &mut BuildProgress,
+        cancel: &CancellationToken,
+    ) -> Result<(CodecMap, CodecMap), Box<dyn std::error::Error>> {
+        let path = self.single_file_path(source_name);
+        if progress.is_committed("indexed", &path) {
+            telemetry::info("   Data already committed by an interrupted build, skipping");
+            return Ok(self.stored_codecs_by_segment(&path)?);
+        }
+        
+        let start_time = std::time::Instant::now();
+        let lossy = self.config.lossy.is_some();
+        let ms1_codecs = self.column_codecs("MS1", Some(ms1_indexed), lossy)?;
+        let sample = ms2_indexed_pairs.iter().map(|(_, data)| data).max_by_key(|data| data.mz_values.len());
+        let ms2_codecs = self.column_codecs("MS2", sample, self.should_compress_file("ms2_indexed") || lossy)?;
+        
+        let mut segments = ms1_segments(ms1_indexed, shard_plan);
+        segments.extend(ms2_segments(ms2_indexed_pairs, self.config.window_compression_min_bytes).into_iter().map(|segment| SegmentInput { codecs: segment.codecs.or(Some(&ms2_codecs)), ..segment }));
+        self.config.retry.run(|| format!("writing {}", path.display()), |_| {
+            self.write_resumable(&path, "indexed", &segments, &WriteOptions { cancel: Some(cancel), lossy: self.config.lossy, ..self.write_options(&ms1_codecs)? }, progress)
+        })?;
+        progress.commit("indexed")?;
+        
+        telemetry::info(&format!("   ├── MS1 + MS2: {:.3}s ({:.1} MB, {} MS1 shards, {} MS2 windows)", start_time.elapsed().as_secs_f32(),
+                 fs::metadata(&path)?.len() as f32 / 1024.0 / 1024.0, shard_plan.shards, ms2_indexed_pairs.len()));
+        Ok((ms1_codecs, ms2_codecs))
+    }
+    
+    // OPTIMIZED: Sequential load with smart compression
+    pub fn load_indexed_data(
+        &self, 
+        source_path: &Path
+    ) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        self.load_indexed_data_cancellable(source_path, &CancellationToken::new())
+    }
+    
+    // load_indexed_data that gives up at the next shard or window once `cancel` is
+    // cancelled, with an Interrupted error
+    pub fn load_indexed_data_cancellable(
+        &self,
+        source_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        self.load_indexed_data_at(source_path, cancel)
+    }
+    
+    // load_indexed_data with float columns of type F, whatever width the cache stores
+    // them at: an f64 load of an f64 cache is exact, f32 loads narrow and f64 loads of an
+    // f32 cache widen
+    pub fn load_indexed_data_as<F: Float>(&self, source_path: &Path) -> Result<LoadedData<F>, Box<dyn std::error::Error>> {
+        self.load_indexed_data_at(source_path, &CancellationToken::new())
+    }
+    
+    // load_indexed_data in the given physical order. RtMajor reads the RT-major copy
+    // (CacheConfig::rt_major, layout.rs): the same datasets sorted by frame, then scan, for
+    // chromatogram extraction that walks frames rather than m/z.
+    pub fn load_indexed_data_with_layout(
+        &self,
+        source_path: &Path,
+        layout: Layout,
+    ) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        if layout == Layout::MzMajor {
+            return self.load_indexed_data(source_path);
+        }
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        self.check_schema(source_name)?;
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let path = self.sidecar_path(source_name, "rt.cache.bin");
+        if !path.exists() {
+            return Err(format!("the cache of {} has no RT-major copy; save it with CacheConfig::rt_major", source_path.display()).into());
+        }
+        self.verify_sidecar(source_name, "rt_sha256", &path)?;
+        
+        let file = RetryingFile::open(&path, &self.config.retry)?;
+        let reader = ContainerReader::open(|offset, len| file.read_at(offset, len), file.file_len()?, self.config.encryption_key.as_ref())?;
+        let (ms1_segments, windows) = group_segments(&reader.header);
+        let (ms1_indexed, ms2_indexed_pairs) = self.in_pool(|| -> std::io::Result<LoadedData> {
+            let ms1_indexed = reader.concatenated_segments(&ms1_segments, None)?;
+            let ms2_indexed_pairs = windows.par_iter()
+                .map(|(range, segments)| Ok((*range, reader.concatenated_segments(segments, None)?)))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Ok((ms1_indexed, ms2_indexed_pairs))
+        })?;
+        
+        let elapsed = start_time.elapsed();
+        let rows_returned = ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, d)| d.mz_values.len()).sum::<usize>();
+        self.finish_load(source_path, QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query: "full load".to_string(),
+            strategy: "rt-major copy".to_string(),
+            segments_read: reader.header.segments.len(),
+            bytes_read: fs::metadata(&path)?.len(),
+            rows_returned: rows_returned as u64,
+            elapsed,
+            io_fallback: None,
+        }, || telemetry::info(&format!("✅ RT-major copy loaded in {:.3}s", elapsed.as_secs_f32())));
+        self.add_content_ref(source_path)?;
+        Ok((ms1_indexed, ms2_indexed_pairs))
+    }
+    
+    // load_indexed_data of one committed generation of the cache, while update_cache may be
+    // appending to it (e.g. from another process during acquisition): a load overlapping a
+    // commit is retried, for up to snapshot::COMMIT_WAIT. Freshness is not checked, since
+    // a source still being acquired changes all the time.
+    pub fn load_snapshot(&self, source_path: &Path) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let generation_path = self.get_generation_path(source_name);
+        let start_time = std::time::Instant::now();
+        loop {
+            let Some(generation) = Generation::read(&generation_path) else {
+                // Committed before generations: nothing tells a concurrent commit apart
+                return Ok(Snapshot { generation: 0, data: self.load_indexed_data(source_path)? });
+            };
+            if generation.is_current(&self.cache_dir) {
+                let loaded = self.load_indexed_data(source_path);
+                if generation.is_current(&self.cache_dir) && Generation::read(&generation_path).as_ref() == Some(&generation) {
+                    return Ok(Snapshot { generation: generation.number, data: loaded? });
+                }
+            }
+            if start_time.elapsed() > snapshot::COMMIT_WAIT {
+                return Err(format!(
+                    "the files of {} haven't matched a committed generation for {}s; save or update the cache again",
+                    source_path.display(), snapshot::COMMIT_WAIT.as_secs()
+                ).into());
+            }
+            std::thread::sleep(snapshot::RETRY_DELAY);
+        }
+    }
+    
+    // Number of the last committed generation, for readers polling for appended data
+    // without loading; 0 for caches committed before generations, None without a cache
+    pub fn generation(&self, source_path: &Path) -> Option<u64> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        match Generation::read(&self.get_generation_path(source_name)) {
+            Some(generation) => Some(generation.number),
+            None => self.get_metadata_path(source_name).exists().then_some(0),
+        }
+    }
+    
+    // Only the MS1 data of a cached source, for callers such as QC tools with no use for
+    // MS2: the MS2 windows are neither read nor decoded
+    pub fn load_ms1(&self, source_path: &Path) -> Result<IndexedTimsTOFData, Box<dyn std::error::Error>> {
+        Ok(self.load_parts(source_path, LoadParts::MS1)?.0)
+    }
+    
+    // The MS2 windows passing `filter`, without the MS1 data. Containers decode only the
+    // matching windows; Parquet and legacy caches are read whole and filtered.
+    pub fn load_ms2_windows(
+        &self,
+        source_path: &Path,
+        filter: WindowFilter,
+    ) -> Result<Ms2Windows, Box<dyn std::error::Error>> {
+        Ok(self.load_parts(source_path, LoadParts::ms2(filter))?.1)
+    }
+    
+    // load_indexed_data of the selected parts; the others come back empty
+    fn load_parts(&self, source_path: &Path, parts: LoadParts) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        self.check_schema(source_name)?;
+        self.mirror_from_remote(source_path, source_name)?;
+        self.verify_signature(source_name)?;
+        
+        let shards = self.recorded_shards(source_name);
+        let mut io_fallback = None;
+        // Sizes of the files opened: an upper bound when only some of their segments are read
+        let (ms1_indexed, ms2_indexed_pairs, segments_read, bytes_read, strategy) = match self.combined_file(source_name) {
+            Some(combined_path) => {
+                let (ms1_parts, windows, segments_read) = self.load_container(&combined_path, shards.as_deref(), false, parts, None, &mut io_fallback)?;
+                let ms1_indexed = ms1_parts.into_iter().next().unwrap_or_else(IndexedTimsTOFData::new);
+                (ms1_indexed, windows, segments_read, fs::metadata(&combined_path)?.len(), "single file")
+            }
+            None => {
+                let mut bytes_read = 0;
+                let ms1_indexed = match parts.ms1 {
+                    true => {
+                        let path = self.get_cache_path(source_name, "ms1_indexed");
+                        bytes_read += fs::metadata(&path)?.len();
+                        self.load_ms1_file(&path, shards.as_deref(), None, &mut io_fallback)?
+                    }
+                    false => IndexedTimsTOFData::new(),
+                };
+                let windows = match parts.windows {
+                    Some(filter) => {
+                        let path = self.get_cache_path(source_name, "ms2_indexed");
+                        bytes_read += fs::metadata(&path)?.len();
+                        self.load_ms2_file(&path, shards.as_deref(), filter, None, &mut io_fallback)?
+                    }
+                    None => Vec::new(),
+                };
+                let segments_read = usize::from(parts.ms1) + windows.len();
+                (ms1_indexed, windows, segments_read, bytes_read, "whole files")
+            }
+        };
+        
+        let query = match parts.windows {
+            None => "ms1 load".to_string(),
+            Some(filter) => format!("ms2 windows {:?}", filter),
+        };
+        let elapsed = start_time.elapsed();
+        let rows_returned = ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, d)| d.mz_values.len()).sum::<usize>();
+        self.finish_load(source_path, QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query: query.clone(),
+            strategy: strategy.to_string(),
+            segments_read,
+            bytes_read,
+            rows_returned: rows_returned as u64,
+            elapsed,
+            io_fallback,
+        }, || telemetry::info(&format!("✅ {} from optimized cache: {} rows in {:.3}s", query, rows_returned, elapsed.as_secs_f32())));
+        self.add_content_ref(source_path)?;
+        Ok((ms1_indexed, ms2_indexed_pairs))
+    }
+    
+    fn load_indexed_data_at<F: Float>(
+        &self,
+        source_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<LoadedData<F>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        telemetry::info("Loading indexed data from optimized cache...");
+        let start_time = std::time::Instant::now();
+        self.check_schema(source_name)?;
+        self.mirror_from_remote(source_path, source_name)?;
+        self.verify_signature(source_name)?;
+        
+        if let Some(combined_path) = self.combined_file(source_name) {
+            let shards = self.recorded_shards(source_name);
+            let mut io_fallback = None;
+            let (ms1_parts, ms2_indexed_pairs, segments_read) = self.load_container(&combined_path, shards.as_deref(), false, LoadParts::ALL, Some(cancel), &mut io_fallback)?;
+            let ms1_indexed = ms1_parts.into_iter().next().unwrap_or_else(|| F::from_f32_data(IndexedTimsTOFData::new()));
+            let elapsed = start_time.elapsed();
+            let rows_returned = ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, d)| d.mz_values.len()).sum::<usize>();
+            self.finish_load(source_path, QueryProfile {
+                source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                query: "full load".to_string(),
+                strategy: if self.is_archived(source_name) { "archive" } else { "single file" }.to_string(),
+                segments_read,
+                bytes_read: fs::metadata(&combined_path)?.len(),
+                rows_returned: rows_returned as u64,
+                elapsed,
+                io_fallback,
+            }, || {
+                telemetry::info(&format!("✅ Optimized cache loaded from {} in {:.3}s",
+                         combined_path.file_name().unwrap().to_string_lossy(), elapsed.as_secs_f32()));
+            });
+            self.add_content_ref(source_path)?;
+            return Ok((ms1_indexed, ms2_indexed_pairs));
+        }
+        
+        // Load MS1 data (fast, no compression)
+        let ms1_start = std::time::Instant::now();
+        let shards = self.recorded_shards(source_name);
+        let mut io_fallback = None;
+        let ms1_cache_path = self.get_cache_path(source_name, "ms1_indexed");
+        let ms1_indexed = self.load_ms1_file(&ms1_cache_path, shards.as_deref(), Some(cancel), &mut io_fallback)?;
+        let ms1_time = ms1_start.elapsed();
+        cancel.check()?;
+        
+        // Load MS2 data (with smart compression detection)
+        let ms2_start = std::time::Instant::now();
+        let ms2_cache_path = self.get_cache_path(source_name, "ms2_indexed");
+        let use_compression = ms2_cache_path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext == "lz4")
+            .unwrap_or(false);
+        let ms2_indexed_pairs = self.load_ms2_file(&ms2_cache_path, shards.as_deref(), WindowFilter::All, Some(cancel), &mut io_fallback)?;
+        let ms2_time = ms2_start.elapsed();
+        
+        let elapsed = start_time.elapsed();
+        let bytes_read = fs::metadata(&ms1_cache_path)?.len() + fs::metadata(&ms2_cache_path)?.len();
+        let rows_returned = ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, d)| d.mz_values.len()).sum::<usize>();
+        self.finish_load(source_path, QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query: "full load".to_string(),
+            strategy: "whole files".to_string(),
+            segments_read: 1 + ms2_indexed_pairs.len(),
+            bytes_read,
+            rows_returned: rows_returned as u64,
+            elapsed,
+            io_fallback,
+        }, || {
+            telemetry::info("✅ Optimized cache loaded");
+            telemetry::info(&format!("   ├── MS1: {:.3}s", ms1_time.as_secs_f32()));
+            telemetry::info(&format!("   ├── MS2: {:.3}s (compressed: {})", ms2_time.as_secs_f32(), use_compression));
+            telemetry::info(&format!("   └── Total time: {:.3}s", elapsed.as_secs_f32()));
+        });
+        
+        self.add_content_ref(source_path)?;
+        Ok((ms1_indexed, ms2_indexed_pairs))
+    }
+    
+    // load_indexed_data through the in-process hot layer: a source loaded before in this
+    // process comes back as the same Arc, without touching disk, as long as its cache
+    // hasn't been rebuilt since
+    pub fn load_indexed_data_hot(&self, source_path: &Path) -> Result<Arc<LoadedData>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        self.mirror_from_remote(source_path, source_name)?;
+        // Taken before loading: a rebuild racing with the load leaves a stale stamp, so the
+        // entry is reloaded next time rather than trusted
+        let stamp = fs::metadata(self.get_metadata_path(source_name))
+            .map(|meta| (meta.len(), meta.modified().ok()))?;
+        if let Some(data) = self.hot.get(source_path, &stamp) {
+            telemetry::info(&format!("✅ In-memory cache hit for {}", source_path.display()));
+            return Ok(data);
+        }
+        let data = Arc::new(self.load_indexed_data(source_path)?);
+        self.hot.insert(source_path.clone(), stamp, Arc::clone(&data));
+        Ok(data)
+    }
+    
+    // Drop a source from the hot layer; returns whether it was there
+    pub fn evict(&self, source_path: &Path) -> bool {
+        self.hot.remove(&self.resolve_source(source_path))
+    }
+    
+    // Drop every source from the hot layer
+    pub fn evict_all(&self) {
+        self.hot.clear();
+    }
+    
+    // The raw frame tier (TimsTOFRawData as read, before indexing) is a cache of its own,
+    // "<source>.raw" with its own metadata and manifest entry, so it stays valid when the
+    // indexed tier is cleared for other indexing parameters and rebuilding that tier skips
+    // the Bruker binary; it is keyed without the parameter fingerprint for the same reason.
+    // Its data file reuses the frame index sidecar name under that key.
+    fn raw_tier_name(&self, source_path: &Path) -> String {
+        format!("{}.raw", self.source_key(source_path))
+    }
+    
+    fn raw_tier_paths(&self, source_path: &Path) -> (PathBuf, PathBuf) {
+        let name = self.raw_tier_name(source_path);
+        (self.cache_dir.join(format!("{}.frames.cache.bin", name)), self.cache_dir.join(format!("{}.meta", name)))
+    }
+    
+    pub fn save_raw_data(&self, source_path: &Path, raw_data: &TimsTOFRawData) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        fs::create_dir_all(&self.cache_dir)?;
+        let (data_path, meta_path) = self.raw_tier_paths(source_path);
+        // Without its metadata a half-replaced tier is never taken for valid
+        if meta_path.exists() {
+            fs::remove_file(&meta_path)?;
+        }
+        let points = raw_data.ms1_data.mz_values.len()
+            + raw_data.ms2_windows.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+        self.ensure_space(source_name, self.estimated_save_bytes(points, 0))?;
+        sidecar::write(&data_path, RAW_TIER_MAGIC, raw_data, self.config.encryption_key.as_ref())?;
+        
+        let mut metadata = format!(
+            "cached at: {:?}\nsource: {}\ntype: raw\nform: raw\nms2_windows: {}\npoints: {}\nencryption: {}\nversion: 1.0\n",
+            self.recorded_time(),
+            recorded_source(source_path),
+            raw_data.ms2_windows.len(),
+            points,
+            self.encryption_description()
+        );
+        metadata.push_str(&freshness::source_metadata(source_path, self.config.validation)?);
+        metadata.push_str(&self.provenance_line(start_time)?);
+        if let Some(key) = &self.config.signing_key {
+            metadata = self.sign_metadata(key, metadata, &[("raw_sha256", data_path.clone())])?;
+        }
+        durability::write_file(&meta_path, metadata, self.config.durability)?;
+        let raw_name = self.raw_tier_name(source_path);
+        let entry = self.manifest_entry(&raw_name, Some(source_path))?;
+        Manifest::transaction(&self.cache_dir, |sources| {
+            sources.insert(raw_name, entry);
+        })?;
+        
+        let size = fs::metadata(&data_path)?.len();
+        let elapsed = start_time.elapsed();
+        self.report(OperationReport::new("save_raw", source_path, size, points as u64, elapsed).with_layout(None, raw_data.ms2_windows.len()), || {
+            telemetry::info(&format!("💾 Raw frame tier saved: {:.2} MB in {:.3}s", size as f32 / 1024.0 / 1024.0, elapsed.as_secs_f32()));
+        });
+        Ok(())
+    }
+    
+    // Like is_cache_valid, for the raw frame tier: only the source can make it stale
+    pub fn is_raw_cache_valid(&self, source_path: &Path) -> bool {
+        let source_path = &self.resolve_source(source_path);
+        let (data_path, meta_path) = self.raw_tier_paths(source_path);
+        let Ok(metadata) = fdlimit::read_to_string(&meta_path) else {
+            return false;
+        };
+        let Ok(cache_modified) = fs::metadata(&data_path).and_then(|m| m.modified()) else {
+            return false;
+        };
+        self.config.content_addressed
+            || freshness::is_fresh(source_path, self.config.validation, &metadata, cache_modified)
+    }
+    
+    pub fn load_raw_data(&self, source_path: &Path) -> Result<TimsTOFRawData, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        if !self.is_raw_cache_valid(source_path) {
+            return Err(format!("no valid raw frame tier for {}", source_path.display()).into());
+        }
+        let start_time = std::time::Instant::now();
+        let (data_path, meta_path) = self.raw_tier_paths(source_path);
+        // As verify_signature, against the tier's own metadata
+        if let Some(key) = &self.config.signing_key {
+            check_signed_checksum(key.verify(&fdlimit::read_to_string(&meta_path)?)?, "raw_sha256", &data_path)?;
+        }
+        let raw_data: TimsTOFRawData = sidecar::read(&data_path, RAW_TIER_MAGIC, self.config.encryption_key.as_ref())?;
+        let points = raw_data.ms1_data.mz_values.len()
+            + raw_data.ms2_windows.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+        let elapsed = start_time.elapsed();
+        let report = OperationReport::new("load_raw", source_path, fs::metadata(&data_path)?.len(), points as u64, elapsed)
+            .with_layout(None, raw_data.ms2_windows.len());
+        self.report(report, || telemetry::info(&format!("✅ Raw frame tier loaded in {:.3}s", elapsed.as_secs_f32())));
+        Ok(raw_data)
+    }
+    
+    // Indexed data from the cheapest valid tier: the indexed cache, else `index` over the
+    // raw frame tier, else `index` over `read_raw` of the source. Tiers that were missing or
+    // stale are saved on the way, so the next call stops at the indexed one.
+    pub fn load_raw_or_indexed<R, I>(&self, source_path: &Path, read_raw: R, index: I) -> Result<LoadedData, Box<dyn std::error::Error>>
+    where
+        R: FnOnce(&Path) -> Result<TimsTOFRawData, Box<dyn std::error::Error>>,
+        I: FnOnce(TimsTOFRawData) -> Result<LoadedData, Box<dyn std::error::Error>>,
+    {
+        let source_path = &self.resolve_source(source_path);
+        if self.is_cache_valid(source_path) {
+            return self.load_indexed_data(source_path);
+        }
+        let raw_data = match self.is_raw_cache_valid(source_path) {
+            true => self.load_raw_data(source_path)?,
+            false => {
+                telemetry::info(&format!("Reading {} (no valid cache tier)...", source_path.display()));
+                let raw_data = read_raw(source_path)?;
+                if !self.config.read_only {
+                    self.save_raw_data(source_path, &raw_data)?;
+                }
+                raw_data
+            }
+        };
+        let (ms1_indexed, ms2_indexed_pairs) = index(raw_data)?;
+        if !self.config.read_only {
+            self.save_indexed_data(source_path, &ms1_indexed, &ms2_indexed_pairs)?;
+        }
+        Ok((ms1_indexed, ms2_indexed_pairs))
+    }
+    
+    // Like load_indexed_data, but MS1 shards stay separate chunks instead of being copied
+    // into one set of columns. Caches without shards (Parquet, legacy) load as one chunk.
+    pub fn load_indexed_data_chunked(
+        &self,
+        source_path: &Path,
+    ) -> Result<(ChunkedIndexedData, Ms2Windows), Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        self.mirror_from_remote(source_path, source_name)?;
+        let data_files = self.data_files(source_name);
+        if data_files.iter().any(|(_, path)| !columnar::is_container(path)) {
+            let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+            return Ok((ChunkedIndexedData::from(ms1_indexed), ms2_indexed_pairs));
+        }
+        telemetry::info("Loading indexed data from optimized cache (chunked)...");
+        let start_time = std::time::Instant::now();
+        self.check_schema(source_name)?;
+        self.verify_signature(source_name)?;
+        
+        let shards = self.recorded_shards(source_name);
+        let mut chunks = Vec::new();
+        let mut ms2_indexed_pairs = Vec::new();
+        let mut segments_read = 0;
+        let mut bytes_read = 0;
+        let mut io_fallback = None;
+        for (_, path) in &data_files {
+            let (ms1_parts, windows, segments) = self.load_container(path, shards.as_deref(), true, LoadParts::ALL, None, &mut io_fallback)?;
+            chunks.extend(ms1_parts);
+            ms2_indexed_pairs.extend(windows);
+            segments_read += segments;
+            bytes_read += fs::metadata(path)?.len();
+        }
+        let ms1_chunked = ChunkedIndexedData::from_chunks(chunks);
+        
+        let elapsed = start_time.elapsed();
+        let rows_returned = ms1_chunked.len() + ms2_indexed_pairs.iter().map(|(_, d)| d.mz_values.len()).sum::<usize>();
+        self.finish_load(source_path, QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query: "full load".to_string(),
+            strategy: "chunked".to_string(),
+            segments_read,
+            bytes_read,
+            rows_returned: rows_returned as u64,
+            elapsed,
+            io_fallback,
+        }, || telemetry::info(&format!("✅ Optimized cache loaded: {} MS1 chunk(s) in {:.3}s", ms1_chunked.chunks().len(), elapsed.as_secs_f32())));
+        self.add_content_ref(source_path)?;
+        Ok((ms1_chunked, ms2_indexed_pairs))
+    }
+    
+    // Decoded data of a source in a shared-memory file, mapped read-only. The first process
+    // to ask decodes the cache into it (uncompressed); every other process maps the same
+    // file, so workers loading one source share a single physical copy. The file is named
+    // after a hash of the metadata, so a rebuilt cache never maps stale data. With a
+    // signing key the file is tagged with it (shm.rs); an encrypted cache would be
+    // decrypted into it, so those need shared_memory_dir set to a private directory.
+    pub fn load_shared(&self, source_path: &Path) -> Result<SharedIndexedData, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if self.config.encryption_key.is_some() && self.config.shared_memory_dir.is_none() {
+            return Err(format!(
+                "{} is encrypted; load_shared() would write it decrypted to {}. Set shared_memory_dir to a private directory",
+                source_path.display(), self.shared_memory_dir().display()
+            ).into());
+        }
+        self.check_schema(source_name)?;
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        self.verify_signature(source_name)?;
+        let fingerprint = self.metadata_fingerprint(source_name)?;
+        let signing_key = self.config.signing_key.as_ref();
+        let dir = self.shared_memory_dir();
+        let path = dir.join(format!("{}.{}.shm", source_name, fingerprint));
+        if let Ok(shared) = SharedIndexedData::open(&path, &fingerprint, signing_key) {
+            if self.config.prefetch {
+                shared.prefetch();
+            }
+            telemetry::info(&format!("✅ Mapped shared cache {}", path.display()));
+            return Ok(shared);
+        }
+        
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+        fs::create_dir_all(&dir)?;
+        shm::write(&path, &fingerprint, signing_key, &ms1_indexed, &ms2_indexed_pairs)?;
+        // Copies of earlier versions of this cache; processes still mapping one keep its pages
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let stale_fingerprint = file_name.strip_prefix(&format!("{}.", source_name)).and_then(|rest| rest.strip_suffix(".shm"));
+            if stale_fingerprint.is_some_and(|stale| stale != fingerprint && stale.len() == 16 && hex::decode(stale).is_ok()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        telemetry::info(&format!("✅ Shared cache written to {}", path.display()));
+        Ok(SharedIndexedData::open(&path, &fingerprint, signing_key)?)
+    }
+    
+    // One MS1 shard (in file order) as an rkyv archive mapped zero-copy, for query-only
+    // workloads (mapped.rs): the first call decodes the shard into an archive under
+    // zero_copy_dir(), later ones map and validate it in O(1). Named after a hash of the
+    // metadata like load_shared's files, tagged with the signing key if there is one.
+    // Encrypted caches fail with Unsupported, since the archive would hold them decrypted.
+    pub fn load_shard_mmap(&self, source_path: &Path, shard: usize) -> Result<MappedShard, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if self.config.encryption_key.is_some() {
+            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Unsupported, format!(
+                "{} is encrypted; a zero-copy shard would store it decrypted", source_path.display()
+            ))));
+        }
+        self.check_schema(source_name)?;
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        self.verify_signature(source_name)?;
+        let fingerprint = self.metadata_fingerprint(source_name)?;
+        let signing_key = self.config.signing_key.as_ref();
+        let shard = u32::try_from(shard)?;
+        let dir = self.zero_copy_dir();
+        let path = dir.join(format!("{}.{}.shard{}.rkyv", source_name, fingerprint, shard));
+        let mapped = match MappedShard::open(&path, &fingerprint, shard, signing_key) {
+            Ok(mapped) => mapped,
+            Err(_) => {
+                let data = self.decode_ms1_shard(source_name, shard as usize)?;
+                fs::create_dir_all(&dir)?;
+                mapped::write(&path, &DataShard::new(&fingerprint, shard, data), signing_key)?;
+                self.remove_zero_copy_shards(source_name, Some(&fingerprint));
+                telemetry::info(&format!("✅ Zero-copy shard written to {}", path.display()));
+                MappedShard::open(&path, &fingerprint, shard, signing_key)?
+            }
+        };
+        if self.config.prefetch {
+            mapped.prefetch();
+        }
+        Ok(mapped)
+    }
+    
+    // Shard archives of a source other than those of cache version `keep`; processes still
+    // mapping one keep its pages
+    fn remove_zero_copy_shards(&self, source_name: &str, keep: Option<&str>) {
+        let Ok(entries) = fs::read_dir(self.zero_copy_dir()) else { return };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let fingerprint = file_name.strip_prefix(&format!("{}.", source_name))
+                .filter(|rest| rest.ends_with(".rkyv"))
+                .and_then(|rest| rest.split_once('.'))
+                .map(|(fingerprint, _)| fingerprint);
+            if fingerprint.is_some_and(|stale| Some(stale) != keep && stale.len() == 16 && hex::decode(stale).is_ok()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+    
+    // MS1 shard `shard` (in file order) of a columnar cache, decoded
+    fn decode_ms1_shard(&self, source_name: &str, shard: usize) -> Result<IndexedTimsTOFData, Box<dyn std::error::Error>> {
+        let path = self.combined_file(source_name)
+            .unwrap_or_else(|| self.get_cache_path(source_name, "ms1_indexed"));
+        if !columnar::is_container(&path) {
+            return Err(format!("{} is not a columnar container and cannot be mapped", path.display()).into());
+        }
+        let file = RetryingFile::open(&path, &self.config.retry)?;
+        let reader = ContainerReader::open(|offset, len| file.read_at(offset, len), file.file_len()?, None)?;
+        let (ms1_segments, _) = group_segments(&reader.header);
+        let segment_index = *ms1_segments.get(shard)
+            .ok_or_else(|| format!("{} has {} MS1 shards, no shard {}", path.display(), ms1_segments.len(), shard))?;
+        Ok(self.in_pool(|| reader.segment(segment_index))?)
+    }
+    
+    // The spectrum of one frame from the frame index (CacheConfig::frame_index), for viewers:
+    // only the index footer and the segment holding the frame are read
+    pub fn load_frame(&self, source_path: &Path, frame_index: u32) -> Result<FrameSpectrum, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        self.check_schema(source_name)?;
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let path = self.sidecar_path(source_name, "frames.cache.bin");
+        if !path.exists() {
+            return Err(format!("the cache of {} has no frame index; save it with CacheConfig::frame_index", source_path.display()).into());
+        }
+        self.verify_sidecar(source_name, "frames_sha256", &path)?;
+        
+        let file = RetryingFile::open(&path, &self.config.retry)?;
+        let bytes_read = AtomicU64::new(0);
+        let read_at = |offset: u64, len: usize| {
+            bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+            file.read_at(offset, len)
+        };
+        let reader = ContainerReader::open(read_at, file.file_len()?, self.config.encryption_key.as_ref())?;
+        let (spectrum, segments_read) = frames::read_frame(&reader, frame_index)?;
+        let spectrum = spectrum
+            .ok_or_else(|| format!("frame {} has no points in the cache of {}", frame_index, source_path.display()))?;
+        self.profiler.record(QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query: format!("frame {}", frame_index),
+            strategy: "frame index".to_string(),
+            segments_read,
+            bytes_read: bytes_read.load(Ordering::Relaxed),
+            rows_returned: spectrum.mz_values.len() as u64,
+            elapsed: start_time.elapsed(),
+            io_fallback: None,
+        });
+        Ok(spectrum)
+    }
+    
+    // Per-frame total ion current, from the sidecar written at save time (chromatogram.rs)
+    pub fn load_tic(&self, source_path: &Path) -> Result<Chromatogram, Box<dyn std::error::Error>> {
+        Ok(self.load_frame_summary(source_path)?.tic())
+    }
+    
+    // Per-frame base peak intensity, from the same sidecar as load_tic
+    pub fn load_bpc(&self, source_path: &Path) -> Result<Chromatogram, Box<dyn std::error::Error>> {
+        Ok(self.load_frame_summary(source_path)?.bpc())
+    }
+    
+    // One level of the heatmap pyramid (CacheConfig::heatmap) cropped to `viewport`; level 0
+    // is the coarsest, and the bins returned cover the viewport
+    pub fn load_heatmap(&self, source_path: &Path, level: usize, viewport: &Viewport) -> Result<Heatmap, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let path = self.sidecar_path(source_name, "heatmap.cache.bin");
+        if !path.exists() {
+            return Err(format!("the cache of {} has no heatmap; save it with CacheConfig::heatmap", source_path.display()).into());
+        }
+        self.verify_sidecar(source_name, "heatmap_sha256", &path)?;
+        Ok(HeatmapPyramid::read(&path, self.config.encryption_key.as_ref())?.crop(level, viewport)?)
+    }
+    
+    fn load_frame_summary(&self, source_path: &Path) -> Result<FrameSummary, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let path = self.sidecar_path(source_name, "chromatograms.cache.bin");
+        if !path.exists() {
+            return Err(format!("the cache of {} predates chromatograms; save it again to add them", source_path.display()).into());
+        }
+        self.verify_sidecar(source_name, "chromatograms_sha256", &path)?;
+        Ok(FrameSummary::read(&path, self.config.encryption_key.as_ref())?)
+    }
+    
+    // Hash of the metadata naming the files derived from one version of a cache
+    // (load_shared, load_shard_mmap)
+    fn metadata_fingerprint(&self, source_name: &str) -> Result<String, std::io::Error> {
+        let metadata = fdlimit::read(self.get_metadata_path(source_name))?;
+        Ok(hex::encode(&Sha256::digest(&metadata)[..8]))
+    }
+    
+    // Where load_shard_mmap() keeps shard archives: next to the cache, where the page cache
+    // keeps them warm, or a temporary directory for read-only managers
+    fn zero_copy_dir(&self) -> PathBuf {
+        match self.config.read_only {
+            true => std::env::temp_dir().join("timstof_cache_zero_copy"),
+            false => self.cache_dir.join("zero_copy"),
+        }
+    }
+    
+    fn shared_memory_dir(&self) -> PathBuf {
+        match &self.config.shared_memory_dir {
+            Some(dir) => dir.clone(),
+            None if Path::new("/dev/shm").is_dir() => PathBuf::from("/dev/shm"),
+            // No tmpfs (macOS, Windows): a file next to the cache, still shared through the page cache
+            None if self.config.read_only => std::env::temp_dir().join("timstof_cache_shm"),
+            None => self.cache_dir.join("shm"),
+        }
+    }
+    
+    // A previous save with another format/compression must not shadow the new file
+    fn remove_stale_variants(&self, keep: &Path, source_name: &str, cache_type: &str) -> Result<(), std::io::Error> {
+        for extension in Self::CACHE_EXTENSIONS {
+            let path = self.cache_dir.join(format!("{}.{}.{}", source_name, cache_type, extension));
+            if path != keep && path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+    
+    // As recorded in the metadata; caches can only be extended with the same settings
+    fn encryption_description(&self) -> String {
+        self.config.encryption_key.as_ref()
+            .map(|key| format!("aes256gcm (key id: {})", key.id))
+            .unwrap_or_else(|| "none".to_string())
+    }
+    
+    // Checksums of the data files and sidecars go into the signed part, so the signature
+    // covers them too
+    fn sign_metadata(&self, key: &SigningKey, mut metadata: String, signed_files: &[(&str, PathBuf)]) -> Result<String, std::io::Error> {
+        for (field, path) in signed_files {
+            metadata.push_str(&format!("{}: {}\n", field, signing::sha256_file(path)?));
+        }
+        metadata.push_str(&format!("signing_key_id: {}\n", key.id));
+        Ok(key.sign(&metadata))
+    }
+    
+    // Refuse caches written for another data schema before decoding anything
+    fn check_schema(&self, source_name: &str) -> Result<(), std::io::Error> {
+        let meta_path = self.get_metadata_path(source_name);
+        let metadata = fdlimit::read_to_string(&meta_path).unwrap_or_default();
+        match schema_mismatch(&meta_path, &metadata) {
+            Some(mismatch) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, mismatch)),
+            None => Ok(()),
+        }
+    }
+    
+    // With a signing key configured, refuse caches whose metadata or data files were modified
+    fn verify_signature(&self, source_name: &str) -> Result<(), std::io::Error> {
+        let key = match &self.config.signing_key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let metadata = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        let body = key.verify(&metadata)?;
+        
+        for (field, path) in self.data_files(source_name) {
+            check_signed_checksum(body, field, &path)?;
+        }
+        
+        telemetry::info(&format!("   Signature verified (key id: {})", key.id));
+        Ok(())
+    }
+    
+    // The same for one sidecar, before a loader reads it; sidecars of caches signed before
+    // they were listed fail the check, as one added later would
+    fn verify_sidecar(&self, source_name: &str, field: &str, path: &Path) -> Result<(), std::io::Error> {
+        let key = match &self.config.signing_key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let metadata = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        check_signed_checksum(key.verify(&metadata)?, field, path)
+    }
+    
+    // Codecs for one file: sampled per column in adaptive mode (columnar only), otherwise
+    // the configured codec or raw throughout as decided by should_compress_file
+    fn column_codecs<F: Float>(
+        &self,
+        label: &str,
+        sample: Option<&IndexedTimsTOFData<F>>,
+        use_compression: bool,
+    ) -> Result<CodecMap, std::io::Error> {
+        let sample = match sample {
+            Some(sample) if self.config.adaptive_compression && self.config.format == CacheFormat::Columnar => sample,
+            _ => return Ok(self.uniform_codecs(use_compression)),
+        };
+        let columns: Vec<&'static str> = INDEXED_COLUMNS.iter().map(|(name, _)| *name).collect();
+        // A configured zstd level is what adaptive mode weighs against lz4
+        let zstd = match self.config.compression {
+            CompressionType::Zstd { .. } => self.config.compression.codec(),
+            CompressionType::Lz4 | CompressionType::Custom { .. } => ColumnCodec::zstd(compression::DEFAULT_ZSTD_LEVEL),
+        };
+        // Throughput varies from run to run, so deterministic saves decide by ratio alone
+        let weigh_speed = !self.config.deterministic;
+        let decisions = compression::select_codecs(sample, &columns, zstd, self.config.precision, self.config.byte_shuffle, weigh_speed)?;
+        telemetry::info(&format!("   {} codecs (sampled):", label));
+        for decision in &decisions {
+            telemetry::info(&format!(
+                "      {:<16} {:<12} (lz4 {:.2}x at {:.0} MB/s, zstd {:.2}x at {:.0} MB/s)",
+                decision.column, decision.codec.label(), decision.lz4_ratio, decision.lz4_mb_per_sec,
+                decision.zstd_ratio, decision.zstd_mb_per_sec
+            ));
+        }
+        Ok(decisions.into_iter().map(|decision| (decision.column, decision.codec)).collect())
+    }
+    
+    fn uniform_codecs(&self, use_compression: bool) -> CodecMap {
+        compression::uniform_codecs(if use_compression { self.config.compression.codec() } else { ColumnCodec::Raw })
+    }
+    
+    // Codecs of a file committed by an interrupted save, so its metadata stays accurate.
+    // Taken from its largest segment: small MS2 windows are stored raw whatever the codecs.
+    fn stored_codecs(&self, path: &Path, use_compression: bool) -> Result<CodecMap, std::io::Error> {
+        if !columnar::is_container(path) {
+            return Ok(self.uniform_codecs(use_compression));
+        }
+        let file = fdlimit::open(path)?;
+        let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+        Ok(header.segments.iter().max_by_key(|segment| segment.rows).map(segment_codecs).unwrap_or_default())
+    }
+    
+    // MS1 and MS2 codecs of a committed single file
+    fn stored_codecs_by_segment(&self, path: &Path) -> Result<(CodecMap, CodecMap), std::io::Error> {
+        let file = fdlimit::open(path)?;
+        let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+        let codecs_of = |name: &str| header.segments.iter().filter(|segment| segment.name == name).max_by_key(|segment| segment.rows).map(segment_codecs);
+        Ok((codecs_of("ms1").unwrap_or_default(), codecs_of("ms2_window").unwrap_or_default()))
+    }
+    
+    // Exact, whatever CacheConfig::lossy says: saves opt in to quantization themselves,
+    // while compaction, repairs and appends store the values they are given
+    fn write_options(&self, codecs: &CodecMap) -> Result<WriteOptions<'_>, std::io::Error> {
+        Ok(WriteOptions {
+            column_order: columnar::resolve_column_order(&self.config.column_order)?,
+            codecs: codecs.clone(),
+            buffer_size: self.config.buffer_size,
+            encryption_key: self.config.encryption_key.as_ref(),
+            cancel: None,
+            lossy: None,
+            precision: self.config.precision,
+            block_rows: self.config.compression_block_bytes.map(|bytes| (bytes / 4).max(1)),
+            byte_shuffle: self.config.byte_shuffle,
+            durability: self.config.durability,
+            deterministic: self.config.deterministic,
+        })
+    }
+    
+    fn parquet_backend(&self, use_compression: bool) -> Result<Option<ParquetBackend>, std::io::Error> {
+        match self.config.format {
+            CacheFormat::Columnar | CacheFormat::SingleFile => Ok(None),
+            CacheFormat::Parquet { .. } if self.config.encryption_key.is_some() => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "encryption at rest is only supported by the columnar cache format",
+            )),
+            CacheFormat::Parquet { .. } if use_compression && matches!(self.config.compression, CompressionType::Custom { .. }) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "registered codecs are only supported by the columnar cache format",
+            )),
+            CacheFormat::Parquet { row_group_size } => Ok(Some(ParquetBackend {
+                row_group_size,
+                compression: use_compression.then_some(self.config.compression),
+            })),
+        }
+    }
+    
+    // Time a save, update or repair records in the metadata: now, or in deterministic mode
+    // SOURCE_DATE_EPOCH (seconds, the reproducible-builds convention) and zero without it
+    fn recorded_time(&self) -> SystemTime {
+        if !self.config.deterministic {
+            return SystemTime::now();
+        }
+        let seconds = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok()).unwrap_or(0);
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+    
+    // Provenance of a save or update that started at `start_time` (provenance.rs)
+    fn provenance_line(&self, start_time: std::time::Instant) -> Result<String, std::io::Error> {
+        let provenance = Provenance::capture(&self.config, self.recorded_time(), start_time.elapsed());
+        let json = serde_json::to_string(&provenance).map_err(std::io::Error::other)?;
+        Ok(format!("provenance: {}\n", json))
+    }
+    
+    // Shard count for an MS1 segment of `points` points; parallelism is that of the pool
+    // the save runs on, so a pool configured for the build is taken into account, except
+    // in deterministic mode, where plans must not depend on the machine
+    fn shard_plan(&self, points: usize) -> ShardPlan {
+        let parallelism = if self.config.deterministic { 1 } else { rayon::current_num_threads() };
+        ShardPlan::new(points, self.config.target_shard_bytes, self.config.target_shard_points, parallelism)
+    }
+    
+    // What the files a save writes depend on besides the source's version: the settings
+    // that shape them, the key, the shard plan and the input's size and bounds. Progress
+    // recorded under another fingerprint is not resumed.
+    fn write_fingerprint<F: Float>(
+        &self,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        shard_plan: &ShardPlan,
+    ) -> String {
+        let config = &self.config;
+        let bounds = |data: &IndexedTimsTOFData<F>| format!(
+            "{}:{:?}:{:?}", data.mz_values.len(), data.mz_values.first().map(|mz| mz.to_f64()), data.mz_values.last().map(|mz| mz.to_f64()),
+        );
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{:?}|{}|{:?}|{:?}|{}|{}|{:?}|{}|{}|{:?}|{}|{:?}|{:?}|{}|{:?}|{:?}",
+            config.format, std::any::type_name::<F>(), config.precision, config.lossy,
+            config.enable_compression, config.auto_compression, config.compression, config.adaptive_compression, config.byte_shuffle,
+            config.column_order, config.window_compression_min_bytes, config.compression_block_bytes,
+            config.encryption_key.as_ref().map(EncryptionKey::fingerprint), config.deterministic, config.params_fingerprint, shard_plan,
+        ));
+        hasher.update(bounds(ms1_indexed));
+        for (window, data) in ms2_indexed_pairs {
+            hasher.update(format!("|{:?}:{}", window.key(), bounds(data)));
+        }
+        hex::encode(&hasher.finalize()[..16])
+    }
+    
+    fn save_ms1_file<F: Float>(
+        &self,
+        path: &Path,
+        ms1_indexed: &IndexedTimsTOFData<F>,
+        shard_plan: &ShardPlan,
+        codecs: &CodecMap,
+        cancel: Option<&CancellationToken>,
+        mut progress: Option<&mut BuildProgress>,
+    ) -> Result<(), std::io::Error> {
+        let use_compression = codecs.values().any(|codec| *codec != ColumnCodec::Raw);
+        let backend = self.parquet_backend(use_compression)?;
+        self.config.retry.run(|| format!("writing {}", path.display()), |_| match (&backend, progress.as_deref_mut()) {
+            (Some(backend), _) => backend.write_ms1(path, &F::as_f32_data(ms1_indexed)),
+            (None, progress) => self.write_columnar(path, "ms1_indexed", &ms1_segments(ms1_indexed, shard_plan), &WriteOptions { cancel, lossy: self.config.lossy, ..self.write_options(codecs)? }, progress),
+        })
+    }
+    
+    fn save_ms2_file<F: Float>(
+        &self,
+        path: &Path,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)],
+        codecs: &CodecMap,
+        cancel: Option<&CancellationToken>,
+        mut progress: Option<&mut BuildProgress>,
+    ) -> Result<(), std::io::Error> {
+        let use_compression = codecs.values().any(|codec| *codec != ColumnCodec::Raw);
+        let backend = self.parquet_backend(use_compression)?;
+        self.config.retry.run(|| format!("writing {}", path.display()), |_| match (&backend, progress.as_deref_mut()) {
+            (Some(backend), _) => backend.write_ms2(path, &F::as_f32_windows(ms2_indexed_pairs)),
+            (None, progress) => self.write_columnar(path, "ms2_indexed", &ms2_segments(ms2_indexed_pairs, self.config.window_compression_min_bytes), &WriteOptions { cancel, lossy: self.config.lossy, ..self.write_options(codecs)? }, progress),
+        })
+    }
+    
+    // A container in one go, or resumably as unit `unit` of a save with progress
+    fn write_columnar<F: Float>(&self, path: &Path, unit: &str, segments: &[SegmentInput<F>], options: &WriteOptions, progress: Option<&mut BuildProgress>) -> Result<(), std::io::Error> {
+        match progress {
+            Some(progress) => self.write_resumable(path, unit, segments, options, progress),
+            None => self.in_pool(|| columnar::write_container(path, segments, options)).map(drop),
+        }
+    }
+    
+    // Write a container as unit `unit` of a save, recording each segment in `progress` once
+    // it is synced, and continuing after the segments an interrupted save of the unit
+    // recorded, as long as they are still intact and are the ones this save would write
+    fn write_resumable<F: Float>(&self, path: &Path, unit: &str, segments: &[SegmentInput<F>], options: &WriteOptions, progress: &mut BuildProgress) -> Result<(), std::io::Error> {
+        let resumed = progress.segments.get(unit)
+            .and_then(|committed| ContainerWriter::resume(path, committed, options).ok())
+            .filter(|writer| {
+                writer.segments().len() <= segments.len()
+                    && writer.segments().iter().zip(segments).all(|(written, planned)| written.name == planned.name && written.rows == planned.rows.len() as u64)
+            });
+        let mut writer = match resumed {
+            Some(writer) => writer,
+            None => ContainerWriter::create_resumable(path, options)?,
+        };
+        let done = writer.segments().len();
+        if done > 0 {
+            telemetry::info(&format!("   {}: {} of {} segments committed by an interrupted build, resuming after them", unit, done, segments.len()));
+        }
+        self.in_pool(|| writer.write_committing(&segments[done..], options, |header| progress.commit_segments(unit, header)))?;
+        writer.finish(options).map(drop)
+    }
+    
+    // Columnar container, or a legacy (v2) bincode stream for caches written before it.
+    // `shards` is the shard table from the metadata, if the cache has one.
+    fn load_ms1_file<F: Float>(&self, path: &Path, shards: Option<&[ShardInfo]>, cancel: Option<&CancellationToken>, io_fallback: &mut Option<String>) -> Result<IndexedTimsTOFData<F>, std::io::Error> {
+        if parquet_backend::is_parquet_path(path) {
+            return ParquetBackend::read_ms1(path).map(F::from_f32_data);
+        }
+        if !columnar::is_container(path) {
+            return Self::load_data_from_file(path, &self.config, is_lz4_path(path)).map(F::from_f32_data);
+        }
+        self.load_container(path, shards, false, LoadParts::MS1, cancel, io_fallback)?.0.into_iter().next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "MS1 cache holds no segments"))
+    }
+    
+    // The windows of an MS2 file passing `filter`; containers decode only those
+    fn load_ms2_file<F: Float>(&self, path: &Path, shards: Option<&[ShardInfo]>, filter: WindowFilter, cancel: Option<&CancellationToken>, io_fallback: &mut Option<String>) -> Result<Vec<(IsolationWindow, IndexedTimsTOFData<F>)>, std::io::Error> {
+        // Parquet and legacy files are f32, and read whole
+        let widen = |windows: Vec<(IsolationWindow, IndexedTimsTOFData)>| {
+            windows.into_iter()
+                .filter(|(window, _)| filter.matches(window))
+                .map(|(window, data)| (window, F::from_f32_data(data)))
+                .collect()
+        };
+        if parquet_backend::is_parquet_path(path) {
+            return ParquetBackend::read_ms2(path).map(widen);
+        }
+        // Legacy files hold the windows' bounds only, in dataset order
+        if !columnar::is_container(path) {
+            return Self::load_data_from_file::<Vec<((f32, f32), IndexedTimsTOFData)>>(path, &self.config, is_lz4_path(path))
+                .map(window::from_bounds)
+                .map(widen);
+        }
+        Ok(self.load_container(path, shards, false, LoadParts::ms2(filter), cancel, io_fallback)?.1)
+    }
+    
+    // MS1 shards and MS2 windows of a container (those `parts` selects), plus the number of
+    // segments read. The MS1 shards come back merged into one dataset (none if there are
+    // none), or one per shard when `chunked`. Output buffers are sized from the footer, whose
+    // row counts must match the shard table: a mismatch means the file was replaced after
+    // the metadata was written. A mapping the filesystem refuses is noted in `io_fallback`,
+    // unless an earlier file of the load already put a note there.
+    fn load_container<F: Float>(
+        &self,
+        path: &Path,
+        shards: Option<&[ShardInfo]>,
+        chunked: bool,
+        parts: LoadParts,
+        cancel: Option<&CancellationToken>,
+        io_fallback: &mut Option<String>,
+    ) -> Result<LoadedSegments<F>, std::io::Error> {
+        let file = RetryingFile::open(path, &self.config.retry)?;
+        let file_len = file.file_len()?;
+        // The footer is always read; the columns from a mapping once io_mode picks one
+        let mapped: OnceLock<Mmap> = OnceLock::new();
+        let read_at = |offset: u64, len: usize| match mapped.get() {
+            Some(mmap) => mmap.get(offset as usize..offset as usize + len)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+            None => file.read_at(offset, len),
+        };
+        let reader = ContainerReader::open(read_at, file_len, self.config.encryption_key.as_ref())?;
+        if self.config.io_mode.use_mmap(file_len, &reader.header) {
+            // Safety: cache containers are published by rename and never modified afterwards
+            match fdlimit::open(path).and_then(|file| unsafe { Mmap::map(&*file) }) {
+                Ok(mmap) if mmap.len() as u64 == file_len => drop(mapped.set(mmap)),
+                Ok(_) => {}
+                Err(e) => {
+                    telemetry::warn(&format!("⚠️  Could not map {}, reading it instead: {}", path.display(), e));
+                    io_fallback.get_or_insert_with(|| iomode::fallback_note(path, &e));
+                }
+            }
+        }
+        if let Some(shards) = shards {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let mut recorded: Vec<(usize, u64)> = shards.iter()
+                .filter(|shard| shard.file == name)
+                .map(|shard| (shard.segment, shard.data_points))
+                .collect();
+            recorded.sort();
+            let stored: Vec<(usize, u64)> = reader.header.segments.iter().map(|segment| segment.rows).enumerate().collect();
+            if recorded != stored {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+                    "segment sizes of {} don't match the shard table in the metadata; it changed after the cache was written", name
+                )));
+            }
+        }
+        let (mut ms1_segments, mut windows) = group_segments(&reader.header);
+        if !parts.ms1 {
+            ms1_segments.clear();
+        }
+        windows.retain(|(window, _)| parts.windows.is_some_and(|filter| filter.matches(window)));
+        let segments_read = ms1_segments.len() + windows.iter().map(|(_, segments)| segments.len()).sum::<usize>();
+        if self.config.prefetch {
+            let selected = ms1_segments.iter().chain(windows.iter().flat_map(|(_, segments)| segments));
+            let ranges = prefetch::column_ranges(selected.map(|&segment_index| &reader.header.segments[segment_index]));
+            match mapped.get() {
+                Some(mmap) => prefetch::mapped(mmap, &ranges),
+                None => file.prefetch(&ranges),
+            }
+        }
+        self.in_pool(|| {
+            let ms1_parts = match chunked {
+                true => ms1_segments.par_iter()
+                    .map(|&segment_index| reader.segment(segment_index))
+                    .collect::<std::io::Result<Vec<_>>>()?,
+                false if ms1_segments.is_empty() => Vec::new(),
+                false => vec![reader.merged_segments(&ms1_segments, cancel)?],
+            };
+            let ms2_indexed_pairs = windows.par_iter()
+                .map(|(range, segments)| Ok((*range, reader.merged_segments(segments, cancel)?)))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Ok((ms1_parts, ms2_indexed_pairs, segments_read))
+        })
+    }
+    
+    // Load only the MS1 points with m/z in [mz_min, mz_max], reading just those rows.
+    // Works on the MS1 file either locally or through the remote store's range reads,
+    // so remote caches don't have to be downloaded for small queries.
+    pub fn load_ms1_mz_range(
+        &self,
+        source_path: &Path,
+        mz_min: f32,
+        mz_max: f32,
+    ) -> Result<IndexedTimsTOFData, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        let (data, bytes_read, strategy) = self.read_ms1_mz_range(source_path, source_name, mz_min, mz_max, None)?;
+        self.record_query(source_path, format!("ms1 mz [{}, {}]", mz_min, mz_max), RangeRead { strategy, segments_read: 1, bytes_read }, &data, start_time);
+        Ok(data)
+    }
+    
+    // Returns the rows, the number of stored bytes read and a description of how
+    fn read_ms1_mz_range(
+        &self,
+        source_path: &Path,
+        source_name: &str,
+        mz_min: f32,
+        mz_max: f32,
+        rt: Option<(f32, f32)>,
+    ) -> Result<(IndexedTimsTOFData, u64, String), Box<dyn std::error::Error>> {
+        // Single files and archives are columnar containers too; their MS1 shards are
+        // range-read the same way
+        let ms1_cache_path = self.combined_file(source_name)
+            .unwrap_or_else(|| self.get_cache_path(source_name, "ms1_indexed"));
+        self.check_schema(source_name)?;
+        
+        // Signed caches are checked as whole files, so fetch them instead of range-reading
+        if self.config.signing_key.is_some() {
+            self.mirror_from_remote(source_path, source_name)?;
+            self.verify_signature(source_name)?;
+        }
+        
+        // Parquet prunes row groups by their m/z statistics
+        if parquet_backend::is_parquet_path(&ms1_cache_path) {
+            self.mirror_from_remote(source_path, source_name)?;
+            let filter = RegionFilter { mz: Some((mz_min, mz_max)), rt: None };
+            let data = ParquetBackend::scan(&ms1_cache_path, filter)?;
+            // Upper bound: pruned row groups are not reported back by the reader
+            return Ok((data, fs::metadata(&ms1_cache_path)?.len(), "parquet scan (row-group pruning)".to_string()));
+        }
+        
+        // A query outside every shard is answered from the shard table, without a footer read
+        if let Some(table) = self.recorded_shards(source_name) {
+            let ms1_shards = table.iter().filter(|shard| shard.name == "ms1").count();
+            if ms1_shards > 0 && !table.iter().any(|shard| shard.name == "ms1" && shard.overlaps(mz_min, mz_max)) {
+                return Ok((IndexedTimsTOFData::new(), 0, format!("pruned by shard table (0/{} shards)", ms1_shards)));
+            }
+        }
+        
+        let bytes_read = AtomicU64::new(0);
+        let counted = |read_at: &dyn Fn(u64, usize) -> std::io::Result<Vec<u8>>, offset: u64, len: usize| {
+            bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+            read_at(offset, len)
+        };
+        
+        if ms1_cache_path.exists() {
+            if !columnar::is_container(&ms1_cache_path) {
+                // Legacy cache: no column table, so load everything and cut the range
+                let ms1_indexed = self.load_ms1_file(&ms1_cache_path, None, None, &mut None)?;
+                let file_len = fs::metadata(&ms1_cache_path)?.len();
+                return Ok((mz_range_of(&ms1_indexed, mz_min, mz_max), file_len, "legacy full load".to_string()));
+            }
+            let file = RetryingFile::open(&ms1_cache_path, &self.config.retry)?;
+            let file_len = file.file_len()?;
+            let file_reader = |offset: u64, len: usize| file.read_at(offset, len);
+            let read_at = |offset: u64, len: usize| counted(&file_reader, offset, len);
+            let reader = ContainerReader::open(read_at, file_len, self.config.encryption_key.as_ref())?;
+            let encoding = ms1_encoding(&reader.header, &ms1_cache_path)?;
+            let (data, shards_read) = self.in_pool(|| reader.region(mz_min, mz_max, rt))?;
+            let strategy = format!("columnar range read ({}, {}/{} shards)", encoding, shards_read, reader.ms1_shards());
+            return Ok((data, bytes_read.load(Ordering::Relaxed), strategy));
+        }
+        
+        let remote = match &self.remote {
+            Some(remote) => remote,
+            None => return Err(format!("no MS1 cache for {:?}", source_path).into()),
+        };
+        let file_name = file_name_of(&ms1_cache_path);
+        let remote_reader = |offset: u64, len: usize| self.config.retry.run(
+            || format!("reading {} bytes at {} of {}", len, offset, file_name), |_| remote.read_range(&file_name, offset, len),
+        );
+        let read_at = |offset: u64, len: usize| counted(&remote_reader, offset, len);
+        let reader = match ContainerReader::open(read_at, remote.len(&file_name)?, self.config.encryption_key.as_ref()) {
+            Ok(reader) => reader,
+            // A server without range requests: mirror the files and read the local copy
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported && !self.config.read_only => {
+                telemetry::info(&format!("{}; mirroring {} instead", e, source_path.display()));
+                self.mirror_from_remote(source_path, source_name)?;
+                if !ms1_cache_path.exists() {
+                    return Err(format!("no MS1 cache for {:?} in {}", source_path, remote.describe()).into());
+                }
+                return self.read_ms1_mz_range(source_path, source_name, mz_min, mz_max, rt);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let encoding = ms1_encoding(&reader.header, &ms1_cache_path)?;
+        let (data, shards_read) = self.in_pool(|| reader.region(mz_min, mz_max, rt))?;
+        let strategy = format!("remote range read ({}, {}/{} shards)", encoding, shards_read, reader.ms1_shards());
+        telemetry::info(&format!("Range-loaded {} MS1 points from {}", data.mz_values.len(), remote.describe()));
+        Ok((data, bytes_read.load(Ordering::Relaxed), strategy))
+    }
+    
+    // Load one MS2 window by its isolation range (matched to 0.001 Th), reading only that
+    // window's segments. None if the cache has no such window.
+    pub fn load_ms2_window_by_range(
+        &self,
+        source_path: &Path,
+        key: WindowKey,
+    ) -> Result<Option<IndexedTimsTOFData>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        let (data, bytes_read, strategy, segments_read) = self.read_ms2_window(source_path, source_name, key)?;
+        if let Some(data) = &data {
+            self.record_query(source_path, format!("ms2 window {}", key), RangeRead { strategy, segments_read, bytes_read }, data, start_time);
+        }
+        Ok(data)
+    }
+    
+    // Returns the window, the number of stored bytes read, a description of how and the
+    // number of segments read
+    fn read_ms2_window(
+        &self,
+        source_path: &Path,
+        source_name: &str,
+        key: WindowKey,
+    ) -> Result<WindowRead, Box<dyn std::error::Error>> {
+        let ms2_cache_path = self.combined_file(source_name)
+            .unwrap_or_else(|| self.get_cache_path(source_name, "ms2_indexed"));
+        self.check_schema(source_name)?;
+        if self.config.signing_key.is_some() {
+            self.mirror_from_remote(source_path, source_name)?;
+            self.verify_signature(source_name)?;
+        }
+        
+        // Parquet and legacy caches have no per-window index to read from
+        if parquet_backend::is_parquet_path(&ms2_cache_path) || (ms2_cache_path.exists() && !columnar::is_container(&ms2_cache_path)) {
+            self.mirror_from_remote(source_path, source_name)?;
+            let data = self.load_ms2_file(&ms2_cache_path, None, WindowFilter::All, None, &mut None)?.into_iter()
+                .find(|(window, _)| window.key() == key)
+                .map(|(_, data)| data);
+            return Ok((data, fs::metadata(&ms2_cache_path)?.len(), "full load".to_string(), 1));
+        }
+        if let Some(table) = self.recorded_shards(source_name) {
+            let mut windows = table.iter().filter(|shard| shard.name == "ms2_window").peekable();
+            if windows.peek().is_some() && !windows.any(|shard| WindowKey::new(shard.mz_range.0, shard.mz_range.1) == key) {
+                return Ok((None, 0, "pruned by shard table".to_string(), 0));
+            }
+        }
+        
+        let bytes_read = AtomicU64::new(0);
+        let counted = |read_at: &dyn Fn(u64, usize) -> std::io::Result<Vec<u8>>, offset: u64, len: usize| {
+            bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+            read_at(offset, len)
+        };
+        
+        if ms2_cache_path.exists() {
+            let file = RetryingFile::open(&ms2_cache_path, &self.config.retry)?;
+            let file_len = file.file_len()?;
+            let file_reader = |offset: u64, len: usize| file.read_at(offset, len);
+            let read_at = |offset: u64, len: usize| counted(&file_reader, offset, len);
+            let reader = ContainerReader::open(read_at, file_len, self.config.encryption_key.as_ref())?;
+            let (data, segments_read) = self.in_pool(|| read_window(&reader, key))?;
+            return Ok((data, bytes_read.load(Ordering::Relaxed), "columnar window read".to_string(), segments_read));
+        }
+        
+        match &self.remote {
+            Some(remote) => {
+                let file_name = file_name_of(&ms2_cache_path);
+                let remote_reader = |offset: u64, len: usize| self.config.retry.run(
+                    || format!("reading {} bytes at {} of {}", len, offset, file_name), |_| remote.read_range(&file_name, offset, len),
+                );
+                let read_at = |offset: u64, len: usize| counted(&remote_reader, offset, len);
+                let reader = ContainerReader::open(read_at, remote.len(&file_name)?, self.config.encryption_key.as_ref())?;
+                let (data, segments_read) = self.in_pool(|| read_window(&reader, key))?;
+                Ok((data, bytes_read.load(Ordering::Relaxed), "remote window read".to_string(), segments_read))
+            }
+            None => Err(format!("no MS2 cache for {:?}", source_path).into()),
+        }
+    }
+    
+    fn record_query(&self, source_path: &Path, query: String, read: RangeRead, data: &IndexedTimsTOFData, start_time: std::time::Instant) {
+        self.profiler.record(QueryProfile {
+            source: source_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            query,
+            strategy: read.strategy,
+            segments_read: read.segments_read,
+            bytes_read: read.bytes_read,
+            rows_returned: data.mz_values.len() as u64,
+            elapsed: start_time.elapsed(),
+            io_fallback: None,
+        });
+    }
+    
+    // End-of-operation summary: a JSON line on the report writer if there is one
+    // (report.rs), else the text `print` shows
+    fn report(&self, report: OperationReport, print: impl FnOnce()) {
+        match &self.reports {
+            Some(writer) => report.write_to(writer),
+            None => print(),
+        }
+    }
+    
+    // Profile and report a finished load
+    fn finish_load(&self, source_path: &Path, profile: QueryProfile, print: impl FnOnce()) {
+        let report = OperationReport::from_profile(source_path, &profile);
+        self.profiler.record(profile);
+        self.report(report, print);
+    }
+    
+    // Parallel work inside `work` runs on the manager's pool if it has one
+    fn in_pool<T: Send>(&self, work: impl FnOnce() -> T + Send) -> T {
+        match &self.pool {
+            Some(pool) => pool.install(work),
+            None => work(),
+        }
+    }
+    
+    fn async_pool(&self) -> &rayon::ThreadPool {
+        self.async_pool.get_or_init(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.config.async_threads)
+                .thread_name(|i| format!("timstof-cache-{}", i))
+                .build()
+                .expect("failed to build cache worker pool")
+        })
+    }
+    
+    // Async variant of load_indexed_data for use inside an async runtime: decoding runs on
+    // the manager's dedicated pool, the returned future just waits for it (any executor)
+    pub fn load_indexed_data_async(
+        self: &Arc<Self>,
+        source_path: &Path,
+    ) -> Offloaded<Result<LoadedData, Box<dyn std::error::Error + Send + Sync>>> {
+        let manager = Arc::clone(self);
+        let source_path = source_path.to_path_buf();
+        offload::offload(self.async_pool(), move || {
+            manager.load_indexed_data(&source_path).map_err(|e| e.to_string().into())
+        })
+    }
+    
+    // Async variant of save_indexed_data. The data is shared with the pool, not copied:
+    // callers keep their own Arc (e.g. to query while the save runs) at no extra memory
+    pub fn save_indexed_data_async(
+        self: &Arc<Self>,
+        source_path: &Path,
+        ms1_indexed: Arc<IndexedTimsTOFData>,
+        ms2_indexed_pairs: Arc<[(IsolationWindow, IndexedTimsTOFData)]>,
+    ) -> Offloaded<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        let manager = Arc::clone(self);
+        let source_path = source_path.to_path_buf();
+        offload::offload(self.async_pool(), move || {
+            manager.save_indexed_data(&source_path, &ms1_indexed, &ms2_indexed_pairs).map_err(|e| e.to_string().into())
+        })
+    }
+    
+    pub fn load_ms1_mz_range_async(
+        self: &Arc<Self>,
+        source_path: &Path,
+        mz_min: f32,
+        mz_max: f32,
+    ) -> Offloaded<Result<IndexedTimsTOFData, Box<dyn std::error::Error + Send + Sync>>> {
+        let manager = Arc::clone(self);
+        let source_path = source_path.to_path_buf();
+        offload::offload(self.async_pool(), move || {
+            manager.load_ms1_mz_range(&source_path, mz_min, mz_max).map_err(|e| e.to_string().into())
+        })
+    }
+    
+    // Read amplification of queries made through this manager (last one) and of every
+    // dataset over all runs (report)
+    pub fn profiler(&self) -> &QueryProfiler {
+        &self.profiler
+    }
+    
+    // HDF5 layout for MATLAB/h5py users: /ms1/<column> datasets plus one group per MS2
+    // window (/ms2/window_0000, ...) carrying isolation_low/isolation_high attributes.
+    // Column names match the Arrow export.
+    #[cfg(feature = "hdf5")]
+    pub fn export_hdf5(&self, source_path: &Path, out_path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+        let start_time = std::time::Instant::now();
+        
+        let write_columns = |group: &hdf5::Group, data: &IndexedTimsTOFData| -> hdf5::Result<()> {
+            group.new_dataset_builder().with_data(data.rt_values_min.as_slice()).create("rt")?;
+            group.new_dataset_builder().with_data(data.mobility_values.as_slice()).create("mobility")?;
+            group.new_dataset_builder().with_data(data.mz_values.as_slice()).create("mz")?;
+            group.new_dataset_builder().with_data(data.intensity_values.as_slice()).create("intensity")?;
+            group.new_dataset_builder().with_data(data.frame_indices.as_slice()).create("frame")?;
+            group.new_dataset_builder().with_data(data.scan_indices.as_slice()).create("scan")?;
+            Ok(())
+        };
+        
+        let partial_path = out_path.with_extension("partial");
+        {
+            let file = hdf5::File::create(&partial_path)?;
+            write_columns(&file.create_group("ms1")?, &ms1_indexed)?;
+            
+            let ms2 = file.create_group("ms2")?;
+            ms2.new_attr::<u32>().create("window_count")?.write_scalar(&(ms2_indexed_pairs.len() as u32))?;
+            for (i, (isolation, data)) in ms2_indexed_pairs.iter().enumerate() {
+                let window = ms2.create_group(&format!("window_{:04}", i))?;
+                window.new_attr::<f32>().create("isolation_low")?.write_scalar(&isolation.mz_low)?;
+                window.new_attr::<f32>().create("isolation_high")?.write_scalar(&isolation.mz_high)?;
+                write_columns(&window, data)?;
+            }
+        } // File is closed (flushed) when dropped
+        fs::rename(&partial_path, out_path)?;
+        
+        let total_rows = ms1_indexed.mz_values.len()
+            + ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+        telemetry::info(&format!("✅ Exported {} rows to {} in {:.3}s", total_rows, out_path.display(), start_time.elapsed().as_secs_f32()));
+        Ok(total_rows)
+    }
+
+    // Rebuild spectra from the cached points and write them as mzML 1.1 for tools that
+    // can't read Bruker .d (OpenMS, Skyline, pyteomics). Returns the number of spectra.
+    pub fn export_mzml(&self, source_path: &Path, out_path: &Path, options: &MzmlOptions) -> Result<usize, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+        let start_time = std::time::Instant::now();
+
+        let source_name = source_path.file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or("unknown".into());
+        let spectra = mzml::write_mzml(out_path, &source_name, &ms1_indexed, &ms2_indexed_pairs, options)?;
+
+        telemetry::info(&format!("✅ Exported {} spectra to {} in {:.3}s", spectra, out_path.display(), start_time.elapsed().as_secs_f32()));
+        Ok(spectra)
+    }
+
+    // MS1 points inside an m/z and/or RT region. Parquet caches push both bounds down to
+    // row-group statistics; columnar caches range-read by m/z, skip compressed blocks
+    // outside the RT range, and filter RT in memory.
+    pub fn load_ms1_region(&self, source_path: &Path, filter: RegionFilter) -> Result<IndexedTimsTOFData, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let start_time = std::time::Instant::now();
+        let query = format!("ms1 region mz {:?} rt {:?}", filter.mz, filter.rt);
+        self.check_schema(source_name)?;
+        let ms1_cache_path = self.get_cache_path(source_name, "ms1_indexed");
+        if parquet_backend::is_parquet_path(&ms1_cache_path) && filter.rt.is_some() {
+            self.mirror_from_remote(source_path, source_name)?;
+            self.verify_signature(source_name)?;
+            let data = ParquetBackend::scan(&ms1_cache_path, filter)?;
+            let bytes_read = fs::metadata(&ms1_cache_path)?.len();
+            let read = RangeRead { strategy: "parquet scan (row-group pruning)".to_string(), segments_read: 1, bytes_read };
+            self.record_query(source_path, query, read, &data, start_time);
+            return Ok(data);
+        }
+        
+        let (mz_min, mz_max) = filter.mz.unwrap_or((f32::NEG_INFINITY, f32::INFINITY));
+        let (data, bytes_read, strategy) = self.read_ms1_mz_range(source_path, source_name, mz_min, mz_max, filter.rt)?;
+        let data = match filter.rt {
+            Some((rt_min, rt_max)) => {
+                let keep: Vec<usize> = (0..data.rt_values_min.len())
+                    .filter(|&i| data.rt_values_min[i] >= rt_min && data.rt_values_min[i] <= rt_max)
+                    .collect();
+                IndexedTimsTOFData {
+                    rt_values_min: keep.iter().map(|&i| data.rt_values_min[i]).collect(),
+                    mobility_values: keep.iter().map(|&i| data.mobility_values[i]).collect(),
+                    mz_values: keep.iter().map(|&i| data.mz_values[i]).collect(),
+                    intensity_values: keep.iter().map(|&i| data.intensity_values[i]).collect(),
+                    frame_indices: keep.iter().map(|&i| data.frame_indices[i]).collect(),
+                    scan_indices: keep.iter().map(|&i| data.scan_indices[i]).collect(),
+                }
+            }
+            None => data,
+        };
+        self.record_query(source_path, query, RangeRead { strategy, segments_read: 1, bytes_read }, &data, start_time);
+        Ok(data)
+    }
+    
+    // The cached MS1 points and all MS2 windows as one table, shared by the Arrow and
+    // Parquet exports. MS1 rows have ms_level 1 and null isolation bounds.
+    fn export_frame(&self, source_path: &Path) -> Result<DataFrame, Box<dyn std::error::Error>> {
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+        
+        let total_rows = ms1_indexed.mz_values.len()
+            + ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+        let mut rt = Vec::with_capacity(total_rows);
+        let mut mobility = Vec::with_capacity(total_rows);
+        let mut mz = Vec::with_capacity(total_rows);
+        let mut intensity = Vec::with_capacity(total_rows);
+        let mut frame = Vec::with_capacity(total_rows);
+        let mut scan = Vec::with_capacity(total_rows);
+        let mut ms_level: Vec<u32> = Vec::with_capacity(total_rows);
+        let mut isolation_low: Vec<Option<f32>> = Vec::with_capacity(total_rows);
+        let mut isolation_high: Vec<Option<f32>> = Vec::with_capacity(total_rows);
+        
+        let parts = std::iter::once((&ms1_indexed, 1u32, None))
+            .chain(ms2_indexed_pairs.iter().map(|(window, data)| (data, 2u32, Some(window.bounds()))));
+        for (data, level, window) in parts {
+            let rows = data.mz_values.len();
+            rt.extend_from_slice(&data.rt_values_min);
+            mobility.extend_from_slice(&data.mobility_values);
+            mz.extend_from_slice(&data.mz_values);
+            intensity.extend_from_slice(&data.intensity_values);
+            frame.extend_from_slice(&data.frame_indices);
+            scan.extend_from_slice(&data.scan_indices);
+            ms_level.extend(std::iter::repeat_n(level, rows));
+            isolation_low.extend(std::iter::repeat_n(window.map(|(low, _)| low), rows));
+            isolation_high.extend(std::iter::repeat_n(window.map(|(_, high)| high), rows));
+        }
+        
+        Ok(DataFrame::new(vec![
+            Series::new("rt", rt),
+            Series::new("mobility", mobility),
+            Series::new("mz", mz),
+            Series::new("intensity", intensity),
+            Series::new("frame", frame),
+            Series::new("scan", scan),
+            Series::new("ms_level", ms_level),
+            Series::new("isolation_low", isolation_low),
+            Series::new("isolation_high", isolation_high),
+        ])?)
+    }
+    
+    // Write the cached data as one Arrow IPC (Feather v2) table, readable with
+    // pyarrow.feather.read_table / arrow::read_feather. Returns the number of rows written.
+    pub fn export_arrow(&self, source_path: &Path, out_path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut df = self.export_frame(source_path)?;
+        let start_time = std::time::Instant::now();
+        let total_rows = df.height();
+        
+        // Uncompressed so readers can memory-map the file; written under a temporary name
+        let partial_path = out_path.with_extension("partial");
+        let mut file = fdlimit::create(&partial_path)?;
+        IpcWriter::new(&mut file).finish(&mut df)?;
+        drop(file);
+        fs::rename(&partial_path, out_path)?;
+        
+        telemetry::info(&format!("✅ Exported {} rows to {} in {:.3}s", total_rows, out_path.display(), start_time.elapsed().as_secs_f32()));
+        Ok(total_rows)
+    }
+    
+    // Same table as export_arrow, as a single Parquet file for DuckDB/Spark/pandas.
+    // Unlike the Parquet cache format, MS1 and MS2 share one file.
+    pub fn export_parquet(&self, source_path: &Path, out_path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut df = self.export_frame(source_path)?;
+        let start_time = std::time::Instant::now();
+        let total_rows = df.height();
+        
+        let partial_path = out_path.with_extension("partial");
+        let file = fdlimit::create(&partial_path)?;
+        ParquetWriter::new(file).with_statistics(true).finish(&mut df)?;
+        fs::rename(&partial_path, out_path)?;
+        
+        telemetry::info(&format!("✅ Exported {} rows to {} in {:.3}s", total_rows, out_path.display(), start_time.elapsed().as_secs_f32()));
+        Ok(total_rows)
+    }
+    
+    // OPTIMIZED: Single-threaded save with optional compression
+    fn save_data_to_file<T>(
+        path: &Path,
+        data: &T,
+        config: &CacheConfig,
+        use_compression: bool,
+    ) -> Result<(), std::io::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        // Write under a temporary name and rename: a file at `path` is always complete
+        let partial_path = path.with_extension("partial");
+        let file = fdlimit::create(&partial_path)?;
+        let mut writer = BufWriter::with_capacity(config.buffer_size, file);
+        
+        if use_compression {
+            // Use LZ4 compression only when beneficial
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+            bincode::serialize_into(&mut encoder, data)
+                .map_err(std::io::Error::other)?;
+            // finish() writes the last block and end mark; dropping the encoder would lose them
+            writer = encoder.finish()
+                .map_err(std::io::Error::other)?;
+        } else {
+            // Direct binary serialization (fastest)
+            bincode::serialize_into(&mut writer, data)
+                .map_err(std::io::Error::other)?;
+        }
+        
+        writer.into_inner().map_err(|e| e.into_error())?;
+        fs::rename(&partial_path, path)
+    }
+    
+    // OPTIMIZED: Single-threaded load with optional compression
+    fn load_data_from_file<T>(
+        path: &Path,
+        config: &CacheConfig,
+        use_compression: bool,
+    ) -> Result<T, std::io::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let file = fdlimit::open(path)?;
+        let reader = BufReader::with_capacity(config.buffer_size, file);
+        
+        if use_compression {
+            // Use LZ4 decompression
+            Self::deserialize_whole(path, lz4_flex::frame::FrameDecoder::new(reader))
+        } else {
+            // Direct binary deserialization (fastest)
+            Self::deserialize_whole(path, reader)
+        }
+    }
+    
+    // bincode has no field tags: a stream written for other structs decodes without error
+    // into wrong values, or stops short of the end. Requiring every byte to be consumed
+    // turns the second case into an error instead of garbage.
+    fn deserialize_whole<T, R>(path: &Path, mut reader: R) -> Result<T, std::io::Error>
+    where
+        T: serde::de::DeserializeOwned,
+        R: std::io::Read,
+    {
+        let layout_error = |detail: String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+            "{} was written with a different data layout ({}); clear and rebuild the cache", path.display(), detail
+        ));
+        let data = bincode::deserialize_from(&mut reader).map_err(|e| layout_error(e.to_string()))?;
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(layout_error("trailing bytes".to_string()));
+        }
+        Ok(data)
+    }
+    
+    pub fn clear_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        if self.cache_dir.exists() {
+            fs::remove_dir_all(&self.cache_dir)?;
+            telemetry::info("Cache cleared");
+        }
+        Ok(())
+    }
+    
+    // Move the files of one source (MS1, MS2 and metadata) to the trash; see restore()
+    pub fn clear_cache_for(&self, source_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        self.hot.remove(source_path);
+        
+        // A shared content-addressed entry only goes once its last user is cleared
+        if self.config.content_addressed {
+            let mut index = ContentIndex::load(&self.cache_dir);
+            let remaining = index.remove_ref(&self.source_key(source_path), source_path);
+            index.save()?;
+            if remaining > 0 {
+                telemetry::info(&format!("Cache {} is still used by {} other source(s), kept", source_name, remaining));
+                return Ok(());
+            }
+        }
+        
+        if !self.trash_cache(source_name)? {
+            telemetry::info(&format!("No cache to clear for {}", source_name));
+        }
+        Ok(())
+    }
+    
+    // Move the files of a cache to the trash; false if it has none
+    fn trash_cache(&self, source_name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let files = self.source_files(source_name)?;
+        if files.is_empty() {
+            return Ok(false);
+        }
+        
+        let trashed_at = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        let entry_dir = self.trash_dir().join(format!("{}@{}", source_name, trashed_at));
+        fs::create_dir_all(&entry_dir)?;
+        for path in files {
+            fs::rename(&path, entry_dir.join(path.file_name().unwrap()))?;
+        }
+        self.drop_from_manifest(source_name)?;
+        // Decoded again on demand, from a restored cache too
+        self.remove_zero_copy_shards(source_name, None);
+        telemetry::info(&format!("Cache for {} moved to trash (restorable for {} days)",
+                 source_name, self.config.trash_retention.as_secs() / 86400));
+        
+        self.purge_trash()?;
+        Ok(true)
+    }
+    
+    // Repack the cache of one source into a single zstd-19 archive (MS1 shards and MS2
+    // windows in one container) for long-term storage. Loads and range reads work on
+    // either form; unpack_cache restores the fast one. Returns the archive size.
+    pub fn compact_cache(&self, source_path: &Path) -> Result<u64, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let archive_path = self.archive_path(source_name);
+        if self.is_archived(source_name) {
+            telemetry::info(&format!("{} is already archived", source_path.display()));
+            return Ok(fs::metadata(&archive_path)?.len());
+        }
+        // The remote copy stays in fast form and would be mirrored back next to the archive
+        if self.remote.is_some() {
+            return Err("compacting a two-tier (remote) cache is not supported".into());
+        }
+        let start_time = std::time::Instant::now();
+        
+        let fast_files = self.data_files(source_name);
+        let codec = CompressionType::archival().codec();
+        let meta_path = self.get_metadata_path(source_name);
+        // The archive keeps the width the cache was saved at, whatever this manager's is
+        let ms1_indexed_shards = match recorded_precision(&fdlimit::read_to_string(&meta_path)?) {
+            Precision::F32 => self.write_archive::<f32>(source_path, &archive_path, codec)?,
+            Precision::F64 => self.write_archive::<f64>(source_path, &archive_path, codec)?,
+        };
+        
+        // Keep the descriptive metadata, replace what described the fast files. The old
+        // metadata goes first, so an interruption leaves an invalid cache, not a wrong one.
+        let mut metadata = carried_metadata(&fdlimit::read_to_string(&meta_path)?, &FORM_SPECIFIC_METADATA);
+        metadata.push_str(&format!("form: archive\narchive_codec: {}\nms1_shards: {}\n", codec.label(), ms1_indexed_shards));
+        metadata.push_str(&shard_lines(&shard_table(&[("archive", archive_path.clone())])?)?);
+        fs::remove_file(&meta_path)?;
+        
+        let mut fast_bytes = 0u64;
+        for (_, path) in fast_files {
+            fast_bytes += fs::metadata(&path)?.len();
+            fs::remove_file(&path)?;
+        }
+        let metadata = match &self.config.signing_key {
+            Some(key) => self.sign_metadata(key, metadata, &self.signed_files(source_name))?,
+            None => metadata,
+        };
+        durability::write_file(&meta_path, metadata, self.config.durability)?;
+        self.commit_generation(source_name)?;
+        self.record_in_manifest(source_path, source_name)?;
+        
+        let archive_bytes = fs::metadata(&archive_path)?.len();
+        telemetry::info(&format!("🗜️  Compacted {}: {:.2} MB → {:.2} MB ({}) in {:.3}s",
+                 source_path.display(), fast_bytes as f32 / 1024.0 / 1024.0, archive_bytes as f32 / 1024.0 / 1024.0,
+                 codec.label(), start_time.elapsed().as_secs_f32()));
+        Ok(archive_bytes)
+    }
+    
+    // MS1 shards and MS2 windows of a source, loaded and written back as one archive with
+    // float columns of type F. Returns the number of MS1 shards.
+    fn write_archive<F: Float>(&self, source_path: &Path, archive_path: &Path, codec: ColumnCodec) -> Result<usize, Box<dyn std::error::Error>> {
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data_as::<F>(source_path)?;
+        let mut segments = ms1_segments(&ms1_indexed, &self.shard_plan(ms1_indexed.mz_values.len()));
+        let ms1_indexed_shards = segments.len();
+        // Archives trade decode speed for size, so even small windows are compressed
+        segments.extend(ms2_segments(&ms2_indexed_pairs, 0));
+        let options = WriteOptions { precision: F::PRECISION, ..self.write_options(&compression::uniform_codecs(codec))? };
+        self.in_pool(|| columnar::write_container(archive_path, &segments, &options))?;
+        Ok(ms1_indexed_shards)
+    }
+    
+    // Load whatever is intact when parts of a cache are damaged: every MS1 shard and MS2
+    // window whose checksums verify, plus one ShardError per missing file or bad segment.
+    // Caches that aren't columnar have no shards and load (or fail) as a whole.
+    pub fn load_indexed_data_tolerant(
+        &self,
+        source_path: &Path,
+    ) -> Result<(IndexedTimsTOFData, Ms2Windows, Vec<ShardError>), Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        // A cache of another schema isn't damaged, it is unreadable as a whole
+        self.check_schema(source_name)?;
+        if let Err(e) = self.mirror_from_remote(source_path, source_name) {
+            telemetry::warn(&format!("Remote cache unavailable: {}", e));
+        }
+        if !self.get_metadata_path(source_name).exists() {
+            return Err(format!("no cache for {}", source_path.display()).into());
+        }
+        let data_files = self.data_files(source_name);
+        if data_files.iter().any(|(_, path)| path.exists() && !columnar::is_container(path)) {
+            let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+            return Ok((ms1_indexed, ms2_indexed_pairs, Vec::new()));
+        }
+        telemetry::info("Loading intact shards from cache...");
+        let start_time = std::time::Instant::now();
+        
+        let mut ms1_parts = Vec::new();
+        let mut ms2_parts = Vec::new();
+        let mut errors = Vec::new();
+        let mut total = 0;
+        for (_, path) in &data_files {
+            let (segments, file_errors) = self.read_segments_tolerant(path, true);
+            total += segments.len() + file_errors.len();
+            errors.extend(file_errors);
+            for (segment, data) in segments {
+                match segment.name.as_str() {
+                    "ms2_window" => ms2_parts.push((isolation_window(&segment, 0), data)),
+                    _ => ms1_parts.push(data),
+                }
+            }
+        }
+        // Damaged files can't match their signed checksums; with every shard intact a
+        // mismatch means tampering, which is never tolerated
+        if let Err(e) = self.verify_signature(source_name) {
+            if errors.is_empty() {
+                return Err(e.into());
+            }
+        }
+        
+        let ms1_indexed = match ms1_parts.is_empty() {
+            true => IndexedTimsTOFData::new(),
+            false => columnar::merge_by_mz(ms1_parts),
+        };
+        if errors.is_empty() {
+            telemetry::info(&format!("✅ All {} segments intact, loaded in {:.3}s", total, start_time.elapsed().as_secs_f32()));
+        } else {
+            telemetry::warn(&format!("⚠️  Loaded {} of {} segments in {:.3}s, {} unreadable:", total - errors.len(), total,
+                     start_time.elapsed().as_secs_f32(), errors.len()));
+            for error in &errors {
+                telemetry::warn(&format!("   ✗ {}", error));
+            }
+        }
+        Ok((ms1_indexed, group_windows(ms2_parts), errors))
+    }
+    
+    // Segments of one container whose stored columns verify (decoded unless `decode` is
+    // false, e.g. when only looking for damage), and errors for the others
+    fn read_segments_tolerant(&self, path: &Path, decode: bool) -> (Vec<(SegmentDescriptor, IndexedTimsTOFData)>, Vec<ShardError>) {
+        let whole_file = |error: String| ShardError {
+            file: path.to_path_buf(),
+            segment: None,
+            name: String::new(),
+            attrs: BTreeMap::new(),
+            error,
+        };
+        let file = match fdlimit::open(path) {
+            Ok(file) => file,
+            Err(e) => return (Vec::new(), vec![whole_file(e.to_string())]),
+        };
+        let reader = match file.metadata().and_then(|m| ContainerReader::open(columnar::file_reader(&file), m.len(), self.config.encryption_key.as_ref())) {
+            Ok(reader) => reader,
+            Err(e) => return (Vec::new(), vec![whole_file(e.to_string())]),
+        };
+        
+        let results: Vec<std::io::Result<IndexedTimsTOFData>> = self.in_pool(|| (0..reader.header.segments.len())
+            .into_par_iter()
+            .map(|segment_index| {
+                reader.verify_segment(segment_index)?;
+                match decode {
+                    true => reader.segment(segment_index),
+                    false => Ok(IndexedTimsTOFData::new()),
+                }
+            })
+            .collect());
+        let mut segments = Vec::new();
+        let mut errors = Vec::new();
+        for ((segment_index, segment), result) in reader.header.segments.iter().enumerate().zip(results) {
+            match result {
+                Ok(data) => segments.push((segment.clone(), data)),
+                Err(e) => errors.push(ShardError {
+                    file: path.to_path_buf(),
+                    segment: Some(segment_index),
+                    name: segment.name.clone(),
+                    attrs: segment.attrs.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        (segments, errors)
+    }
+    
+    // Regenerate only the missing or corrupt parts of a cache. `raw_loader` reads and
+    // indexes the source (as a build would); intact segments are copied as stored and only
+    // damaged ones are re-encoded from its output. Returns the number of segments (or
+    // whole files, when a file is gone or its footer unreadable) regenerated.
+    pub fn repair_cache<F>(&self, source_path: &Path, raw_loader: F) -> Result<usize, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&Path) -> Result<(IndexedTimsTOFData, Vec<(IsolationWindow, IndexedTimsTOFData)>), Box<dyn std::error::Error>>,
+    {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let meta_path = self.get_metadata_path(source_name);
+        if !meta_path.exists() {
+            return Err(format!("no cache metadata for {}; build the cache instead", source_path.display()).into());
+        }
+        let data_files = self.data_files(source_name);
+        if data_files.iter().any(|(_, path)| path.exists() && !columnar::is_container(path)) {
+            return Err("only columnar caches can be repaired shard by shard; rebuild it".into());
+        }
+        let damage: Vec<ShardError> = data_files.iter()
+            .flat_map(|(_, path)| self.read_segments_tolerant(path, false).1)
+            .collect();
+        if damage.is_empty() {
+            telemetry::info(&format!("{}: no missing or corrupt shards", source_path.display()));
+            return Ok(0);
+        }
+        for error in &damage {
+            telemetry::warn(&format!("   ✗ {}", error));
+        }
+        
+        let start_time = std::time::Instant::now();
+        let (ms1_indexed, ms2_indexed_pairs) = raw_loader(source_path)?;
+        
+        // Locate every replacement before touching the cache, so a mismatch leaves it as it was
+        let mut plans = Vec::new();
+        for (_, path) in &data_files {
+            let damaged: Vec<&ShardError> = damage.iter().filter(|error| &error.file == path).collect();
+            if damaged.is_empty() {
+                continue;
+            }
+            if damaged.iter().any(|error| error.segment.is_none()) {
+                plans.push((path, None));
+                continue;
+            }
+            let file = fdlimit::open(path)?;
+            let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+            let mut replacements = Vec::new();
+            for segment_index in damaged.iter().filter_map(|error| error.segment) {
+                let (data, rows) = regenerated_rows(&header, segment_index, &ms1_indexed, &ms2_indexed_pairs)?;
+                replacements.push((segment_index, data, rows, segment_codecs(&header.segments[segment_index])));
+            }
+            plans.push((path, Some((header, replacements))));
+        }
+        
+        let previous = fdlimit::read_to_string(&meta_path)?;
+        fs::remove_file(&meta_path)?;
+        let mut repaired = 0;
+        for (path, plan) in plans {
+            let (header, replacements) = match plan {
+                Some(plan) => plan,
+                None => {
+                    self.regenerate_file(source_name, path, &ms1_indexed, &ms2_indexed_pairs)?;
+                    repaired += 1;
+                    continue;
+                }
+            };
+            let inputs: BTreeMap<usize, SegmentInput> = replacements.iter()
+                .map(|(segment_index, data, rows, codecs)| {
+                    let segment = &header.segments[*segment_index];
+                    (*segment_index, SegmentInput {
+                        name: segment.name.clone(),
+                        attrs: segment.attrs.clone(),
+                        data,
+                        rows: rows.clone(),
+                        codecs: Some(codecs),
+                    })
+                })
+                .collect();
+            self.in_pool(|| columnar::rewrite_container(path, &inputs, &self.write_options(&CodecMap::new())?))?;
+            repaired += inputs.len();
+        }
+        
+        let mut ms1_shards = 0;
+        for (_, path) in self.data_files(source_name) {
+            let file = fdlimit::open(&path)?;
+            let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+            ms1_shards += header.segments.iter().filter(|segment| segment.name == "ms1").count();
+        }
+        let mut metadata = carried_metadata(&previous, &["repaired at", "ms1_shards", "shard", "signing_key_id", "signature"]);
+        metadata.push_str(&format!("repaired at: {:?}\nms1_shards: {}\n", self.recorded_time(), ms1_shards));
+        metadata.push_str(&shard_lines(&shard_table(&self.data_files(source_name))?)?);
+        let metadata = match &self.config.signing_key {
+            Some(key) => self.sign_metadata(key, metadata, &self.signed_files(source_name))?,
+            None => metadata,
+        };
+        durability::write_file(&meta_path, metadata, self.config.durability)?;
+        self.commit_generation(source_name)?;
+        self.record_in_manifest(source_path, source_name)?;
+        
+        if let Some(remote) = &self.remote {
+            for local_path in self.source_file_paths(source_name).into_iter().filter(|path| path.exists()) {
+                remote.put(&file_name_of(&local_path), &local_path)?;
+            }
+            telemetry::info(&format!("Published cache to {}", remote.describe()));
+        }
+        
+        telemetry::info(&format!("🔧 Repaired {} part(s) of the cache of {} in {:.3}s", repaired, source_path.display(), start_time.elapsed().as_secs_f32()));
+        Ok(repaired)
+    }
+    
+    // Write one data file of a cache from scratch: it is gone, or its footer is unreadable
+    fn regenerate_file(
+        &self,
+        source_name: &str,
+        path: &Path,
+        ms1_indexed: &IndexedTimsTOFData,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let shard_plan = self.shard_plan(ms1_indexed.mz_values.len());
+        let ms2_sample = ms2_indexed_pairs.iter().map(|(_, data)| data).max_by_key(|data| data.mz_values.len());
+        if path == self.get_cache_path(source_name, "ms1_indexed") {
+            let codecs = self.column_codecs("MS1", Some(ms1_indexed), false)?;
+            self.save_ms1_file(path, ms1_indexed, &shard_plan, &codecs, None, None)?;
+        } else if path == self.get_cache_path(source_name, "ms2_indexed") {
+            let codecs = self.column_codecs("MS2", ms2_sample, self.should_compress_file("ms2_indexed"))?;
+            self.save_ms2_file(path, ms2_indexed_pairs, &codecs, None, None)?;
+        } else {
+            let archive = path == self.archive_path(source_name);
+            let (ms1_codecs, ms2_codecs) = match archive {
+                true => (compression::uniform_codecs(CompressionType::archival().codec()), compression::uniform_codecs(CompressionType::archival().codec())),
+                false => (
+                    self.column_codecs("MS1", Some(ms1_indexed), false)?,
+                    self.column_codecs("MS2", ms2_sample, self.should_compress_file("ms2_indexed"))?,
+                ),
+            };
+            let compression_min_bytes = if archive { 0 } else { self.config.window_compression_min_bytes };
+            let mut segments = ms1_segments(ms1_indexed, &shard_plan);
+            segments.extend(ms2_segments(ms2_indexed_pairs, compression_min_bytes).into_iter().map(|segment| SegmentInput { codecs: segment.codecs.or(Some(&ms2_codecs)), ..segment }));
+            self.in_pool(|| columnar::write_container(path, &segments, &self.write_options(&ms1_codecs)?))?;
+        }
+        Ok(())
+    }
+    
+    // One cached source holding the union of several (e.g. the fractions of an experiment),
+    // registered as the alias `output_key`. MS1 points are merged in m/z order; MS2 windows
+    // with the same isolation range are merged into one. Frame indices of each source are
+    // offset past those of the previous ones, so frames stay distinct; the offsets are
+    // recorded with the sources, and the merged cache is valid while every source is.
+    // Returns the path the merged source is cached under.
+    pub fn merge_sources(&self, sources: &[&Path], output_key: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        if self.config.content_addressed {
+            return Err("merged sources have no content to address; use a location-keyed cache".into());
+        }
+        if sources.is_empty() {
+            return Err("no sources to merge".into());
+        }
+        let start_time = std::time::Instant::now();
+        let mut ms1_parts = Vec::new();
+        let mut windows: Vec<(IsolationWindow, Vec<IndexedTimsTOFData>)> = Vec::new();
+        let mut merged = Vec::new();
+        let mut frame_offset = 0u32;
+        for source in sources {
+            let source_path = self.resolve_source(source);
+            let (mut ms1_indexed, mut ms2_indexed_pairs) = self.load_indexed_data(&source_path)?;
+            let last_frame = max_frame(&ms1_indexed, &ms2_indexed_pairs);
+            for data in std::iter::once(&mut ms1_indexed).chain(ms2_indexed_pairs.iter_mut().map(|(_, data)| data)) {
+                data.frame_indices.par_iter_mut().for_each(|frame| *frame += frame_offset);
+            }
+            merged.push((source_path, frame_offset));
+            frame_offset = last_frame.map_or(frame_offset, |frame| frame + 1);
+            
+            ms1_parts.push(ms1_indexed);
+            for (window, data) in ms2_indexed_pairs {
+                match windows.iter_mut().find(|(known, _)| *known == window) {
+                    Some((_, parts)) => parts.push(data),
+                    None => windows.push((window.at(windows.len()), vec![data])),
+                }
+            }
+        }
+        
+        let (ms1_indexed, ms2_indexed_pairs) = self.in_pool(|| {
+            let ms2: Vec<(IsolationWindow, IndexedTimsTOFData)> = windows.into_par_iter()
+                .map(|(window, parts)| (window, columnar::merge_by_mz(parts)))
+                .collect();
+            (columnar::merge_by_mz(ms1_parts), ms2)
+        });
+        
+        // Merged caches live under a key of their own in the cache directory, not a folder
+        // (absolute, as the alias must resolve from any working directory)
+        let virtual_path = std::path::absolute(self.cache_dir.join("merged").join(output_key))?;
+        let mut registry = AliasRegistry::load(&self.cache_dir);
+        registry.register(output_key, &virtual_path)?;
+        registry.save()?;
+        self.save_indexed(&virtual_path, &ms1_indexed, &ms2_indexed_pairs, &CancellationToken::new(), &merged)?;
+        telemetry::info(&format!("🔗 Merged {} sources into {} ({} MS1 points, {} MS2 windows) in {:.3}s", sources.len(), output_key,
+                 ms1_indexed.mz_values.len(), ms2_indexed_pairs.len(), start_time.elapsed().as_secs_f32()));
+        Ok(virtual_path)
+    }
+    
+    // Independent caches of the points in each rt segment [start, end) (minutes) of a cached
+    // source, for distributed workers that each fetch only their segment (with a remote
+    // store configured, every segment is published like any cache). A segment is cached
+    // under "<source>/rt-<start>-<end>", with every MS2 window (empty where it has no
+    // points there), and stays valid while the source is unchanged. Returns those paths.
+    pub fn split_cache_by_rt(&self, source_path: &Path, segments: &[(f32, f32)]) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        if self.config.content_addressed {
+            return Err("rt segments are cached by location; use a location-keyed cache".into());
+        }
+        if let Some((start, end)) = segments.iter().find(|(start, end)| start.partial_cmp(end) != Some(std::cmp::Ordering::Less)) {
+            return Err(format!("empty rt segment {}-{}", start, end).into());
+        }
+        let source_path = &self.resolve_source(source_path);
+        let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+        let mut paths = Vec::new();
+        for &(start, end) in segments {
+            let (ms1_segment, ms2_segment) = self.in_pool(|| {
+                let ms2: Vec<(IsolationWindow, IndexedTimsTOFData)> = ms2_indexed_pairs.par_iter()
+                    .map(|(window, data)| (*window, rt_slice(data, start, end)))
+                    .collect();
+                (rt_slice(&ms1_indexed, start, end), ms2)
+            });
+            let segment_path = source_path.join(format!("rt-{}-{}", start, end));
+            self.save_indexed(&segment_path, &ms1_segment, &ms2_segment, &CancellationToken::new(), &[(source_path.clone(), 0)])?;
+            telemetry::info(&format!("✂️  rt {}-{} min of {}: {} MS1 points", start, end, source_path.display(), ms1_segment.mz_values.len()));
+            paths.push(segment_path);
+        }
+        Ok(paths)
+    }
+    
+    // Incremental caching for acquisitions still in progress: append the points of frames
+    // acquired after the cached ones (last_frame in the metadata) as new MS1 shards and MS2
+    // segments instead of rebuilding. The new data may cover the whole acquisition so far;
+    // frames already cached are skipped. Returns the number of points appended.
+    pub fn update_cache(
+        &self,
+        source_path: &Path,
+        new_ms1: &IndexedTimsTOFData,
+        new_ms2_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        input::check_shapes(new_ms1, new_ms2_pairs)?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        // The content key changes as the acquisition grows, so there is no entry to extend
+        if self.config.content_addressed {
+            return Err("incremental updates are not supported for content-addressed caches".into());
+        }
+        if let Err(e) = self.mirror_from_remote(source_path, source_name) {
+            telemetry::warn(&format!("Remote cache unavailable: {}", e));
+        }
+        
+        // Not is_cache_valid: a growing source is always newer than its cache
+        let meta_path = self.get_metadata_path(source_name);
+        let data_files = self.data_files(source_name);
+        if !meta_path.exists() || data_files.iter().any(|(_, path)| !path.exists()) {
+            return Err(format!("no cache for {} to update; build it first", source_path.display()).into());
+        }
+        if self.is_archived(source_name) {
+            return Err("cache is archived; unpack it before updating".into());
+        }
+        if data_files.iter().any(|(_, path)| !columnar::is_container(path)) {
+            return Err("only columnar and single-file caches can be updated; rebuild it".into());
+        }
+        // Re-signing must not bless files modified since they were signed
+        self.check_schema(source_name)?;
+        self.verify_signature(source_name)?;
+        
+        let previous = fdlimit::read_to_string(&meta_path)?;
+        let value_of = |key: &str| previous.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(": "));
+        if value_of("encryption") != Some(self.encryption_description().as_str()) {
+            return Err("cache was written with other encryption settings; rebuild it".into());
+        }
+        if !freshness::merged_sources(&previous).is_empty() {
+            return Err("caches derived from other sources (merge_sources, split_cache_by_rt) can't be extended; derive them again".into());
+        }
+        let last_frame: u32 = value_of("last_frame")
+            .and_then(|value| value.parse().ok())
+            .ok_or("cache records no last_frame (written before incremental updates); rebuild it")?;
+        
+        let start_time = std::time::Instant::now();
+        let ms1_indexed = frames_after(new_ms1, last_frame);
+        let ms2_indexed_pairs: Vec<(IsolationWindow, IndexedTimsTOFData)> = new_ms2_pairs.iter()
+            .map(|(window, data)| (*window, frames_after(data, last_frame)))
+            .filter(|(_, data)| !data.mz_values.is_empty())
+            .collect();
+        let scrubbed = input::scrub_non_finite(&ms1_indexed, &ms2_indexed_pairs, self.config.non_finite)?;
+        let (ms1_sorted, sorted_on_save) = self.in_pool(|| input::sort_ms1(scrubbed.ms1, self.config.sort));
+        let (ms1_indexed, ms2_indexed_pairs) = (ms1_sorted.as_ref(), scrubbed.ms2.as_ref());
+        input::check_save_input(ms1_indexed, ms2_indexed_pairs)?;
+        if scrubbed.rows > 0 {
+            telemetry::warn(&format!("⚠️  {} appended row(s) with NaN or infinite values ({})", scrubbed.rows, self.config.non_finite.describe()));
+        }
+        if sorted_on_save {
+            telemetry::info(&format!("   Appended MS1 was not sorted by m/z; sorted {} points", ms1_indexed.mz_values.len()));
+        }
+        let appended = ms1_indexed.mz_values.len() + ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+        let new_last_frame = match max_frame(ms1_indexed, ms2_indexed_pairs) {
+            Some(frame) => frame,
+            None => {
+                telemetry::info(&format!("{} has no frames after {}, cache is up to date", source_path.display(), last_frame));
+                return Ok(0);
+            }
+        };
+        
+        // Appending rewrites a copy of each data file
+        let copied: u64 = data_files.iter().map(|(_, path)| fs::metadata(path).map(|m| m.len()).unwrap_or(0)).sum();
+        let ms2_points = appended - ms1_indexed.mz_values.len();
+        self.ensure_space(source_name, copied + self.estimated_save_bytes(ms1_indexed.mz_values.len(), ms2_points))?;
+        
+        // The appended copies are committed together at the end, so until then the cache
+        // stays loadable as it was (load_snapshot)
+        let mut partials: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let ms1_plan = self.shard_plan(ms1_indexed.mz_values.len());
+        let new_ms1_segments = match ms1_indexed.mz_values.is_empty() {
+            true => Vec::new(),
+            false => ms1_segments(ms1_indexed, &ms1_plan),
+        };
+        // Appended segments keep the codecs of the file they join
+        let headers = match self.combined_file(source_name) {
+            Some(path) => {
+                let (ms1_codecs, ms2_codecs) = self.stored_codecs_by_segment(&path)?;
+                let mut segments: Vec<SegmentInput> = new_ms1_segments.into_iter()
+                    .map(|segment| SegmentInput { codecs: Some(&ms1_codecs), ..segment })
+                    .collect();
+                segments.extend(ms2_segments(ms2_indexed_pairs, self.config.window_compression_min_bytes).into_iter().map(|segment| SegmentInput { codecs: segment.codecs.or(Some(&ms2_codecs)), ..segment }));
+                let (partial_path, header) = self.in_pool(|| columnar::append_container_partial(&path, &segments, &self.write_options(&ms1_codecs)?))?;
+                partials.push((partial_path, path));
+                vec![header]
+            }
+            None => {
+                let mut headers = Vec::new();
+                for ((_, path), segments) in data_files.iter().zip([new_ms1_segments, ms2_segments(ms2_indexed_pairs, self.config.window_compression_min_bytes)]) {
+                    let codecs = self.stored_codecs(path, false)?;
+                    headers.push(if segments.is_empty() {
+                        let file = fdlimit::open(path)?;
+                        let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+                        header
+                    } else {
+                        let (partial_path, header) = self.in_pool(|| columnar::append_container_partial(path, &segments, &self.write_options(&codecs)?))?;
+                        partials.push((partial_path, path.clone()));
+                        header
+                    });
+                }
+                headers
+            }
+        };
+        
+        // New frames come after the indexed ones, so appending keeps the frame index and the
+        // RT-major copy sorted
+        let frame_index_path = self.sidecar_path(source_name, "frames.cache.bin");
+        if frame_index_path.exists() {
+            let ms2_parts: Vec<&IndexedTimsTOFData> = ms2_indexed_pairs.iter().map(|(_, data)| data).collect();
+            let (ms1_sorted, ms2_sorted) = self.in_pool(|| (frames::frame_sorted(&[ms1_indexed]), frames::frame_sorted(&ms2_parts)));
+            let mut segments = frames::segments("ms1", &ms1_sorted);
+            segments.extend(frames::segments("ms2", &ms2_sorted));
+            let codecs = self.stored_codecs(&frame_index_path, true)?;
+            self.in_pool(|| columnar::append_container(&frame_index_path, &segments, &self.write_options(&codecs)?))?;
+        }
+        let rt_major_path = self.sidecar_path(source_name, "rt.cache.bin");
+        if rt_major_path.exists() {
+            let (ms1_sorted, ms2_sorted) = self.in_pool(|| {
+                let ms2_sorted: Vec<_> = ms2_indexed_pairs.par_iter().map(|(window, data)| (*window, layout::rt_sorted(data))).collect();
+                (layout::rt_sorted(ms1_indexed), ms2_sorted)
+            });
+            let segments = layout::segments(&ms1_sorted, &ms2_sorted);
+            let codecs = self.stored_codecs(&rt_major_path, true)?;
+            self.in_pool(|| columnar::append_container(&rt_major_path, &segments, &self.write_options(&codecs)?))?;
+        }
+        // Caches saved before chromatograms existed get them on the next full save
+        let chromatograms_path = self.sidecar_path(source_name, "chromatograms.cache.bin");
+        if chromatograms_path.exists() {
+            let ms2_parts: Vec<&IndexedTimsTOFData> = ms2_indexed_pairs.iter().map(|(_, data)| data).collect();
+            let mut summary = FrameSummary::read(&chromatograms_path, self.config.encryption_key.as_ref())?;
+            summary.extend(self.in_pool(|| FrameSummary::compute(ms1_indexed, &ms2_parts)));
+            summary.write(&chromatograms_path, self.config.encryption_key.as_ref())?;
+        }
+        // Heatmap bins span the rt range at save time, which new frames fall outside of
+        let heatmap_path = self.sidecar_path(source_name, "heatmap.cache.bin");
+        if heatmap_path.exists() {
+            fs::remove_file(&heatmap_path)?;
+            telemetry::warn(&format!("⚠️  Removed the heatmap of {}; save the cache again to rebuild it", source_path.display()));
+        }
+        
+        let segments = || headers.iter().flat_map(|header| header.segments.iter());
+        let ms1_shards = segments().filter(|segment| segment.name == "ms1").count();
+        let mut windows: Vec<WindowKey> = Vec::new();
+        for key in segments().filter(|segment| segment.name == "ms2_window").map(WindowKey::of) {
+            if !windows.contains(&key) {
+                windows.push(key);
+            }
+        }
+        let written: u64 = partials.iter()
+            .filter_map(|(partial_path, _)| fs::metadata(partial_path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        // Same protocol as a save: no metadata while the data files change
+        fs::remove_file(&meta_path)?;
+        for (partial_path, path) in &partials {
+            durability::commit(partial_path, path, self.config.durability)?;
+        }
+        let mut metadata = carried_metadata(&previous, &["updated at", "ms2_windows", "ms2_window_ids", "ms1_shards", "shard", "last_frame", "summary", "window_scheme", "source_files", "source_hash", "non_finite", "non_finite_rows", "sorted_on_save", "provenance", "signing_key_id", "signature"]);
+        metadata.push_str(&format!(
+            "updated at: {:?}\nms2_windows: {}\nms2_window_ids: {}\nms1_shards: {}\nlast_frame: {}\n",
+            self.recorded_time(), windows.len(), window_ids(windows.iter().copied()), ms1_shards, new_last_frame
+        ));
+        if self.config.non_finite != NonFinitePolicy::Keep {
+            let before: usize = value_of("non_finite_rows").and_then(|rows| rows.parse().ok()).unwrap_or(0);
+            metadata.push_str(&format!("non_finite: {}\nnon_finite_rows: {}\n", self.config.non_finite.describe(), before + scrubbed.rows));
+        }
+        let sorted_before = value_of("sorted_on_save") == Some("true");
+        metadata.push_str(&format!("sorted_on_save: {}\n", sorted_before || sorted_on_save));
+        // Without a recorded summary there is nothing to add the new points to
+        if let Some(summary) = recorded_summary(&previous) {
+            let appended = self.in_pool(|| DatasetSummary::compute(ms1_indexed, ms2_indexed_pairs));
+            metadata.push_str(&summary_line(&summary.merge(&appended))?);
+        }
+        // Likewise for the window scheme: the stored windows' first frames are unknown
+        if let Some(mut scheme) = recorded_scheme(&previous) {
+            scheme.add(ms2_indexed_pairs);
+            metadata.push_str(&scheme_line(&scheme)?);
+        }
+        metadata.push_str(&freshness::source_metadata(source_path, self.config.validation)?);
+        metadata.push_str(&self.provenance_line(start_time)?);
+        metadata.push_str(&shard_lines(&shard_table(&self.data_files(source_name))?)?);
+        let metadata = match &self.config.signing_key {
+            Some(key) => self.sign_metadata(key, metadata, &self.signed_files(source_name))?,
+            None => metadata,
+        };
+        durability::write_file(&meta_path, metadata, self.config.durability)?;
+        self.commit_generation(source_name)?;
+        self.record_in_manifest(source_path, source_name)?;
+        
+        if let Some(remote) = &self.remote {
+            for local_path in self.source_file_paths(source_name).into_iter().filter(|path| path.exists()) {
+                remote.put(&file_name_of(&local_path), &local_path)?;
+            }
+            telemetry::info(&format!("Published cache to {}", remote.describe()));
+        }
+        
+        let elapsed = start_time.elapsed();
+        telemetry::record_write("update", written, elapsed);
+        self.report(OperationReport::new("update", source_path, written, appended as u64, elapsed).with_layout(Some(ms1_shards), windows.len()), || {
+            telemetry::info(&format!("➕ Appended {} points of frames {}..={} to {} in {:.3}s",
+                     appended, last_frame + 1, new_last_frame, source_path.display(), elapsed.as_secs_f32()));
+        });
+        Ok(appended)
+    }
+    
+    // Reverse of compact_cache: rewrite an archived source as regular cache files with the
+    // current configuration. The save removes the archive once the new files are complete.
+    pub fn unpack_cache(&self, source_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_archived(source_name) {
+            telemetry::info(&format!("{} is not archived", source_path.display()));
+            return Ok(());
+        }
+        match self.config.precision {
+            Precision::F32 => {
+                let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+                self.save_indexed_data(source_path, &ms1_indexed, &ms2_indexed_pairs)
+            }
+            Precision::F64 => {
+                let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data_as::<f64>(source_path)?;
+                self.save_indexed_data(source_path, &ms1_indexed, &ms2_indexed_pairs)
+            }
+        }
+    }
+    
+    // Duplicate the cache of one source into another cache directory (e.g. a per-job
+    // sandbox) using reflinks or hard links where the filesystem allows, so even caches of
+    // tens of GB clone near-instantly. Open the clone with CacheManager::with_cache_dir.
+    pub fn clone_cache(&self, source_path: &Path, new_cache_dir: &Path) -> Result<Vec<(PathBuf, CloneMethod)>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let start_time = std::time::Instant::now();
+        fs::create_dir_all(new_cache_dir)?;
+        
+        let mut cloned = Vec::new();
+        let mut total_bytes = 0u64;
+        for path in self.source_file_paths(source_name).into_iter().filter(|path| path.exists()) {
+            let target = new_cache_dir.join(path.file_name().unwrap());
+            let method = reflink::clone_file(&path, &target)?;
+            total_bytes += fs::metadata(&target)?.len();
+            cloned.push((target, method));
+        }
+        let entry = self.manifest_entry(source_name, Some(source_path))?;
+        Manifest::transaction(new_cache_dir, |sources| {
+            sources.insert(source_name.to_string(), entry);
+        })?;
+        
+        let mut methods: Vec<String> = cloned.iter().map(|(_, method)| method.to_string()).collect();
+        methods.dedup();
+        telemetry::info(&format!("✅ Cloned cache of {} to {} ({:.2} MB via {}) in {:.3}s",
+                 source_path.display(), new_cache_dir.display(), total_bytes as f32 / 1024.0 / 1024.0,
+                 methods.join(", "), start_time.elapsed().as_secs_f32()));
+        Ok(cloned)
+    }
+    
+    // Written by import_bundle; lists where the cache came from
+    fn imported_marker_path(&self, source_name: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.imported", source_name))
     }
     
-    fn get_cache_path(&self, source_path: &Path, cache_type: &str) -> PathBuf {
-        let source_name = source_path.file_name().unwrap().to_str().unwrap();
-        let extension = if self.should_compress_file(cache_type) { "cache.lz4" } else { "cache.bin" };
-        let cache_name = format!("{}.{}.{}", source_name, cache_type, extension);
-        self.cache_dir.join(cache_name)
+    // The cache of a source packed into one file (bundle.rs) for import_bundle elsewhere;
+    // returns the bundle's size in bytes
+    pub fn export_bundle(&self, source_path: &Path, bundle_path: &Path) -> Result<u64, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        let start_time = std::time::Instant::now();
+        let files: Vec<PathBuf> = self.source_file_paths(source_name).into_iter().filter(|path| path.exists()).collect();
+        let bytes = bundle::write(bundle_path, &recorded_source(source_path), source_name, &files)?;
+        telemetry::info(&format!("📦 Bundled cache of {} into {} ({} files, {:.2} MB) in {:.3}s",
+                 source_path.display(), bundle_path.display(), files.len(),
+                 bytes as f32 / 1024.0 / 1024.0, start_time.elapsed().as_secs_f32()));
+        Ok(bytes)
     }
     
-    fn get_metadata_path(&self, source_path: &Path) -> PathBuf {
-        let source_name = source_path.file_name().unwrap().to_str().unwrap();
-        let meta_name = format!("{}.meta", source_name);
-        self.cache_dir.join(meta_name)
+    // Unpack a bundle from export_bundle, replacing any cache of the same source. Returns
+    // the source path it was exported for, which loads and queries take as usual (or
+    // register_alias a local name for it). The cache stays valid while no folder exists
+    // at that path; once one does, it is checked against it like any other cache.
+    pub fn import_bundle(&self, bundle_path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let start_time = std::time::Instant::now();
+        fs::create_dir_all(&self.cache_dir)?;
+        let (index, mut staged) = bundle::unpack(bundle_path, &self.cache_dir, |index| self.cache_name(Path::new(&index.source)))?;
+        let source_path = PathBuf::from(&index.source);
+        let source_name = &self.cache_name(&source_path);
+        self.hot.remove(&source_path);
+        
+        // Files of an earlier cache of the source (another format, say) would shadow these.
+        // Metadata last, so an interrupted import is never taken for a complete cache.
+        for path in self.source_files(source_name)?.into_iter().filter(|path| !staged.iter().any(|(partial, _)| partial == path)) {
+            fs::remove_file(path)?;
+        }
+        staged.sort_by_key(|(_, path)| path.extension().is_some_and(|ext| ext == "meta"));
+        for (partial_path, path) in &staged {
+            fs::rename(partial_path, path)?;
+        }
+        fdlimit::write(
+            self.imported_marker_path(source_name),
+            format!("imported at: {:?}\nbundle: {}\n", self.recorded_time(), bundle_path.display()),
+        )?;
+        self.record_in_manifest(&source_path, source_name)?;
+        
+        telemetry::info(&format!("📦 Imported cache of {} from {} ({} files) in {:.3}s",
+                 source_path.display(), bundle_path.display(), staged.len(), start_time.elapsed().as_secs_f32()));
+        Ok(source_path)
     }
     
-    // Smart compression decision based on file type and size
-    fn should_compress_file(&self, cache_type: &str) -> bool {
-        if !self.config.auto_compression {
-            return self.config.enable_compression;
+    fn trash_dir(&self) -> PathBuf {
+        self.cache_dir.join("trash")
+    }
+    
+    // Trash entries, newest first
+    pub fn trash_entries(&self) -> Result<Vec<TrashEntry>, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        let trash_dir = self.trash_dir();
+        if !trash_dir.exists() {
+            return Ok(entries);
         }
         
-        // Only compress larger files where the CPU overhead is worth it
-        // MS2 data is typically much larger and benefits from compression
-        match cache_type {
-            "ms2_indexed" => true,  // Large, repetitive data - good compression ratio
-            "ms1_indexed" => false, // Smaller, less compressible - not worth the CPU cost
-            _ => false,
+        for entry in fs::read_dir(&trash_dir)? {
+            let path = entry?.path();
+            let dir_name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            // "<source>@<unix seconds>"
+            let (source_name, secs) = match dir_name.rsplit_once('@').and_then(|(n, t)| Some((n, t.parse::<u64>().ok()?))) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            let mut size_bytes = 0;
+            for file in fs::read_dir(&path)? {
+                size_bytes += file?.metadata()?.len();
+            }
+            entries.push(TrashEntry {
+                source_name: source_name.to_string(),
+                trashed_at: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                size_bytes,
+                path,
+            });
         }
+        
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.trashed_at));
+        Ok(entries)
     }
     
-    pub fn is_cache_valid(&self, source_path: &Path) -> bool {
-        let ms1_cache_path = self.get_cache_path(source_path, "ms1_indexed");
-        let ms2_cache_path = self.get_cache_path(source_path, "ms2_indexed");
-        let meta_path = self.get_metadata_path(source_path);
+    // Bring back the most recently trashed cache of a source
+    pub fn restore(&self, source_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
         
-        if !ms1_cache_path.exists() || !ms2_cache_path.exists() || !meta_path.exists() {
-            return false;
+        let entry = self.trash_entries()?
+            .into_iter()
+            .find(|e| &e.source_name == source_name)
+            .ok_or_else(|| format!("no trashed cache for {}", source_name))?;
+        if !self.source_files(source_name)?.is_empty() {
+            return Err(format!("{} already has a cache; clear it before restoring", source_name).into());
         }
         
-        // Check source folder modification time
-        let source_modified = fs::metadata(source_path)
-            .and_then(|m| m.modified())
-            .unwrap_or(SystemTime::UNIX_EPOCH);
-            
-        let cache_modified = fs::metadata(&ms1_cache_path)
-            .and_then(|m| m.modified())
-            .unwrap_or(SystemTime::UNIX_EPOCH);
-            
-        cache_modified > source_modified
+        // Renames keep the original mtimes, so the restored cache validates as before
+        for file in fs::read_dir(&entry.path)? {
+            let path = file?.path();
+            fs::rename(&path, self.cache_dir.join(path.file_name().unwrap()))?;
+        }
+        fs::remove_dir(&entry.path)?;
+        self.add_content_ref(source_path)?;
+        self.record_in_manifest(source_path, source_name)?;
+        telemetry::info(&format!("Restored cache for {} ({:.2} MB)", source_name, entry.size_bytes as f32 / 1024.0 / 1024.0));
+        Ok(())
     }
     
-    // OPTIMIZED: Sequential save with smart compression
-    pub fn save_indexed_data(
-        &self, 
-        source_path: &Path, 
-        ms1_indexed: &IndexedTimsTOFData,
-        ms2_indexed_pairs: &Vec<((f32, f32), IndexedTimsTOFData)>
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Saving indexed data to optimized cache...");
-        let start_time = std::time::Instant::now();
+    // Permanently delete trash entries older than the retention window
+    pub fn purge_trash(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let mut freed = 0u64;
+        for entry in self.trash_entries()? {
+            let age = SystemTime::now().duration_since(entry.trashed_at).unwrap_or_default();
+            if age > self.config.trash_retention {
+                fs::remove_dir_all(&entry.path)?;
+                freed += entry.size_bytes;
+                telemetry::info(&format!("Purged {} from trash", entry.source_name));
+            }
+        }
+        Ok(freed)
+    }
+    
+    pub fn empty_trash(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let trash_dir = self.trash_dir();
+        if trash_dir.exists() {
+            fs::remove_dir_all(&trash_dir)?;
+            telemetry::info("Trash emptied");
+        }
+        Ok(())
+    }
+
+    // "<source>.meta", "<source>.progress" and "<source>.<cache_type>.cache.*"
+    fn source_files(&self, source_name: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+        let prefix = format!("{}.", source_name);
+        let mut files = Vec::new();
+
+        if self.cache_dir.exists() {
+            for entry in fs::read_dir(&self.cache_dir)? {
+                let path = entry?.path();
+                let file_name = match path.file_name().and_then(|n| n.to_str()) {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                let rest = match file_name.strip_prefix(&prefix) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                // "<cache_type>.cache.*" exactly, so "run" does not claim the files of "run.d"
+                let is_data = rest.split_once('.').is_some_and(|(_, tail)| tail.starts_with("cache."));
+                if rest == "meta" || rest == "progress" || rest == "imported" || rest == "generation" || is_data {
+                    files.push(path);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    // Permanent removal, used by quota enforcement and mirror eviction
+    fn remove_source_files(&self, source_name: &str) -> Result<u64, std::io::Error> {
+        let mut freed = 0u64;
+        for path in self.source_files(source_name)? {
+            freed += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            fs::remove_file(&path)?;
+        }
+        self.drop_from_manifest(source_name)?;
+        Ok(freed)
+    }
+    
+    // Collect what interrupted saves, older format versions and half-removed caches leave
+    // behind: cache files no manifest entry lists, metadata without its data, stale
+    // temporary (.partial) files and abandoned resumable saves (.progress). The manifest
+    // is reconciled first: entries whose files are gone are dropped, complete caches it
+    // doesn't know (written by older versions) are adopted rather than deleted.
+    pub fn gc(&self, options: GcOptions) -> Result<GcReport, Box<dyn std::error::Error>> {
+        if !options.dry_run {
+            self.ensure_writable()?;
+        }
+        let mut report = GcReport { dry_run: options.dry_run, ..GcReport::default() };
+        if !self.cache_dir.exists() {
+            return Ok(report);
+        }
+        let manifest = self.manifest()?;
         
-        // Save MS1 data (fast, no compression)
-        let ms1_start = std::time::Instant::now();
-        let ms1_cache_path = self.get_cache_path(source_path, "ms1_indexed");
-        Self::save_data_to_file(&ms1_cache_path, ms1_indexed, &self.config, false)?;
-        let ms1_time = ms1_start.elapsed();
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        let exists = |name: &str| names.iter().any(|n| n == name);
         
-        // Save MS2 data (with smart compression)
-        let ms2_start = std::time::Instant::now();
-        let ms2_cache_path = self.get_cache_path(source_path, "ms2_indexed");
-        let use_compression = self.should_compress_file("ms2_indexed");
-        Self::save_data_to_file(&ms2_cache_path, ms2_indexed_pairs, &self.config, use_compression)?;
-        let ms2_time = ms2_start.elapsed();
+        report.dropped_entries = manifest.sources.iter()
+            .filter(|(_, entry)| entry.files.iter().any(|file| !exists(&file.name)))
+            .map(|(source_name, _)| source_name.clone())
+            .collect();
+        let mut adopted = Vec::new();
+        for source_name in names.iter().filter_map(|name| name.strip_suffix(".meta")) {
+            if manifest.sources.contains_key(source_name) && !report.dropped_entries.iter().any(|n| n == source_name) {
+                continue;
+            }
+            // A metadata file alone is an incomplete cache, not one to adopt
+            let entry = self.manifest_entry(source_name, None)?;
+            if entry.files.len() > 1 && self.cache_is_complete(source_name) {
+                adopted.push((source_name.to_string(), entry));
+            }
+        }
+        report.adopted_entries = adopted.iter().map(|(source_name, _)| source_name.clone()).collect();
         
-        // Save metadata
-        let meta_path = self.get_metadata_path(source_path);
-        let metadata = format!(
-            "cached at: {:?}\nms2_windows: {}\ntype: indexed\nms1_compression: false\nms2_compression: {}\nversion: 2.0\n",
-            SystemTime::now(),
-            ms2_indexed_pairs.len(),
-            use_compression
-        );
-        fs::write(meta_path, metadata)?;
+        let listed: HashSet<String> = manifest.sources.iter()
+            .filter(|(source_name, _)| !report.dropped_entries.contains(source_name))
+            .map(|(_, entry)| entry)
+            .chain(adopted.iter().map(|(_, entry)| entry))
+            .flat_map(|entry| entry.files.iter().map(|file| file.name.clone()))
+            .collect();
+        let now = SystemTime::now();
+        for name in &names {
+            let collectable = name.contains(".cache") || name.ends_with(".meta") || name.ends_with(".partial") || name.ends_with(".progress");
+            if !collectable || listed.contains(name) {
+                continue;
+            }
+            let path = self.cache_dir.join(name);
+            let metadata = fs::metadata(&path)?;
+            let age = metadata.modified().ok().and_then(|modified| now.duration_since(modified).ok()).unwrap_or_default();
+            if age < options.min_age {
+                continue;
+            }
+            report.bytes += metadata.len();
+            report.orphans.push((path, metadata.len()));
+        }
+        report.orphans.sort();
         
-        let elapsed = start_time.elapsed();
-        let ms1_size = fs::metadata(&ms1_cache_path)?.len();
-        let ms2_size = fs::metadata(&ms2_cache_path)?.len();
-        let total_size_mb = (ms1_size + ms2_size) as f32 / 1024.0 / 1024.0;
+        if !options.dry_run {
+            for (path, _) in &report.orphans {
+                fs::remove_file(path)?;
+            }
+            Manifest::transaction(&self.cache_dir, |sources| {
+                for source_name in &report.dropped_entries {
+                    sources.remove(source_name);
+                }
+                for (source_name, entry) in adopted {
+                    sources.insert(source_name, entry);
+                }
+            })?;
+        }
+        
+        let verb = if options.dry_run { "Would remove" } else { "Removed" };
+        for (path, bytes) in &report.orphans {
+            telemetry::info(&format!("   {} {} ({:.2} MB)", verb, path.display(), *bytes as f32 / 1024.0 / 1024.0));
+        }
+        telemetry::info(&format!("🧹 {} {} orphaned file(s) ({:.2} MB); manifest: {} stale entries dropped, {} caches adopted",
+                 verb, report.orphans.len(), report.bytes as f32 / 1024.0 / 1024.0,
+                 report.dropped_entries.len(), report.adopted_entries.len()));
+        Ok(report)
+    }
+    
+    // Whether the metadata of a source has all the data files it describes
+    fn cache_is_complete(&self, source_name: &str) -> bool {
+        let exists = |name: String| self.cache_dir.join(name).exists();
+        let any_variant = |cache_type: &str| Self::CACHE_EXTENSIONS.iter()
+            .any(|extension| exists(format!("{}.{}.{}", source_name, cache_type, extension)));
+        exists(format!("{}.indexed.cache.bin", source_name))
+            || exists(format!("{}.archive.cache.zst", source_name))
+            || (source_name.ends_with(".raw") && exists(format!("{}.frames.cache.bin", source_name)))
+            || (any_variant("ms1_indexed") && any_variant("ms2_indexed"))
+    }
+    
+    // Entry describing the files of a cached source as they are on disk now
+    fn manifest_entry(&self, source_name: &str, source_path: Option<&Path>) -> Result<ManifestEntry, std::io::Error> {
+        let metadata = fdlimit::read_to_string(self.cache_dir.join(format!("{}.meta", source_name)))?;
+        let value_of = |key: &str| metadata.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(": "));
         
-        println!("✅ Optimized cache saved: {:.2} MB total", total_size_mb);
-        println!("   ├── MS1: {:.3}s ({:.1} MB)", ms1_time.as_secs_f32(), ms1_size as f32 / 1024.0 / 1024.0);
-        println!("   ├── MS2: {:.3}s ({:.1} MB, compressed: {})", ms2_time.as_secs_f32(), ms2_size as f32 / 1024.0 / 1024.0, use_compression);
-        println!("   └── Total time: {:.3}s", elapsed.as_secs_f32());
+        // Only the names this cache writes, so stray files sharing the prefix stay orphans
+        let mut names = vec![
+            format!("{}.meta", source_name),
+            format!("{}.indexed.cache.bin", source_name),
+            format!("{}.archive.cache.zst", source_name),
+        ];
+        for cache_type in ["ms1_indexed", "ms2_indexed"] {
+            names.extend(Self::CACHE_EXTENSIONS.iter().map(|extension| format!("{}.{}.{}", source_name, cache_type, extension)));
+        }
+        names.extend(Self::SIDECAR_FILES.iter().map(|(file, _)| format!("{}.{}", source_name, file)));
+        names.push(format!("{}.imported", source_name));
+        let mut files = Vec::new();
+        for path in names.iter().map(|name| self.cache_dir.join(name)).filter(|path| path.exists()) {
+            files.push(manifest::describe_file(&path)?);
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
         
+        Ok(ManifestEntry {
+            // JSON holds only UTF-8 paths; others stay unknown, as for recovered entries
+            source_path: source_path
+                .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+                .filter(|path| path.to_str().is_some()),
+            version: value_of("version").unwrap_or("unknown").to_string(),
+            form: value_of("form").unwrap_or("fast").to_string(),
+            lossy: lossy_description(&metadata).map(str::to_string),
+            instrument: recorded_instrument(&metadata).map(|instrument| instrument.describe()),
+            updated_secs: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            files,
+        })
+    }
+    
+    // Publish the files of a cache, metadata included, as its next generation (snapshot.rs).
+    // Only containers: other data files would be hashed in full on every snapshot load,
+    // and they are never updated in place of a reader anyway.
+    // Returns the generation number, None for other formats.
+    fn commit_generation(&self, source_name: &str) -> Result<Option<u64>, std::io::Error> {
+        let generation_path = self.get_generation_path(source_name);
+        let mut files: Vec<PathBuf> = self.data_files(source_name).into_iter().map(|(_, path)| path).collect();
+        if !files.iter().all(|path| columnar::is_container(path)) {
+            if generation_path.exists() {
+                fs::remove_file(&generation_path)?;
+            }
+            return Ok(None);
+        }
+        files.push(self.get_metadata_path(source_name));
+        let generation = Generation::next(Generation::read(&generation_path).as_ref(), &files)?;
+        generation.write(&generation_path, self.config.durability)?;
+        if self.config.history_generations > 0 {
+            self.record_history(source_name, &generation)?;
+        }
+        Ok(Some(generation.number))
+    }
+    
+    fn history_dir(&self) -> PathBuf {
+        self.cache_dir.join("history")
+    }
+    
+    // Keep the files of a just-committed generation, sidecars included, for rollback():
+    // cloned (reflink.rs), so they share their data with the live files until a later
+    // commit replaces those. Only the newest history_generations are kept.
+    fn record_history(&self, source_name: &str, generation: &Generation) -> Result<(), std::io::Error> {
+        let entry_dir = self.history_dir().join(format!("{}@{}", source_name, generation.number));
+        fs::create_dir_all(&entry_dir)?;
+        let sidecars = Self::SIDECAR_FILES.iter()
+            .map(|(file, _)| self.sidecar_path(source_name, file))
+            .filter(|path| path.exists());
+        for path in generation.files.iter().map(|file| self.cache_dir.join(&file.name)).chain(sidecars) {
+            reflink::clone_file(&path, &entry_dir.join(file_name_of(&path)))?;
+        }
+        for stale in self.history_entries(source_name)?.into_iter().skip(self.config.history_generations) {
+            fs::remove_dir_all(&stale.path)?;
+        }
         Ok(())
     }
     
-    // OPTIMIZED: Sequential load with smart compression
-    pub fn load_indexed_data(
-        &self, 
-        source_path: &Path
-    ) -> Result<(IndexedTimsTOFData, Vec<((f32, f32), IndexedTimsTOFData)>), Box<dyn std::error::Error>> {
-        println!("Loading indexed data from optimized cache...");
-        let start_time = std::time::Instant::now();
+    // Kept generations of a cache, newest first
+    fn history_entries(&self, source_name: &str) -> Result<Vec<HistoryEntry>, std::io::Error> {
+        let mut entries = Vec::new();
+        let history_dir = self.history_dir();
+        if !history_dir.exists() {
+            return Ok(entries);
+        }
+        for entry in fs::read_dir(&history_dir)? {
+            let path = entry?.path();
+            // "<source>@<generation>"
+            let generation = match file_name_of(&path).rsplit_once('@') {
+                Some((name, generation)) if name == source_name => generation.parse::<u64>().ok(),
+                _ => None,
+            };
+            let Some(generation) = generation else {
+                continue;
+            };
+            let mut size_bytes = 0;
+            for file in fs::read_dir(&path)? {
+                size_bytes += file?.metadata()?.len();
+            }
+            entries.push(HistoryEntry { generation, size_bytes, path });
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.generation));
+        Ok(entries)
+    }
+    
+    // Generations of a source that rollback() can return to, newest first
+    pub fn history(&self, source_path: &Path) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        Ok(self.history_entries(&self.cache_name(source_path))?)
+    }
+    
+    // Put back a generation kept in the history, e.g. after a rebuild by a faulty converter,
+    // without reading the raw data. The restored files are committed as a new generation,
+    // so snapshot readers move on to it and the rollback can be rolled back in turn.
+    // Returns the new generation number.
+    pub fn rollback(&self, source_path: &Path, generation: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let entries = self.history_entries(source_name)?;
+        let entry = entries.iter().find(|entry| entry.generation == generation).ok_or_else(|| {
+            let kept: Vec<String> = entries.iter().map(|entry| entry.generation.to_string()).collect();
+            format!("generation {} of {} is not in the history (kept: {})", generation, source_path.display(),
+                    if kept.is_empty() { "none".to_string() } else { kept.join(", ") })
+        })?;
+        let meta_path = self.get_metadata_path(source_name);
+        let mut names = Vec::new();
+        for file in fs::read_dir(&entry.path)? {
+            names.push(file?.file_name().to_string_lossy().into_owned());
+        }
+        if !names.contains(&file_name_of(&meta_path)) {
+            return Err(format!("generation {} of {} has no metadata in the history", generation, source_path.display()).into());
+        }
         
-        // Load MS1 data (fast, no compression)
-        let ms1_start = std::time::Instant::now();
-        let ms1_cache_path = self.get_cache_path(source_path, "ms1_indexed");
-        let ms1_indexed = Self::load_data_from_file(&ms1_cache_path, &self.config, false)?;
-        let ms1_time = ms1_start.elapsed();
+        // Same protocol as a save: no metadata while the data files change
+        if meta_path.exists() {
+            fs::remove_file(&meta_path)?;
+        }
+        // Data files and sidecars the generation doesn't have (another format, a frame
+        // index built since) would describe other data
+        for path in self.source_files(source_name)? {
+            if file_name_of(&path).contains(".cache.") && !names.contains(&file_name_of(&path)) {
+                fs::remove_file(&path)?;
+            }
+        }
+        names.sort_by_key(|name| *name == file_name_of(&meta_path));
+        for name in &names {
+            let partial_path = self.cache_dir.join(format!("{}.partial", name));
+            reflink::clone_file(&entry.path.join(name), &partial_path)?;
+            durability::commit(&partial_path, &self.cache_dir.join(name), self.config.durability)?;
+        }
+        self.hot.remove(source_path);
+        let committed = self.commit_generation(source_name)?.unwrap_or(0);
+        self.record_in_manifest(source_path, source_name)?;
+        telemetry::info(&format!("⏪ Rolled back {} to generation {} (committed as generation {})", source_path.display(), generation, committed));
+        Ok(committed)
+    }
+    
+    fn record_in_manifest(&self, source_path: &Path, source_name: &str) -> Result<(), std::io::Error> {
+        let entry = self.manifest_entry(source_name, Some(source_path))?;
+        Manifest::transaction(&self.cache_dir, |sources| {
+            sources.insert(source_name.to_string(), entry);
+        })
+    }
+    
+    fn drop_from_manifest(&self, source_name: &str) -> Result<(), std::io::Error> {
+        Manifest::transaction(&self.cache_dir, |sources| {
+            sources.remove(source_name);
+        })
+    }
+    
+    // Writability, free space, manifest lock and manifest consistency of the cache directory
+    // (health.rs), cheap enough for a service's readiness probe
+    pub fn health(&self) -> HealthReport {
+        HealthReport::check(&self.cache_dir, self.config.read_only)
+    }
+    
+    // The manifest of the cache directory, rebuilt from the metadata files if there is none
+    // (in memory only when read-only)
+    pub fn manifest(&self) -> Result<Manifest, Box<dyn std::error::Error>> {
+        match Manifest::read(&self.cache_dir) {
+            Some(manifest) => Ok(manifest),
+            None if self.config.read_only => Ok(Manifest { generation: 0, sources: self.scanned_manifest_entries()? }),
+            None => self.rebuild_manifest(),
+        }
+    }
+    
+    // Re-create the manifest from the metadata files on disk, e.g. for a cache directory
+    // written before manifests existed or one whose files were changed by hand
+    pub fn rebuild_manifest(&self) -> Result<Manifest, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let entries = self.scanned_manifest_entries()?;
+        Manifest::transaction(&self.cache_dir, |sources| *sources = entries)?;
+        Ok(Manifest::read(&self.cache_dir).unwrap_or_default())
+    }
+    
+    fn scanned_manifest_entries(&self) -> Result<BTreeMap<String, ManifestEntry>, Box<dyn std::error::Error>> {
+        let known = Manifest::read(&self.cache_dir).unwrap_or_default();
+        let mut entries = BTreeMap::new();
+        for source in self.cached_sources()? {
+            let source_path = known.sources.get(&source.source_name).and_then(|entry| entry.source_path.as_deref());
+            let entry = self.manifest_entry(&source.source_name, source_path)?;
+            entries.insert(source.source_name, entry);
+        }
+        Ok(entries)
+    }
+    
+    // Cache files no manifest entry accounts for, left by interrupted saves, older format
+    // versions or manual copies. Sources with a resumable save pending are left out.
+    pub fn orphaned_files(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        if !self.cache_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let manifest = self.manifest()?;
+        let listed: HashSet<&str> = manifest.sources.values()
+            .flat_map(|entry| entry.files.iter().map(|file| file.name.as_str()))
+            .collect();
         
-        // Load MS2 data (with smart compression detection)
-        let ms2_start = std::time::Instant::now();
-        let ms2_cache_path = self.get_cache_path(source_path, "ms2_indexed");
-        let use_compression = ms2_cache_path.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext == "lz4")
-            .unwrap_or(false);
-        let ms2_indexed_pairs = Self::load_data_from_file(&ms2_cache_path, &self.config, use_compression)?;
-        let ms2_time = ms2_start.elapsed();
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        let pending: Vec<String> = names.iter()
+            .filter_map(|name| name.strip_suffix(".progress"))
+            .map(|source_name| format!("{}.", source_name))
+            .collect();
         
-        let elapsed = start_time.elapsed();
-        println!("✅ Optimized cache loaded");
-        println!("   ├── MS1: {:.3}s", ms1_time.as_secs_f32());
-        println!("   ├── MS2: {:.3}s (compressed: {})", ms2_time.as_secs_f32(), use_compression);
-        println!("   └── Total time: {:.3}s", elapsed.as_secs_f32());
+        let mut orphans: Vec<PathBuf> = names.iter()
+            .filter(|name| name.contains(".cache") && !listed.contains(name.as_str()))
+            .filter(|name| !pending.iter().any(|prefix| name.starts_with(prefix.as_str())))
+            .map(|name| self.cache_dir.join(name))
+            .collect();
+        orphans.sort();
+        Ok(orphans)
+    }
+
+    // One entry per cached source, found through its metadata file
+    pub fn cached_sources(&self) -> Result<Vec<CachedSource>, Box<dyn std::error::Error>> {
+        let mut sources = Vec::new();
+
+        if !self.cache_dir.exists() {
+            return Ok(sources);
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let path = entry?.path();
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                files.push((name.to_string(), fs::metadata(&path)?));
+            }
+        }
+
+        for (file_name, meta) in &files {
+            let source_name = match file_name.strip_suffix(".meta") {
+                Some(name) => name,
+                None => continue,
+            };
+            let prefix = format!("{}.", source_name);
+
+            let mut size_bytes = meta.len();
+            for (other_name, other_meta) in &files {
+                if other_name.starts_with(&prefix) && other_name.contains(".cache") {
+                    size_bytes += other_meta.len();
+                }
+            }
+
+            sources.push(CachedSource {
+                source_name: source_name.to_string(),
+                size_bytes,
+                cached_at: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+
+        Ok(sources)
+    }
+
+    // Evict the oldest sources until the cache directory fits into max_bytes
+    pub fn enforce_quota(&self, max_bytes: u64) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let mut sources = self.cached_sources()?;
+        let mut total: u64 = sources.iter().map(|s| s.size_bytes).sum();
+        let mut evicted = Vec::new();
+
+        sources.sort_by_key(|s| s.cached_at);
+        for source in sources {
+            if total <= max_bytes {
+                break;
+            }
+            let freed = self.remove_source_files(&source.source_name)?;
+            total = total.saturating_sub(freed);
+            telemetry::info(&format!("Evicted {} ({:.2} MB) to stay within quota", source.source_name, freed as f32 / 1024.0 / 1024.0));
+            evicted.push(source.source_name);
+        }
+
+        Ok(evicted)
+    }
+
+    // Verify the next slice of the cache dir (bounded by `budget`), continuing where the
+    // previous run stopped; corrupt files are reported, not removed
+    pub fn scrub(&self, budget: ScrubBudget) -> Result<ScrubReport, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let report = scrub::scrub(&self.cache_dir, budget)?;
+        telemetry::info(&format!("Scrubbed {:.2} MB in {} chunk(s){}", report.bytes_verified as f32 / 1024.0 / 1024.0,
+                 report.units_verified, if report.pass_completed { ", full pass completed" } else { "" }));
+        for problem in &report.corrupt {
+            telemetry::error(&format!("✗ Corrupt cache data: {}", problem));
+        }
+        Ok(report)
+    }
+
+    // Check one source completely: the metadata signature (with a signing key configured)
+    // and every stored column of its data files. Unlike scrub() there is no budget or cursor.
+    pub fn verify(&self, source_path: &Path) -> Result<ScrubReport, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
+        }
+        self.mirror_from_remote(source_path, source_name)?;
+        self.verify_signature(source_name)?;
         
-        Ok((ms1_indexed, ms2_indexed_pairs))
+        let mut report = ScrubReport::default();
+        for (_, path) in self.data_files(source_name) {
+            scrub::verify_file(&path, &mut report)?;
+        }
+        report.pass_completed = true;
+        Ok(report)
     }
     
-    // OPTIMIZED: Single-threaded save with optional compression
-    fn save_data_to_file<T>(
-        path: &Path,
-        data: &T,
-        config: &CacheConfig,
-        use_compression: bool,
-    ) -> Result<(), std::io::Error>
-    where
-        T: serde::Serialize + ?Sized,
-    {
-        let file = File::create(path)?;
-        let writer = BufWriter::with_capacity(config.buffer_size, file);
-        
-        if use_compression {
-            // Use LZ4 compression only when beneficial
-            let encoder = lz4_flex::frame::FrameEncoder::new(writer);
-            bincode::serialize_into(encoder, data)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        } else {
-            // Direct binary serialization (fastest)
-            bincode::serialize_into(writer, data)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    // Check that a source's cache has the layout the loaders assume: every MS1 shard sorted
+    // by m/z within its recorded bounds, shards following each other without overlap (one
+    // run per save or update), the MS2 windows the metadata lists and consistent point
+    // counts. Runs after every save; problems are reported, not fixed (see repair_cache).
+    pub fn validate_layout(&self, source_path: &Path) -> Result<LayoutReport, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if let Err(e) = self.mirror_from_remote(source_path, source_name) {
+            telemetry::warn(&format!("Remote cache unavailable: {}", e));
         }
-        
-        Ok(())
+        let meta_path = self.get_metadata_path(source_name);
+        let metadata = fdlimit::read_to_string(&meta_path)
+            .map_err(|e| format!("no cache metadata for {}: {}", source_path.display(), e))?;
+        let data_files = self.data_files(source_name);
+        if data_files.iter().any(|(_, path)| path.exists() && !columnar::is_container(path)) {
+            return Err("only columnar and single-file caches have a shard layout to validate".into());
+        }
+        Ok(self.check_layout(&data_files, &meta_path, &metadata))
     }
     
-    // OPTIMIZED: Single-threaded load with optional compression
-    fn load_data_from_file<T>(
-        path: &Path,
-        config: &CacheConfig,
-        use_compression: bool,
-    ) -> Result<T, std::io::Error>
-    where
-        T: serde::de::DeserializeOwned,
-    {
-        let file = File::open(path)?;
-        let reader = BufReader::with_capacity(config.buffer_size, file);
+    fn check_layout(&self, data_files: &[(&str, PathBuf)], meta_path: &Path, metadata: &str) -> LayoutReport {
+        let mut report = LayoutReport::default();
+        let mut issues = Vec::new();
+        let mut windows = BTreeSet::new();
+        let mut previous_mz_max: Option<f32> = None;
+        let mut recorded: Option<BTreeMap<(String, usize), ShardInfo>> = recorded_shards(metadata).map(|table| {
+            table.into_iter().map(|shard| ((shard.file.clone(), shard.segment), shard)).collect()
+        });
+        for (_, path) in data_files {
+            let mut issue = |segment: Option<usize>, problem: String| {
+                issues.push(LayoutIssue { file: path.clone(), segment, problem });
+            };
+            let file = match fdlimit::open(path) {
+                Ok(file) => file,
+                Err(e) => {
+                    issue(None, e.to_string());
+                    continue;
+                }
+            };
+            let reader = match file.metadata().and_then(|m| ContainerReader::open(columnar::file_reader(&file), m.len(), self.config.encryption_key.as_ref())) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    issue(None, e.to_string());
+                    continue;
+                }
+            };
+            let ms1_segments: Vec<usize> = reader.header.segments.iter()
+                .enumerate()
+                .filter(|(_, segment)| segment.name == "ms1")
+                .map(|(segment_index, _)| segment_index)
+                .collect();
+            let ms1_mz: BTreeMap<usize, std::io::Result<Vec<f32>>> = self.in_pool(|| ms1_segments.into_par_iter()
+                .map(|segment_index| (segment_index, reader.segment_mz(segment_index)))
+                .collect());
+            
+            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            for (segment_index, segment) in reader.header.segments.iter().enumerate() {
+                for column in segment.columns.iter().filter(|column| column.rows != segment.rows) {
+                    issue(Some(segment_index), format!("column {} has {} rows, the segment {}", column.name, column.rows, segment.rows));
+                }
+                if let Some(recorded) = &mut recorded {
+                    let stored = ShardInfo::of(file_name, segment_index, segment);
+                    match recorded.remove(&(file_name.to_string(), segment_index)) {
+                        None => issue(Some(segment_index), "missing from the shard table".to_string()),
+                        Some(shard) if shard.data_points != stored.data_points => issue(Some(segment_index), format!(
+                            "holds {} points, the shard table records {}", stored.data_points, shard.data_points
+                        )),
+                        Some(shard) if shard != stored => issue(Some(segment_index), format!(
+                            "differs from the shard table (recorded {:?}, stored {:?})", shard, stored
+                        )),
+                        Some(_) => {}
+                    }
+                }
+                report.points += segment.rows;
+                match segment.name.as_str() {
+                    "ms1" => {
+                        report.ms1_shards += 1;
+                        let mz = match &ms1_mz[&segment_index] {
+                            Ok(mz) => mz,
+                            Err(e) => {
+                                issue(Some(segment_index), format!("m/z column unreadable: {}", e));
+                                continue;
+                            }
+                        };
+                        if let Some(row) = mz.windows(2).position(|pair| matches!(pair[0].partial_cmp(&pair[1]), Some(std::cmp::Ordering::Greater) | None)) {
+                            issue(Some(segment_index), format!("not sorted by m/z at row {}", row + 1));
+                        }
+                        let (Some(&first), Some(&last)) = (mz.first(), mz.last()) else {
+                            continue;
+                        };
+                        let bound = |name: &str| segment.attrs.get(name).map(|&mz| mz as f32);
+                        if bound("mz_min") != Some(first) || bound("mz_max") != Some(last) {
+                            issue(Some(segment_index), format!(
+                                "recorded m/z bounds {:?}-{:?} don't match its data {}-{}",
+                                bound("mz_min"), bound("mz_max"), first, last
+                            ));
+                        }
+                        // Shards of one run follow each other in m/z; overlapping the
+                        // previous shard starts a new run
+                        if !previous_mz_max.is_some_and(|max| first >= max) {
+                            report.ms1_runs += 1;
+                        }
+                        previous_mz_max = Some(last);
+                    }
+                    "ms2_window" => {
+                        let (low, high) = isolation_range(segment);
+                        if low.partial_cmp(&high) != Some(std::cmp::Ordering::Less) {
+                            issue(Some(segment_index), format!("empty isolation window {}-{}", low, high));
+                        }
+                        windows.insert(WindowKey::of(segment));
+                    }
+                    other => issue(Some(segment_index), format!("unknown segment type '{}'", other)),
+                }
+            }
+        }
+        report.ms2_windows = windows.len();
+        for (file, segment) in recorded.into_iter().flat_map(|table| table.into_keys()) {
+            issues.push(LayoutIssue { file: meta_path.to_path_buf(), segment: None, problem: format!("lists shard {} of {} which isn't stored", segment, file) });
+        }
         
-        if use_compression {
-            // Use LZ4 decompression
-            let decoder = lz4_flex::frame::FrameDecoder::new(reader);
-            bincode::deserialize_from(decoder)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-        } else {
-            // Direct binary deserialization (fastest)
-            bincode::deserialize_from(reader)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        let mut issue = |problem: String| {
+            issues.push(LayoutIssue { file: meta_path.to_path_buf(), segment: None, problem });
+        };
+        let value_of = |key: &str| metadata.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(": "));
+        if let Some(shards) = value_of("ms1_shards").and_then(|value| value.parse::<usize>().ok()) {
+            if shards != report.ms1_shards {
+                issue(format!("lists {} MS1 shards, the files hold {}", shards, report.ms1_shards));
+            }
+        }
+        // Updates append shards covering the whole m/z range again
+        if report.ms1_runs > 1 && value_of("updated at").is_none() {
+            issue(format!("MS1 shards overlap in m/z ({} runs) but the cache was never updated", report.ms1_runs));
+        }
+        match value_of("ms2_window_ids") {
+            Some(ids) => {
+                let listed: BTreeSet<&str> = ids.split(',').filter(|id| !id.is_empty()).collect();
+                let stored: Vec<String> = windows.iter().map(|key| key.to_string()).collect();
+                let stored: BTreeSet<&str> = stored.iter().map(String::as_str).collect();
+                for id in listed.difference(&stored) {
+                    issue(format!("lists MS2 window {} which isn't stored", id));
+                }
+                for id in stored.difference(&listed) {
+                    issue(format!("stores MS2 window {} which isn't listed", id));
+                }
+            }
+            // Written before windows were listed by key: only the count can be checked
+            None => if let Some(count) = value_of("ms2_windows").and_then(|value| value.parse::<usize>().ok()) {
+                if count != windows.len() {
+                    issue(format!("lists {} MS2 windows, the files hold {}", count, windows.len()));
+                }
+            },
         }
+        report.issues = issues;
+        report
     }
     
-    pub fn clear_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.cache_dir.exists() {
-            fs::remove_dir_all(&self.cache_dir)?;
-            println!("Cache cleared");
+    // Shard table from a source's local metadata file
+    fn recorded_shards(&self, source_name: &str) -> Option<Vec<ShardInfo>> {
+        recorded_shards(&fdlimit::read_to_string(self.get_metadata_path(source_name)).ok()?)
+    }
+    
+    // Ranges, point counts and intensity percentiles recorded at save time (summary.rs);
+    // reads only the metadata file
+    pub fn describe(&self, source_path: &Path) -> Result<DatasetSummary, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        if !self.is_valid(source_path, source_name) {
+            return Err(format!("no valid cache for {}", source_path.display()).into());
         }
-        Ok(())
+        let metadata = self.read_metadata(source_name)?;
+        recorded_summary(&metadata)
+            .ok_or_else(|| format!("the cache of {} predates dataset summaries; save it again to add one", source_path.display()).into())
+    }
+    
+    // Instrument, acquisition date and method recorded at save time (instrument.rs); None
+    // if the source had no readable analysis.tdf
+    pub fn instrument(&self, source_path: &Path) -> Result<Option<InstrumentInfo>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let metadata = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        Ok(recorded_instrument(&metadata))
+    }
+    
+    // Crate version and commit, configuration, host and time of the save or update that last
+    // wrote the cache (provenance.rs); None for caches written before provenance
+    pub fn provenance(&self, source_path: &Path) -> Result<Option<Provenance>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let metadata = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        Ok(recorded_provenance(&metadata))
+    }
+    
+    // DIA isolation window scheme recorded at save time, in cycle order (scheme.rs); None
+    // for caches saved before schemes or without MS2 windows. Reads only the metadata file.
+    pub fn window_scheme(&self, source_path: &Path) -> Result<Option<WindowScheme>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let metadata = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        Ok(recorded_scheme(&metadata))
     }
     
-    pub fn get_cache_info(&self) -> Result<Vec<(String, u32, String)>, Box<dyn std::error::Error>> {
+    // Compare the caches of two sources (diff.rs) from their metadata, e.g. a raw file
+    // before and after re-conversion; neither cache's data is read
+    pub fn diff_caches(&self, a: &Path, b: &Path) -> Result<CacheDiff, Box<dyn std::error::Error>> {
+        let side = |source_path: &Path| -> Result<CacheSide, Box<dyn std::error::Error>> {
+            let source_path = self.resolve_source(source_path);
+            let source_name = &self.cache_name(&source_path);
+            if !self.is_valid(&source_path, source_name) {
+                return Err(format!("no valid cache for {}", source_path.display()).into());
+            }
+            let text = self.read_metadata(source_name)?;
+            Ok(CacheSide {
+                metadata: metadata_lines(&text),
+                summary: recorded_summary(&text),
+                shards: recorded_shards(&text),
+                source: source_path,
+            })
+        };
+        Ok(CacheDiff::compare(&side(a)?, &side(b)?))
+    }
+    
+    // "key: value" lines of a source's metadata file, in file order
+    pub fn metadata(&self, source_path: &Path) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        let source_name = &self.cache_name(source_path);
+        let text = fdlimit::read_to_string(self.get_metadata_path(source_name))?;
+        Ok(metadata_lines(&text))
+    }
+    
+    pub fn get_cache_info(&self) -> Result<Vec<CacheFileInfo>, Box<dyn std::error::Error>> {
         let mut info = Vec::new();
         
+        // Listed from the manifest rather than by scanning the directory
         if self.cache_dir.exists() {
-            for entry in fs::read_dir(&self.cache_dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                let file_name = path.file_name().unwrap().to_str().unwrap();
-                
-                // Check for cache files
-                if file_name.ends_with(".cache.bin") || file_name.ends_with(".cache.lz4") || file_name.ends_with(".cache") {
-                    let metadata = fs::metadata(&path)?;
-                    let size = metadata.len() as u32;
-                    let name = file_name.to_string();
+            for entry in self.manifest()?.sources.values() {
+                // Quantized caches are flagged on every file, so they can't pass for exact ones
+                let lossy = entry.lossy.as_ref().map(|lossy| format!(" [LOSSY: {}]", lossy)).unwrap_or_default();
+                // So caches identify their acquisition once the raw folder is archived
+                let instrument = entry.instrument.as_ref().map(|instrument| format!(" [{}]", instrument)).unwrap_or_default();
+                for file in &entry.files {
+                    if !file.name.contains(".cache") {
+                        continue;
+                    }
+                    let size = file.bytes as u32;
+                    let name = file.name.clone();
                     let size_mb = size as f32 / 1024.0 / 1024.0;
                     let size_gb = size as f32 / 1024.0 / 1024.0 / 1024.0;
                     
                     let size_str = if size_gb >= 1.0 {
-                        format!("{:.2} GB", size_gb)
+                        format!("{:.2} GB{}{}", size_gb, lossy, instrument)
                     } else {
-                        format!("{:.2} MB", size_mb)
+                        format!("{:.2} MB{}{}", size_mb, lossy, instrument)
                     };
                     
                     info.push((name, size, size_str));
@@ -276,7 +5394,8 @@ impl CacheManager {
     
     // Benchmark cache performance
     pub fn benchmark_cache(&self, test_data_size: usize) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔬 Benchmarking cache performance...");
+        self.ensure_writable()?;
+        telemetry::info("🔬 Benchmarking cache performance...");
         
         // Create test data
         let test_data: Vec<u8> = (0..test_data_size).map(|i| (i % 256) as u8).collect();
@@ -305,16 +5424,60 @@ impl CacheManager {
         // Cleanup
         let _ = fs::remove_file(&test_path);
         
-        println!("📊 Cache Benchmark Results:");
-        println!("   ├── Uncompressed: Save {:.3}s, Load {:.3}s, Size {:.1}MB", 
+        telemetry::info("📊 Cache Benchmark Results:");
+        telemetry::info(&format!("   ├── Uncompressed: Save {:.3}s, Load {:.3}s, Size {:.1}MB", 
                  save_time_uncompressed.as_secs_f32(),
                  load_time_uncompressed.as_secs_f32(),
-                 uncompressed_size as f32 / 1024.0 / 1024.0);
-        println!("   └── Compressed:   Save {:.3}s, Load {:.3}s, Size {:.1}MB ({:.1}% of original)", 
+                 uncompressed_size as f32 / 1024.0 / 1024.0));
+        telemetry::info(&format!("   └── Compressed:   Save {:.3}s, Load {:.3}s, Size {:.1}MB ({:.1}% of original)", 
                  save_time_compressed.as_secs_f32(),
                  load_time_compressed.as_secs_f32(),
                  compressed_size as f32 / 1024.0 / 1024.0,
-                 compressed_size as f32 / uncompressed_size as f32 * 100.0);
+                 compressed_size as f32 / uncompressed_size as f32 * 100.0));
+        
+        Ok(())
+    }
+    
+    // Benchmark a real cached source: full loads, then narrow MS1 m/z range queries
+    // spread over the MS1 m/z range
+    pub fn benchmark_source(&self, source_path: &Path, iterations: usize, mz_width: f32) -> Result<(), Box<dyn std::error::Error>> {
+        let source_path = &self.resolve_source(source_path);
+        telemetry::info(&format!("🔬 Benchmarking cache of {}...", source_path.display()));
+        
+        let mut load_times = Vec::new();
+        let mut total_points = 0;
+        let mut mz_bounds = (0.0f32, 0.0f32);
+        for _ in 0..iterations.max(1) {
+            let start = std::time::Instant::now();
+            let (ms1_indexed, ms2_indexed_pairs) = self.load_indexed_data(source_path)?;
+            load_times.push(start.elapsed().as_secs_f32());
+            total_points = ms1_indexed.mz_values.len()
+                + ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).sum::<usize>();
+            mz_bounds = (
+                ms1_indexed.mz_values.first().copied().unwrap_or(0.0),
+                ms1_indexed.mz_values.last().copied().unwrap_or(0.0),
+            );
+        }
+        
+        const RANGE_QUERIES: usize = 10;
+        let mut query_times = Vec::new();
+        let mut amplification = 0.0;
+        for i in 0..RANGE_QUERIES {
+            let mz_min = mz_bounds.0 + (mz_bounds.1 - mz_bounds.0) * i as f32 / RANGE_QUERIES as f32;
+            let start = std::time::Instant::now();
+            self.load_ms1_mz_range(source_path, mz_min, mz_min + mz_width)?;
+            query_times.push(start.elapsed().as_secs_f32());
+            amplification += self.profiler.last().map(|p| p.amplification()).unwrap_or(0.0);
+        }
+        
+        let mean = |times: &[f32]| times.iter().sum::<f32>() / times.len().max(1) as f32;
+        let min = load_times.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = load_times.iter().copied().fold(0.0, f32::max);
+        telemetry::info(&format!("📊 Benchmark Results for {}:", source_path.display()));
+        telemetry::info(&format!("   ├── Full load ({} runs): mean {:.3}s, min {:.3}s, max {:.3}s", load_times.len(), mean(&load_times), min, max));
+        telemetry::info(&format!("   ├── Throughput: {:.1} M points/s ({} points)", total_points as f32 / min.max(1e-6) / 1e6, total_points));
+        telemetry::info(&format!("   └── MS1 range query ({} m/z wide, {} queries): mean {:.4}s, read amplification {:.1}x",
+                 mz_width, RANGE_QUERIES, mean(&query_times), amplification / RANGE_QUERIES as f64));
         
         Ok(())
     }
@@ -0,0 +1,107 @@
+// File: src/calibration.rs
+//
+// m/z and mobility calibrations applied before indexing. A new calibration leaves the raw
+// folder untouched, so freshness checks pass while every cached m/z or 1/K0 is off. The
+// calibration registered for a source (CacheManager::register_calibration) is therefore
+// part of its cache name ("-c<fingerprint>") and recorded in the metadata: once another
+// calibration is registered the old cache is never served, and register_calibration moves
+// it to the trash. Registrations are persisted in the cache dir, so every process sharing
+// it sees them.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use crate::fdlimit;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub id: String,         // Caller's name for it, e.g. the calibration run
+    pub mz: Vec<f64>,       // Coefficients of the m/z calibration, empty if none applied
+    pub mobility: Vec<f64>, // Coefficients of the mobility calibration, empty if none applied
+}
+
+impl Calibration {
+    pub fn new(id: &str) -> Self {
+        Self { id: id.to_string(), mz: Vec::new(), mobility: Vec::new() }
+    }
+
+    pub fn with_mz(mut self, coefficients: &[f64]) -> Self {
+        self.mz = coefficients.to_vec();
+        self
+    }
+
+    pub fn with_mobility(mut self, coefficients: &[f64]) -> Self {
+        self.mobility = coefficients.to_vec();
+        self
+    }
+
+    // Hash of the id and the exact coefficient bits: any change is another calibration
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update((self.id.len() as u64).to_le_bytes());
+        hasher.update(self.id.as_bytes());
+        for coefficients in [&self.mz, &self.mobility] {
+            hasher.update((coefficients.len() as u64).to_le_bytes());
+            for coefficient in coefficients {
+                hasher.update(coefficient.to_bits().to_le_bytes());
+            }
+        }
+        u64::from_le_bytes(hasher.finalize()[..8].try_into().unwrap())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.id.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a calibration needs an id"));
+        }
+        match self.mz.iter().chain(&self.mobility).find(|coefficient| !coefficient.is_finite()) {
+            Some(coefficient) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("calibration {} has a coefficient that is not finite ({})", self.id, coefficient),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// Calibrations by source key, persisted in the cache dir like AliasRegistry
+pub struct CalibrationRegistry {
+    path: PathBuf,
+    calibrations: BTreeMap<String, Calibration>,
+}
+
+impl CalibrationRegistry {
+    const FILE_NAME: &'static str = "calibrations.json";
+
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(Self::FILE_NAME);
+        let calibrations = fdlimit::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, calibrations }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(&self.calibrations).map_err(io::Error::other)?;
+        // Write-then-rename so concurrent readers never see a truncated registry
+        let partial = self.path.with_extension("partial");
+        fdlimit::write(&partial, text)?;
+        fs::rename(partial, &self.path)
+    }
+
+    // Returns the calibration registered before, if any
+    pub fn register(&mut self, source_key: &str, calibration: Calibration) -> io::Result<Option<Calibration>> {
+        calibration.check()?;
+        Ok(self.calibrations.insert(source_key.to_string(), calibration))
+    }
+
+    pub fn remove(&mut self, source_key: &str) -> Option<Calibration> {
+        self.calibrations.remove(source_key)
+    }
+
+    pub fn get(&self, source_key: &str) -> Option<&Calibration> {
+        self.calibrations.get(source_key)
+    }
+}
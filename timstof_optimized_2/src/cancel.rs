@@ -0,0 +1,42 @@
+// File: src/cancel.rs
+//
+// Cooperative cancellation of long cache builds and loads. A GUI or job scheduler keeps a
+// clone of the token and calls cancel(); the operation notices at its next shard or
+// window boundary and returns an Interrupted error. Saves stop before the metadata is
+// written, so a cancelled build never leaves a cache that looks complete: files already
+// committed stay as progress and a later save of the same source resumes from them.
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Err once cancelled; called between units of work
+    pub fn check(&self) -> io::Result<()> {
+        match self.is_cancelled() {
+            true => Err(io::Error::new(io::ErrorKind::Interrupted, "operation cancelled")),
+            false => Ok(()),
+        }
+    }
+}
+
+// Whether an error returned by a cache operation means it was cancelled
+pub fn is_cancelled_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::Interrupted)
+}
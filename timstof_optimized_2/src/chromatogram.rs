@@ -0,0 +1,167 @@
+// File: src/chromatogram.rs
+//
+// Per-frame total ion current (TIC) and base peak (BPC) chromatograms, computed during a
+// save and stored as a small sidecar next to the cache ("<source>.chromatograms.cache.bin"),
+// so QC dashboards read a few KB instead of the shards. Every frame is summarized, MS1 and
+// MS2 alike, with the MS level it was indexed under; a DIA frame sums all its windows.
+use std::io;
+use std::path::Path;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::encryption::EncryptionKey;
+use crate::sidecar;
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+
+const MAGIC: &[u8; 8] = b"TSCHROM1";
+
+// One value per frame, in frame order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chromatogram {
+    pub frame_indices: Vec<u32>,
+    pub ms_levels: Vec<u8>,
+    pub rt_values_min: Vec<f32>,
+    pub values: Vec<u64>, // Summed (TIC) or largest (BPC) intensity of the frame
+}
+
+impl Chromatogram {
+    // The frames of one MS level, e.g. the MS1 TIC usually plotted for QC
+    pub fn ms_level(&self, ms_level: u8) -> Self {
+        let keep: Vec<usize> = (0..self.ms_levels.len()).filter(|&i| self.ms_levels[i] == ms_level).collect();
+        Self {
+            frame_indices: keep.iter().map(|&i| self.frame_indices[i]).collect(),
+            ms_levels: keep.iter().map(|&i| self.ms_levels[i]).collect(),
+            rt_values_min: keep.iter().map(|&i| self.rt_values_min[i]).collect(),
+            values: keep.iter().map(|&i| self.values[i]).collect(),
+        }
+    }
+}
+
+// What the sidecar holds: both chromatograms over the same frames
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameSummary {
+    frame_indices: Vec<u32>,
+    ms_levels: Vec<u8>,
+    rt_values_min: Vec<f32>,
+    tic: Vec<u64>,
+    bpc: Vec<u64>,
+}
+
+// Per frame: MS level, rt, TIC, BPC; None for frames without points
+type Totals = Vec<Option<(u8, f32, u64, u64)>>;
+
+impl FrameSummary {
+    pub fn compute<F: Float>(ms1: &IndexedTimsTOFData<F>, ms2: &[&IndexedTimsTOFData<F>]) -> Self {
+        let mut totals = Totals::new();
+        merge(&mut totals, frame_totals(ms1, 1));
+        for data in ms2 {
+            merge(&mut totals, frame_totals(data, 2));
+        }
+        Self::from_totals(totals)
+    }
+
+    fn from_totals(totals: Totals) -> Self {
+        let mut summary = Self::default();
+        for (frame, total) in totals.into_iter().enumerate() {
+            if let Some((ms_level, rt, tic, bpc)) = total {
+                summary.frame_indices.push(frame as u32);
+                summary.ms_levels.push(ms_level);
+                summary.rt_values_min.push(rt);
+                summary.tic.push(tic);
+                summary.bpc.push(bpc);
+            }
+        }
+        summary
+    }
+
+    fn totals(&self) -> Totals {
+        let mut totals = vec![None; self.frame_indices.last().map_or(0, |&frame| frame as usize + 1)];
+        for (i, &frame) in self.frame_indices.iter().enumerate() {
+            totals[frame as usize] = Some((self.ms_levels[i], self.rt_values_min[i], self.tic[i], self.bpc[i]));
+        }
+        totals
+    }
+
+    // Add the points of other chunks of the same run, whatever frames they hold (streamed
+    // saves): TICs of a frame add up, BPCs take the larger
+    pub fn merge(&mut self, other: &Self) {
+        let mut totals = self.totals();
+        merge(&mut totals, other.totals());
+        *self = Self::from_totals(totals);
+    }
+
+    // Add the frames of `other`, which all come after ours (incremental updates)
+    pub fn extend(&mut self, other: Self) {
+        self.frame_indices.extend(other.frame_indices);
+        self.ms_levels.extend(other.ms_levels);
+        self.rt_values_min.extend(other.rt_values_min);
+        self.tic.extend(other.tic);
+        self.bpc.extend(other.bpc);
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frame_indices.len()
+    }
+
+    pub fn tic(&self) -> Chromatogram {
+        self.chromatogram(&self.tic)
+    }
+
+    pub fn bpc(&self) -> Chromatogram {
+        self.chromatogram(&self.bpc)
+    }
+
+    fn chromatogram(&self, values: &[u64]) -> Chromatogram {
+        Chromatogram {
+            frame_indices: self.frame_indices.clone(),
+            ms_levels: self.ms_levels.clone(),
+            rt_values_min: self.rt_values_min.clone(),
+            values: values.to_vec(),
+        }
+    }
+
+    pub fn write(&self, path: &Path, key: Option<&EncryptionKey>) -> io::Result<()> {
+        sidecar::write(path, MAGIC, self, key)
+    }
+
+    pub fn read(path: &Path, key: Option<&EncryptionKey>) -> io::Result<Self> {
+        sidecar::read(path, MAGIC, key)
+    }
+}
+
+// Totals of one dataset, indexed by frame; data in m/z order visits frames at random, so
+// each thread sums into a dense table
+fn frame_totals<F: Float>(data: &IndexedTimsTOFData<F>, ms_level: u8) -> Totals {
+    let frames = data.frame_indices.iter().max().map_or(0, |&frame| frame as usize + 1);
+    (0..data.frame_indices.len()).into_par_iter()
+        .with_min_len(1 << 16)
+        .fold(|| vec![None; frames], |mut totals: Totals, row| {
+            let intensity = data.intensity_values[row] as u64;
+            let total = totals[data.frame_indices[row] as usize]
+                .get_or_insert((ms_level, data.rt_values_min[row].to_f32(), 0, 0));
+            total.2 += intensity;
+            total.3 = total.3.max(intensity);
+            totals
+        })
+        .reduce(Totals::new, |mut a, b| {
+            merge(&mut a, b);
+            a
+        })
+}
+
+fn merge(totals: &mut Totals, other: Totals) {
+    if totals.len() < other.len() {
+        totals.resize(other.len(), None);
+    }
+    for (total, other) in totals.iter_mut().zip(other) {
+        match (total.as_mut(), other) {
+            (Some(total), Some((_, _, tic, bpc))) => {
+                total.2 += tic;
+                total.3 = total.3.max(bpc);
+            }
+            (None, other) => *total = other,
+            (_, None) => {}
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// File: src/chunked.rs
+//
+// MS1 data kept as the shards it was stored in. Loading a columnar cache into one
+// IndexedTimsTOFData copies every shard into a single set of columns; a
+// ChunkedIndexedData keeps the decoded shards as they are, plus a small index (where each
+// chunk starts and which m/z it covers), and answers the same range queries from them.
+// to_contiguous() builds the flat columns for callers that need plain slices.
+use std::ops::Range;
+use rayon::prelude::*;
+
+use crate::columnar;
+use crate::utils::{IndexedTimsTOFData, TimsTOFData};
+
+#[derive(Debug, Clone, Default)]
+pub struct ChunkedIndexedData {
+    chunks: Vec<IndexedTimsTOFData>, // Each sorted by m/z; chunks may overlap (incremental updates)
+    starts: Vec<usize>,              // Global index of the first point of each chunk
+    mz_bounds: Vec<(f32, f32)>,      // First and last m/z of each chunk
+    len: usize,
+}
+
+impl ChunkedIndexedData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Chunks must each be sorted by m/z; empty ones are dropped
+    pub fn from_chunks(chunks: Vec<IndexedTimsTOFData>) -> Self {
+        let chunks: Vec<IndexedTimsTOFData> = chunks.into_iter().filter(|chunk| !chunk.mz_values.is_empty()).collect();
+        let mut starts = Vec::with_capacity(chunks.len());
+        let mut len = 0;
+        for chunk in &chunks {
+            starts.push(len);
+            len += chunk.mz_values.len();
+        }
+        let mz_bounds = chunks.iter().map(mz_bounds).collect();
+        Self { chunks, starts, mz_bounds, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn chunks(&self) -> &[IndexedTimsTOFData] {
+        &self.chunks
+    }
+
+    // Chunk and position within it of the point at a global index
+    pub fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        let chunk = self.starts.partition_point(|&start| start <= index) - 1;
+        Some((chunk, index - self.starts[chunk]))
+    }
+
+    // Columns across all chunks, in chunk order
+    pub fn rt_values_min(&self) -> impl Iterator<Item = f32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.rt_values_min.iter().copied())
+    }
+
+    pub fn mobility_values(&self) -> impl Iterator<Item = f32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.mobility_values.iter().copied())
+    }
+
+    pub fn mz_values(&self) -> impl Iterator<Item = f32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.mz_values.iter().copied())
+    }
+
+    pub fn intensity_values(&self) -> impl Iterator<Item = u32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.intensity_values.iter().copied())
+    }
+
+    pub fn frame_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.frame_indices.iter().copied())
+    }
+
+    pub fn scan_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.scan_indices.iter().copied())
+    }
+
+    // Peaks with m/z in [mz_min, mz_max], in m/z order like IndexedTimsTOFData's
+    pub fn slice_by_mz_range(&self, mz_min: f32, mz_max: f32) -> TimsTOFData {
+        self.select(mz_min, mz_max, rows_of)
+    }
+
+    pub fn slice_by_mz_im_range(&self, mz_min: f32, mz_max: f32, im_min: f32, im_max: f32) -> TimsTOFData {
+        self.select(mz_min, mz_max, |chunk, rows| {
+            let rows: Vec<usize> = rows
+                .filter(|&i| chunk.mobility_values[i] >= im_min && chunk.mobility_values[i] <= im_max)
+                .collect();
+            picked_rows(chunk, &rows)
+        })
+    }
+
+    pub fn filter_by_im_range(&self, im_min: f32, im_max: f32) -> TimsTOFData {
+        self.slice_by_mz_im_range(f32::NEG_INFINITY, f32::INFINITY, im_min, im_max)
+    }
+
+    // Multiply m/z by 1000 (monotonic, so chunks stay sorted)
+    pub fn convert_mz_to_integer(&mut self) {
+        self.chunks.par_iter_mut().for_each(|chunk| chunk.convert_mz_to_integer());
+        self.mz_bounds = self.chunks.iter().map(mz_bounds).collect();
+    }
+
+    // One flat, m/z-sorted copy of all chunks
+    pub fn to_contiguous(&self) -> IndexedTimsTOFData {
+        columnar::merge_by_mz(self.chunks.clone())
+    }
+
+    pub fn into_contiguous(self) -> IndexedTimsTOFData {
+        columnar::merge_by_mz(self.chunks)
+    }
+
+    // Rows of the chunks overlapping [mz_min, mz_max] (found by binary search, then cut by
+    // `part`), merged into m/z order
+    fn select<P>(&self, mz_min: f32, mz_max: f32, part: P) -> TimsTOFData
+    where
+        P: Fn(&IndexedTimsTOFData, Range<usize>) -> IndexedTimsTOFData + Sync,
+    {
+        let parts: Vec<IndexedTimsTOFData> = self.chunks.par_iter()
+            .zip(self.mz_bounds.par_iter())
+            .filter(|(_, &(first, last))| first <= mz_max && last >= mz_min)
+            .map(|(chunk, _)| {
+                let start = chunk.mz_values.partition_point(|&x| x < mz_min);
+                let end = chunk.mz_values.partition_point(|&x| x <= mz_max);
+                part(chunk, start..end)
+            })
+            .collect();
+        let merged = columnar::merge_by_mz(parts);
+        TimsTOFData {
+            rt_values_min: merged.rt_values_min,
+            mobility_values: merged.mobility_values,
+            mz_values: merged.mz_values,
+            intensity_values: merged.intensity_values,
+            frame_indices: merged.frame_indices,
+            scan_indices: merged.scan_indices,
+        }
+    }
+}
+
+impl From<IndexedTimsTOFData> for ChunkedIndexedData {
+    fn from(data: IndexedTimsTOFData) -> Self {
+        Self::from_chunks(vec![data])
+    }
+}
+
+fn mz_bounds(chunk: &IndexedTimsTOFData) -> (f32, f32) {
+    (chunk.mz_values[0], chunk.mz_values[chunk.mz_values.len() - 1])
+}
+
+fn rows_of(chunk: &IndexedTimsTOFData, rows: Range<usize>) -> IndexedTimsTOFData {
+    IndexedTimsTOFData {
+        rt_values_min: chunk.rt_values_min[rows.clone()].to_vec(),
+        mobility_values: chunk.mobility_values[rows.clone()].to_vec(),
+        mz_values: chunk.mz_values[rows.clone()].to_vec(),
+        intensity_values: chunk.intensity_values[rows.clone()].to_vec(),
+        frame_indices: chunk.frame_indices[rows.clone()].to_vec(),
+        scan_indices: chunk.scan_indices[rows].to_vec(),
+    }
+}
+
+fn picked_rows(chunk: &IndexedTimsTOFData, rows: &[usize]) -> IndexedTimsTOFData {
+    IndexedTimsTOFData {
+        rt_values_min: rows.iter().map(|&i| chunk.rt_values_min[i]).collect(),
+        mobility_values: rows.iter().map(|&i| chunk.mobility_values[i]).collect(),
+        mz_values: rows.iter().map(|&i| chunk.mz_values[i]).collect(),
+        intensity_values: rows.iter().map(|&i| chunk.intensity_values[i]).collect(),
+        frame_indices: rows.iter().map(|&i| chunk.frame_indices[i]).collect(),
+        scan_indices: rows.iter().map(|&i| chunk.scan_indices[i]).collect(),
+    }
+}
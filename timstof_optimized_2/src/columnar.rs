@@ -0,0 +1,1478 @@
+// File: src/columnar.rs
+//
+// Self-describing columnar container used for cache files:
+//
+//   "TTFC" | format_version: u32 | column payloads ... | header (JSON) | header_len: u64 | "TTFC"
+//
+// The JSON header lists every segment (MS1, or one per MS2 window) and, per segment, a
+// column descriptor table (name, type, encoding, offset, length). Readers look columns up
+// by name, skip columns they don't know unless the writer marked them required, and only
+// reject files whose required columns they truly cannot interpret. New columns and
+// encodings can therefore be added without breaking older readers.
+//
+// Long compressed columns are stored as independent blocks (encoding "<codec>:blocks"),
+// listed in the descriptor with their row counts and value bounds, so a query for part of
+// a segment decompresses only the blocks holding its rows. Readers from before blocks
+// don't know the encoding and reject the column, as they should.
+//
+// Exact float columns may be byte-shuffled before compression (WriteOptions::byte_shuffle),
+// each block on its own, which adds ":shuffle" after the codec ("lz4:shuffle:blocks").
+// Readers from before it likewise reject those columns rather than decode them wrong.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::IndexedTimsTOFData;
+use crate::encryption::{self, EncryptionKey};
+use crate::cancel::CancellationToken;
+use crate::durability::{self, Durability, DurableFile};
+use crate::fdlimit::{self, FilePermit};
+use crate::compression::{self, CodecMap, ColumnCodec};
+use crate::quantize::{self, LossyOptions, Quantization};
+use crate::precision::{self, Float, Precision};
+use crate::telemetry;
+
+pub const MAGIC: &[u8; 4] = b"TTFC";
+pub const FORMAT_VERSION: u32 = 1;
+
+// Columns of IndexedTimsTOFData in their canonical order
+pub const INDEXED_COLUMNS: [(&str, &str); 6] = [
+    ("rt_values_min", "f32"),
+    ("mobility_values", "f32"),
+    ("mz_values", "f32"),
+    ("intensity_values", "u32"),
+    ("frame_indices", "u32"),
+    ("scan_indices", "u32"),
+];
+
+// Hash of the data schema (INDEXED_COLUMNS: names, types, order), recorded as "schema" in
+// the cache metadata. A build whose columns differ computes another hash and refuses the
+// cache with SchemaMismatch instead of filling the wrong fields.
+pub fn schema_hash() -> String {
+    let mut hasher = Sha256::new();
+    for (name, dtype) in INDEXED_COLUMNS {
+        hasher.update(format!("{}:{};", name, dtype));
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+// Returned (inside an InvalidData io::Error) by loads of a cache written for another schema
+#[derive(Debug, Clone)]
+pub struct SchemaMismatch {
+    pub metadata_path: PathBuf,
+    pub recorded: String, // Hash in the cache metadata
+    pub expected: String, // schema_hash() of this build
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cache {} was written for data schema {}, this build reads schema {}; clear and rebuild it",
+               self.metadata_path.display(), self.recorded, self.expected)
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDescriptor {
+    pub name: String,
+    pub dtype: String,     // "f32" | "f64" | "u32", or the stored type of a quantized column ("u16")
+    pub encoding: String,  // "raw" (little-endian) | "lz4" (block) | "zstd", then ":shuffle", ":blocks", "+aes256gcm" as applied
+    pub offset: u64,       // Absolute file offset of the stored bytes
+    pub stored_len: u64,
+    pub rows: u64,
+    #[serde(default)]
+    pub required: bool,    // Readers that can't decode this column must reject the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>, // hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc32: Option<u32>,    // Of the stored bytes, so it can be checked without decoding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<Quantization>, // How lossy stored values map back (quantize.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<ColumnBlock>>,   // In stored order from `offset`, for ":blocks" encodings
+}
+
+// One independently compressed (and encrypted) run of rows of a blocked column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnBlock {
+    pub rows: u64,
+    pub stored_len: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>, // hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,      // Smallest value as decoded; None if the block has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl ColumnBlock {
+    // Whether the block may hold values in [low, high]; without bounds it may
+    fn overlaps(&self, low: f64, high: f64) -> bool {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => max >= low && min <= high,
+            _ => true,
+        }
+    }
+}
+
+// Suffix of the codec in the encoding of a blocked column
+pub const BLOCKED: &str = ":blocks";
+// ... and of a byte-shuffled one, before BLOCKED
+pub const SHUFFLED: &str = ":shuffle";
+
+impl ColumnDescriptor {
+    fn base_encoding(&self) -> &str {
+        self.encoding.split('+').next().unwrap_or("")
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.encoding.contains('+')
+    }
+
+    // Codec of the stored bytes, of the whole column or of each block
+    pub fn codec(&self) -> Option<ColumnCodec> {
+        ColumnCodec::from_encoding(self.base_encoding().trim_end_matches(BLOCKED).trim_end_matches(SHUFFLED))
+    }
+
+    // Whether the values were byte-shuffled before compression (compression::byte_shuffle)
+    pub fn is_shuffled(&self) -> bool {
+        self.base_encoding().trim_end_matches(BLOCKED).ends_with(SHUFFLED)
+    }
+
+    // Rows [start, end) of each block, with the file offset of its stored bytes
+    fn block_spans(&self) -> Vec<(Range<u64>, u64)> {
+        let (mut row, mut offset) = (0, self.offset);
+        self.blocks.iter().flatten()
+            .map(|block| {
+                let span = (row..row + block.rows, offset);
+                row += block.rows;
+                offset += block.stored_len;
+                span
+            })
+            .collect()
+    }
+
+    // Bytes per row once decrypted and decompressed
+    pub fn stored_width(&self) -> usize {
+        match self.quantization {
+            Some(quantization) => quantization.stored_width(),
+            None => Precision::from_dtype(&self.dtype).map_or(4, |precision| precision.width()),
+        }
+    }
+
+    // Bytes per row as decode_column returns them (quantized values come back as f32 / u32)
+    fn decoded_width(&self) -> usize {
+        match self.quantization {
+            Some(_) => 4,
+            None => self.stored_width(),
+        }
+    }
+
+    // Width of a float column's values as decode_column returns them
+    fn decoded_precision(&self) -> Precision {
+        match self.quantization {
+            Some(_) => Precision::F32,
+            None => Precision::from_dtype(&self.dtype).unwrap_or_default(),
+        }
+    }
+
+    // Whether rows can be read straight from disk: plain raw values of the column's dtype
+    fn is_sliceable(&self) -> bool {
+        self.encoding == "raw" && self.quantization.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SegmentDescriptor {
+    pub name: String,                  // "ms1" or "ms2_window"
+    #[serde(default)]
+    pub attrs: BTreeMap<String, f64>,  // e.g. isolation_low / isolation_high
+    pub rows: u64,
+    pub columns: Vec<ColumnDescriptor>,
+}
+
+impl SegmentDescriptor {
+    pub fn column(&self, name: &str) -> Option<&ColumnDescriptor> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerHeader {
+    pub format_version: u32,
+    pub segments: Vec<SegmentDescriptor>,
+}
+
+// What to write for one segment: the given rows of `data`
+pub struct SegmentInput<'a, F = f32> {
+    pub name: String,
+    pub attrs: BTreeMap<String, f64>,
+    pub data: &'a IndexedTimsTOFData<F>,
+    pub rows: Range<usize>,
+    pub codecs: Option<&'a CodecMap>, // Overrides WriteOptions::codecs for this segment
+}
+
+// Stored size of one point: six 4-byte columns
+pub const POINT_BYTES: u64 = 24;
+
+// Input bytes a write encodes in memory at once, across segments (write_segments)
+const ENCODE_BATCH_BYTES: u64 = 256 * 1024 * 1024;
+
+// Auto-tuned shards aim for this size, and are only made smaller (down to the minimum)
+// to give every decode thread a shard
+const AUTO_SHARD_BYTES: u64 = 64 * 1024 * 1024;
+const MIN_AUTO_SHARD_BYTES: u64 = 4 * 1024 * 1024;
+
+// How the MS1 segment is split into m/z-ordered shards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShardPlan {
+    pub shards: usize,
+    pub points_per_shard: usize,
+    pub tuning: &'static str, // "target_points" | "target_bytes" | "auto"
+}
+
+impl ShardPlan {
+    // An explicit point target wins over a byte target; without either, the shard count
+    // follows from the data size and the available parallelism
+    pub fn new(points: usize, target_bytes: Option<u64>, target_points: Option<usize>, parallelism: usize) -> Self {
+        let bytes = points as u64 * POINT_BYTES;
+        let (shards, tuning) = match (target_points, target_bytes) {
+            (Some(target), _) => (points.div_ceil(target.max(1)), "target_points"),
+            (None, Some(target)) => (bytes.div_ceil(target.max(POINT_BYTES)) as usize, "target_bytes"),
+            (None, None) => {
+                let by_size = bytes.div_ceil(AUTO_SHARD_BYTES) as usize;
+                let by_threads = parallelism.min((bytes / MIN_AUTO_SHARD_BYTES) as usize);
+                (by_size.max(by_threads), "auto")
+            }
+        };
+        let shards = shards.max(1);
+        Self { shards, points_per_shard: points.div_ceil(shards).max(1), tuning }
+    }
+
+    // Consecutive row ranges, one per shard (a single empty range for no points)
+    pub fn ranges(&self, points: usize) -> Vec<Range<usize>> {
+        (0..points.max(1))
+            .step_by(self.points_per_shard)
+            .map(|start| start..(start + self.points_per_shard).min(points))
+            .collect()
+    }
+}
+
+enum ColumnValues<'a, F> {
+    Float(&'a [F]),
+    U32(&'a [u32]),
+}
+
+fn column_values<'a, F>(data: &'a IndexedTimsTOFData<F>, name: &str, rows: Range<usize>) -> Option<ColumnValues<'a, F>> {
+    match name {
+        "rt_values_min" => Some(ColumnValues::Float(&data.rt_values_min[rows])),
+        "mobility_values" => Some(ColumnValues::Float(&data.mobility_values[rows])),
+        "mz_values" => Some(ColumnValues::Float(&data.mz_values[rows])),
+        "intensity_values" => Some(ColumnValues::U32(&data.intensity_values[rows])),
+        "frame_indices" => Some(ColumnValues::U32(&data.frame_indices[rows])),
+        "scan_indices" => Some(ColumnValues::U32(&data.scan_indices[rows])),
+        _ => None,
+    }
+}
+
+// Concatenate decoded segments (MS1 shards are stored in m/z order)
+pub fn concat_segments<F>(parts: Vec<IndexedTimsTOFData<F>>) -> IndexedTimsTOFData<F> {
+    if parts.len() == 1 {
+        return parts.into_iter().next().unwrap();
+    }
+    let rows: usize = parts.iter().map(|part| part.mz_values.len()).sum();
+    let mut data = IndexedTimsTOFData {
+        rt_values_min: Vec::with_capacity(rows),
+        mobility_values: Vec::with_capacity(rows),
+        mz_values: Vec::with_capacity(rows),
+        intensity_values: Vec::with_capacity(rows),
+        frame_indices: Vec::with_capacity(rows),
+        scan_indices: Vec::with_capacity(rows),
+    };
+    for part in parts {
+        data.rt_values_min.extend(part.rt_values_min);
+        data.mobility_values.extend(part.mobility_values);
+        data.mz_values.extend(part.mz_values);
+        data.intensity_values.extend(part.intensity_values);
+        data.frame_indices.extend(part.frame_indices);
+        data.scan_indices.extend(part.scan_indices);
+    }
+    data
+}
+
+// Merge segments that are each sorted by m/z into one sorted segment. Shards written by
+// one save cover disjoint m/z ranges and are just concatenated; shards appended by
+// incremental updates overlap the earlier ones and need a real merge.
+pub fn merge_by_mz<F: Float>(parts: Vec<IndexedTimsTOFData<F>>) -> IndexedTimsTOFData<F> {
+    let non_empty: Vec<&IndexedTimsTOFData<F>> = parts.iter().filter(|part| !part.mz_values.is_empty()).collect();
+    let ordered = non_empty.windows(2).all(|pair| pair[0].mz_values.last() <= pair[1].mz_values.first());
+    let data = concat_segments(parts);
+    if ordered {
+        return data;
+    }
+    sort_by_mz(&data)
+}
+
+// Stable, so points of equal m/z keep their acquisition order
+pub fn sort_by_mz<F: Float>(data: &IndexedTimsTOFData<F>) -> IndexedTimsTOFData<F> {
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.par_sort_by(|&a, &b| data.mz_values[a].total_cmp(&data.mz_values[b]));
+    let permute = |values: &[F]| order.iter().map(|&i| values[i]).collect::<Vec<_>>();
+    let permute_u32 = |values: &[u32]| order.iter().map(|&i| values[i]).collect::<Vec<_>>();
+    IndexedTimsTOFData {
+        rt_values_min: permute(&data.rt_values_min),
+        mobility_values: permute(&data.mobility_values),
+        mz_values: permute(&data.mz_values),
+        intensity_values: permute_u32(&data.intensity_values),
+        frame_indices: permute_u32(&data.frame_indices),
+        scan_indices: permute_u32(&data.scan_indices),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Validate a user-supplied column order: a permutation of the canonical column names
+pub fn resolve_column_order(order: &[String]) -> io::Result<Vec<&'static str>> {
+    let mut resolved = Vec::with_capacity(INDEXED_COLUMNS.len());
+    for name in order {
+        let known = INDEXED_COLUMNS.iter().find(|(n, _)| n == name)
+            .ok_or_else(|| invalid_data(format!("unknown column in column_order: {}", name)))?;
+        if resolved.contains(&known.0) {
+            return Err(invalid_data(format!("column listed twice in column_order: {}", name)));
+        }
+        resolved.push(known.0);
+    }
+    for (name, _) in INDEXED_COLUMNS {
+        if !resolved.contains(&name) {
+            return Err(invalid_data(format!("column_order is missing column: {}", name)));
+        }
+    }
+    Ok(resolved)
+}
+
+// Stored bytes of a lossy column; None for the columns stored exactly
+fn quantize_column<F: Float>(values: &ColumnValues<F>, name: &str, lossy: LossyOptions) -> Option<(Quantization, Vec<u8>)> {
+    match (name, values) {
+        ("intensity_values", ColumnValues::U32(values)) => Some(quantize::quantize_intensity(values)),
+        ("mz_values", ColumnValues::Float(values)) => quantize::quantize_mz(values, lossy.mz_error_ppm),
+        _ => None,
+    }
+}
+
+// Little-endian values of an exactly stored column, before compression
+fn column_bytes<F: Float>(values: &ColumnValues<F>, precision: Precision) -> Vec<u8> {
+    match values {
+        ColumnValues::Float(v) => precision::floats_to_le(v, precision),
+        ColumnValues::U32(v) => v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+    }
+}
+
+pub struct WriteOptions<'a> {
+    pub column_order: Vec<&'static str>,
+    pub codecs: CodecMap, // Per column; missing columns stay raw
+    pub buffer_size: usize,
+    pub encryption_key: Option<&'a EncryptionKey>, // Applied after compression
+    pub cancel: Option<&'a CancellationToken>,     // Checked before each segment
+    pub lossy: Option<LossyOptions>,               // Quantize m/z and intensities before compression
+    pub precision: Precision,                      // Stored width of the float columns
+    pub block_rows: Option<usize>,                 // Compressed columns longer than this are stored in blocks of this many rows
+    pub byte_shuffle: bool,                        // Byte-shuffle compressed exact float columns first
+    pub durability: Durability,                    // Syncing of the file and its directory (durability.rs)
+    pub deterministic: bool,                       // Same bytes for the same input whatever the machine: no spare zstd workers
+}
+
+// Additional authenticated data: ties each ciphertext to its segment and column
+fn column_aad(segment_index: usize, segment_name: &str, column_name: &str) -> Vec<u8> {
+    format!("{}:{}:{}", segment_index, segment_name, column_name).into_bytes()
+}
+
+// ... and to its place in the column, so blocks can't be reordered
+fn block_aad(column_aad: &[u8], block_index: usize) -> Vec<u8> {
+    [column_aad, format!(":{}", block_index).as_bytes()].concat()
+}
+
+// Write all segments into one container file (atomically, via a temporary name)
+pub fn write_container<F: Float>(
+    path: &Path,
+    segments: &[SegmentInput<F>],
+    options: &WriteOptions,
+) -> io::Result<ContainerHeader> {
+    let mut writer = ContainerWriter::create(path, options)?;
+    writer.write(segments, options)?;
+    writer.finish(options)
+}
+
+// A container written a few segments at a time, for writers that never hold all of its
+// data (CacheManager::save_from_stream). Written under a temporary name and renamed into
+// place by finish(); dropped before that, the temporary file is removed, unless the writer
+// is resumable: then the segments it committed are kept for resume() to continue after.
+pub struct ContainerWriter {
+    path: PathBuf,
+    partial_path: PathBuf,
+    writer: Option<BufWriter<DurableFile>>,
+    offset: u64,
+    header: ContainerHeader,
+    resumable: bool,
+    _permit: FilePermit,
+}
+
+impl ContainerWriter {
+    pub fn create(path: &Path, options: &WriteOptions) -> io::Result<Self> {
+        let partial_path = path.with_extension("partial");
+        let (file, permit) = fdlimit::create(&partial_path)?.into_parts();
+        let file = DurableFile::new(file, options.durability)?;
+        let mut container = Self {
+            path: path.to_path_buf(),
+            partial_path,
+            writer: Some(BufWriter::with_capacity(options.buffer_size, file)),
+            offset: (MAGIC.len() + 4) as u64,
+            header: ContainerHeader { format_version: FORMAT_VERSION, segments: Vec::new() },
+            resumable: false,
+            _permit: permit,
+        };
+        let writer = container.writer.as_mut().unwrap();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(container)
+    }
+
+    // A writer whose temporary file outlives it, for write_committing()
+    pub fn create_resumable(path: &Path, options: &WriteOptions) -> io::Result<Self> {
+        let mut container = Self::create(path, options)?;
+        container.resumable = true;
+        Ok(container)
+    }
+
+    // Continue the temporary file of a resumable writer that stopped after the segments of
+    // `committed`. Segments are kept up to the first one missing from the file or failing
+    // its checksum (data not yet synced when the machine went down), and the file is cut
+    // after them.
+    pub fn resume(path: &Path, committed: &ContainerHeader, options: &WriteOptions) -> io::Result<Self> {
+        let partial_path = path.with_extension("partial");
+        let (mut file, permit) = fdlimit::open_with(OpenOptions::new().read(true).write(true), &partial_path)?.into_parts();
+        let file_len = file.metadata()?.len();
+        let start = (MAGIC.len() + 4) as u64;
+        let read_at = file_reader(&file);
+        if file_len < start || read_at(0, MAGIC.len())? != MAGIC {
+            return Err(invalid_data(format!("{} is not a columnar cache", partial_path.display())));
+        }
+        let mut header = ContainerHeader { format_version: FORMAT_VERSION, segments: Vec::new() };
+        let mut offset = start;
+        for segment in &committed.segments {
+            let intact = segment.columns.iter().all(|column| {
+                column.offset.checked_add(column.stored_len).is_some_and(|end| end <= file_len)
+                    && column.crc32.is_some()
+                    && verify_stored_column(&read_at, column).is_ok()
+            });
+            if !intact {
+                break;
+            }
+            offset = segment.columns.iter().map(|column| column.offset + column.stored_len).max().unwrap_or(offset);
+            header.segments.push(segment.clone());
+        }
+        drop(read_at);
+        file.set_len(offset)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            partial_path,
+            writer: Some(BufWriter::with_capacity(options.buffer_size, DurableFile::new(file, options.durability)?)),
+            offset,
+            header,
+            resumable: true,
+            _permit: permit,
+        })
+    }
+
+    // Segments after those already written
+    pub fn write<F: Float>(&mut self, segments: &[SegmentInput<F>], options: &WriteOptions) -> io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        write_segments(writer, &mut self.offset, segments, options, &mut self.header, &mut |_, _| Ok(()))
+    }
+
+    // write() that syncs the file after each segment and passes the header of the segments
+    // written so far to `commit`, which records it for resume()
+    pub fn write_committing<F: Float>(
+        &mut self,
+        segments: &[SegmentInput<F>],
+        options: &WriteOptions,
+        mut commit: impl FnMut(&ContainerHeader) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        write_segments(writer, &mut self.offset, segments, options, &mut self.header, &mut |writer, header| {
+            writer.flush()?;
+            writer.get_mut().sync()?;
+            commit(header)
+        })
+    }
+
+    pub fn segments(&self) -> &[SegmentDescriptor] {
+        &self.header.segments
+    }
+
+    pub fn finish(mut self, options: &WriteOptions) -> io::Result<ContainerHeader> {
+        let mut writer = self.writer.take().unwrap();
+        let finished = write_footer(&mut writer, &self.header)
+            .and_then(|_| writer.into_inner().map_err(|e| e.into_error())?.finish())
+            .and_then(|_| durability::commit(&self.partial_path, &self.path, options.durability));
+        if let Err(e) = finished {
+            let _ = fs::remove_file(&self.partial_path);
+            return Err(e);
+        }
+        Ok(std::mem::take(&mut self.header))
+    }
+}
+
+impl Drop for ContainerWriter {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() && !self.resumable {
+            let _ = fs::remove_file(&self.partial_path);
+        }
+    }
+}
+
+// Add segments to an existing container. The file is never modified in place (clones may
+// share its blocks or inode): the existing column payloads are copied to a temporary
+// file, the new columns and a footer listing all segments are written after them, and
+// the result is renamed over the original.
+pub fn append_container<F: Float>(
+    path: &Path,
+    segments: &[SegmentInput<F>],
+    options: &WriteOptions,
+) -> io::Result<ContainerHeader> {
+    let (partial_path, header) = append_container_partial(path, segments, options)?;
+    durability::commit(&partial_path, path, options.durability)?;
+    Ok(header)
+}
+
+// append_container up to the rename: the appended copy is left at the returned temporary
+// path for the caller to commit together with other files
+pub fn append_container_partial<F: Float>(
+    path: &Path,
+    segments: &[SegmentInput<F>],
+    options: &WriteOptions,
+) -> io::Result<(PathBuf, ContainerHeader)> {
+    let file = fdlimit::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut header = read_header_with(&file_reader(&file), file_len)?;
+    let payload_end = header.segments.iter()
+        .flat_map(|segment| segment.columns.iter())
+        .map(|column| column.offset + column.stored_len)
+        .max()
+        .unwrap_or((MAGIC.len() + 4) as u64);
+    drop(file);
+
+    let partial_path = path.with_extension("partial");
+    fs::copy(path, &partial_path)?;
+    let (mut file, _permit) = fdlimit::open_with(OpenOptions::new().write(true), &partial_path)?.into_parts();
+    file.set_len(payload_end)?;
+    file.seek(SeekFrom::End(0))?;
+    let mut writer = BufWriter::with_capacity(options.buffer_size, DurableFile::new(file, options.durability)?);
+
+    // Written under the current format version, whatever version wrote the existing part
+    header.format_version = header.format_version.max(FORMAT_VERSION);
+    let mut offset = payload_end;
+    if let Err(e) = write_segments(&mut writer, &mut offset, segments, options, &mut header, &mut |_, _| Ok(())) {
+        drop(writer);
+        let _ = fs::remove_file(&partial_path);
+        return Err(e);
+    }
+    write_footer(&mut writer, &header)?;
+
+    writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+    Ok((partial_path, header))
+}
+
+// Rewrite a container with some segments replaced (by index), e.g. to repair corrupt
+// shards. Other segments are copied as stored, without decoding; segment indices stay the
+// same, so encrypted columns remain valid. Written atomically like write_container.
+pub fn rewrite_container<F: Float>(
+    path: &Path,
+    replacements: &BTreeMap<usize, SegmentInput<F>>,
+    options: &WriteOptions,
+) -> io::Result<ContainerHeader> {
+    let source = fdlimit::open(path)?;
+    let source_len = source.metadata()?.len();
+    let read_at = file_reader(&source);
+    let old_header = read_header_with(&read_at, source_len)?;
+
+    let partial_path = path.with_extension("partial");
+    let (file, _permit) = fdlimit::create(&partial_path)?.into_parts();
+    let mut writer = BufWriter::with_capacity(options.buffer_size, DurableFile::new(file, options.durability)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    let mut offset = (MAGIC.len() + 4) as u64;
+
+    let mut header = ContainerHeader {
+        format_version: old_header.format_version.max(FORMAT_VERSION),
+        segments: Vec::with_capacity(old_header.segments.len()),
+    };
+    for (segment_index, segment) in old_header.segments.iter().enumerate() {
+        if let Some(replacement) = replacements.get(&segment_index) {
+            write_segments(&mut writer, &mut offset, std::slice::from_ref(replacement), options, &mut header, &mut |_, _| Ok(()))?;
+            continue;
+        }
+        let mut copied = segment.clone();
+        for column in &mut copied.columns {
+            writer.write_all(&read_at(column.offset, column.stored_len as usize)?)?;
+            column.offset = offset;
+            offset += column.stored_len;
+        }
+        header.segments.push(copied);
+    }
+    write_footer(&mut writer, &header)?;
+
+    writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+    durability::commit(&partial_path, path, options.durability)?;
+    Ok(header)
+}
+
+// Encode and write the columns of `segments`, adding their descriptors to `header`.
+// Segment indices (part of the encryption AAD) continue after the existing segments.
+// Segments are encoded a batch at a time, the batch's segments and their columns in
+// parallel (CPU), and written sequentially (I/O): one segment at a time, a file of many
+// small MS2 windows kept most threads idle. `written` is called after each segment.
+fn write_segments<W: Write, F: Float>(
+    writer: &mut W,
+    offset: &mut u64,
+    segments: &[SegmentInput<F>],
+    options: &WriteOptions,
+    header: &mut ContainerHeader,
+    written: &mut dyn FnMut(&mut W, &ContainerHeader) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut remaining = segments;
+    while !remaining.is_empty() {
+        if let Some(cancel) = options.cancel {
+            cancel.check()?;
+        }
+        let batch = &remaining[..encode_batch_len(remaining)];
+        remaining = &remaining[batch.len()..];
+        let first_index = header.segments.len();
+        // Threads left over when the batch has fewer columns than the pool has threads
+        // (a few big shards) go to zstd's workers for the columns compressed whole; not
+        // in deterministic mode, where the frames must not depend on the pool's size
+        let spare_threads = match options.deterministic {
+            true => 0,
+            false => rayon::current_num_threads() / (batch.len() * options.column_order.len()).max(1),
+        };
+        let encoded: Vec<Vec<EncodedColumn>> = batch.par_iter()
+            .enumerate()
+            .map(|(i, segment)| encode_segment(first_index + i, segment, options, spare_threads))
+            .collect::<io::Result<_>>()?;
+
+        for (segment, encoded) in batch.iter().zip(encoded) {
+            let codecs = segment.codecs.unwrap_or(&options.codecs);
+            let codec_of = |name: &str| codecs.get(name).copied().unwrap_or(ColumnCodec::Raw);
+            let encoding_of = |name: &str, shuffled: bool, blocked: bool| {
+                let codec = format!(
+                    "{}{}{}", codec_of(name), if shuffled { SHUFFLED } else { "" }, if blocked { BLOCKED } else { "" }
+                );
+                match options.encryption_key {
+                    Some(_) => format!("{}+{}", codec, encryption::ALGORITHM),
+                    None => codec,
+                }
+            };
+            let rows = segment.rows.len() as u64;
+            let mut columns = Vec::with_capacity(encoded.len());
+            for (name, dtype, quantization, nonce, blocks, bytes) in encoded {
+                writer.write_all(&bytes)?;
+                columns.push(ColumnDescriptor {
+                    name: name.to_string(),
+                    dtype: dtype.to_string(),
+                    encoding: encoding_of(name, shuffles(options, codec_of(name), dtype, quantization), blocks.is_some()),
+                    offset: *offset,
+                    stored_len: bytes.len() as u64,
+                    rows,
+                    required: true,
+                    key_id: options.encryption_key.map(|key| key.id.clone()),
+                    nonce: nonce.map(hex::encode),
+                    crc32: Some(crc32fast::hash(&bytes)),
+                    quantization,
+                    blocks,
+                });
+                *offset += bytes.len() as u64;
+            }
+
+            // Recorded m/z bounds must be those of the stored values, which quantization moves
+            let mut attrs = segment.attrs.clone();
+            if let Some(quantization) = columns.iter().find(|column| column.name == "mz_values").and_then(|column| column.quantization) {
+                for key in ["mz_min", "mz_max"] {
+                    if let Some(bound) = attrs.get_mut(key) {
+                        *bound = quantization.round_trip_mz(*bound as f32) as f64;
+                    }
+                }
+            }
+            header.segments.push(SegmentDescriptor {
+                name: segment.name.clone(),
+                attrs,
+                rows,
+                columns,
+            });
+            written(writer, header)?;
+        }
+    }
+    Ok(())
+}
+
+// Name, stored dtype, quantization, nonce (if encrypted and whole), blocks (if blocked)
+// and stored bytes of one column
+type EncodedColumn = (&'static str, &'static str, Option<Quantization>, Option<Vec<u8>>, Option<Vec<ColumnBlock>>, Vec<u8>);
+
+// Whether a column is byte-shuffled before compression: exact floats only, as quantized
+// columns are integer codes already delta-coded
+fn shuffles(options: &WriteOptions, codec: ColumnCodec, dtype: &str, quantization: Option<Quantization>) -> bool {
+    options.byte_shuffle && codec != ColumnCodec::Raw && quantization.is_none() && dtype.starts_with('f')
+}
+
+// Segments encoded together: one per thread, fewer if they would hold more than
+// ENCODE_BATCH_BYTES in memory (always at least one)
+fn encode_batch_len<F>(segments: &[SegmentInput<F>]) -> usize {
+    let mut bytes = 0;
+    let mut len = 0;
+    for segment in segments.iter().take(rayon::current_num_threads().max(1)) {
+        bytes += segment.rows.len() as u64 * POINT_BYTES;
+        if len > 0 && bytes > ENCODE_BATCH_BYTES {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+fn encode_segment<F: Float>(segment_index: usize, segment: &SegmentInput<F>, options: &WriteOptions, spare_threads: usize) -> io::Result<Vec<EncodedColumn>> {
+    let codecs = segment.codecs.unwrap_or(&options.codecs);
+    let codec_of = |name: &str| codecs.get(name).copied().unwrap_or(ColumnCodec::Raw);
+    options.column_order
+        .par_iter()
+        .map(|&name| -> io::Result<_> {
+            let dtype = INDEXED_COLUMNS.iter().find(|(n, _)| *n == name).unwrap().1;
+            let values = column_values(segment.data, name, segment.rows.clone()).unwrap();
+            let (quantization, stored) = match options.lossy.and_then(|lossy| quantize_column(&values, name, lossy)) {
+                Some((quantization, stored)) => (Some(quantization), stored),
+                None => (None, column_bytes(&values, options.precision)),
+            };
+            let dtype = match quantization {
+                Some(quantization) => quantization.stored_dtype(),
+                None if dtype == "f32" => options.precision.dtype(),
+                None => dtype,
+            };
+            let codec = codec_of(name);
+            let shuffled = shuffles(options, codec, dtype, quantization);
+            let filter = |bytes: &[u8]| match shuffled {
+                true => compression::byte_shuffle(bytes, options.precision.width()),
+                false => bytes.to_vec(),
+            };
+            let aad = column_aad(segment_index, &segment.name, name);
+            let seal = |bytes: Vec<u8>, aad: &[u8]| -> io::Result<(Option<Vec<u8>>, Vec<u8>)> {
+                match options.encryption_key {
+                    Some(key) => key.encrypt(&bytes, aad).map(|(nonce, ciphertext)| (Some(nonce), ciphertext)),
+                    None => Ok((None, bytes)),
+                }
+            };
+
+            // Quantized m/z is delta-coded across the whole column, so only exact columns are blocked
+            let block_rows = options.block_rows
+                .filter(|&block_rows| codec != ColumnCodec::Raw && quantization.is_none() && segment.rows.len() > block_rows.max(1));
+            let Some(block_rows) = block_rows else {
+                let codec = codec.with_spare_workers(stored.len(), spare_threads);
+                let stored = if shuffled { filter(&stored) } else { stored };
+                let (nonce, bytes) = seal(codec.compress(stored)?, &aad)?;
+                return Ok((name, dtype, quantization, nonce, None, bytes));
+            };
+            let width = stored.len() / segment.rows.len();
+            let encoded = stored.par_chunks(block_rows * width)
+                .enumerate()
+                .map(|(block_index, chunk)| -> io::Result<(ColumnBlock, Vec<u8>)> {
+                    let (nonce, bytes) = seal(codec.compress(filter(chunk))?, &block_aad(&aad, block_index))?;
+                    let first = block_index * block_rows;
+                    let (min, max) = value_bounds(&values, first..first + chunk.len() / width, options.precision);
+                    let block = ColumnBlock { rows: (chunk.len() / width) as u64, stored_len: bytes.len() as u64, nonce: nonce.map(hex::encode), min, max };
+                    Ok((block, bytes))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let (blocks, bytes): (Vec<ColumnBlock>, Vec<Vec<u8>>) = encoded.into_iter().unzip();
+            Ok((name, dtype, quantization, None, Some(blocks), bytes.concat()))
+        })
+        .collect()
+}
+
+// Smallest and largest of some rows of a column, as a reader will decode them
+fn value_bounds<F: Float>(values: &ColumnValues<F>, rows: Range<usize>, precision: Precision) -> (Option<f64>, Option<f64>) {
+    let decoded: Box<dyn Iterator<Item = f64> + '_> = match values {
+        ColumnValues::Float(v) => match precision {
+            Precision::F32 => Box::new(v[rows].iter().map(|value| value.to_f32() as f64)),
+            Precision::F64 => Box::new(v[rows].iter().map(|value| value.to_f64())),
+        },
+        ColumnValues::U32(v) => Box::new(v[rows].iter().map(|&value| value as f64)),
+    };
+    decoded.filter(|value| !value.is_nan())
+        .fold((None, None), |(min, max): (Option<f64>, Option<f64>), value| {
+            (Some(min.map_or(value, |min| min.min(value))), Some(max.map_or(value, |max| max.max(value))))
+        })
+}
+
+fn write_footer<W: Write>(writer: &mut W, header: &ContainerHeader) -> io::Result<()> {
+    let header_json = serde_json::to_vec(header)
+        .map_err(io::Error::other)?;
+    writer.write_all(&header_json)?;
+    writer.write_all(&(header_json.len() as u64).to_le_bytes())?;
+    writer.write_all(MAGIC)
+}
+
+pub fn is_container(path: &Path) -> bool {
+    let file = match fdlimit::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+    let mut magic = [0u8; 4];
+    read_exact_at(&file, &mut magic, 0).is_ok() && &magic == MAGIC
+}
+
+// Parse the footer through any positional reader (local pread or remote range request)
+pub fn read_header_with<F>(read_at: &F, file_len: u64) -> io::Result<ContainerHeader>
+where
+    F: Fn(u64, usize) -> io::Result<Vec<u8>>,
+{
+    let trailer_len = 8 + MAGIC.len() as u64;
+    if file_len < (MAGIC.len() as u64 + 4) + trailer_len {
+        return Err(invalid_data("file too small for a columnar cache".to_string()));
+    }
+
+    let trailer = read_at(file_len - trailer_len, trailer_len as usize)?;
+    if &trailer[8..] != MAGIC {
+        return Err(invalid_data("missing columnar cache footer".to_string()));
+    }
+    let header_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if header_len > file_len - trailer_len {
+        return Err(invalid_data("corrupt columnar cache footer".to_string()));
+    }
+
+    let header_bytes = read_at(file_len - trailer_len - header_len, header_len as usize)?;
+    let header: ContainerHeader = serde_json::from_slice(&header_bytes)
+        .map_err(|e| invalid_data(format!("unreadable column table: {}", e)))?;
+    if header.format_version > FORMAT_VERSION {
+        telemetry::warn(&format!("Note: cache written by a newer format version ({}), reading known columns only", header.format_version));
+    }
+    Ok(header)
+}
+
+fn is_supported(column: &ColumnDescriptor) -> bool {
+    let encryption_ok = match column.encoding.split_once('+') {
+        Some((_, algorithm)) => algorithm == encryption::ALGORITHM,
+        None => true,
+    };
+    let dtype_ok = |dtype: &str| match column.quantization {
+        None => column.dtype == dtype || (dtype == "f32" && column.dtype == "f64"),
+        Some(quantization) => column.dtype == quantization.stored_dtype() && quantization.value_dtype() == dtype,
+    };
+    INDEXED_COLUMNS.iter().any(|(name, dtype)| *name == column.name && dtype_ok(dtype))
+        && column.codec().is_some_and(|codec| codec.is_available())
+        && column.blocks.is_some() == column.base_encoding().ends_with(BLOCKED)
+        && encryption_ok
+}
+
+// Presence negotiation: every column this reader needs must be present, and every
+// column the writer marked required must be one this reader understands
+fn check_segment(segment: &SegmentDescriptor) -> io::Result<()> {
+    for column in &segment.columns {
+        if column.required && !is_supported(column) {
+            return Err(invalid_data(format!(
+                "segment {} requires column {} ({}/{}) which this reader cannot interpret",
+                segment.name, column.name, column.dtype, column.encoding
+            )));
+        }
+    }
+    for (name, _) in INDEXED_COLUMNS {
+        if !segment.columns.iter().any(|c| c.name == name && is_supported(c)) {
+            return Err(invalid_data(format!("segment {} is missing column {}", segment.name, name)));
+        }
+    }
+    Ok(())
+}
+
+// Integrity check of one column as stored: checksum if the writer recorded one, otherwise
+// whatever can be checked without the encryption key (length, codec structure)
+pub fn verify_stored_column<F>(read_at: &F, column: &ColumnDescriptor) -> io::Result<()>
+where
+    F: Fn(u64, usize) -> io::Result<Vec<u8>>,
+{
+    let bytes = read_at(column.offset, column.stored_len as usize)?;
+    if let Some(expected) = column.crc32 {
+        let actual = crc32fast::hash(&bytes);
+        if actual != expected {
+            return Err(invalid_data(format!("column {} checksum mismatch ({:08x} != {:08x})", column.name, actual, expected)));
+        }
+        return Ok(());
+    }
+    let expected = column.rows * column.stored_width() as u64;
+    match column.encoding.as_str() {
+        "raw" if bytes.len() as u64 != expected => {
+            Err(invalid_data(format!("column {} has {} bytes, expected {}", column.name, bytes.len(), expected)))
+        }
+        // Shuffling keeps the length, so the codec alone can be checked
+        encoding => match ColumnCodec::from_encoding(encoding.trim_end_matches(SHUFFLED)) {
+            // A registered codec this process lacks can't check anything
+            Some(codec) if codec != ColumnCodec::Raw && codec.is_available() => codec
+                .decompress(bytes, expected as usize)
+                .map(|_| ())
+                .map_err(|e| invalid_data(format!("column {}: {}", column.name, e))),
+            _ => Ok(()),
+        },
+    }
+}
+
+fn to_u32(raw: &[u8]) -> Vec<u32> {
+    raw.chunks_exact(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect()
+}
+
+// Decode little-endian values straight into their place in a preallocated column
+fn fill<T>(target: &mut [T], raw: &[u8], from_le: fn([u8; 4]) -> T) -> io::Result<()> {
+    if raw.len() != target.len() * 4 {
+        return Err(invalid_data(format!("column has {} bytes, expected {}", raw.len(), target.len() * 4)));
+    }
+    for (value, bytes) in target.iter_mut().zip(raw.chunks_exact(4)) {
+        *value = from_le(bytes.try_into().unwrap());
+    }
+    Ok(())
+}
+
+// fill for a float column of either stored width
+fn fill_floats<T: Float>(target: &mut [T], raw: &[u8], stored: Precision) -> io::Result<()> {
+    if raw.len() != target.len() * stored.width() {
+        return Err(invalid_data(format!("column has {} bytes, expected {}", raw.len(), target.len() * stored.width())));
+    }
+    for (value, decoded) in target.iter_mut().zip(precision::floats_from_le(raw, stored)) {
+        *value = decoded;
+    }
+    Ok(())
+}
+
+// The memory of a column of plain numbers as bytes, to decode into in place.
+// Safety: every bit pattern must be a valid T.
+unsafe fn bytes_of_mut<T: Copy>(values: &mut [T]) -> &mut [u8] {
+    std::slice::from_raw_parts_mut(values.as_mut_ptr() as *mut u8, std::mem::size_of_val(values))
+}
+
+// The rows of one segment within preallocated output columns
+struct RowsMut<'a, T> {
+    rt_values_min: &'a mut [T],
+    mobility_values: &'a mut [T],
+    mz_values: &'a mut [T],
+    intensity_values: &'a mut [u32],
+    frame_indices: &'a mut [u32],
+    scan_indices: &'a mut [u32],
+}
+
+// Cut a column into consecutive disjoint slices of the given lengths
+fn split_rows<'a, T>(mut column: &'a mut [T], rows: &[usize]) -> Vec<&'a mut [T]> {
+    rows.iter()
+        .map(|&len| {
+            let (head, tail) = std::mem::take(&mut column).split_at_mut(len);
+            column = tail;
+            head
+        })
+        .collect()
+}
+
+fn rows_mut<'a, T>(data: &'a mut IndexedTimsTOFData<T>, rows: &[usize]) -> Vec<RowsMut<'a, T>> {
+    let mut rt_values_min = split_rows(&mut data.rt_values_min, rows).into_iter();
+    let mut mobility_values = split_rows(&mut data.mobility_values, rows).into_iter();
+    let mut mz_values = split_rows(&mut data.mz_values, rows).into_iter();
+    let mut intensity_values = split_rows(&mut data.intensity_values, rows).into_iter();
+    let mut frame_indices = split_rows(&mut data.frame_indices, rows).into_iter();
+    let mut scan_indices = split_rows(&mut data.scan_indices, rows).into_iter();
+    rows.iter()
+        .map(|_| RowsMut {
+            rt_values_min: rt_values_min.next().unwrap(),
+            mobility_values: mobility_values.next().unwrap(),
+            mz_values: mz_values.next().unwrap(),
+            intensity_values: intensity_values.next().unwrap(),
+            frame_indices: frame_indices.next().unwrap(),
+            scan_indices: scan_indices.next().unwrap(),
+        })
+        .collect()
+}
+
+// Positional reader over one container: a local file (pread) or a remote store (ranges)
+pub struct ContainerReader<'k, F> {
+    read_at: F,
+    pub header: ContainerHeader,
+    key: Option<&'k EncryptionKey>,
+}
+
+impl<'k, F> ContainerReader<'k, F>
+where
+    F: Fn(u64, usize) -> io::Result<Vec<u8>> + Sync,
+{
+    pub fn open(read_at: F, file_len: u64, key: Option<&'k EncryptionKey>) -> io::Result<Self> {
+        let header = read_header_with(&read_at, file_len)?;
+        Ok(Self { read_at, header, key })
+    }
+
+    fn segment_descriptor(&self, segment_index: usize) -> io::Result<&SegmentDescriptor> {
+        self.header.segments.get(segment_index)
+            .ok_or_else(|| invalid_data(format!("segment {} out of range", segment_index)))
+    }
+
+    // Stored bytes → decrypted → decompressed → dequantized little-endian values. Blocked
+    // columns are decoded block by block, in parallel.
+    fn decode_column(&self, segment_index: usize, column: &ColumnDescriptor) -> io::Result<Vec<u8>> {
+        if column.blocks.is_some() {
+            return self.column_rows(segment_index, column, 0, column.rows);
+        }
+        let bytes = (self.read_at)(column.offset, column.stored_len as usize)?;
+        self.decode_stored(segment_index, column, bytes, column.nonce.as_deref(), None, column.rows)
+    }
+
+    // Stored bytes of a whole column, or of block `block` of it, holding `rows` rows
+    fn decode_stored(
+        &self,
+        segment_index: usize,
+        column: &ColumnDescriptor,
+        mut bytes: Vec<u8>,
+        nonce: Option<&str>,
+        block: Option<usize>,
+        rows: u64,
+    ) -> io::Result<Vec<u8>> {
+        if column.is_encrypted() {
+            bytes = self.decrypt(segment_index, column, &bytes, nonce, block)?;
+        }
+
+        let expected = rows as usize * column.stored_width();
+        if let Some(codec) = column.codec() {
+            bytes = codec.decompress(bytes, expected)
+                .map_err(|e| invalid_data(format!("column {}: {}", column.name, e)))?;
+        }
+        if bytes.len() != expected {
+            return Err(invalid_data(format!("column {} has {} bytes, expected {}", column.name, bytes.len(), expected)));
+        }
+        if column.is_shuffled() {
+            let mut unshuffled = vec![0u8; expected];
+            compression::byte_unshuffle_into(&bytes, column.stored_width(), &mut unshuffled)?;
+            bytes = unshuffled;
+        }
+        Ok(match column.quantization {
+            Some(quantization) => quantization.dequantize(&bytes),
+            None => bytes,
+        })
+    }
+
+    fn decrypt(&self, segment_index: usize, column: &ColumnDescriptor, bytes: &[u8], nonce: Option<&str>, block: Option<usize>) -> io::Result<Vec<u8>> {
+        let segment = self.segment_descriptor(segment_index)?;
+        let key_id = column.key_id.as_deref().unwrap_or("");
+        let key = self.key
+            .filter(|key| key.id == key_id)
+            .ok_or_else(|| invalid_data(format!(
+                "cache is encrypted with key '{}'; set it in CacheConfig or {}", key_id, encryption::KEY_ENV
+            )))?;
+        let nonce = hex::decode(nonce.unwrap_or(""))
+            .map_err(|e| invalid_data(format!("bad nonce for column {}: {}", column.name, e)))?;
+        let aad = column_aad(segment_index, &segment.name, &column.name);
+        match block {
+            Some(block_index) => key.decrypt(&nonce, bytes, &block_aad(&aad, block_index)),
+            None => key.decrypt(&nonce, bytes, &aad),
+        }
+    }
+
+    // decode_stored for an exact column, decompressing straight into `out`
+    fn decode_stored_into(
+        &self,
+        segment_index: usize,
+        column: &ColumnDescriptor,
+        stored: &[u8],
+        nonce: Option<&str>,
+        block: Option<usize>,
+        out: &mut [u8],
+    ) -> io::Result<()> {
+        let decrypted;
+        let stored = match column.is_encrypted() {
+            true => {
+                decrypted = self.decrypt(segment_index, column, stored, nonce, block)?;
+                &decrypted[..]
+            }
+            false => stored,
+        };
+        let codec = column.codec().unwrap_or(ColumnCodec::Raw);
+        let decoded = match column.is_shuffled() {
+            true => codec.decompress(stored.to_vec(), out.len())
+                .and_then(|shuffled| compression::byte_unshuffle_into(&shuffled, column.stored_width(), out)),
+            false => codec.decompress_into(stored, out),
+        };
+        decoded.map_err(|e| invalid_data(format!("column {}: {}", column.name, e)))
+    }
+
+    // A whole column decoded into its place in a preallocated output. When the stored
+    // values are the output's in-memory layout (exact, same width, little-endian target)
+    // they are decompressed directly into it, block by block in parallel for blocked
+    // columns, so the only buffer besides the output is the stored bytes; otherwise the
+    // column is decoded to bytes and converted by `convert`.
+    fn decode_column_into<T: Copy>(
+        &self,
+        segment_index: usize,
+        column: &ColumnDescriptor,
+        target: &mut [T],
+        convert: impl Fn(&mut [T], &[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let in_place = cfg!(target_endian = "little")
+            && column.quantization.is_none()
+            && column.stored_width() == std::mem::size_of::<T>()
+            && target.len() as u64 == column.rows;
+        if !in_place {
+            return convert(target, &self.decode_column(segment_index, column)?);
+        }
+        // SAFETY: only called with u32 and Float (f32, f64) columns, for which any bytes
+        // are a valid value
+        let out = unsafe { bytes_of_mut(target) };
+        let Some(blocks) = &column.blocks else {
+            let stored = (self.read_at)(column.offset, column.stored_len as usize)?;
+            return self.decode_stored_into(segment_index, column, &stored, column.nonce.as_deref(), None, out);
+        };
+        let width = column.stored_width();
+        let block_bytes: Vec<usize> = blocks.iter().map(|block| block.rows as usize * width).collect();
+        if block_bytes.iter().sum::<usize>() != out.len() {
+            return Err(invalid_data(format!("blocks of column {} don't cover its rows", column.name)));
+        }
+        let spans = column.block_spans();
+        let Some(last) = blocks.len().checked_sub(1) else {
+            return Ok(());
+        };
+        let read_start = spans[0].1;
+        let stored = (self.read_at)(read_start, (spans[last].1 + blocks[last].stored_len - read_start) as usize)?;
+        split_rows(out, &block_bytes)
+            .into_par_iter()
+            .enumerate()
+            .try_for_each(|(i, out)| {
+                let from = (spans[i].1 - read_start) as usize;
+                let bytes = &stored[from..from + blocks[i].stored_len as usize];
+                self.decode_stored_into(segment_index, column, bytes, blocks[i].nonce.as_deref(), Some(i), out)
+            })
+    }
+
+    // Decoded values of rows [start, start + rows) of one column. Plain raw columns are
+    // sliced on disk, blocked columns read and decode just the blocks holding the rows (in
+    // one read), anything else is decoded whole, then cut.
+    fn column_rows(&self, segment_index: usize, column: &ColumnDescriptor, start: u64, rows: u64) -> io::Result<Vec<u8>> {
+        let width = column.decoded_width() as u64;
+        if column.is_sliceable() {
+            return (self.read_at)(column.offset + start * width, (rows * width) as usize);
+        }
+        let Some(blocks) = &column.blocks else {
+            let raw = self.decode_column(segment_index, column)?;
+            return Ok(raw[(start * width) as usize..((start + rows) * width) as usize].to_vec());
+        };
+        let end = start + rows;
+        let spans = column.block_spans();
+        let needed: Vec<usize> = (0..blocks.len())
+            .filter(|&i| spans[i].0.start < end && spans[i].0.end > start)
+            .collect();
+        let (Some(&first), Some(&last)) = (needed.first(), needed.last()) else {
+            return Ok(Vec::new());
+        };
+        let read_start = spans[first].1;
+        let stored = (self.read_at)(read_start, (spans[last].1 + blocks[last].stored_len - read_start) as usize)?;
+        let decoded = needed.par_iter()
+            .map(|&i| {
+                let from = (spans[i].1 - read_start) as usize;
+                let bytes = stored[from..from + blocks[i].stored_len as usize].to_vec();
+                let raw = self.decode_stored(segment_index, column, bytes, blocks[i].nonce.as_deref(), Some(i), blocks[i].rows)?;
+                let (rows, block_start) = (&spans[i].0, spans[i].0.start);
+                let cut = (start.max(rows.start) - block_start) * width..(end.min(rows.end) - block_start) * width;
+                Ok(raw[cut.start as usize..cut.end as usize].to_vec())
+            })
+            .collect::<io::Result<Vec<Vec<u8>>>>()?;
+        let bytes = decoded.concat();
+        if bytes.len() as u64 != rows * width {
+            return Err(invalid_data(format!("blocks of column {} don't cover its rows", column.name)));
+        }
+        Ok(bytes)
+    }
+
+    // Rows [start, end) whose values of a column sorted ascending lie in [low, high]. Raw
+    // columns are binary-searched on disk; blocked ones decode only the blocks whose bounds
+    // overlap, others are decoded whole.
+    fn sorted_range(&self, segment_index: usize, name: &str, low: f64, high: f64) -> io::Result<(u64, u64)> {
+        let segment = self.segment_descriptor(segment_index)?;
+        let column = segment.columns.iter().find(|c| c.name == name && is_supported(c)).unwrap();
+        let is_float = INDEXED_COLUMNS.iter().any(|&(n, dtype)| n == name && dtype == "f32");
+        let values = |bytes: &[u8]| -> Vec<f64> {
+            match is_float {
+                true => precision::floats_from_le(bytes, column.decoded_precision()),
+                false => to_u32(bytes).into_iter().map(f64::from).collect(),
+            }
+        };
+
+        if column.is_sliceable() {
+            let width = column.decoded_width() as u64;
+            let value_at = |i: u64| -> io::Result<f64> {
+                Ok(values(&(self.read_at)(column.offset + width * i, width as usize)?)[0])
+            };
+            let partition_point = |pred: &dyn Fn(f64) -> bool| -> io::Result<u64> {
+                let (mut lo, mut hi) = (0u64, segment.rows);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if pred(value_at(mid)?) { lo = mid + 1; } else { hi = mid; }
+                }
+                Ok(lo)
+            };
+            let start = partition_point(&|value| value < low)?;
+            return Ok((start, partition_point(&|value| value <= high)?.max(start)));
+        }
+
+        // Sorted, so the overlapping blocks are consecutive
+        let (first, last) = match &column.blocks {
+            Some(blocks) => {
+                let spans = column.block_spans();
+                let overlapping: Vec<usize> = (0..blocks.len()).filter(|&i| blocks[i].overlaps(low, high)).collect();
+                match (overlapping.first(), overlapping.last()) {
+                    (Some(&first), Some(&last)) => (spans[first].0.start, spans[last].0.end),
+                    _ => return Ok((0, 0)),
+                }
+            }
+            None => (0, segment.rows),
+        };
+        let decoded = values(&self.column_rows(segment_index, column, first, last - first)?);
+        let start = decoded.partition_point(|&x| x < low) as u64;
+        Ok((first + start, first + (decoded.partition_point(|&x| x <= high) as u64).max(start)))
+    }
+
+    // Decode rows [start, start + rows) of one segment
+    fn segment_rows<T: Float>(&self, segment_index: usize, start: u64, rows: u64) -> io::Result<IndexedTimsTOFData<T>> {
+        let segment = self.segment_descriptor(segment_index)?;
+        check_segment(segment)?;
+        let start_time = Instant::now();
+
+        let column = |name: &str| segment.columns.iter().find(|c| c.name == name && is_supported(c)).unwrap();
+        let column_bytes = |name: &str| self.column_rows(segment_index, column(name), start, rows);
+        let floats = |name: &str| -> io::Result<Vec<T>> {
+            Ok(precision::floats_from_le(&column_bytes(name)?, column(name).decoded_precision()))
+        };
+
+        let data = IndexedTimsTOFData {
+            rt_values_min: floats("rt_values_min")?,
+            mobility_values: floats("mobility_values")?,
+            mz_values: floats("mz_values")?,
+            intensity_values: to_u32(&column_bytes("intensity_values")?),
+            frame_indices: to_u32(&column_bytes("frame_indices")?),
+            scan_indices: to_u32(&column_bytes("scan_indices")?),
+        };
+        telemetry::record_shard_load(&segment.name, start_time.elapsed());
+        Ok(data)
+    }
+
+    // Checksums (or structure) of every stored column of one segment
+    pub fn verify_segment(&self, segment_index: usize) -> io::Result<()> {
+        for column in &self.segment_descriptor(segment_index)?.columns {
+            verify_stored_column(&self.read_at, column)?;
+        }
+        Ok(())
+    }
+
+    // Only the m/z column of one segment, e.g. to check its order without decoding the rest
+    pub fn segment_mz(&self, segment_index: usize) -> io::Result<Vec<f32>> {
+        let segment = self.segment_descriptor(segment_index)?;
+        check_segment(segment)?;
+        let column = segment.column("mz_values").unwrap();
+        Ok(precision::floats_from_le(&self.decode_column(segment_index, column)?, column.decoded_precision()))
+    }
+
+    pub fn segment<T: Float>(&self, segment_index: usize) -> io::Result<IndexedTimsTOFData<T>> {
+        let rows = self.segment_descriptor(segment_index)?.rows;
+        self.segment_rows(segment_index, 0, rows)
+    }
+
+    // Rows with m/z in [mz_min, mz_max]. With plain raw encoding only the m/z probes and
+    // the matching row range are transferred, which is what makes remote range loads cheap;
+    // blocked columns transfer and decompress only the blocks holding those rows.
+    pub fn segment_mz_range<T: Float>(&self, segment_index: usize, mz_min: f32, mz_max: f32) -> io::Result<IndexedTimsTOFData<T>> {
+        self.segment_region(segment_index, mz_min, mz_max, None)
+    }
+
+    // segment_mz_range, leaving out whole blocks whose recorded RT bounds miss `rt`. Rows
+    // of the blocks read are not filtered by RT; callers do that.
+    pub fn segment_region<T: Float>(&self, segment_index: usize, mz_min: f32, mz_max: f32, rt: Option<(f32, f32)>) -> io::Result<IndexedTimsTOFData<T>> {
+        check_segment(self.segment_descriptor(segment_index)?)?;
+        let (start, end) = self.sorted_range(segment_index, "mz_values", mz_min as f64, mz_max as f64)?;
+        let rt_column = self.segment_descriptor(segment_index)?.column("rt_values_min").filter(|column| column.blocks.is_some());
+        let (Some((rt_min, rt_max)), Some(rt_column)) = (rt, rt_column) else {
+            return self.segment_rows(segment_index, start, end - start);
+        };
+
+        // Row ranges of the RT blocks that may match, within the m/z rows, adjacent ones joined
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for (block, (rows, _)) in rt_column.blocks.iter().flatten().zip(rt_column.block_spans()) {
+            let rows = rows.start.max(start)..rows.end.min(end);
+            if rows.is_empty() || !block.overlaps(rt_min as f64, rt_max as f64) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(previous) if previous.end == rows.start => previous.end = rows.end,
+                _ => ranges.push(rows),
+            }
+        }
+        if ranges.is_empty() {
+            return self.segment_rows(segment_index, 0, 0);
+        }
+        let parts = ranges.iter()
+            .map(|rows| self.segment_rows(segment_index, rows.start, rows.end - rows.start))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(concat_segments(parts))
+    }
+
+    // Rows of `frame` in a segment sorted by frame (frames.rs), found by binary search
+    // over the frame column; as with m/z ranges, only those rows are decoded where the
+    // encoding allows
+    pub fn segment_frame<T: Float>(&self, segment_index: usize, frame: u32) -> io::Result<IndexedTimsTOFData<T>> {
+        check_segment(self.segment_descriptor(segment_index)?)?;
+        let (start, end) = self.sorted_range(segment_index, "frame_indices", frame as f64, frame as f64)?;
+        self.segment_rows(segment_index, start, end - start)
+    }
+
+    pub fn ms1_shards(&self) -> usize {
+        self.header.segments.iter().filter(|segment| segment.name == "ms1").count()
+    }
+
+    // MS1 rows with m/z in [mz_min, mz_max] across all MS1 shards, skipping those whose
+    // recorded m/z bounds don't overlap. Returns the data and the number of shards read.
+    pub fn mz_range<T: Float>(&self, mz_min: f32, mz_max: f32) -> io::Result<(IndexedTimsTOFData<T>, usize)> {
+        self.region(mz_min, mz_max, None)
+    }
+
+    // mz_range, also skipping blocks outside `rt` (segment_region)
+    pub fn region<T: Float>(&self, mz_min: f32, mz_max: f32, rt: Option<(f32, f32)>) -> io::Result<(IndexedTimsTOFData<T>, usize)> {
+        let overlapping: Vec<usize> = self.header.segments.iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.name == "ms1")
+            .filter(|(_, segment)| {
+                let below = segment.attrs.get("mz_max").is_some_and(|&max| max < mz_min as f64);
+                let above = segment.attrs.get("mz_min").is_some_and(|&min| min > mz_max as f64);
+                !below && !above
+            })
+            .map(|(segment_index, _)| segment_index)
+            .collect();
+        let parts = overlapping.par_iter()
+            .map(|&segment_index| self.segment_region(segment_index, mz_min, mz_max, rt))
+            .collect::<io::Result<Vec<_>>>()?;
+        if parts.is_empty() {
+            let empty = IndexedTimsTOFData {
+                rt_values_min: Vec::new(),
+                mobility_values: Vec::new(),
+                mz_values: Vec::new(),
+                intensity_values: Vec::new(),
+                frame_indices: Vec::new(),
+                scan_indices: Vec::new(),
+            };
+            return Ok((empty, 0));
+        }
+        Ok((merge_by_mz(parts), overlapping.len()))
+    }
+
+    // Segments merged into one dataset sorted by m/z. Every output column is allocated once
+    // at its exact size (the row counts in the footer) and each segment is decoded by its
+    // own worker straight into its disjoint slice, so loading scales with cores instead of
+    // being bounded by a serial merge. Only segments overlapping in m/z (appended by
+    // incremental updates) still need a sort afterwards.
+    pub fn merged_segments<T: Float>(&self, segment_indices: &[usize], cancel: Option<&CancellationToken>) -> io::Result<IndexedTimsTOFData<T>> {
+        let rows = segment_indices.iter()
+            .map(|&segment_index| Ok(self.segment_descriptor(segment_index)?.rows as usize))
+            .collect::<io::Result<Vec<usize>>>()?;
+        let data = self.concatenated_segments(segment_indices, cancel)?;
+        let mut ordered = true;
+        let mut previous_last: Option<T> = None;
+        let mut start = 0;
+        for &len in rows.iter().filter(|&&len| len > 0) {
+            ordered &= previous_last.is_none_or(|last| last <= data.mz_values[start]);
+            previous_last = Some(data.mz_values[start + len - 1]);
+            start += len;
+        }
+        Ok(if ordered { data } else { sort_by_mz(&data) })
+    }
+
+    // Segments one after the other in stored order, decoded in parallel as above; for
+    // layouts not sorted by m/z (layout.rs)
+    pub fn concatenated_segments<T: Float>(&self, segment_indices: &[usize], cancel: Option<&CancellationToken>) -> io::Result<IndexedTimsTOFData<T>> {
+        let rows = segment_indices.iter()
+            .map(|&segment_index| Ok(self.segment_descriptor(segment_index)?.rows as usize))
+            .collect::<io::Result<Vec<usize>>>()?;
+        let total: usize = rows.iter().sum();
+        let zero = T::from_f32(0.0);
+        let mut data = IndexedTimsTOFData {
+            rt_values_min: vec![zero; total],
+            mobility_values: vec![zero; total],
+            mz_values: vec![zero; total],
+            intensity_values: vec![0; total],
+            frame_indices: vec![0; total],
+            scan_indices: vec![0; total],
+        };
+        rows_mut(&mut data, &rows)
+            .into_par_iter()
+            .zip(segment_indices.par_iter())
+            .try_for_each(|(target, &segment_index)| {
+                if let Some(cancel) = cancel {
+                    cancel.check()?;
+                }
+                self.decode_segment_into(segment_index, target)
+            })?;
+        Ok(data)
+    }
+
+    fn decode_segment_into<T: Float>(&self, segment_index: usize, target: RowsMut<'_, T>) -> io::Result<()> {
+        let segment = self.segment_descriptor(segment_index)?;
+        check_segment(segment)?;
+        let start_time = Instant::now();
+        let column = |name: &str| segment.columns.iter().find(|c| c.name == name && is_supported(c)).unwrap();
+        let floats = |target: &mut [T], name: &str| {
+            let precision = column(name).decoded_precision();
+            self.decode_column_into(segment_index, column(name), target, |target, raw| fill_floats(target, raw, precision))
+        };
+        let ints = |target: &mut [u32], name: &str| {
+            self.decode_column_into(segment_index, column(name), target, |target, raw| fill(target, raw, u32::from_le_bytes))
+        };
+        floats(target.rt_values_min, "rt_values_min")?;
+        floats(target.mobility_values, "mobility_values")?;
+        floats(target.mz_values, "mz_values")?;
+        ints(target.intensity_values, "intensity_values")?;
+        ints(target.frame_indices, "frame_indices")?;
+        ints(target.scan_indices, "scan_indices")?;
+        telemetry::record_shard_load(&segment.name, start_time.elapsed());
+        Ok(())
+    }
+}
+
+pub fn file_reader(file: &File) -> impl Fn(u64, usize) -> io::Result<Vec<u8>> + Sync + '_ {
+    move |offset: u64, len: usize| {
+        let mut buffer = vec![0u8; len];
+        read_exact_at(file, &mut buffer, offset)?;
+        Ok(buffer)
+    }
+}
+
+#[cfg(unix)]
+pub fn read_exact_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buffer, offset)
+}
+
+#[cfg(windows)]
+pub fn read_exact_at(file: &File, buffer: &mut [u8], mut offset: u64) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = std::os::windows::fs::FileExt::seek_read(file, &mut buffer[filled..], offset)?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        filled += read;
+        offset += read as u64;
+    }
+    Ok(())
+}
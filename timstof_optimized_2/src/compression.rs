@@ -0,0 +1,402 @@
+// File: src/compression.rs
+//
+// Column codecs of the columnar container, and adaptive codec selection: instead of a
+// fixed per-file choice, a sample of every column is compressed with each codec and the
+// cheapest one that pays off is picked per column. Exact float columns can be byte-shuffled
+// before compression (byte_shuffle, CacheConfig::byte_shuffle).
+//
+// Other crates can add codecs of their own (e.g. Blosc, bitshuffle + lz4) by implementing
+// Codec and registering it under an id (register_codec), then selecting it with
+// CompressionType::Custom. Columns it compressed are stored with the encoding
+// "custom-<id>" in the header of their shard, so a reader needs the same codec registered
+// under the same id, and rejects the shard as one it cannot interpret otherwise.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Instant;
+use zstd::zstd_safe::{CParameter, DParameter};
+
+use crate::precision::{self, Float, Precision};
+use crate::utils::IndexedTimsTOFData;
+
+// Codec used for compressed cache files (CacheConfig::compression)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompressionType {
+    #[default]
+    Lz4, // lz4 block format, which has no levels
+    Zstd {
+        level: i32,              // 1..=22
+        long_mode: bool,         // Long-distance matching: finds matches up to the window apart
+        workers: u32,            // zstd's own compression threads for one column; 0 compresses on the calling thread
+        window_log: Option<u32>, // log2 of the match window, 10..=30; None is the level's default (27 in long mode)
+    },
+    Custom { id: u16 }, // A codec registered with register_codec
+}
+
+impl CompressionType {
+    pub const fn zstd(level: i32) -> Self {
+        CompressionType::Zstd { level, long_mode: false, workers: 0, window_log: None }
+    }
+
+    // Smallest caches: for archival copies that are written once and rarely loaded
+    pub fn archival() -> Self {
+        CompressionType::Zstd { level: 19, long_mode: true, workers: 0, window_log: None }
+    }
+
+    pub fn codec(&self) -> ColumnCodec {
+        match *self {
+            CompressionType::Lz4 => ColumnCodec::Lz4,
+            CompressionType::Zstd { level, long_mode, workers, window_log } => ColumnCodec::Zstd { level, long_mode, workers, window_log },
+            CompressionType::Custom { id } => ColumnCodec::Custom(id),
+        }
+    }
+}
+
+// A compression codec supplied by the caller. compress and decompress must be inverses
+// and stable across versions: the id is all a cache records of the codec.
+pub trait Codec: Send + Sync {
+    fn id(&self) -> u16;
+    fn name(&self) -> &str; // For logs and errors
+    fn compress(&self, raw: &[u8]) -> io::Result<Vec<u8>>;
+    fn decompress(&self, stored: &[u8], raw_len: usize) -> io::Result<Vec<u8>>;
+}
+
+type CodecRegistry = BTreeMap<u16, Arc<dyn Codec>>;
+
+// Registered codecs by id, for the whole process
+static CODECS: OnceLock<RwLock<CodecRegistry>> = OnceLock::new();
+
+fn codec_registry() -> &'static RwLock<CodecRegistry> {
+    CODECS.get_or_init(Default::default)
+}
+
+// Make a codec available to every cache in the process under its id. Registering the same
+// codec again is a no-op; an id taken by a codec of another name is refused, as caches
+// written with one would be decoded with the other.
+pub fn register_codec(codec: Arc<dyn Codec>) -> io::Result<()> {
+    let mut codecs = codec_registry().write().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = codecs.get(&codec.id()) {
+        if existing.name() != codec.name() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("codec id {} is already registered to {}, not {}", codec.id(), existing.name(), codec.name()),
+            ));
+        }
+    }
+    codecs.insert(codec.id(), codec);
+    Ok(())
+}
+
+pub fn registered_codec(id: u16) -> io::Result<Arc<dyn Codec>> {
+    let codecs = codec_registry().read().unwrap_or_else(|e| e.into_inner());
+    codecs.get(&id).cloned().ok_or_else(|| io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("codec custom-{} is not registered (register_codec)", id),
+    ))
+}
+
+// Ids and names of the registered codecs
+pub fn registered_codecs() -> Vec<(u16, String)> {
+    let codecs = codec_registry().read().unwrap_or_else(|e| e.into_inner());
+    codecs.iter().map(|(id, codec)| (*id, codec.name().to_string())).collect()
+}
+
+// Codec of each column of a segment, by column name
+pub type CodecMap = BTreeMap<&'static str, ColumnCodec>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnCodec {
+    Raw,                                  // Little-endian values; the only codec that can be sliced on disk
+    Lz4,                                  // lz4 block, very fast to decode
+    Zstd { level: i32, long_mode: bool, workers: u32, window_log: Option<u32> }, // zstd frame, smaller but slower to decode
+    Custom(u16),                          // Registered codec (register_codec) with this id
+}
+
+impl ColumnCodec {
+    pub const fn zstd(level: i32) -> Self {
+        ColumnCodec::Zstd { level, long_mode: false, workers: 0, window_log: None }
+    }
+
+    // Name of the kind of codec; the column encoding in the container header, except for
+    // registered codecs, whose encoding adds their id (Display)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnCodec::Raw => "raw",
+            ColumnCodec::Lz4 => "lz4",
+            ColumnCodec::Zstd { .. } => "zstd",
+            ColumnCodec::Custom(_) => "custom",
+        }
+    }
+
+    // Encoding plus the settings it was written with, for metadata and logs
+    pub fn label(&self) -> String {
+        match *self {
+            // Workers don't change what is written, only how fast
+            ColumnCodec::Zstd { level, long_mode, window_log, .. } => format!(
+                "zstd-{}{}{}", level, if long_mode { "-long" } else { "" }, window_log.map(|log| format!("-w{}", log)).unwrap_or_default()
+            ),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn from_encoding(encoding: &str) -> Option<Self> {
+        match encoding {
+            "raw" => Some(ColumnCodec::Raw),
+            "lz4" => Some(ColumnCodec::Lz4),
+            // Levels only matter when writing
+            "zstd" => Some(ColumnCodec::zstd(DEFAULT_ZSTD_LEVEL)),
+            _ => encoding.strip_prefix("custom-")?.parse().ok().map(ColumnCodec::Custom),
+        }
+    }
+
+    // Whether this process can decode it: registered codecs only once registered
+    pub fn is_available(&self) -> bool {
+        match self {
+            ColumnCodec::Custom(id) => registered_codec(*id).is_ok(),
+            _ => true,
+        }
+    }
+
+    // This codec with `threads` zstd workers for an input of `len` bytes, when it is zstd
+    // without workers of its own and the input is big enough to split. The output is the
+    // same kind of frame, so readers can't tell; lz4 blocks can't be split like this.
+    pub fn with_spare_workers(self, len: usize, threads: usize) -> Self {
+        match self {
+            ColumnCodec::Zstd { level, long_mode, workers: 0, window_log } if len >= PARALLEL_MIN_BYTES && threads > 1 => {
+                ColumnCodec::Zstd { level, long_mode, workers: threads as u32, window_log }
+            }
+            codec => codec,
+        }
+    }
+
+    pub fn compress(&self, raw: Vec<u8>) -> io::Result<Vec<u8>> {
+        match *self {
+            ColumnCodec::Raw => Ok(raw),
+            ColumnCodec::Lz4 => Ok(lz4_flex::block::compress(&raw)),
+            ColumnCodec::Zstd { level, long_mode: false, workers: 0, window_log: None } => zstd::bulk::compress(&raw, level),
+            ColumnCodec::Zstd { level, long_mode, workers, window_log } => {
+                let mut compressor = zstd::bulk::Compressor::new(level)?;
+                if long_mode {
+                    compressor.set_parameter(CParameter::EnableLongDistanceMatching(true))?;
+                }
+                // 128 MB by default in long mode: the largest any zstd decoder accepts without opting in
+                if let Some(window_log) = window_log.or(long_mode.then_some(LONG_WINDOW_LOG)) {
+                    compressor.set_parameter(CParameter::WindowLog(window_log))?;
+                }
+                if workers > 0 {
+                    compressor.set_parameter(CParameter::NbWorkers(workers))?;
+                }
+                compressor.compress(&raw)
+            }
+            ColumnCodec::Custom(id) => registered_codec(id)?.compress(&raw),
+        }
+    }
+
+    pub fn decompress(&self, stored: Vec<u8>, raw_len: usize) -> io::Result<Vec<u8>> {
+        match self {
+            ColumnCodec::Raw => Ok(stored),
+            ColumnCodec::Lz4 => lz4_flex::block::decompress(&stored, raw_len)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            ColumnCodec::Zstd { .. } => {
+                // Accept the windows window_log allows, beyond the decoder's default limit
+                let mut decompressor = zstd::bulk::Decompressor::new()?;
+                decompressor.set_parameter(DParameter::WindowLogMax(MAX_WINDOW_LOG))?;
+                decompressor.decompress(&stored, raw_len)
+            }
+            ColumnCodec::Custom(id) => {
+                let raw = registered_codec(*id)?.decompress(&stored, raw_len)?;
+                match raw.len() == raw_len {
+                    true => Ok(raw),
+                    false => Err(io::Error::new(io::ErrorKind::InvalidData, format!("decoded {} bytes, expected {}", raw.len(), raw_len))),
+                }
+            }
+        }
+    }
+
+    // decompress into a caller's buffer of exactly the raw length, e.g. the output column
+    // itself, instead of a new Vec
+    pub fn decompress_into(&self, stored: &[u8], raw: &mut [u8]) -> io::Result<()> {
+        let written = match self {
+            ColumnCodec::Raw => {
+                if stored.len() == raw.len() {
+                    raw.copy_from_slice(stored);
+                }
+                stored.len()
+            }
+            ColumnCodec::Lz4 => lz4_flex::block::decompress_into(stored, raw)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            ColumnCodec::Zstd { .. } => {
+                let mut decompressor = zstd::bulk::Decompressor::new()?;
+                decompressor.set_parameter(DParameter::WindowLogMax(MAX_WINDOW_LOG))?;
+                decompressor.decompress_to_buffer(stored, raw)?
+            }
+            ColumnCodec::Custom(id) => {
+                let decoded = registered_codec(*id)?.decompress(stored, raw.len())?;
+                if decoded.len() == raw.len() {
+                    raw.copy_from_slice(&decoded);
+                }
+                decoded.len()
+            }
+        };
+        if written != raw.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("decoded {} bytes, expected {}", written, raw.len())));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ColumnCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnCodec::Custom(id) => write!(f, "custom-{}", id),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const LONG_WINDOW_LOG: u32 = 27;
+pub const MAX_WINDOW_LOG: u32 = 30;
+
+// Inputs smaller than this are compressed on one thread: zstd hands workers jobs of
+// several MB, so they would sit idle
+const PARALLEL_MIN_BYTES: usize = 8 * 1024 * 1024;
+
+// Byte transposition of fixed-width values: every value's first byte, then every second
+// byte, and so on. Neighbouring m/z or mobility values share their sign, exponent and high
+// mantissa bytes, which become long runs that lz4 and zstd compress far better than the
+// interleaved values. Trailing bytes short of a value stay at the end.
+pub fn byte_shuffle(bytes: &[u8], width: usize) -> Vec<u8> {
+    let values = bytes.len() / width;
+    let mut shuffled = vec![0u8; bytes.len()];
+    for (byte_index, plane) in shuffled[..values * width].chunks_exact_mut(values.max(1)).enumerate() {
+        for (value, byte) in plane.iter_mut().enumerate() {
+            *byte = bytes[value * width + byte_index];
+        }
+    }
+    shuffled[values * width..].copy_from_slice(&bytes[values * width..]);
+    shuffled
+}
+
+// Inverse of byte_shuffle, into a buffer of the same length
+pub fn byte_unshuffle_into(shuffled: &[u8], width: usize, out: &mut [u8]) -> io::Result<()> {
+    if shuffled.len() != out.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("decoded {} bytes, expected {}", shuffled.len(), out.len())));
+    }
+    let values = out.len() / width;
+    for (value, bytes) in out.chunks_exact_mut(width).enumerate() {
+        for (byte_index, byte) in bytes.iter_mut().enumerate() {
+            *byte = shuffled[byte_index * values + value];
+        }
+    }
+    out[values * width..].copy_from_slice(&shuffled[values * width..]);
+    Ok(())
+}
+
+// Bytes of each column compressed to decide its codec
+const SAMPLE_BYTES: usize = 1024 * 1024;
+
+// Compression must save at least this fraction to be worth decoding on every load, and
+// zstd must beat lz4 by this much more to be worth its slower decoding
+const MIN_SAVING: f64 = 0.10;
+const ZSTD_MIN_GAIN: f64 = 0.15;
+
+// zstd is skipped for columns it compresses slower than this (MB/s), so adaptive saves
+// never become much slower than lz4 ones
+const ZSTD_MIN_MB_PER_SEC: f64 = 50.0;
+
+// Outcome of sampling one column
+#[derive(Debug, Clone)]
+pub struct CodecDecision {
+    pub column: &'static str,
+    pub codec: ColumnCodec,
+    pub lz4_ratio: f64,  // Raw size / compressed size
+    pub zstd_ratio: f64,
+    pub lz4_mb_per_sec: f64,
+    pub zstd_mb_per_sec: f64,
+}
+
+fn column_bytes<F: Float>(data: &IndexedTimsTOFData<F>, name: &str, stored: Precision) -> Vec<u8> {
+    match name {
+        "rt_values_min" => precision::floats_to_le(&data.rt_values_min, stored),
+        "mobility_values" => precision::floats_to_le(&data.mobility_values, stored),
+        "mz_values" => precision::floats_to_le(&data.mz_values, stored),
+        "intensity_values" => data.intensity_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        "frame_indices" => data.frame_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        "scan_indices" => data.scan_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Ratio and throughput of one codec on a sample
+fn measure(codec: ColumnCodec, sample: &[u8]) -> io::Result<(f64, f64)> {
+    let start = Instant::now();
+    let compressed = codec.compress(sample.to_vec())?;
+    let seconds = start.elapsed().as_secs_f64().max(1e-6);
+    let ratio = sample.len() as f64 / compressed.len().max(1) as f64;
+    Ok((ratio, sample.len() as f64 / 1024.0 / 1024.0 / seconds))
+}
+
+// Pick a codec for every column from a sample taken from the middle of `data`, where
+// the values are most representative (edges of sorted data are often degenerate).
+// `zstd` is the zstd configuration to try against lz4, `stored` the width floats are saved at,
+// `shuffle` whether float columns will be byte-shuffled before compression, `weigh_speed`
+// whether zstd too slow on the sample is skipped.
+pub fn select_codecs<F: Float>(
+    data: &IndexedTimsTOFData<F>,
+    columns: &[&'static str],
+    zstd: ColumnCodec,
+    stored: Precision,
+    shuffle: bool,
+    weigh_speed: bool,
+) -> io::Result<Vec<CodecDecision>> {
+    columns.iter()
+        .map(|&column| {
+            let bytes = column_bytes(data, column, stored);
+            let float = matches!(column, "rt_values_min" | "mobility_values" | "mz_values");
+            let width = if float { stored.width() } else { 4 };
+            let sample_len = bytes.len().min(SAMPLE_BYTES) / width * width;
+            let start = (bytes.len() - sample_len) / 2 / width * width;
+            let sample = &bytes[start..start + sample_len];
+            let shuffled;
+            let sample = match shuffle && float {
+                true => {
+                    shuffled = byte_shuffle(sample, width);
+                    &shuffled[..]
+                }
+                false => sample,
+            };
+            if sample.is_empty() {
+                return Ok(CodecDecision {
+                    column, codec: ColumnCodec::Raw,
+                    lz4_ratio: 1.0, zstd_ratio: 1.0, lz4_mb_per_sec: 0.0, zstd_mb_per_sec: 0.0,
+                });
+            }
+
+            let (lz4_ratio, lz4_mb_per_sec) = measure(ColumnCodec::Lz4, sample)?;
+            let (zstd_ratio, zstd_mb_per_sec) = measure(zstd, sample)?;
+            let saving = |ratio: f64| 1.0 - 1.0 / ratio;
+            let codec = if (zstd_mb_per_sec >= ZSTD_MIN_MB_PER_SEC || !weigh_speed)
+                && saving(zstd_ratio) >= MIN_SAVING
+                && saving(zstd_ratio) - saving(lz4_ratio) >= ZSTD_MIN_GAIN
+            {
+                zstd
+            } else if saving(lz4_ratio) >= MIN_SAVING {
+                ColumnCodec::Lz4
+            } else {
+                ColumnCodec::Raw
+            };
+            Ok(CodecDecision { column, codec, lz4_ratio, zstd_ratio, lz4_mb_per_sec, zstd_mb_per_sec })
+        })
+        .collect()
+}
+
+pub fn uniform_codecs(codec: ColumnCodec) -> CodecMap {
+    crate::columnar::INDEXED_COLUMNS.iter().map(|(name, _)| (*name, codec)).collect()
+}
+
+// "column=codec,..." as stored in the cache metadata
+pub fn describe_codecs(codecs: &CodecMap) -> String {
+    codecs.iter().map(|(column, codec)| format!("{}={}", column, codec.label())).collect::<Vec<_>>().join(",")
+}
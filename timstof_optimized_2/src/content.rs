@@ -0,0 +1,199 @@
+// File: src/content.rs
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use crate::fdlimit;
+
+// Files up to this size are hashed completely (analysis.tdf, method files); larger ones
+// (analysis.tdf_bin) by size plus sampled blocks, so keying a 20 GB acquisition stays fast
+const FULL_HASH_LIMIT: u64 = 64 * 1024 * 1024;
+const SAMPLE_BLOCK: u64 = 4 * 1024 * 1024;
+const SAMPLE_COUNT: u64 = 8;
+
+// Cheap change detector for a source folder: newest mtime and total size of its files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    modified_secs: u64,
+    total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyEntry {
+    key: String,
+    fingerprint: Fingerprint,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexFile {
+    keys: BTreeMap<PathBuf, KeyEntry>,            // Source path → content key (hash memo)
+    refs: BTreeMap<String, BTreeSet<PathBuf>>,    // Content key → sources using that cache
+}
+
+// Content-addressed cache naming: the cache of a source is stored under a hash of its
+// content, so copies of the same acquisition under different paths share one entry.
+// Reference counts record which paths use each entry, so clearing one path keeps the
+// cache for the others.
+pub struct ContentIndex {
+    path: PathBuf,
+    index: IndexFile,
+    changed: bool,
+}
+
+impl ContentIndex {
+    const FILE_NAME: &'static str = "content_index.json";
+
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(Self::FILE_NAME);
+        let index = fdlimit::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, index, changed: false }
+    }
+
+    // Whether key_for() hashed a source since load, i.e. whether save() has anything new
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(&self.index)
+            .map_err(io::Error::other)?;
+        let partial = self.path.with_extension("partial");
+        fdlimit::write(&partial, text)?;
+        fs::rename(partial, &self.path)
+    }
+
+    // Content key of a source, rehashed only when its files changed. A source that no
+    // longer exists keeps its last known key, so its cache can still be cleared.
+    pub fn key_for(&mut self, source_path: &Path) -> io::Result<String> {
+        let source_path = canonical(source_path);
+        let known = self.index.keys.get(&source_path);
+        let fingerprint = match fingerprint(&source_path) {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => return known.map(|entry| entry.key.clone()).ok_or(e),
+        };
+        if let Some(entry) = known.filter(|entry| entry.fingerprint == fingerprint) {
+            return Ok(entry.key.clone());
+        }
+
+        let key = content_key(&source_path)?;
+        self.index.keys.insert(source_path, KeyEntry { key: key.clone(), fingerprint });
+        self.changed = true;
+        Ok(key)
+    }
+
+    // Returns whether the reference is new
+    pub fn add_ref(&mut self, key: &str, source_path: &Path) -> bool {
+        self.index.refs.entry(key.to_string()).or_default().insert(canonical(source_path))
+    }
+
+    // Returns the number of sources still using the entry
+    pub fn remove_ref(&mut self, key: &str, source_path: &Path) -> usize {
+        let remaining = match self.index.refs.get_mut(key) {
+            Some(refs) => {
+                refs.remove(&canonical(source_path));
+                refs.len()
+            }
+            None => 0,
+        };
+        if remaining == 0 {
+            self.index.refs.remove(key);
+        }
+        remaining
+    }
+}
+
+// Sources that no longer exist cannot be canonicalized; they are at least made absolute
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Cache key of a source by location: its folder name made safe for file names, plus a
+// hash of the canonical absolute path. Sources sharing a folder name in different
+// directories get different keys, and non-UTF-8 names or a root path still get one.
+pub fn path_key(source_path: &Path) -> String {
+    let source_path = canonical(source_path);
+    let digest = Sha256::digest(source_path.as_os_str().as_encoded_bytes());
+    format!("{}-{}", safe_file_name(&source_path), hex::encode(&digest[..8]))
+}
+
+// The folder name of a source with characters other than [A-Za-z0-9._-] replaced, at
+// most 64 of them and no leading dots; "source" if nothing is left
+pub fn safe_file_name(source_path: &Path) -> String {
+    let name: String = source_path.file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .take(64)
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "source".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// Regular files below `dir`, sorted by relative path so the hash is path-independent
+fn source_files(dir: &Path) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                let relative = entry.path().strip_prefix(dir).unwrap_or(&entry.path()).to_path_buf();
+                files.push((relative, metadata));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+pub fn fingerprint(source_path: &Path) -> io::Result<Fingerprint> {
+    let mut modified = fs::metadata(source_path)?.modified()?;
+    let mut total_bytes = 0;
+    for (_, metadata) in source_files(source_path)? {
+        modified = modified.max(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+        total_bytes += metadata.len();
+    }
+    let modified_secs = modified.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    Ok(Fingerprint { modified_secs, total_bytes })
+}
+
+// "sha256-<32 hex>" over relative file names, sizes and (sampled) contents
+pub fn content_key(source_path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; SAMPLE_BLOCK as usize];
+
+    for (relative, metadata) in source_files(source_path)? {
+        let len = metadata.len();
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update(len.to_le_bytes());
+
+        let mut file = fdlimit::open(source_path.join(&relative))?;
+        if len <= FULL_HASH_LIMIT {
+            io::copy(&mut file, &mut hasher)?;
+            continue;
+        }
+        // Evenly spaced blocks, always including the first and the last
+        for i in 0..SAMPLE_COUNT {
+            let offset = (len - SAMPLE_BLOCK) * i / (SAMPLE_COUNT - 1);
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buffer)?;
+            hasher.update(&buffer);
+        }
+    }
+
+    Ok(format!("sha256-{}", hex::encode(&hasher.finalize()[..16])))
+}
@@ -0,0 +1,451 @@
+// File: src/daemon.rs
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Serialize, Deserialize};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::cache::CacheManager;
+use crate::fdlimit;
+use crate::utils::{read_timstof_data, build_indexed_data};
+use crate::telemetry;
+
+#[derive(Clone)]
+pub struct DaemonConfig {
+    pub watch_dir: PathBuf,
+    pub poll_interval: Duration,    // Full rescan interval; a fallback when filesystem events work
+    pub settle_time: Duration,      // Source must be untouched this long (acquisition finished)
+    pub quota_bytes: Option<u64>,   // Evict oldest caches above this size
+    pub status_addr: Option<String>, // e.g. "127.0.0.1:8787"
+    pub use_notify: bool,           // React to filesystem events instead of waiting for the next poll
+    pub build_threads: usize,       // Threads a build may use, leaving the rest to analysts
+    pub low_priority: bool,         // Run builds at reduced CPU priority (nice 10 on Linux)
+}
+
+impl DaemonConfig {
+    pub fn new(watch_dir: PathBuf) -> Self {
+        Self {
+            watch_dir,
+            poll_interval: Duration::from_secs(30),
+            settle_time: Duration::from_secs(120),
+            quota_bytes: None,
+            status_addr: None,
+            use_notify: true,
+            build_threads: (num_cpus::get() / 2).max(1),
+            low_priority: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum BuildState {
+    Queued,
+    Building,
+    Cached { seconds: f32 },
+    Failed(String),
+    Evicted,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DaemonStatus {
+    pub watch_dir: String,
+    pub started_at: String,
+    pub last_scan: Option<String>,
+    pub current: Option<String>,
+    pub queue: Vec<String>,
+    pub builds_completed: usize,
+    pub builds_failed: usize,
+    pub sources: BTreeMap<String, BuildState>,
+}
+
+// FIFO of sources waiting to be cached; each path is queued at most once
+#[derive(Default)]
+pub struct BuildQueue {
+    pending: VecDeque<PathBuf>,
+    queued: HashSet<PathBuf>,
+}
+
+impl BuildQueue {
+    pub fn push(&mut self, source: PathBuf) -> bool {
+        if !self.queued.insert(source.clone()) {
+            return false;
+        }
+        self.pending.push_back(source);
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<PathBuf> {
+        let source = self.pending.pop_front()?;
+        self.queued.remove(&source);
+        Some(source)
+    }
+
+    pub fn snapshot(&self) -> Vec<String> {
+        self.pending.iter().map(|p| display_name(p)).collect()
+    }
+}
+
+// Queue persisted in the cache dir so a restarted daemon picks up where it stopped
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueJournal {
+    in_progress: Option<PathBuf>,
+    pending: Vec<PathBuf>,
+}
+
+impl QueueJournal {
+    const FILE_NAME: &'static str = "daemon_queue.json";
+
+    fn load(cache_dir: &Path) -> Self {
+        fdlimit::read_to_string(cache_dir.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, cache_dir: &Path) {
+        if let Ok(text) = serde_json::to_string_pretty(self) {
+            if let Err(e) = fdlimit::write(cache_dir.join(Self::FILE_NAME), text) {
+                telemetry::error(&format!("✗ Could not persist daemon queue: {}", e));
+            }
+        }
+    }
+}
+
+pub struct CacheDaemon {
+    config: DaemonConfig,
+    cache_manager: CacheManager,
+    queue: BuildQueue,
+    in_progress: Option<PathBuf>,
+    failed: HashMap<PathBuf, SystemTime>, // Source mtime at the time of failure
+    status: Arc<Mutex<DaemonStatus>>,
+    shutdown: Arc<AtomicBool>,
+    build_pool: rayon::ThreadPool,
+    watcher: Option<(RecommendedWatcher, mpsc::Receiver<()>)>,
+    next_settle: Option<Instant>, // When the earliest unsettled source becomes buildable
+}
+
+impl CacheDaemon {
+    pub fn new(config: DaemonConfig, cache_manager: CacheManager) -> Self {
+        let status = DaemonStatus {
+            watch_dir: config.watch_dir.display().to_string(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            ..Default::default()
+        };
+
+        // Interrupted builds go first, then whatever was still waiting
+        let journal = QueueJournal::load(cache_manager.cache_dir());
+        let mut queue = BuildQueue::default();
+        for source in journal.in_progress.into_iter().chain(journal.pending) {
+            if source.is_dir() && queue.push(source.clone()) {
+                telemetry::info(&format!("Resuming queued build: {}", display_name(&source)));
+            }
+        }
+
+        // Builds run on their own bounded pool, so parallel loops inside reading and
+        // indexing can't take over every core of an analysis workstation
+        let low_priority = config.low_priority;
+        let build_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.build_threads.max(1))
+            .thread_name(|i| format!("cache-build-{}", i))
+            .start_handler(move |_| if low_priority { lower_thread_priority() })
+            .build()
+            .expect("failed to create the build thread pool");
+
+        Self {
+            config,
+            cache_manager,
+            queue,
+            in_progress: None,
+            failed: HashMap::new(),
+            status: Arc::new(Mutex::new(status)),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            build_pool,
+            watcher: None,
+            next_settle: None,
+        }
+    }
+
+    pub fn status(&self) -> Arc<Mutex<DaemonStatus>> {
+        Arc::clone(&self.status)
+    }
+
+    // Setting this flag stops the daemon after the build that is currently running
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    fn persist_queue(&self) {
+        QueueJournal {
+            in_progress: self.in_progress.clone(),
+            pending: self.queue.pending.iter().cloned().collect(),
+        }.save(self.cache_manager.cache_dir());
+    }
+
+    // Recursive watch on the acquisition folder; any change (new .d folder, data still being
+    // written) wakes the daemon for a rescan. Network shares often deliver no events, which
+    // is why polling stays on as a fallback.
+    fn start_watcher(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    let _ = sender.send(());
+                }
+            }
+        }).and_then(|mut watcher| {
+            watcher.watch(&self.config.watch_dir, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+
+        match watcher {
+            Ok(watcher) => self.watcher = Some((watcher, receiver)),
+            Err(e) => telemetry::error(&format!("✗ Filesystem events unavailable ({}), polling only", e)),
+        }
+    }
+
+    // Wait for the next poll, a source settling, or a filesystem event; in short steps so
+    // a shutdown request is noticed promptly
+    fn idle(&self) {
+        let mut deadline = Instant::now() + self.config.poll_interval;
+        if let Some(settle) = self.next_settle {
+            deadline = deadline.min(settle);
+        }
+        while Instant::now() < deadline && !self.shutdown.load(Ordering::SeqCst) {
+            let step = Duration::from_millis(250);
+            match &self.watcher {
+                Some((_, events)) => {
+                    if events.recv_timeout(step).is_ok() {
+                        // Files being written fire events continuously; let them pass
+                        // before rescanning
+                        thread::sleep(Duration::from_secs(1));
+                        while events.try_recv().is_ok() {}
+                        return;
+                    }
+                }
+                None => thread::sleep(step),
+            }
+        }
+    }
+
+    // Main loop: scan, build one source at a time, enforce quota, repeat
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.config.watch_dir.is_dir() {
+            return Err(format!("watch folder {:?} not found", self.config.watch_dir).into());
+        }
+
+        if let Some(addr) = self.config.status_addr.clone() {
+            spawn_status_server(&addr, self.status())?;
+            telemetry::info(&format!("Status endpoint listening on http://{}/", addr));
+        }
+
+        if self.config.use_notify {
+            self.start_watcher();
+        }
+        telemetry::info(&format!("👀 Watching {} for new .d folders ({}, poll every {}s, {} build thread(s){})",
+                 self.config.watch_dir.display(),
+                 if self.watcher.is_some() { "filesystem events" } else { "polling" },
+                 self.config.poll_interval.as_secs(),
+                 self.config.build_threads,
+                 if self.config.low_priority { ", low priority" } else { "" }));
+
+        while !self.shutdown.load(Ordering::SeqCst) {
+            // A watch folder on a share can vanish for a while (unmounted, network down);
+            // the queue is still worked off and the next poll scans again
+            if let Err(e) = self.scan_once() {
+                telemetry::error(&format!("✗ Could not scan {}: {}", self.config.watch_dir.display(), e));
+            }
+            self.persist_queue();
+
+            match self.queue.pop() {
+                Some(source) => self.build(&source),
+                None => self.idle(),
+            }
+        }
+
+        self.persist_queue();
+        telemetry::info(&format!("Daemon stopped; {} queued build(s) will resume on restart", self.queue.pending.len()));
+        Ok(())
+    }
+
+    // Queue every settled .d folder that has no valid cache yet
+    pub fn scan_once(&mut self) -> Result<usize, Box<dyn Error>> {
+        let mut added = 0;
+        self.next_settle = None;
+
+        for entry in fs::read_dir(&self.config.watch_dir)?.flatten() {
+            let path = entry.path();
+            if !is_bruker_folder(&path) || self.cache_manager.is_cache_valid(&path) {
+                continue;
+            }
+
+            let modified = latest_modification(&path);
+            if let Some(failed_at) = self.failed.get(&path) {
+                // Only retry a failed source once it has been touched again
+                if modified <= *failed_at {
+                    continue;
+                }
+            }
+
+            let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+            let settled = age >= self.config.settle_time;
+            if !settled {
+                let settle_at = Instant::now() + (self.config.settle_time - age);
+                self.next_settle = Some(self.next_settle.map_or(settle_at, |next| next.min(settle_at)));
+            }
+            if settled && self.queue.push(path.clone()) {
+                telemetry::info(&format!("Queued new acquisition: {}", display_name(&path)));
+                self.status.lock().unwrap().sources.insert(display_name(&path), BuildState::Queued);
+                added += 1;
+            }
+        }
+
+        let mut status = self.status.lock().unwrap();
+        status.last_scan = Some(chrono::Local::now().to_rfc3339());
+        status.queue = self.queue.snapshot();
+        Ok(added)
+    }
+
+    fn build(&mut self, source: &Path) {
+        let name = display_name(source);
+        {
+            let mut status = self.status.lock().unwrap();
+            status.current = Some(name.clone());
+            status.queue = self.queue.snapshot();
+            status.sources.insert(name.clone(), BuildState::Building);
+        }
+
+        self.in_progress = Some(source.to_path_buf());
+        self.persist_queue();
+
+        telemetry::info(&format!("\n🔨 Building cache for {}", name));
+        let start = Instant::now();
+        // Box<dyn Error> is not Send, so only the message comes back from the pool
+        let cache_manager = &self.cache_manager;
+        let result = self.build_pool.install(|| build_source_cache(cache_manager, source).map_err(|e| e.to_string()));
+        self.in_progress = None;
+        self.persist_queue();
+
+        let mut status = self.status.lock().unwrap();
+        status.current = None;
+        match result {
+            Ok(()) => {
+                let seconds = start.elapsed().as_secs_f32();
+                telemetry::info(&format!("✅ Cached {} in {:.1}s", name, seconds));
+                self.failed.remove(source);
+                status.builds_completed += 1;
+                status.sources.insert(name, BuildState::Cached { seconds });
+            }
+            Err(e) => {
+                telemetry::error(&format!("✗ Failed to cache {}: {}", name, e));
+                self.failed.insert(source.to_path_buf(), latest_modification(source));
+                status.builds_failed += 1;
+                status.sources.insert(name, BuildState::Failed(e.to_string()));
+            }
+        }
+        drop(status);
+
+        if let Some(quota) = self.config.quota_bytes {
+            match self.cache_manager.enforce_quota(quota) {
+                Ok(evicted) => {
+                    let mut status = self.status.lock().unwrap();
+                    for source_name in evicted {
+                        status.sources.insert(source_name, BuildState::Evicted);
+                    }
+                }
+                Err(e) => telemetry::error(&format!("✗ Quota enforcement failed: {}", e)),
+            }
+        }
+    }
+}
+
+// Raw read → index → save, the same pipeline main() runs on a cache miss
+pub fn build_source_cache(cache_manager: &CacheManager, source: &Path) -> Result<(), Box<dyn Error>> {
+    let raw_data = read_timstof_data(source)?;
+    let (ms1_indexed, ms2_indexed_pairs) = build_indexed_data(raw_data)?;
+    cache_manager.save_indexed_data(source, &ms1_indexed, &ms2_indexed_pairs)
+}
+
+// Builds yield to interactive work: nice 10 for each build thread. Only on Linux, where
+// setpriority with PRIO_PROCESS and id 0 applies to the calling thread alone; elsewhere
+// it would renice the whole process, status server and embedding application included
+#[cfg(target_os = "linux")]
+fn lower_thread_priority() {
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_thread_priority() {}
+
+fn is_bruker_folder(path: &Path) -> bool {
+    path.is_dir() && path.extension().and_then(|e| e.to_str()) == Some("d")
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+// Newest mtime of the folder and its direct children (analysis.tdf, analysis.tdf_bin, ...)
+fn latest_modification(path: &Path) -> SystemTime {
+    let mut latest = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                latest = latest.max(modified);
+            }
+        }
+    }
+
+    latest
+}
+
+// A client that connects and sends nothing can hold the endpoint this long at most
+const STATUS_CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Minimal HTTP endpoint: every request gets the current status as JSON
+fn spawn_status_server(addr: &str, status: Arc<Mutex<DaemonStatus>>) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr)?;
+
+    thread::spawn(move || {
+        loop {
+            // A connection is a descriptor of its own (fdlimit.rs): wait for one first
+            let Ok(_permit) = fdlimit::acquire(1) else { continue };
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(_) => continue,
+            };
+
+            let _ = stream.set_read_timeout(Some(STATUS_CLIENT_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(STATUS_CLIENT_TIMEOUT));
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+
+            let body = {
+                let status = status.lock().unwrap();
+                serde_json::to_string_pretty(&*status).unwrap_or_else(|_| "{}".to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    Ok(())
+}
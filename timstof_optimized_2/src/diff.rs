@@ -0,0 +1,166 @@
+// File: src/diff.rs
+//
+// Comparison of two cached sources from their metadata alone (diff_caches), to tell
+// whether a re-converted raw file actually changed before re-running downstream analysis.
+// Point counts per window and rt / m/z coverage come from the dataset summaries, data
+// equality from the shard tables' checksums, which only compare when both caches were
+// written with the same shard layout and codecs; otherwise the data is "not comparable"
+// and the counts and ranges are all there is to go on.
+use std::path::PathBuf;
+
+use crate::cache::ShardInfo;
+use crate::summary::DatasetSummary;
+
+// Metadata keys that differ between any two saves (times, paths, stamps of the source
+// files, signatures, data file checksums, provenance) or are compared in their own right (summary,
+// shard table)
+const VOLATILE_KEYS: [&str; 10] = [
+    "cached at", "updated at", "repaired at", "source", "source_files", "signature", "signing_key_id", "summary", "shard",
+    "provenance",
+];
+
+// What diff_caches reads of one cache
+pub struct CacheSide {
+    pub source: PathBuf,
+    pub metadata: Vec<(String, String)>,
+    pub summary: Option<DatasetSummary>,
+    pub shards: Option<Vec<ShardInfo>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataComparison {
+    Identical,
+    Different { segments: usize }, // Segments whose checksum or point count differs
+    NotComparable(String),
+}
+
+// Point counts of one MS2 window that differ; None where a cache lacks the window
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowDiff {
+    pub isolation: (f32, f32),
+    pub points: (Option<u64>, Option<u64>),
+}
+
+// A metadata value that differs; None where a cache has no such key
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataDiff {
+    pub key: String,
+    pub values: (Option<String>, Option<String>),
+}
+
+type Range = (f32, f32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheDiff {
+    pub sources: (PathBuf, PathBuf),
+    pub ms1_points: (Option<u64>, Option<u64>), // None for caches saved before summaries
+    pub ms2_points: (Option<u64>, Option<u64>),
+    pub rt_range: (Option<Range>, Option<Range>),
+    pub mz_range: (Option<Range>, Option<Range>),
+    pub windows: Vec<WindowDiff>,
+    pub data: DataComparison,
+    pub metadata: Vec<MetadataDiff>,
+}
+
+impl CacheDiff {
+    pub fn compare(a: &CacheSide, b: &CacheSide) -> Self {
+        let (summary_a, summary_b) = (&a.summary, &b.summary);
+        let field = |get: &dyn Fn(&DatasetSummary) -> Option<Range>| {
+            (summary_a.as_ref().and_then(get), summary_b.as_ref().and_then(get))
+        };
+        Self {
+            sources: (a.source.clone(), b.source.clone()),
+            ms1_points: (summary_a.as_ref().map(|s| s.ms1_points), summary_b.as_ref().map(|s| s.ms1_points)),
+            ms2_points: (summary_a.as_ref().map(|s| s.ms2_points), summary_b.as_ref().map(|s| s.ms2_points)),
+            rt_range: field(&|s| s.rt_range),
+            mz_range: field(&|s| s.mz_range),
+            windows: window_diffs(summary_a.as_ref(), summary_b.as_ref()),
+            data: match encrypted(a) || encrypted(b) {
+                // Every save draws fresh nonces, so equal data encrypts to other checksums
+                true => DataComparison::NotComparable("encrypted caches have no comparable checksums".to_string()),
+                false => compare_data(a.shards.as_deref(), b.shards.as_deref()),
+            },
+            metadata: metadata_diffs(&a.metadata, &b.metadata),
+        }
+    }
+
+    // Same data and same settings: nothing downstream needs re-running
+    pub fn is_identical(&self) -> bool {
+        self.data == DataComparison::Identical && self.metadata.is_empty()
+    }
+
+    // Same points, counts and coverage as far as the caches can tell, whatever the settings
+    pub fn same_data(&self) -> bool {
+        match self.data {
+            DataComparison::Identical => true,
+            DataComparison::Different { .. } => false,
+            DataComparison::NotComparable(_) => {
+                self.ms1_points.0.is_some()
+                    && self.ms1_points.0 == self.ms1_points.1
+                    && self.ms2_points.0 == self.ms2_points.1
+                    && self.rt_range.0 == self.rt_range.1
+                    && self.mz_range.0 == self.mz_range.1
+                    && self.windows.is_empty()
+            }
+        }
+    }
+}
+
+fn encrypted(side: &CacheSide) -> bool {
+    side.metadata.iter().any(|(key, value)| key == "encryption" && value != "none")
+}
+
+fn window_diffs(a: Option<&DatasetSummary>, b: Option<&DatasetSummary>) -> Vec<WindowDiff> {
+    let (Some(a), Some(b)) = (a, b) else {
+        return Vec::new();
+    };
+    let points = |summary: &DatasetSummary, isolation: (f32, f32)| {
+        summary.windows.iter().find(|w| w.isolation == isolation).map(|w| w.points)
+    };
+    let mut isolations: Vec<(f32, f32)> = a.windows.iter().chain(&b.windows).map(|w| w.isolation).collect();
+    isolations.sort_by(|x, y| x.0.total_cmp(&y.0).then(x.1.total_cmp(&y.1)));
+    isolations.dedup();
+    isolations.into_iter()
+        .map(|isolation| WindowDiff { isolation, points: (points(a, isolation), points(b, isolation)) })
+        .filter(|diff| diff.points.0 != diff.points.1)
+        .collect()
+}
+
+fn compare_data(a: Option<&[ShardInfo]>, b: Option<&[ShardInfo]>) -> DataComparison {
+    let (Some(a), Some(b)) = (a, b) else {
+        return DataComparison::NotComparable("a cache lists no shard table (Parquet or an older cache)".to_string());
+    };
+    // Segments are compared in order; the data file names differ with the source name
+    let layout = |shards: &[ShardInfo]| -> Vec<(String, Option<String>)> {
+        shards.iter().map(|shard| (shard.name.clone(), shard.codecs.clone())).collect()
+    };
+    if layout(a) != layout(b) {
+        return DataComparison::NotComparable("the caches differ in shard layout or codecs".to_string());
+    }
+    if a.iter().chain(b).any(|shard| shard.checksum.is_none()) {
+        return DataComparison::NotComparable("a cache has segments without checksums".to_string());
+    }
+    let segments = a.iter().zip(b)
+        .filter(|(x, y)| x.checksum != y.checksum || x.data_points != y.data_points || x.mz_range != y.mz_range)
+        .count();
+    match segments {
+        0 => DataComparison::Identical,
+        segments => DataComparison::Different { segments },
+    }
+}
+
+fn metadata_diffs(a: &[(String, String)], b: &[(String, String)]) -> Vec<MetadataDiff> {
+    let value = |metadata: &[(String, String)], key: &str| {
+        metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+    };
+    let mut keys: Vec<&String> = a.iter().chain(b)
+        .map(|(key, _)| key)
+        .filter(|key| !VOLATILE_KEYS.contains(&key.as_str()) && !key.ends_with("_sha256"))
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .map(|key| MetadataDiff { key: key.clone(), values: (value(a, key), value(b, key)) })
+        .filter(|diff| diff.values.0 != diff.values.1)
+        .collect()
+}
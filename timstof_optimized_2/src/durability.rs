@@ -0,0 +1,156 @@
+// File: src/durability.rs
+//
+// How far a save goes to survive a power loss (CacheConfig::durability). Files are always
+// written under a temporary name and renamed, so a crash never leaves a half-written file
+// under the real name, but without syncing the rename can reach the disk before the data
+// does. Flush syncs each file's data before it is renamed; Fsync also syncs its metadata
+// and, after the rename, the directory holding it. While a container is written, a
+// background thread syncs what has been written so far, so the final sync only waits for
+// the tail and the encoding workers never block on the disk: when the disk falls behind,
+// syncs are skipped rather than queued.
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+use crate::fdlimit;
+
+// Bytes written between background syncs
+const SYNC_INTERVAL_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    #[default]
+    None,  // Leave writeback to the OS
+    Flush, // Sync file data before the rename
+    Fsync, // Sync file data and metadata, and the directory after the rename
+}
+
+impl Durability {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Self::None),
+            "flush" => Ok(Self::Flush),
+            "fsync" => Ok(Self::Fsync),
+            other => Err(format!("unknown durability {} (none, flush, fsync)", other)),
+        }
+    }
+
+    fn sync(self, file: &File) -> io::Result<()> {
+        match self {
+            Durability::None => Ok(()),
+            Durability::Flush => file.sync_data(),
+            Durability::Fsync => file.sync_all(),
+        }
+    }
+}
+
+// A file being written, synced in the background as it grows (see above). finish() must
+// be called once everything is written.
+pub struct DurableFile {
+    file: File,
+    durability: Durability,
+    unsynced: u64,
+    syncer: Option<(SyncSender<()>, JoinHandle<io::Result<()>>)>,
+}
+
+impl DurableFile {
+    pub fn new(file: File, durability: Durability) -> io::Result<Self> {
+        let syncer = match durability {
+            Durability::None => None,
+            _ => {
+                // The clone is a descriptor of its own, held by the thread
+                let permit = fdlimit::acquire(1)?;
+                let clone = file.try_clone().map_err(fdlimit::explain)?;
+                let (sender, receiver) = mpsc::sync_channel::<()>(1);
+                let handle = std::thread::Builder::new()
+                    .name("cache-fsync".to_string())
+                    .spawn(move || {
+                        let _permit = permit;
+                        for () in receiver {
+                            clone.sync_data()?;
+                        }
+                        Ok(())
+                    })?;
+                Some((sender, handle))
+            }
+        };
+        Ok(Self { file, durability, unsynced: 0, syncer })
+    }
+
+    // Stop the background thread (reporting any error it hit) and sync the rest
+    pub fn finish(mut self) -> io::Result<File> {
+        if let Some((sender, handle)) = self.syncer.take() {
+            drop(sender);
+            handle.join().map_err(|_| io::Error::other("fsync thread panicked"))??;
+        }
+        self.durability.sync(&self.file)?;
+        Ok(self.file)
+    }
+
+    // Sync what was written so far now, as the durability asks, e.g. before recording it
+    // as committed
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.durability.sync(&self.file)
+    }
+}
+
+impl Write for DurableFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.unsynced += written as u64;
+        if self.unsynced >= SYNC_INTERVAL_BYTES {
+            if let Some((sender, _)) = &self.syncer {
+                match sender.try_send(()) {
+                    // A sync is pending: it will cover these bytes too
+                    Ok(()) | Err(TrySendError::Full(())) => {}
+                    Err(TrySendError::Disconnected(())) => return Err(io::Error::other("fsync thread stopped")),
+                }
+            }
+            self.unsynced = 0;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Rename a finished temporary file into place, then sync the directory under Fsync
+pub fn commit(partial_path: &Path, path: &Path, durability: Durability) -> io::Result<()> {
+    fs::rename(partial_path, path)?;
+    match durability {
+        Durability::Fsync => sync_dir(path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."))),
+        _ => Ok(()),
+    }
+}
+
+// fs::write with the durability of a data file: under Flush or Fsync through a
+// temporary file, synced and renamed, so the old content survives a crash mid-write
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>, durability: Durability) -> io::Result<()> {
+    if durability == Durability::None {
+        return fdlimit::write(path, contents);
+    }
+    let mut partial_path = path.as_os_str().to_owned();
+    partial_path.push(".partial");
+    let partial_path = PathBuf::from(partial_path);
+    let mut file = fdlimit::create(&partial_path)?;
+    file.write_all(contents.as_ref())?;
+    durability.sync(&file)?;
+    drop(file);
+    commit(&partial_path, path, durability)
+}
+
+// Persist the entries of a directory (renames into it). Directories can't be opened for
+// syncing on Windows, where NTFS journals renames itself.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fdlimit::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// File: src/encryption.rs
+use std::io;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::telemetry;
+
+pub const ALGORITHM: &str = "aes256gcm";
+pub const KEY_ENV: &str = "TIMSTOF_CACHE_KEY";       // 64 hex chars (256-bit key)
+pub const KEY_ID_ENV: &str = "TIMSTOF_CACHE_KEY_ID"; // Optional, defaults to "default"
+
+// AES-256-GCM key plus the id recorded in cache headers to pick the right key on load
+#[derive(Clone)]
+pub struct EncryptionKey {
+    pub id: String,
+    key: [u8; 32],
+    synthetic_nonces: bool,
+}
+
+impl EncryptionKey {
+    pub fn new(id: impl Into<String>, key: [u8; 32]) -> Self {
+        Self { id: id.into(), key, synthetic_nonces: false }
+    }
+
+    // Nonces derived from the key, the location and the plaintext instead of drawn at random
+    // (CacheConfig::deterministic), so the same data encrypts to the same bytes. A nonce then
+    // only repeats for the same plaintext at the same location, and all it reveals is that
+    // two files hold the same data there.
+    pub fn with_synthetic_nonces(self) -> Self {
+        Self { synthetic_nonces: true, ..self }
+    }
+
+    pub fn from_hex(id: impl Into<String>, key_hex: &str) -> Result<Self, String> {
+        let bytes = hex::decode(key_hex.trim()).map_err(|e| format!("invalid key hex: {}", e))?;
+        let key: [u8; 32] = bytes.try_into()
+            .map_err(|b: Vec<u8>| format!("key must be 32 bytes, got {}", b.len()))?;
+        Ok(Self::new(id, key))
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let key_hex = std::env::var(KEY_ENV).ok()?;
+        let id = std::env::var(KEY_ID_ENV).unwrap_or_else(|_| "default".to_string());
+        match Self::from_hex(id, &key_hex) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                telemetry::warn(&format!("Ignoring {}: {}", KEY_ENV, e));
+                None
+            }
+        }
+    }
+
+    // Identifies the key (id, bytes and nonce mode) without revealing it, e.g. to tell
+    // whether files written earlier used the same key
+    pub fn fingerprint(&self) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(b"timstof-cache-key-fingerprint");
+        mac.update(self.id.as_bytes());
+        mac.update(&[self.synthetic_nonces as u8]);
+        hex::encode(&mac.finalize().into_bytes()[..8])
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+
+    // Returns (nonce, ciphertext+tag). `aad` binds the ciphertext to its location.
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let nonce = match self.synthetic_nonces {
+            true => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+                mac.update(b"timstof-cache-nonce");
+                mac.update(&(aad.len() as u64).to_le_bytes());
+                mac.update(aad);
+                mac.update(plaintext);
+                *Nonce::from_slice(&mac.finalize().into_bytes()[..12])
+            }
+            false => Aes256Gcm::generate_nonce(&mut OsRng),
+        };
+        let ciphertext = self.cipher()
+            .encrypt(&nonce, Payload { msg: plaintext, aad })
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    // Authenticated decryption: fails if the data, nonce or location was altered
+    pub fn decrypt(&self, nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> io::Result<Vec<u8>> {
+        if nonce.len() != 12 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad nonce length"));
+        }
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("decryption failed with key '{}' (wrong key or tampered data)", self.id),
+            ))
+    }
+}
@@ -0,0 +1,191 @@
+// File: src/fdlimit.rs
+//
+// Cap on the files the cache holds open at once, process-wide. A load keeps one handle per
+// data file, but loads of many sources or windows run in parallel on wide pools, and the
+// merge of a stream save (stream.rs) opens every spilled run at once; macOS' default soft
+// limit of 256 descriptors is soon exhausted, and the EMFILE that follows surfaces far from
+// its cause. Handles are taken as permits (acquire): by default half the soft
+// RLIMIT_NOFILE, the other half left to the embedding application, or
+// TIMSTOF_CACHE_MAX_OPEN_FILES. An open past the cap waits for another to close. Only an
+// operation that needs more files at once than the cap, a wait that runs out (OPEN_WAIT),
+// or an open the OS refuses fails, with an error saying how to raise the limit.
+//
+// Cache code opens files through open(), create(), open_with(), read(), read_to_string()
+// and write() below, which take the permit; RetryingFile, stream.rs's merge and the
+// daemon's status connections take theirs themselves.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Longest an open waits for handles to close before giving up
+pub const OPEN_WAIT: Duration = Duration::from_secs(60);
+// Cap never below this, whatever the soft limit
+const MIN_CAP: usize = 16;
+
+struct OpenFiles {
+    cap: Option<usize>, // None without a known limit: no cap
+    open: Mutex<usize>,
+    closed: Condvar,
+}
+
+fn open_files() -> &'static OpenFiles {
+    static OPEN_FILES: OnceLock<OpenFiles> = OnceLock::new();
+    OPEN_FILES.get_or_init(|| {
+        let cap = std::env::var("TIMSTOF_CACHE_MAX_OPEN_FILES").ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .or_else(|| soft_limit().map(|limit| (limit / 2) as usize))
+            .map(|cap| cap.max(MIN_CAP));
+        OpenFiles { cap, open: Mutex::new(0), closed: Condvar::new() }
+    })
+}
+
+// Soft RLIMIT_NOFILE, None if unlimited or unknown
+#[cfg(unix)]
+pub fn soft_limit() -> Option<u64> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)] // rlim_t isn't u64 everywhere
+    Some(limit.rlim_cur as u64)
+}
+
+// Windows has no per-process descriptor limit the cache could run into
+#[cfg(not(unix))]
+pub fn soft_limit() -> Option<u64> {
+    None
+}
+
+// Handles of `count` files; released when dropped
+pub struct FilePermit {
+    count: usize,
+}
+
+impl Drop for FilePermit {
+    fn drop(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+        let files = open_files();
+        *files.open.lock().unwrap_or_else(|e| e.into_inner()) -= self.count;
+        files.closed.notify_all();
+    }
+}
+
+// Permits for `count` files that must be open at the same time, waiting up to OPEN_WAIT
+// for other opens to close
+pub fn acquire(count: usize) -> io::Result<FilePermit> {
+    let files = open_files();
+    let Some(cap) = files.cap else {
+        return Ok(FilePermit { count: 0 });
+    };
+    if count > cap {
+        return Err(exceeded(&format!("{} files must be open at once, more than the cap of {}", count, cap)));
+    }
+    let deadline = Instant::now() + OPEN_WAIT;
+    let mut open = files.open.lock().unwrap_or_else(|e| e.into_inner());
+    while *open + count > cap {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(exceeded(&format!("all {} file handles stayed in use for {}s", cap, OPEN_WAIT.as_secs())));
+        }
+        open = files.closed.wait_timeout(open, deadline - now).unwrap_or_else(|e| e.into_inner()).0;
+    }
+    *open += count;
+    Ok(FilePermit { count })
+}
+
+// A file opened under a permit, held until the file is closed
+pub struct PermittedFile {
+    file: File,
+    permit: FilePermit,
+}
+
+impl PermittedFile {
+    // For APIs that take a File by value; the permit must live as long as it
+    pub fn into_parts(self) -> (File, FilePermit) {
+        (self.file, self.permit)
+    }
+}
+
+impl Deref for PermittedFile {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Read for PermittedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for PermittedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for PermittedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+pub fn open(path: impl AsRef<Path>) -> io::Result<PermittedFile> {
+    open_with(OpenOptions::new().read(true), path)
+}
+
+pub fn create(path: impl AsRef<Path>) -> io::Result<PermittedFile> {
+    open_with(OpenOptions::new().write(true).create(true).truncate(true), path)
+}
+
+pub fn open_with(options: &OpenOptions, path: impl AsRef<Path>) -> io::Result<PermittedFile> {
+    let permit = acquire(1)?;
+    let file = options.open(path).map_err(explain)?;
+    Ok(PermittedFile { file, permit })
+}
+
+// fs::read and fs::write under a permit
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let _permit = acquire(1)?;
+    fs::read(path).map_err(explain)
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let _permit = acquire(1)?;
+    fs::read_to_string(path).map_err(explain)
+}
+
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let _permit = acquire(1)?;
+    fs::write(path, contents).map_err(explain)
+}
+
+// An open that failed because the process or system is out of descriptors, as the error
+// acquire returns; other errors unchanged
+pub fn explain(error: io::Error) -> io::Error {
+    #[cfg(unix)]
+    if matches!(error.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE)) {
+        return exceeded(&error.to_string());
+    }
+    error
+}
+
+fn exceeded(what: &str) -> io::Error {
+    let limit = soft_limit().map(|limit| limit.to_string()).unwrap_or_else(|| "unknown".to_string());
+    io::Error::other(format!(
+        "too many open files: {}. Raise the descriptor limit (ulimit -n 4096; the soft limit is {}) \
+         and the cache's share of it with TIMSTOF_CACHE_MAX_OPEN_FILES",
+        what, limit
+    ))
+}
@@ -0,0 +1,110 @@
+// File: src/frames.rs
+//
+// Frame index: an optional sidecar container (CacheConfig::frame_index) holding the points
+// sorted by frame, then m/z, instead of by m/z alone, so the spectrum of one frame is a
+// contiguous row range. Every segment covers whole frames, recorded as frame_min /
+// frame_max, so load_frame() reads the footer and decodes only the segment holding the
+// frame (with raw columns, only the frame's rows). MS1 and MS2 points are kept in
+// separate segments ("ms1" / "ms2"), which gives the MS level of a frame.
+use std::collections::BTreeMap;
+use std::io;
+use rayon::prelude::*;
+
+use crate::columnar::{self, ContainerReader, SegmentInput};
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+
+// Points per segment, rounded up to whole frames: a frame query decodes one segment
+pub const SEGMENT_POINTS: usize = 256 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameSpectrum {
+    pub frame_index: u32,
+    pub ms_level: u8,
+    pub rt_min: f32,
+    pub mz_values: Vec<f32>, // Ascending; DIA frames hold the points of all their windows
+    pub intensity_values: Vec<u32>,
+    pub scan_indices: Vec<u32>,
+}
+
+// The points of `parts` in one dataset sorted by frame, then m/z
+pub fn frame_sorted<F: Float>(parts: &[&IndexedTimsTOFData<F>]) -> IndexedTimsTOFData<F> {
+    let mut order: Vec<(usize, usize)> = parts.iter()
+        .enumerate()
+        .flat_map(|(part, data)| (0..data.mz_values.len()).map(move |row| (part, row)))
+        .collect();
+    order.par_sort_by(|&(a, i), &(b, j)| {
+        parts[a].frame_indices[i].cmp(&parts[b].frame_indices[j])
+            .then_with(|| parts[a].mz_values[i].total_cmp(&parts[b].mz_values[j]))
+    });
+    IndexedTimsTOFData {
+        rt_values_min: order.par_iter().map(|&(part, row)| parts[part].rt_values_min[row]).collect(),
+        mobility_values: order.par_iter().map(|&(part, row)| parts[part].mobility_values[row]).collect(),
+        mz_values: order.par_iter().map(|&(part, row)| parts[part].mz_values[row]).collect(),
+        intensity_values: order.par_iter().map(|&(part, row)| parts[part].intensity_values[row]).collect(),
+        frame_indices: order.par_iter().map(|&(part, row)| parts[part].frame_indices[row]).collect(),
+        scan_indices: order.par_iter().map(|&(part, row)| parts[part].scan_indices[row]).collect(),
+    }
+}
+
+// Segments of frame-sorted `data`, each of about SEGMENT_POINTS and ending on a frame
+// boundary; none for empty data
+pub fn segments<'a, F>(name: &str, data: &'a IndexedTimsTOFData<F>) -> Vec<SegmentInput<'a, F>> {
+    let frames = &data.frame_indices;
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < frames.len() {
+        let last_frame = frames[(start + SEGMENT_POINTS).min(frames.len()) - 1];
+        let end = start + frames[start..].partition_point(|&frame| frame <= last_frame);
+        segments.push(SegmentInput {
+            name: name.to_string(),
+            attrs: BTreeMap::from([
+                ("frame_min".to_string(), frames[start] as f64),
+                ("frame_max".to_string(), last_frame as f64),
+            ]),
+            data,
+            rows: start..end,
+            codecs: None,
+        });
+        start = end;
+    }
+    segments
+}
+
+// The spectrum of `frame` from a frame index, None if it has no points there, and the
+// number of segments read
+pub fn read_frame<R>(reader: &ContainerReader<R>, frame: u32) -> io::Result<(Option<FrameSpectrum>, usize)>
+where
+    R: Fn(u64, usize) -> io::Result<Vec<u8>> + Sync,
+{
+    let covering: Vec<usize> = reader.header.segments.iter()
+        .enumerate()
+        .filter(|(_, segment)| {
+            let attr = |name: &str| segment.attrs.get(name).copied().unwrap_or(f64::NAN);
+            attr("frame_min") <= frame as f64 && frame as f64 <= attr("frame_max")
+        })
+        .map(|(segment_index, _)| segment_index)
+        .collect();
+    let mut parts = Vec::new();
+    let mut ms_level = 0;
+    for &segment_index in &covering {
+        let part: IndexedTimsTOFData = reader.segment_frame(segment_index, frame)?;
+        if !part.mz_values.is_empty() {
+            ms_level = if reader.header.segments[segment_index].name == "ms1" { 1 } else { 2 };
+            parts.push(part);
+        }
+    }
+    if parts.is_empty() {
+        return Ok((None, covering.len()));
+    }
+    let data = columnar::merge_by_mz(parts);
+    let spectrum = FrameSpectrum {
+        frame_index: frame,
+        ms_level,
+        rt_min: data.rt_values_min[0],
+        mz_values: data.mz_values,
+        intensity_values: data.intensity_values,
+        scan_indices: data.scan_indices,
+    };
+    Ok((Some(spectrum), covering.len()))
+}
@@ -0,0 +1,156 @@
+// File: src/freshness.rs
+//
+// Whether a cache still matches its source folder. The folder's own mtime only changes
+// when entries are added or removed, and on some filesystems (network mounts, copies
+// with preserved times) not even then, so rewriting analysis.tdf_bin in place can leave
+// a stale cache looking valid. Saves record what the chosen policy compares against in
+// the metadata ("source_files", "source_hash"); caches written before a value was
+// recorded fall back to the mtime checks. A cache derived from other caches (merge_sources,
+// split_cache_by_rt) has no folder of its own: it records each source it was made from
+// ("merged_source") and is fresh while all of them are.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+
+use crate::content;
+
+// The files of a Bruker .d folder that hold the acquisition
+const KEY_FILES: [&str; 2] = ["analysis.tdf", "analysis.tdf_bin"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationPolicy {
+    DirMtime,    // The folder's mtime against the cache's, nothing else
+    #[default]
+    DeepMtime,   // Plus every file below the folder, and size + mtime of the key files
+    ContentHash, // Plus a hash of the content (content::content_key): sampled reads, slowest
+}
+
+impl ValidationPolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "dir-mtime" => Ok(Self::DirMtime),
+            "deep-mtime" => Ok(Self::DeepMtime),
+            "content-hash" => Ok(Self::ContentHash),
+            other => Err(format!("unknown validation policy {} (dir-mtime, deep-mtime, content-hash)", other)),
+        }
+    }
+}
+
+// Metadata lines describing the source as saved; the hash only under ContentHash
+pub fn source_metadata(source_path: &Path, policy: ValidationPolicy) -> io::Result<String> {
+    let mut lines = format!("source_files: {}\n", key_file_stamps(source_path));
+    if policy == ValidationPolicy::ContentHash {
+        lines.push_str(&format!("source_hash: {}\n", content::content_key(source_path)?));
+    }
+    Ok(lines)
+}
+
+// One source of a merged cache, as recorded in its metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergedSource {
+    pub path: PathBuf,
+    pub frame_offset: u32, // Added to the source's frame indices in the merged data
+    pub source_files: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+}
+
+// "merged_source" lines for the sources of a merged cache, with their frame offsets
+pub fn merged_source_metadata(sources: &[(PathBuf, u32)], policy: ValidationPolicy) -> io::Result<String> {
+    let mut lines = String::new();
+    for (path, frame_offset) in sources {
+        let source = MergedSource {
+            path: path.clone(),
+            frame_offset: *frame_offset,
+            source_files: key_file_stamps(path),
+            source_hash: match policy {
+                ValidationPolicy::ContentHash => Some(content::content_key(path)?),
+                _ => None,
+            },
+        };
+        lines.push_str(&format!("merged_source: {}\n", serde_json::to_string(&source).map_err(io::Error::other)?));
+    }
+    Ok(lines)
+}
+
+// The sources recorded in a merged cache's metadata; empty for other caches
+pub fn merged_sources(metadata: &str) -> Vec<MergedSource> {
+    metadata.lines()
+        .filter_map(|line| line.strip_prefix("merged_source: "))
+        .filter_map(|json| serde_json::from_str(json).ok())
+        .collect()
+}
+
+// Whether every source of a merged cache is unchanged, each checked like a cache of its
+// own; None if the metadata is not a merged cache's
+pub fn merged_is_fresh(metadata: &str, policy: ValidationPolicy, cache_modified: SystemTime) -> Option<bool> {
+    let sources = merged_sources(metadata);
+    if sources.is_empty() {
+        return None;
+    }
+    Some(sources.iter().all(|source| {
+        let mut recorded = format!("source_files: {}\n", source.source_files);
+        if let Some(hash) = &source.source_hash {
+            recorded.push_str(&format!("source_hash: {}\n", hash));
+        }
+        source.path.exists() && is_fresh(&source.path, policy, &recorded, cache_modified)
+    }))
+}
+
+// Whether the source is unchanged since a cache was written at `cache_modified`, with
+// `metadata` the cache's metadata text
+pub fn is_fresh(source_path: &Path, policy: ValidationPolicy, metadata: &str, cache_modified: SystemTime) -> bool {
+    let value_of = |key: &str| metadata.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(": "));
+    let dir_modified = fs::metadata(source_path)
+        .and_then(|m| m.modified())
+        .unwrap_or(UNIX_EPOCH);
+    if dir_modified >= cache_modified {
+        return false;
+    }
+    match policy {
+        ValidationPolicy::DirMtime => true,
+        ValidationPolicy::DeepMtime => deep_fresh(source_path, value_of("source_files"), cache_modified),
+        ValidationPolicy::ContentHash => match value_of("source_hash") {
+            Some(recorded) => content::content_key(source_path).is_ok_and(|key| key == recorded),
+            None => deep_fresh(source_path, value_of("source_files"), cache_modified),
+        },
+    }
+}
+
+fn deep_fresh(source_path: &Path, recorded_files: Option<&str>, cache_modified: SystemTime) -> bool {
+    if recorded_files.is_some_and(|recorded| recorded != key_file_stamps(source_path)) {
+        return false;
+    }
+    newest_below(source_path).is_ok_and(|newest| newest < cache_modified)
+}
+
+// "analysis.tdf=<bytes>@<mtime ns>;..." for the key files present, in KEY_FILES order
+fn key_file_stamps(source_path: &Path) -> String {
+    KEY_FILES.iter()
+        .filter_map(|name| {
+            let metadata = fs::metadata(source_path.join(name)).ok()?;
+            let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            Some(format!("{}={}@{}", name, metadata.len(), modified.as_nanos()))
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// Newest mtime of anything below `dir`, subfolders included
+fn newest_below(dir: &Path) -> io::Result<SystemTime> {
+    let mut newest = UNIX_EPOCH;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            newest = newest.max(metadata.modified()?);
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+    Ok(newest)
+}
@@ -0,0 +1,140 @@
+// File: src/health.rs
+//
+// Health of a cache directory for services exposing the cache (CacheManager::health), e.g.
+// behind a readiness probe. Every check is cheap enough to run every few seconds: no data
+// file is read, the manifest is compared with the directory by file sizes only (checksums
+// are for `verify`), and the lock is probed without waiting for it. A degraded check
+// leaves the cache usable (low space, a stale lock the next writer takes over, manifest
+// drift a rebuild fixes); a failed one means saves or loads will fail, and the service
+// isn't ready.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+use crate::fdlimit;
+use crate::manifest::{LockState, Manifest};
+use crate::space;
+
+// Below this a save fails on its metadata and bookkeeping files, whatever its size
+const MIN_FREE_BYTES: u64 = 64 * 1024 * 1024;
+// Below this most saves of a real acquisition won't fit
+const LOW_FREE_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str, // "writable", "free_space", "manifest_lock" or "manifest"
+    pub status: HealthStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub cache_dir: PathBuf,
+    pub status: HealthStatus, // The worst of the checks
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    // Read-only caches skip the writability and lock checks: nothing is ever written
+    pub fn check(cache_dir: &Path, read_only: bool) -> Self {
+        let checks = vec![
+            check_writable(cache_dir, read_only),
+            check_free_space(cache_dir, read_only),
+            check_lock(cache_dir, read_only),
+            check_manifest(cache_dir),
+        ];
+        let status = checks.iter().map(|check| check.status).max().unwrap_or(HealthStatus::Ok);
+        Self { cache_dir: cache_dir.to_path_buf(), status, checks }
+    }
+
+    // For readiness probes: degraded caches still serve saves and loads
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthStatus::Failed
+    }
+}
+
+fn check(name: &'static str, status: HealthStatus, detail: impl Into<String>) -> HealthCheck {
+    HealthCheck { name, status, detail: detail.into() }
+}
+
+// A probe file created, written and removed, as a save's first file would be
+fn check_writable(cache_dir: &Path, read_only: bool) -> HealthCheck {
+    if !cache_dir.is_dir() {
+        return check("writable", HealthStatus::Failed, format!("{} is not a directory", cache_dir.display()));
+    }
+    if read_only {
+        return check("writable", HealthStatus::Ok, "read-only by configuration");
+    }
+    let probe = cache_dir.join(format!(".health-{}.probe", std::process::id()));
+    let written = fdlimit::create(&probe)
+        .and_then(|mut file| file.write_all(b"ok"));
+    let _ = fs::remove_file(&probe);
+    match written {
+        Ok(()) => check("writable", HealthStatus::Ok, "probe file written"),
+        Err(e) => check("writable", HealthStatus::Failed, format!("can't write to {}: {}", cache_dir.display(), e)),
+    }
+}
+
+fn check_free_space(cache_dir: &Path, read_only: bool) -> HealthCheck {
+    if read_only {
+        return check("free_space", HealthStatus::Ok, "read-only by configuration");
+    }
+    let available = match space::available_bytes(cache_dir) {
+        Ok(available) => available,
+        Err(e) => return check("free_space", HealthStatus::Degraded, format!("unknown: {}", e)),
+    };
+    let detail = format!("{:.2} GB available", available as f64 / 1e9);
+    match available {
+        bytes if bytes < MIN_FREE_BYTES => check("free_space", HealthStatus::Failed, detail),
+        bytes if bytes < LOW_FREE_BYTES => check("free_space", HealthStatus::Degraded, detail),
+        _ => check("free_space", HealthStatus::Ok, detail),
+    }
+}
+
+fn check_lock(cache_dir: &Path, read_only: bool) -> HealthCheck {
+    if read_only {
+        return check("manifest_lock", HealthStatus::Ok, "read-only by configuration");
+    }
+    match Manifest::probe_lock(cache_dir) {
+        Ok(LockState::Free) => check("manifest_lock", HealthStatus::Ok, "free"),
+        Ok(LockState::Held(age)) => check("manifest_lock", HealthStatus::Ok, format!("held by a writer for {:.1}s", age.as_secs_f32())),
+        Ok(LockState::Stale(age)) => check(
+            "manifest_lock",
+            HealthStatus::Degraded,
+            format!("stale for {:.0}s, left by a crashed writer; the next save takes it over", age.as_secs_f32()),
+        ),
+        Err(e) => check("manifest_lock", HealthStatus::Failed, format!("can't take the manifest lock: {}", e)),
+    }
+}
+
+// Listed files that are missing or changed size since they were recorded
+fn check_manifest(cache_dir: &Path) -> HealthCheck {
+    let path = cache_dir.join(Manifest::FILE_NAME);
+    let manifest = match Manifest::read(cache_dir) {
+        Some(manifest) => manifest,
+        None if path.exists() => return check("manifest", HealthStatus::Failed, "manifest.json can't be parsed; rebuild_manifest() re-creates it"),
+        None => return check("manifest", HealthStatus::Ok, "no manifest yet; listing the cache creates it"),
+    };
+    let files = manifest.sources.values().flat_map(|entry| entry.files.iter());
+    let drifted: Vec<&str> = files.clone()
+        .filter(|file| fs::metadata(cache_dir.join(&file.name)).map_or(true, |meta| meta.len() != file.bytes))
+        .map(|file| file.name.as_str())
+        .collect();
+    match drifted.first() {
+        None => check("manifest", HealthStatus::Ok, format!("{} source(s), {} file(s) present", manifest.sources.len(), files.count())),
+        Some(first) => check(
+            "manifest",
+            HealthStatus::Degraded,
+            format!("{} listed file(s) missing or resized (first: {}); rebuild_manifest() re-reads the directory", drifted.len(), first),
+        ),
+    }
+}
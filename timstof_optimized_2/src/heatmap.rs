@@ -0,0 +1,181 @@
+// File: src/heatmap.rs
+//
+// Multi-resolution binned intensity grids of the MS1 points for interactive viewers, built
+// once at save time (CacheConfig::heatmap) because binning the full point cloud per view
+// is far too slow. Two maps, rt × m/z and rt × mobility, each a pyramid like map tiles:
+// level 0 is COARSEST_BINS square, every level doubles both axes, and the finest level is
+// binned from the points while coarser ones sum 2×2 blocks of the next. load_heatmap()
+// crops one level to a viewport. Bins span the data's own ranges at save time.
+use std::io;
+use std::path::Path;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::encryption::EncryptionKey;
+use crate::precision::Float;
+use crate::sidecar;
+use crate::utils::IndexedTimsTOFData;
+
+const MAGIC: &[u8; 8] = b"TSHEATM1";
+
+pub const COARSEST_BINS: usize = 64;
+pub const LEVELS: usize = 5; // Up to 1024 × 1024 bins
+
+// The second axis of a map; the first is always rt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeatmapAxis {
+    Mz,
+    Mobility,
+}
+
+// Region to load: rt in minutes, `y` in units of `axis`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub axis: HeatmapAxis,
+    pub rt: (f32, f32),
+    pub y: (f32, f32),
+}
+
+// The bins of one level overlapping a viewport
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    pub level: usize,
+    pub rt_range: (f32, f32), // Outer edges of the returned bins
+    pub y_range: (f32, f32),
+    pub rt_bins: usize,
+    pub y_bins: usize,
+    pub intensities: Vec<f32>, // Summed per bin; rt_bins rows of y_bins
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapPyramid {
+    rt_range: (f32, f32),
+    mz_range: (f32, f32),
+    mobility_range: (f32, f32),
+    mz_levels: Vec<Vec<f32>>, // Coarsest first, each (COARSEST_BINS << level)² bins
+    mobility_levels: Vec<Vec<f32>>,
+}
+
+impl HeatmapPyramid {
+    pub fn build<F: Float>(ms1: &IndexedTimsTOFData<F>) -> Self {
+        let rt_range = value_range(&ms1.rt_values_min);
+        let mz_range = value_range(&ms1.mz_values);
+        let mobility_range = value_range(&ms1.mobility_values);
+        let pyramid = |values: &[F], range: (f32, f32)| {
+            let mut levels = vec![bin(ms1, values, rt_range, range)];
+            while levels.len() < LEVELS {
+                let coarser = downsample(&levels[0], COARSEST_BINS << (LEVELS - levels.len()));
+                levels.insert(0, coarser);
+            }
+            levels
+        };
+        Self {
+            mz_levels: pyramid(&ms1.mz_values, mz_range),
+            mobility_levels: pyramid(&ms1.mobility_values, mobility_range),
+            rt_range,
+            mz_range,
+            mobility_range,
+        }
+    }
+
+    pub fn crop(&self, level: usize, viewport: &Viewport) -> io::Result<Heatmap> {
+        let (levels, y_range) = match viewport.axis {
+            HeatmapAxis::Mz => (&self.mz_levels, self.mz_range),
+            HeatmapAxis::Mobility => (&self.mobility_levels, self.mobility_range),
+        };
+        let grid = levels.get(level).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("heatmap level {} does not exist (levels 0..={})", level, levels.len() - 1),
+        ))?;
+        let bins = COARSEST_BINS << level;
+        let rt = bin_span(self.rt_range, viewport.rt, bins);
+        let y = bin_span(y_range, viewport.y, bins);
+        let intensities = rt.clone()
+            .flat_map(|row| grid[row * bins + y.start..row * bins + y.end].iter().copied())
+            .collect();
+        Ok(Heatmap {
+            level,
+            rt_range: (edge(self.rt_range, bins, rt.start), edge(self.rt_range, bins, rt.end)),
+            y_range: (edge(y_range, bins, y.start), edge(y_range, bins, y.end)),
+            rt_bins: rt.len(),
+            y_bins: y.len(),
+            intensities,
+        })
+    }
+
+    pub fn write(&self, path: &Path, key: Option<&EncryptionKey>) -> io::Result<()> {
+        sidecar::write(path, MAGIC, self, key)
+    }
+
+    pub fn read(path: &Path, key: Option<&EncryptionKey>) -> io::Result<Self> {
+        sidecar::read(path, MAGIC, key)
+    }
+}
+
+// Finite min / max, (0, 0) without finite values
+fn value_range<F: Float>(values: &[F]) -> (f32, f32) {
+    let (min, max) = values.par_iter()
+        .map(|value| value.to_f32())
+        .filter(|value| value.is_finite())
+        .fold(|| (f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)))
+        .reduce(|| (f32::INFINITY, f32::NEG_INFINITY), |a, b| (a.0.min(b.0), a.1.max(b.1)));
+    if min <= max { (min, max) } else { (0.0, 0.0) }
+}
+
+// Bin of `value` among `bins` over `range`; the maximum falls in the last bin
+fn bin_of(value: f32, range: (f32, f32), bins: usize) -> Option<usize> {
+    if !value.is_finite() {
+        return None;
+    }
+    let width = range.1 - range.0;
+    match width > 0.0 {
+        true => Some((((value - range.0) / width * bins as f32) as usize).min(bins - 1)),
+        false => Some(0),
+    }
+}
+
+// The finest level: intensities summed per (rt, y) bin, each thread into its own grid
+fn bin<F: Float>(ms1: &IndexedTimsTOFData<F>, y_values: &[F], rt_range: (f32, f32), y_range: (f32, f32)) -> Vec<f32> {
+    let bins = COARSEST_BINS << (LEVELS - 1);
+    // Each split allocates a full grid, so splits are large
+    (0..ms1.mz_values.len()).into_par_iter()
+        .with_min_len(1 << 20)
+        .fold(|| vec![0.0f32; bins * bins], |mut grid, row| {
+            let rt = bin_of(ms1.rt_values_min[row].to_f32(), rt_range, bins);
+            let y = bin_of(y_values[row].to_f32(), y_range, bins);
+            if let (Some(rt), Some(y)) = (rt, y) {
+                grid[rt * bins + y] += ms1.intensity_values[row] as f32;
+            }
+            grid
+        })
+        .reduce(|| vec![0.0f32; bins * bins], |mut a, b| {
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+            a
+        })
+}
+
+// Sum 2×2 blocks of a `bins` square grid
+fn downsample(grid: &[f32], bins: usize) -> Vec<f32> {
+    let half = bins / 2;
+    (0..half * half).into_par_iter()
+        .map(|i| {
+            let (row, column) = (2 * (i / half), 2 * (i % half));
+            grid[row * bins + column] + grid[row * bins + column + 1]
+                + grid[(row + 1) * bins + column] + grid[(row + 1) * bins + column + 1]
+        })
+        .collect()
+}
+
+// Bins overlapping `view` (empty if it misses `range`)
+fn bin_span(range: (f32, f32), view: (f32, f32), bins: usize) -> std::ops::Range<usize> {
+    if !(view.0 <= range.1 && view.1 >= range.0) {
+        return 0..0;
+    }
+    let first = bin_of(view.0.max(range.0), range, bins).unwrap_or(0);
+    let last = bin_of(view.1.min(range.1), range, bins).unwrap_or(bins - 1);
+    first..last + 1
+}
+
+fn edge(range: (f32, f32), bins: usize, bin: usize) -> f32 {
+    range.0 + (range.1 - range.0) * bin as f32 / bins as f32
+}
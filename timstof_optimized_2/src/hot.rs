@@ -0,0 +1,91 @@
+// File: src/hot.rs
+//
+// In-process layer over load_indexed_data: decoded sources stay in memory behind an Arc,
+// so repeated loads of one source within a process share a single copy instead of
+// reading and decoding the cache again. An entry remembers which version of the cache it
+// came from (the metadata file's size and mtime) and is dropped when that changes. Past
+// `capacity` sources the least recently used one goes.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use dashmap::DashMap;
+
+use crate::utils::IndexedTimsTOFData;
+use crate::window::Ms2Window;
+
+pub type LoadedData<F = f32> = (IndexedTimsTOFData<F>, Ms2Windows<F>);
+pub type Ms2Windows<F = f32> = Vec<Ms2Window<F>>;
+
+// Version of a cache: size and modification time of its metadata file
+pub type Stamp = (u64, Option<SystemTime>);
+
+struct HotEntry {
+    data: Arc<LoadedData>,
+    stamp: Stamp,
+    last_used: AtomicU64,
+}
+
+pub struct HotCache {
+    entries: DashMap<PathBuf, HotEntry>,
+    capacity: usize, // Sources kept; 0 keeps none
+    clock: AtomicU64,
+}
+
+impl HotCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: DashMap::new(), capacity, clock: AtomicU64::new(0) }
+    }
+
+    // The data loaded for `source`, if it is still from the cache version `stamp`
+    pub fn get(&self, source: &Path, stamp: &Stamp) -> Option<Arc<LoadedData>> {
+        let entry = self.entries.get(source)?;
+        if entry.stamp != *stamp {
+            drop(entry); // Removing while holding the shard's read lock would deadlock
+            self.entries.remove_if(source, |_, entry| entry.stamp != *stamp);
+            return None;
+        }
+        entry.last_used.store(self.tick(), Ordering::Relaxed);
+        Some(Arc::clone(&entry.data))
+    }
+
+    // Two threads missing on the same source both load it; the later insert wins, which
+    // costs a duplicate load but never blocks other sources behind a long one
+    pub fn insert(&self, source: PathBuf, stamp: Stamp, data: Arc<LoadedData>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let last_used = AtomicU64::new(self.tick());
+        self.entries.insert(source, HotEntry { data, stamp, last_used });
+        while self.entries.len() > self.capacity {
+            let oldest = self.entries.iter()
+                .min_by_key(|entry| entry.last_used.load(Ordering::Relaxed))
+                .map(|entry| entry.key().clone());
+            match oldest {
+                Some(oldest) => { self.entries.remove(&oldest); }
+                None => break,
+            }
+        }
+    }
+
+    // Callers holding the Arc keep their copy; the memory goes when the last one drops it
+    pub fn remove(&self, source: &Path) -> bool {
+        self.entries.remove(source).is_some()
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,241 @@
+// File: src/input.rs
+//
+// Checks of the data handed to a save, before anything is written. Columns of different
+// lengths used to be persisted as they were and only panicked later, in whatever load
+// indexed past the shortest one; MS1 out of m/z order was caught by the layout check
+// after the whole save. Both now fail up front with the part, column and row at fault.
+// Before that, NaN and infinite floats (converters occasionally emit NaN mobilities, which
+// break binary searches after load) are handled by CacheConfig::non_finite, and MS1
+// out of m/z order is sorted first when CacheConfig::sort asks for it.
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::columnar;
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::Ms2Window as Window;
+
+// Returned (boxed) by saves of inconsistent data; downcast to tell it from other errors
+#[derive(Debug, Clone)]
+pub struct InvalidInput {
+    pub part: String,        // "ms1" or "ms2 window 400.000-425.000"
+    pub field: &'static str, // Column at fault
+    pub index: usize,        // First offending row; for a length mismatch, the column's length
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid input in {}: {} at index {}: {}", self.part, self.field, self.index, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidInput {}
+
+// What a save does with NaN or infinite rt, mobility or m/z values
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NonFinitePolicy {
+    #[default]
+    Keep,             // Store them as given (m/z still has to pass the order check)
+    Reject,           // Fail with InvalidInput at the first one
+    DropRows,         // Leave out every row holding one
+    ReplaceWith(f32), // Store this value instead; m/z replaced out of order fails the order check
+}
+
+impl NonFinitePolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "keep" => Ok(Self::Keep),
+            "reject" => Ok(Self::Reject),
+            "drop-rows" => Ok(Self::DropRows),
+            other => match other.strip_prefix("replace:").map(str::parse::<f32>) {
+                Some(Ok(value)) if value.is_finite() => Ok(Self::ReplaceWith(value)),
+                Some(_) => Err(format!("replacement in {} must be a finite number", other)),
+                None => Err(format!("unknown non-finite policy {} (keep, reject, drop-rows, replace:<value>)", other)),
+            },
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            NonFinitePolicy::Keep => "keep".to_string(),
+            NonFinitePolicy::Reject => "reject".to_string(),
+            NonFinitePolicy::DropRows => "drop-rows".to_string(),
+            NonFinitePolicy::ReplaceWith(value) => format!("replace:{}", value),
+        }
+    }
+}
+
+// What a save does with MS1 that is not sorted by m/z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortPolicy {
+    #[default]
+    Verify, // Fail with InvalidInput at the first row out of order
+    Sort,   // Sort all columns by m/z (stable, in parallel) before sharding
+}
+
+impl SortPolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "verify" => Ok(Self::Verify),
+            "sort" => Ok(Self::Sort),
+            other => Err(format!("unknown sort policy {} (verify, sort)", other)),
+        }
+    }
+}
+
+// MS1 in m/z order under SortPolicy::Sort, and whether it had to be sorted. Under
+// Verify it is left to check_save_input.
+pub fn sort_ms1<F: Float>(ms1: Cow<'_, IndexedTimsTOFData<F>>, policy: SortPolicy) -> (Cow<'_, IndexedTimsTOFData<F>>, bool) {
+    if policy == SortPolicy::Verify || is_sorted_by_mz(&ms1.mz_values) {
+        return (ms1, false);
+    }
+    (Cow::Owned(columnar::sort_by_mz(&ms1)), true)
+}
+
+fn is_sorted_by_mz<F: Float>(mz_values: &[F]) -> bool {
+    mz_values.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+// Save input after the non-finite policy, borrowed where nothing had to change. The
+// shapes must have been checked (check_shapes).
+pub struct Scrubbed<'a, F: Float> {
+    pub ms1: Cow<'a, IndexedTimsTOFData<F>>,
+    pub ms2: Cow<'a, [Window<F>]>,
+    pub rows: usize, // Rows dropped or with a value replaced, MS1 and MS2 together
+}
+
+pub fn scrub_non_finite<'a, F: Float>(
+    ms1_indexed: &'a IndexedTimsTOFData<F>,
+    ms2_indexed_pairs: &'a [Window<F>],
+    policy: NonFinitePolicy,
+) -> Result<Scrubbed<'a, F>, InvalidInput> {
+    if policy == NonFinitePolicy::Keep {
+        return Ok(Scrubbed { ms1: Cow::Borrowed(ms1_indexed), ms2: Cow::Borrowed(ms2_indexed_pairs), rows: 0 });
+    }
+    let (ms1, mut rows) = scrub("ms1", ms1_indexed, policy)?;
+    let mut windows = Vec::with_capacity(ms2_indexed_pairs.len());
+    for (window, data) in ms2_indexed_pairs {
+        let (data, affected) = scrub(&format!("ms2 window {}", window), data, policy)?;
+        rows += affected;
+        windows.push((*window, data));
+    }
+    let ms2 = match windows.iter().all(|(_, data)| matches!(data, Cow::Borrowed(_))) {
+        true => Cow::Borrowed(ms2_indexed_pairs),
+        false => Cow::Owned(windows.into_iter().map(|(window, data)| (window, data.into_owned())).collect()),
+    };
+    Ok(Scrubbed { ms1, ms2, rows })
+}
+
+// Columns must be of equal length (check_shapes)
+fn scrub<'a, F: Float>(part: &str, data: &'a IndexedTimsTOFData<F>, policy: NonFinitePolicy) -> Result<(Cow<'a, IndexedTimsTOFData<F>>, usize), InvalidInput> {
+    let is_bad = |value: &F| !value.to_f64().is_finite();
+    let columns = [
+        ("rt_values_min", &data.rt_values_min),
+        ("mobility_values", &data.mobility_values),
+        ("mz_values", &data.mz_values),
+    ];
+    let bad: Vec<bool> = (0..data.mz_values.len())
+        .map(|row| columns.iter().any(|(_, values)| is_bad(&values[row])))
+        .collect();
+    let Some(index) = bad.iter().position(|&bad| bad) else {
+        return Ok((Cow::Borrowed(data), 0));
+    };
+    let (field, values) = *columns.iter().find(|(_, values)| is_bad(&values[index])).unwrap();
+    let affected = bad.iter().filter(|&&bad| bad).count();
+    match policy {
+        NonFinitePolicy::Keep => Ok((Cow::Borrowed(data), 0)),
+        NonFinitePolicy::Reject => Err(InvalidInput {
+            part: part.to_string(),
+            field,
+            index,
+            reason: format!("{:?} is not finite ({} rows hold NaN or infinite values)", values[index], affected),
+        }),
+        NonFinitePolicy::DropRows => {
+            let kept: Vec<usize> = (0..bad.len()).filter(|&row| !bad[row]).collect();
+            let pick = |values: &[F]| -> Vec<F> { kept.iter().map(|&row| values[row]).collect() };
+            let pick_u32 = |values: &[u32]| -> Vec<u32> { kept.iter().map(|&row| values[row]).collect() };
+            Ok((Cow::Owned(IndexedTimsTOFData {
+                rt_values_min: pick(&data.rt_values_min),
+                mobility_values: pick(&data.mobility_values),
+                mz_values: pick(&data.mz_values),
+                intensity_values: pick_u32(&data.intensity_values),
+                frame_indices: pick_u32(&data.frame_indices),
+                scan_indices: pick_u32(&data.scan_indices),
+            }), affected))
+        }
+        NonFinitePolicy::ReplaceWith(replacement) => {
+            let replace = |values: &[F]| -> Vec<F> {
+                values.iter().map(|value| if is_bad(value) { F::from_f32(replacement) } else { *value }).collect()
+            };
+            Ok((Cow::Owned(IndexedTimsTOFData {
+                rt_values_min: replace(&data.rt_values_min),
+                mobility_values: replace(&data.mobility_values),
+                mz_values: replace(&data.mz_values),
+                intensity_values: data.intensity_values.clone(),
+                frame_indices: data.frame_indices.clone(),
+                scan_indices: data.scan_indices.clone(),
+            }), affected))
+        }
+    }
+}
+
+// Column lengths of MS1 and every MS2 window, and the m/z order of MS1 that sharding and
+// range loads rely on. MS2 windows are stored as given, so their order is not checked.
+pub fn check_save_input<F: Float>(
+    ms1_indexed: &IndexedTimsTOFData<F>,
+    ms2_indexed_pairs: &[Window<F>],
+) -> Result<(), InvalidInput> {
+    check_shapes(ms1_indexed, ms2_indexed_pairs)?;
+    check_mz_order("ms1", &ms1_indexed.mz_values)
+}
+
+// check_save_input without the order check, for data still to be scrubbed or sorted
+pub fn check_shapes<F: Float>(
+    ms1_indexed: &IndexedTimsTOFData<F>,
+    ms2_indexed_pairs: &[Window<F>],
+) -> Result<(), InvalidInput> {
+    check_lengths("ms1", ms1_indexed)?;
+    for (window, data) in ms2_indexed_pairs {
+        let part = format!("ms2 window {}", window);
+        if matches!(window.mz_low.partial_cmp(&window.mz_high), Some(Ordering::Greater) | None) {
+            return Err(InvalidInput { part, field: "isolation_window", index: 0, reason: format!("bounds {}-{} are not ordered", window.mz_low, window.mz_high) });
+        }
+        check_lengths(&part, data)?;
+    }
+    Ok(())
+}
+
+fn check_lengths<F: Float>(part: &str, data: &IndexedTimsTOFData<F>) -> Result<(), InvalidInput> {
+    let rows = data.mz_values.len();
+    let lengths = [
+        ("rt_values_min", data.rt_values_min.len()),
+        ("mobility_values", data.mobility_values.len()),
+        ("intensity_values", data.intensity_values.len()),
+        ("frame_indices", data.frame_indices.len()),
+        ("scan_indices", data.scan_indices.len()),
+    ];
+    match lengths.into_iter().find(|&(_, len)| len != rows) {
+        Some((field, len)) => Err(InvalidInput {
+            part: part.to_string(),
+            field,
+            index: len,
+            reason: format!("has {} values, mz_values has {}", len, rows),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn check_mz_order<F: Float>(part: &str, mz_values: &[F]) -> Result<(), InvalidInput> {
+    let Some(row) = mz_values.windows(2).position(|pair| matches!(pair[0].partial_cmp(&pair[1]), Some(Ordering::Greater) | None)) else {
+        return Ok(());
+    };
+    let (previous, value) = (mz_values[row], mz_values[row + 1]);
+    let (index, reason) = match (previous.to_f64().is_nan(), value.to_f64().is_nan()) {
+        (true, _) => (row, format!("m/z {:?} is not a number", previous)),
+        (false, true) => (row + 1, format!("m/z {:?} is not a number", value)),
+        (false, false) => (row + 1, format!("m/z {:?} is below the previous {:?}; sort by m/z before saving", value, previous)),
+    };
+    Err(InvalidInput { part: part.to_string(), field: "mz_values", index, reason })
+}
@@ -0,0 +1,80 @@
+// File: src/instrument.rs
+//
+// Acquisition metadata of a .d folder (instrument, serial number, acquisition date, method),
+// read from the GlobalMetadata table of analysis.tdf at save time and recorded in the cache
+// metadata ("instrument" line, JSON), so a cache still says where its data came from after
+// the raw folder has been archived. Capture is best effort: a folder without a readable
+// analysis.tdf (synthetic data, merged caches) is saved without it.
+use std::path::Path;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Serialize, Deserialize};
+use crate::telemetry;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentInfo {
+    pub instrument: Option<String>, // Model, e.g. "timsTOF Pro"
+    pub serial: Option<String>,
+    pub acquired: Option<String>,   // As recorded, ISO 8601 with offset
+    pub method: Option<String>,
+    pub software: Option<String>,   // Acquisition software and version
+    pub sample: Option<String>,
+    pub operator: Option<String>,
+}
+
+impl InstrumentInfo {
+    // None without analysis.tdf or if none of the keys are present
+    pub fn read(d_folder: &Path) -> Option<Self> {
+        let tdf_path = d_folder.join("analysis.tdf");
+        if !tdf_path.is_file() {
+            return None;
+        }
+        match read_global_metadata(&tdf_path) {
+            Ok(pairs) => {
+                let value = |key: &str| pairs.iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.trim().to_string())
+                    .filter(|v| !v.is_empty());
+                let software = match (value("AcquisitionSoftware"), value("AcquisitionSoftwareVersion")) {
+                    (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+                    (name, _) => name,
+                };
+                let info = Self {
+                    instrument: value("InstrumentName"),
+                    serial: value("InstrumentSerialNumber"),
+                    acquired: value("AcquisitionDateTime"),
+                    method: value("MethodName"),
+                    software,
+                    sample: value("SampleName"),
+                    operator: value("OperatorName"),
+                };
+                (info != Self::default()).then_some(info)
+            }
+            Err(e) => {
+                telemetry::warn(&format!("⚠️  No acquisition metadata from {}: {}", tdf_path.display(), e));
+                None
+            }
+        }
+    }
+
+    // One line for listings: "timsTOF Pro #1894, 2023-05-04T10:12:00+02:00, dia-PASEF.m"
+    pub fn describe(&self) -> String {
+        let instrument = match (&self.instrument, &self.serial) {
+            (Some(name), Some(serial)) => Some(format!("{} #{}", name, serial)),
+            (Some(name), None) => Some(name.clone()),
+            (None, serial) => serial.as_ref().map(|serial| format!("#{}", serial)),
+        };
+        [instrument, self.acquired.clone(), self.method.clone()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Every Key / Value of the GlobalMetadata table, values as text
+fn read_global_metadata(tdf_path: &Path) -> rusqlite::Result<Vec<(String, String)>> {
+    let connection = Connection::open_with_flags(tdf_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    let mut statement = connection.prepare("SELECT Key, CAST(Value AS TEXT) FROM GlobalMetadata")?;
+    let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?.unwrap_or_default())))?;
+    rows.collect()
+}
@@ -0,0 +1,67 @@
+// File: src/iomode.rs
+//
+// How container loads read shard columns (CacheConfig::io_mode): positional reads into
+// buffers, or copies out of a memory mapping of the whole file. Mapping saves a syscall
+// and a kernel copy per column, which pays off for large files of raw columns; compressed
+// columns are read once into a buffer for the decoder anyway, and a mapping bigger than
+// the free memory only turns reads into page faults under memory pressure. Auto decides
+// per file from its size, how much of it is stored raw, and the memory available now.
+// Some filesystems refuse mappings (overlayfs setups in containers, SELinux policies): a
+// file that can't be mapped is read with positional reads instead, in any mode, and the
+// load's profile and report say so (fallback_note).
+use std::path::Path;
+use sysinfo::{System, SystemExt};
+
+use crate::columnar::ContainerHeader;
+
+// Files smaller than this are read, whatever they hold: mapping costs more than it saves
+const MMAP_MIN_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMode {
+    #[default]
+    Auto,     // Map large, mostly raw files that fit in half the available memory
+    Buffered, // Always positional reads (retried on transient errors, see retry.rs)
+    Mmap,     // Always map where the filesystem allows; reads of a mapped file are not retried
+}
+
+impl IoMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "buffered" => Ok(Self::Buffered),
+            "mmap" => Ok(Self::Mmap),
+            other => Err(format!("unknown I/O mode {} (auto, buffered, mmap)", other)),
+        }
+    }
+
+    // Whether to map a container of `file_len` bytes with this header
+    pub fn use_mmap(self, file_len: u64, header: &ContainerHeader) -> bool {
+        match self {
+            IoMode::Buffered => false,
+            IoMode::Mmap => true,
+            IoMode::Auto => {
+                let (raw, total) = header.segments.iter()
+                    .flat_map(|segment| segment.columns.iter())
+                    .fold((0u64, 0u64), |(raw, total), column| match column.encoding == "raw" {
+                        true => (raw + column.stored_len, total + column.stored_len),
+                        false => (raw, total + column.stored_len),
+                    });
+                file_len >= MMAP_MIN_BYTES && raw * 2 >= total && file_len <= available_memory() / 2
+            }
+        }
+    }
+}
+
+// What the profile and report of a load record when `path` couldn't be mapped
+pub fn fallback_note(path: &Path, error: &std::io::Error) -> String {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    format!("mmap of {} failed, read buffered: {}", name, error)
+}
+
+// Memory the system can give out without swapping, in bytes
+fn available_memory() -> u64 {
+    let mut system = System::new();
+    system.refresh_memory();
+    system.available_memory()
+}
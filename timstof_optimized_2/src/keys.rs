@@ -0,0 +1,95 @@
+// File: src/keys.rs
+//
+// How a source maps to the name its cache files start with (CacheManager::with_key_strategy).
+// The default keys by location. Pipelines that already identify their samples (a sample
+// UUID from a LIMS, say) can key by that instead, so a sample finds its cache on any machine
+// and under any mount point. Keys end up in file names and remote object names, so they are
+// checked: ASCII letters, digits, '.', '_' and '-', not starting with '.', at most 128
+// characters. Content-addressed mode (CacheConfig::content_addressed) overrides any strategy.
+use std::io;
+use std::path::Path;
+
+use crate::content;
+
+const MAX_KEY_LEN: usize = 128;
+
+pub trait CacheKeyStrategy: Send + Sync {
+    fn describe(&self) -> String;
+    // The same for every call with the same source, different for sources that differ
+    fn key(&self, source_path: &Path) -> io::Result<String>;
+}
+
+// Folder name alone, e.g. "run_01.d": portable between machines, but folders of the same
+// name in different directories share one cache
+pub struct FileNameKey;
+
+// Folder name plus a hash of the absolute path (content::path_key); the default
+pub struct PathHashKey;
+
+// Hash of the folder's contents (content::content_key): survives moves and copies of the
+// source, but hashes it on every lookup. Unlike content-addressed mode, copies don't share
+// a reference-counted cache; each clear removes it.
+pub struct ContentHashKey;
+
+// Keys the caller supplies, e.g. `UserKey(|path| sample_ids.get(path).cloned())`. Sources
+// without one are cached by location.
+pub struct UserKey<F>(pub F)
+where
+    F: Fn(&Path) -> Option<String> + Send + Sync;
+
+impl CacheKeyStrategy for FileNameKey {
+    fn describe(&self) -> String {
+        "file name".to_string()
+    }
+
+    fn key(&self, source_path: &Path) -> io::Result<String> {
+        Ok(content::safe_file_name(source_path))
+    }
+}
+
+impl CacheKeyStrategy for PathHashKey {
+    fn describe(&self) -> String {
+        "path hash".to_string()
+    }
+
+    fn key(&self, source_path: &Path) -> io::Result<String> {
+        Ok(content::path_key(source_path))
+    }
+}
+
+impl CacheKeyStrategy for ContentHashKey {
+    fn describe(&self) -> String {
+        "content hash".to_string()
+    }
+
+    fn key(&self, source_path: &Path) -> io::Result<String> {
+        content::content_key(source_path)
+    }
+}
+
+impl<F> CacheKeyStrategy for UserKey<F>
+where
+    F: Fn(&Path) -> Option<String> + Send + Sync,
+{
+    fn describe(&self) -> String {
+        "user-supplied".to_string()
+    }
+
+    fn key(&self, source_path: &Path) -> io::Result<String> {
+        (self.0)(source_path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no key supplied for this source"))
+    }
+}
+
+// `key` if it is usable as the start of a cache file name
+pub fn checked(key: String) -> io::Result<String> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && !key.starts_with('.')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    match valid {
+        true => Ok(key),
+        false => Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "cache key {:?} must be 1 to {} ASCII letters, digits, '.', '_' or '-', not starting with '.'", key, MAX_KEY_LEN
+        ))),
+    }
+}
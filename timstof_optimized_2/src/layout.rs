@@ -0,0 +1,58 @@
+// File: src/layout.rs
+//
+// RT-major copy: an optional sidecar container (CacheConfig::rt_major) holding the points
+// of the cache a second time, sorted by frame, then scan, instead of by m/z. Spectrum
+// extraction wants an m/z range to be contiguous, chromatogram extraction a run of frames;
+// with both on disk each gets the locality it needs, for the price of a second copy.
+// Like the cache itself it keeps MS1 ("ms1") and every MS2 window ("ms2_window", with its
+// isolation bounds) in segments of their own, so a load gives back the same datasets, and
+// cuts them into segments of about frames::SEGMENT_POINTS on frame boundaries, recorded as
+// frame_min / frame_max. Within a scan the points stay in m/z order.
+use rayon::prelude::*;
+
+use crate::columnar::SegmentInput;
+use crate::frames;
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+
+// Physical order a load reads the points in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    #[default]
+    MzMajor, // The cache itself, every dataset sorted by m/z
+    RtMajor, // The RT-major copy, sorted by frame, then scan; needs CacheConfig::rt_major
+}
+
+// The points of `data` sorted by frame, then scan; stable, so m/z order is kept within a scan
+pub fn rt_sorted<F: Float>(data: &IndexedTimsTOFData<F>) -> IndexedTimsTOFData<F> {
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.par_sort_by_key(|&row| (data.frame_indices[row], data.scan_indices[row]));
+    let permute = |values: &[F]| order.par_iter().map(|&row| values[row]).collect::<Vec<_>>();
+    let permute_u32 = |values: &[u32]| order.par_iter().map(|&row| values[row]).collect::<Vec<_>>();
+    IndexedTimsTOFData {
+        rt_values_min: permute(&data.rt_values_min),
+        mobility_values: permute(&data.mobility_values),
+        mz_values: permute(&data.mz_values),
+        intensity_values: permute_u32(&data.intensity_values),
+        frame_indices: permute_u32(&data.frame_indices),
+        scan_indices: permute_u32(&data.scan_indices),
+    }
+}
+
+// Segments of RT-sorted MS1 and MS2 windows (rt_sorted), named and attributed like the
+// cache's own so the same grouping reads them back
+pub fn segments<'a, F>(
+    ms1_sorted: &'a IndexedTimsTOFData<F>,
+    ms2_sorted: &'a [(IsolationWindow, IndexedTimsTOFData<F>)],
+) -> Vec<SegmentInput<'a, F>> {
+    let mut segments = frames::segments("ms1", ms1_sorted);
+    for (window, data) in ms2_sorted {
+        segments.extend(frames::segments("ms2_window", data).into_iter().map(|mut segment| {
+            segment.attrs.insert("isolation_low".to_string(), window.mz_low as f64);
+            segment.attrs.insert("isolation_high".to_string(), window.mz_high as f64);
+            segment
+        }));
+    }
+    segments
+}
@@ -0,0 +1,68 @@
+// File: src/lib.rs
+// The cache as a library: the Rust API the two binaries are built on, and a C ABI
+// (cabi.rs) for embedding it in non-Rust programs. Build with `cargo build --release --lib`
+// to get libtimstof_cache_ffi.{so,a}.
+pub mod utils;
+pub mod cache;
+pub mod daemon;
+pub mod remote;
+mod columnar;
+pub mod encryption;
+pub mod signing;
+pub mod aliases;
+pub mod parquet_backend;
+pub mod scrub;
+pub mod profile;
+pub mod offload;
+pub mod mzml;
+mod content;
+pub mod reflink;
+pub mod compression;
+mod manifest;
+pub mod chunked;
+pub mod shm;
+pub mod hot;
+pub mod cancel;
+pub mod freshness;
+mod space;
+pub mod retry;
+pub mod quantize;
+mod simd;
+pub mod precision;
+pub mod mapped;
+pub mod frames;
+pub mod chromatogram;
+mod sidecar;
+pub mod heatmap;
+pub mod summary;
+pub mod diff;
+pub mod settings;
+pub mod instrument;
+pub mod keys;
+mod bundle;
+mod prefetch;
+pub mod iomode;
+pub mod durability;
+mod input;
+pub mod layout;
+pub mod scheme;
+pub mod calibration;
+pub mod snapshot;
+pub mod stream;
+pub mod window;
+pub mod provenance;
+pub mod telemetry;
+pub mod report;
+pub mod health;
+mod fdlimit;
+mod longpath;
+// Benchmark matrix and synthetic data; the hot-path entry points for the criterion
+// benches are behind the `bench` feature
+pub mod bench;
+// Data generators and backend round trips for property tests (tests/roundtrip.rs)
+pub mod testing;
+pub mod cabi;
+
+pub use cache::{CacheConfig, CacheFormat, CacheManager, GcOptions, GcReport};
+pub use daemon::{CacheDaemon, DaemonConfig};
+pub use remote::{DirectoryStore, HttpStore, RemoteStore};
@@ -0,0 +1,41 @@
+// File: src/longpath.rs
+//
+// Extended-length paths on Windows. Win32 file functions refuse paths longer than MAX_PATH
+// (260 characters) unless they carry the `\\?\` prefix, and cache files under a deep
+// project tree (a job directory several levels into a share, a long source name, a shard
+// suffix) go past it easily; the errors that follow ("The system cannot find the path
+// specified") don't mention the length. The cache directory is normalized once, when the
+// CacheManager is created, and every data, metadata and bookkeeping path is joined onto it.
+// Source paths are left as given: their names key the cache files. Elsewhere a no-op.
+use std::path::{Path, PathBuf};
+
+// `C:\dir` as `\\?\C:\dir`, `\\server\share\dir` as `\\?\UNC\server\share\dir`; relative
+// paths are made absolute first, since the prefix turns off the resolution of `.`, `..`
+// and the current directory. Paths already verbatim, device paths, and paths that can't
+// be made absolute are returned unchanged
+#[cfg(windows)]
+pub fn extended(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Component, Prefix};
+
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let Some(Component::Prefix(prefix)) = absolute.components().next() else {
+        return absolute;
+    };
+    let wide: Vec<u16> = absolute.as_os_str().encode_wide().collect();
+    let extended: Vec<u16> = match prefix.kind() {
+        Prefix::Disk(_) => r"\\?\".encode_utf16().chain(wide).collect(),
+        // Drop the leading `\\` of `\\server\share`
+        Prefix::UNC(..) => r"\\?\UNC\".encode_utf16().chain(wide.into_iter().skip(2)).collect(),
+        _ => return absolute,
+    };
+    PathBuf::from(OsString::from_wide(&extended))
+}
+
+#[cfg(not(windows))]
+pub fn extended(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
@@ -1,27 +1,22 @@
-mod utils;
-mod cache;
 mod processing;
 
-use cache::{CacheManager, CacheConfig};
-use utils::{
+use timstof_cache_ffi::cache::{CacheManager, CacheConfig};
+use timstof_cache_ffi::remote::{DirectoryStore, HttpStore, RemoteStore};
+use timstof_cache_ffi::telemetry;
+use timstof_cache_ffi::utils::{
     read_timstof_data, build_indexed_data, read_parquet_with_polars,
     library_records_to_dataframe, merge_library_and_report, get_unique_precursor_ids, 
-    process_library_fast, create_rt_im_dicts, build_lib_matrix, build_precursors_matrix_step1, 
-    build_precursors_matrix_step2, build_range_matrix_step3, build_precursors_matrix_step3, 
-    build_frag_info, LibCols, PrecursorLibData, prepare_precursor_lib_data
-};
-use processing::{
-    FastChunkFinder, build_intensity_matrix_optimized, prepare_precursor_features,
-    calculate_mz_range, extract_ms2_data, build_mask_matrices, extract_aligned_rt_values,
-    reshape_and_combine_matrices, create_final_dataframe, process_single_precursor
+    process_library_fast, create_rt_im_dicts, LibCols, prepare_precursor_lib_data
 };
+use processing::{FastChunkFinder, process_single_precursor};
 
 use rayon::prelude::*;
-use std::{error::Error, path::Path, time::Instant, env, fs::File};
-use ndarray::{Array2, Array3, Array4, s, Axis};
-use polars::prelude::*;
+use std::{error::Error, path::Path, time::Instant, env};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // Cache progress and warnings on the terminal
+    telemetry::set_log_hook(telemetry::print_to_terminal);
+    
     // Configurable parallel processing parameter
     let parallel_threads = 16; // Set to 1 for sequential, 2+ for parallel processing
     
@@ -47,11 +42,15 @@ fn main() -> Result<(), Box<dyn Error>> {
     if let Some(arg) = args.get(1) {
         match arg.as_str() {
             "--clear-cache" => {
-                CacheManager::new().clear_cache()?;
+                // Optional argument: clear a single source (path or alias) instead of everything
+                match args.get(2) {
+                    Some(source) => CacheManager::from_env()?.clear_cache_for(Path::new(source))?,
+                    None => CacheManager::from_env()?.clear_cache()?,
+                }
                 return Ok(());
             }
             "--cache-info" => {
-                let cache_manager = CacheManager::new();
+                let cache_manager = CacheManager::from_env()?;
                 let info = cache_manager.get_cache_info()?;
                 if info.is_empty() {
                     println!("Cache is empty");
@@ -64,7 +63,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 return Ok(());
             }
             "--benchmark-cache" => {
-                let cache_manager = CacheManager::new().configure_for_threads(parallel_threads);
+                let cache_manager = CacheManager::from_env()?.configure_for_threads(parallel_threads);
                 cache_manager.benchmark_cache(10 * 1024 * 1024)?; // 10MB test
                 return Ok(());
             }
@@ -94,8 +93,11 @@ fn main() -> Result<(), Box<dyn Error>> {
         )
     };
     
-    // Set data folder path (can still be overridden by command line argument)
-    let d_folder = args.get(1).cloned().unwrap_or(default_data_folder);
+    // Set data folder path (can still be overridden by command line argument, path or alias)
+    let d_folder = match args.get(1) {
+        Some(arg) => CacheManager::from_env()?.resolve_source(Path::new(arg)).display().to_string(),
+        None => default_data_folder,
+    };
     
     let d_path = Path::new(&d_folder);
     if !d_path.exists() {
@@ -108,23 +110,38 @@ fn main() -> Result<(), Box<dyn Error>> {
     
     // ================================ OPTIMIZED CACHE CONFIGURATION ================================
     
-    // Create truly optimized cache configuration (sequential I/O + smart compression)
-    let cache_config = CacheConfig {
-        enable_compression: false,       // Disabled by default for maximum speed
-        buffer_size: 1024 * 1024 * 32,  // Optimal buffer size for sequential I/O
-        auto_compression: true,          // Smart compression only where beneficial
-    };
+    // Defaults (sequential I/O, compression only where beneficial) overridden by the
+    // TIMSTOF_CACHE_* variables (settings.rs), e.g. TIMSTOF_CACHE_FORMAT=single-file
+    let mut cache_config = CacheConfig::from_env()?;
+    // Legacy variable, applied only when set: LRU bound for the remote mirror in GB
+    if let Ok(gb) = env::var("TIMSTOF_MIRROR_GB") {
+        let gb = gb.parse::<f64>().map_err(|e| format!("TIMSTOF_MIRROR_GB: {}", e))?;
+        cache_config.local_mirror_bytes = Some((gb * 1024.0 * 1024.0 * 1024.0) as u64);
+    }
+    let (cache_format, buffer_mb) = (cache_config.format, cache_config.buffer_size / 1024 / 1024);
     
     // Create cache manager with optimized configuration
-    let cache_manager = CacheManager::with_config(cache_config)
+    let mut cache_manager = CacheManager::with_config(cache_config)
         .configure_for_threads(parallel_threads);
     
+    // Optional two-tier mode: primary cache on shared/object storage, local dir as mirror
+    if let Ok(remote_location) = env::var("TIMSTOF_REMOTE_CACHE") {
+        println!("Using remote cache: {} (local read-through mirror)", remote_location);
+        let remote: std::sync::Arc<dyn RemoteStore> = if remote_location.starts_with("http://") || remote_location.starts_with("https://") {
+            std::sync::Arc::new(HttpStore::new(remote_location))
+        } else {
+            std::sync::Arc::new(DirectoryStore::new(remote_location))
+        };
+        cache_manager = cache_manager.with_remote(remote);
+    }
+    
     // ================================ DATA LOADING AND INDEXING ================================
     println!("\n========== DATA PREPARATION PHASE ==========");
     println!("Cache configuration:");
     println!("  - Sequential I/O: optimized for disk performance");
     println!("  - Smart compression: MS2 only (where beneficial)");
-    println!("  - Buffer size: 32 MB (optimized for speed)");
+    println!("  - Format: {:?}", cache_format);
+    println!("  - Buffer size: {} MB", buffer_mb);
     println!("  - Thread optimization: CPU tasks only");
     
     let total_start = Instant::now();
@@ -180,10 +197,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     
     println!("Library and report processing time: {:.5} seconds", lib_processing_start.elapsed().as_secs_f32());
     
-    // Set processing parameters
-    let device = "cpu";
-    let frag_repeat_num = 5;
-    
     // ================================ BATCH PRECURSOR PROCESSING ================================
     println!("\n========== BATCH PRECURSOR PROCESSING ==========");
     
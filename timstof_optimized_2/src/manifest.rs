@@ -0,0 +1,169 @@
+// File: src/manifest.rs
+//
+// Top-level manifest of a cache directory: every cached source with its format version,
+// form and files (size and checksum). Listing the cache reads this one file instead of
+// scanning and parsing thousands of file names, and files it doesn't list are orphans.
+//
+// Updates are transactions: taken under a lock file, applied to the latest manifest and
+// published by renaming a complete new file over the old one, so readers never need the
+// lock and concurrent writers (other processes) never lose each other's changes.
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::columnar;
+use crate::fdlimit;
+use crate::signing;
+
+// A lock older than this was left behind by a crashed writer
+const STALE_LOCK: Duration = Duration::from_secs(30);
+const LOCK_RETRY: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub name: String,
+    pub bytes: u64,
+    pub checksum: String, // See file_checksum
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>, // Unknown for entries recovered from file names
+    pub version: String,              // Cache format version from the metadata
+    pub form: String,                 // "fast" | "archive"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lossy: Option<String>,        // The "lossy" metadata value of a quantized cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<String>,   // InstrumentInfo::describe() of the recorded acquisition
+    pub updated_secs: u64,
+    pub files: Vec<ManifestFile>,     // Data files and the metadata file
+}
+
+impl ManifestEntry {
+    pub fn size_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub generation: u64, // Incremented by every transaction
+    pub sources: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    pub const FILE_NAME: &'static str = "manifest.json";
+    const LOCK_NAME: &'static str = "manifest.lock";
+
+    // The last committed manifest, None if the directory has none (yet)
+    pub fn read(cache_dir: &Path) -> Option<Self> {
+        let text = fdlimit::read_to_string(cache_dir.join(Self::FILE_NAME)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    // Whether a transaction could take the lock now, without waiting for it (health.rs)
+    pub fn probe_lock(cache_dir: &Path) -> io::Result<LockState> {
+        let path = cache_dir.join(Self::LOCK_NAME);
+        match fdlimit::open_with(OpenOptions::new().write(true).create_new(true), &path) {
+            Ok(_) => {
+                drop(ManifestLock { path });
+                Ok(LockState::Free)
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let age = lock_age(&path).unwrap_or_default();
+                Ok(if age > STALE_LOCK { LockState::Stale(age) } else { LockState::Held(age) })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Apply `change` to the latest manifest and commit the result atomically
+    pub fn transaction<F>(cache_dir: &Path, change: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut BTreeMap<String, ManifestEntry>),
+    {
+        fs::create_dir_all(cache_dir)?;
+        let _lock = ManifestLock::acquire(&cache_dir.join(Self::LOCK_NAME))?;
+        let mut manifest = Self::read(cache_dir).unwrap_or_default();
+        change(&mut manifest.sources);
+        manifest.generation += 1;
+
+        let text = serde_json::to_string_pretty(&manifest)
+            .map_err(io::Error::other)?;
+        let path = cache_dir.join(Self::FILE_NAME);
+        let partial = path.with_extension("partial");
+        fdlimit::write(&partial, text)?;
+        fs::rename(partial, path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    Free,
+    Held(Duration),  // By a writer, for this long
+    Stale(Duration), // Left by a crashed writer; the next transaction takes it over
+}
+
+fn lock_age(path: &Path) -> Option<Duration> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+}
+
+// Exclusive lock file, removed on drop
+struct ManifestLock {
+    path: PathBuf,
+}
+
+impl ManifestLock {
+    fn acquire(path: &Path) -> io::Result<Self> {
+        loop {
+            match fdlimit::open_with(OpenOptions::new().write(true).create_new(true), path) {
+                Ok(_) => return Ok(Self { path: path.to_path_buf() }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if lock_age(path).is_some_and(|age| age > STALE_LOCK) {
+                        let _ = fs::remove_file(path);
+                        continue;
+                    }
+                    thread::sleep(LOCK_RETRY);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for ManifestLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Columnar containers are identified by a hash of their footer, which holds the CRC32 of
+// every stored column, so large data files need not be read in full. Other files
+// (metadata, Parquet, legacy caches) are hashed completely.
+pub fn file_checksum(path: &Path) -> io::Result<String> {
+    if !columnar::is_container(path) {
+        return Ok(format!("sha256:{}", signing::sha256_file(path)?));
+    }
+    let file = fdlimit::open(path)?;
+    let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+    let header_json = serde_json::to_vec(&header)
+        .map_err(io::Error::other)?;
+    Ok(format!("footer-sha256:{}", hex::encode(Sha256::digest(&header_json))))
+}
+
+pub fn describe_file(path: &Path) -> io::Result<ManifestFile> {
+    Ok(ManifestFile {
+        name: path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string(),
+        bytes: fs::metadata(path)?.len(),
+        checksum: file_checksum(path)?,
+    })
+}
@@ -0,0 +1,175 @@
+// File: src/mapped.rs
+//
+// Zero-copy views of MS1 shards for query-only workloads (CacheManager::load_shard_mmap).
+// The first load of a shard decodes it once into an rkyv archive of a DataShard, named
+// after a hash of the cache metadata so a rebuilt cache never maps stale data. Every later
+// load maps that file and validates it with rkyv::access, which checks the root's relative
+// pointers and lengths against the mapping without reading the columns (f32 and u32 have
+// no invalid bit patterns), so on a warm page cache a "load" is a mapping and a few bounds
+// checks. Files written with a signing key end in an HMAC-SHA256 of the archive, as shared
+// files do (shm.rs); checking it reads the whole file. Files are published by rename,
+// never changed in place, so a mapping stays valid while it lives.
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use memmap2::Mmap;
+use rkyv::rancor;
+use rkyv::rend::{f32_le, u32_le};
+
+use crate::fdlimit;
+use crate::prefetch;
+use crate::signing::{self, SigningKey};
+use crate::utils::{IndexedTimsTOFData, TimsTOFData};
+
+const TAG_LEN: usize = 32;
+
+// One decoded MS1 shard, laid out like IndexedTimsTOFData
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct DataShard {
+    pub fingerprint: String, // Of the cache metadata the shard was decoded from
+    pub shard: u32,          // In file order
+    pub rt_values_min: Vec<f32>,
+    pub mobility_values: Vec<f32>,
+    pub mz_values: Vec<f32>,
+    pub intensity_values: Vec<u32>,
+    pub frame_indices: Vec<u32>,
+    pub scan_indices: Vec<u32>,
+}
+
+impl DataShard {
+    pub fn new(fingerprint: &str, shard: u32, data: IndexedTimsTOFData) -> Self {
+        Self {
+            fingerprint: fingerprint.to_string(),
+            shard,
+            rt_values_min: data.rt_values_min,
+            mobility_values: data.mobility_values,
+            mz_values: data.mz_values,
+            intensity_values: data.intensity_values,
+            frame_indices: data.frame_indices,
+            scan_indices: data.scan_indices,
+        }
+    }
+}
+
+impl ArchivedDataShard {
+    pub fn rows(&self) -> usize {
+        self.mz_values.len()
+    }
+
+    // m/z of the first and last point, if the shard has any
+    pub fn mz_bounds(&self) -> Option<(f32, f32)> {
+        Some((self.mz_values.first()?.to_native(), self.mz_values.last()?.to_native()))
+    }
+
+    // Peaks with m/z in [mz_min, mz_max], as IndexedTimsTOFData::slice_by_mz_range
+    pub fn slice_by_mz_range(&self, mz_min: f32, mz_max: f32) -> TimsTOFData {
+        let start = self.mz_values.partition_point(|x| x.to_native() < mz_min);
+        let end = self.mz_values.partition_point(|x| x.to_native() <= mz_max).max(start);
+        TimsTOFData {
+            rt_values_min: f32s(&self.rt_values_min[start..end]),
+            mobility_values: f32s(&self.mobility_values[start..end]),
+            mz_values: f32s(&self.mz_values[start..end]),
+            intensity_values: u32s(&self.intensity_values[start..end]),
+            frame_indices: u32s(&self.frame_indices[start..end]),
+            scan_indices: u32s(&self.scan_indices[start..end]),
+        }
+    }
+
+    // Private copy, for APIs that take owned data
+    pub fn to_indexed(&self) -> IndexedTimsTOFData {
+        IndexedTimsTOFData {
+            rt_values_min: f32s(&self.rt_values_min),
+            mobility_values: f32s(&self.mobility_values),
+            mz_values: f32s(&self.mz_values),
+            intensity_values: u32s(&self.intensity_values),
+            frame_indices: u32s(&self.frame_indices),
+            scan_indices: u32s(&self.scan_indices),
+        }
+    }
+}
+
+fn f32s(values: &[f32_le]) -> Vec<f32> {
+    values.iter().map(|x| x.to_native()).collect()
+}
+
+fn u32s(values: &[u32_le]) -> Vec<u32> {
+    values.iter().map(|x| x.to_native()).collect()
+}
+
+// A mapped, validated shard archive, dereferencing to the ArchivedDataShard; cheap to clone
+#[derive(Clone)]
+pub struct MappedShard {
+    mmap: Arc<Mmap>,
+    archive_len: usize, // Without the tag of a signed file
+    path: PathBuf,
+}
+
+impl MappedShard {
+    // Map the archive at `path`, checking it holds shard `shard` of the cache `fingerprint`
+    // names and, with a signing key, that it was written with that key
+    pub fn open(path: &Path, fingerprint: &str, shard: u32, signing_key: Option<&SigningKey>) -> io::Result<Self> {
+        let file = fdlimit::open(path)?;
+        // Safety: shard archives are published by rename and never modified afterwards
+        let mmap = unsafe { Mmap::map(&*file)? };
+        let mut archive_len = mmap.len();
+        if let Some(key) = signing_key {
+            archive_len = archive_len.checked_sub(TAG_LEN)
+                .ok_or_else(|| signing::tampered(format!("{} is not signed with key '{}'", path.display(), key.id)))?;
+            key.verify_tag(&mmap[..archive_len], &mmap[archive_len..])?;
+        }
+        let archived = rkyv::access::<ArchivedDataShard, rancor::Error>(&mmap[..archive_len])
+            .map_err(|e| invalid_data(format!("{} is not a valid shard archive: {}", path.display(), e)))?;
+        if archived.fingerprint != fingerprint || archived.shard != shard {
+            return Err(invalid_data(format!("{} was made from another version of the cache", path.display())));
+        }
+        let rows = archived.rows();
+        let columns = [
+            archived.rt_values_min.len(), archived.mobility_values.len(),
+            archived.intensity_values.len(), archived.frame_indices.len(), archived.scan_indices.len(),
+        ];
+        if columns.iter().any(|&len| len != rows) {
+            return Err(invalid_data(format!("{} has columns of different lengths", path.display())));
+        }
+        Ok(Self { mmap: Arc::new(mmap), archive_len, path: path.to_path_buf() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Start paging in the archive, for callers about to scan all of it
+    pub fn prefetch(&self) {
+        prefetch::mapped(&self.mmap, &[(0, self.archive_len as u64)]);
+    }
+}
+
+impl Deref for MappedShard {
+    type Target = ArchivedDataShard;
+
+    fn deref(&self) -> &ArchivedDataShard {
+        // Safety: open() validated these bytes, and the mapping lives as long as `self`
+        unsafe { rkyv::access_unchecked::<ArchivedDataShard>(&self.mmap[..self.archive_len]) }
+    }
+}
+
+// Write the archive of `shard` and publish it at `path`, tagged with `signing_key` if given.
+// Processes racing to create the same file each write their own partial file; whichever
+// rename lands last wins, and both are complete.
+pub fn write(path: &Path, shard: &DataShard, signing_key: Option<&SigningKey>) -> io::Result<()> {
+    let bytes = rkyv::to_bytes::<rancor::Error>(shard).map_err(io::Error::other)?;
+    let partial = path.with_extension(format!("partial.{}", std::process::id()));
+    let mut file = fdlimit::create(&partial)?;
+    file.write_all(&bytes)?;
+    if let Some(key) = signing_key {
+        file.write_all(&key.tag(&bytes))?;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::rename(partial, path)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
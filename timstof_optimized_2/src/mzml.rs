@@ -0,0 +1,374 @@
+// File: src/mzml.rs
+//
+// mzML 1.1 writer for cached data, so OpenMS/Skyline/pyteomics can consume a cache without
+// the Bruker SDK. Spectra are rebuilt from the indexed columns by grouping points per frame
+// (or per frame and TIMS scan); MS2 spectra are emitted per isolation window and frame.
+// Binary arrays are plain, zlib or MS-Numpress encoded. mzMLb (HDF5 container) is not
+// written; use export_hdf5 for HDF5 consumers.
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use base64::Engine;
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::fdlimit;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectrumGrouping {
+    Frame,     // One spectrum per frame (all TIMS scans), mobility kept as an extra array
+    FrameScan, // One spectrum per (frame, scan): many more, smaller spectra
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrayCompression {
+    None,
+    Zlib,
+    Numpress, // Linear prediction for m/z, positive integer for intensities
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MzmlOptions {
+    pub grouping: SpectrumGrouping,
+    pub compression: ArrayCompression,
+    pub include_mobility: bool,
+}
+
+impl Default for MzmlOptions {
+    fn default() -> Self {
+        Self {
+            grouping: SpectrumGrouping::Frame,
+            compression: ArrayCompression::Zlib,
+            include_mobility: true,
+        }
+    }
+}
+
+// One spectrum to write: rows of `data` belonging to it, in m/z order
+struct SpectrumRows<'a> {
+    data: &'a IndexedTimsTOFData,
+    rows: Vec<usize>,
+    frame: u32,
+    scan: u32,
+    window: Option<(f32, f32)>,
+}
+
+fn group_spectra<'a>(
+    data: &'a IndexedTimsTOFData,
+    window: Option<(f32, f32)>,
+    grouping: SpectrumGrouping,
+) -> Vec<SpectrumRows<'a>> {
+    let key = |i: usize| match grouping {
+        SpectrumGrouping::Frame => (data.frame_indices[i], 0),
+        SpectrumGrouping::FrameScan => (data.frame_indices[i], data.scan_indices[i]),
+    };
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.sort_by(|&a, &b| key(a).cmp(&key(b)).then(data.mz_values[a].total_cmp(&data.mz_values[b])));
+
+    let mut spectra: Vec<SpectrumRows> = Vec::new();
+    for i in order {
+        match spectra.last_mut() {
+            Some(last) if key(last.rows[0]) == key(i) => {
+                last.scan = last.scan.min(data.scan_indices[i]);
+                last.rows.push(i);
+            }
+            _ => spectra.push(SpectrumRows {
+                data,
+                rows: vec![i],
+                frame: data.frame_indices[i],
+                scan: data.scan_indices[i],
+                window,
+            }),
+        }
+    }
+    spectra
+}
+
+// ---- MS-Numpress (port of the reference MSNumpress.cpp encoders) ----
+
+// Encode x as a variable number of half-bytes (leading zero/one nibbles are dropped)
+fn encode_int(x: u32, half_bytes: &mut Vec<u8>) {
+    let mask: u32 = 0xf000_0000;
+    let init = x & mask;
+    if init == 0 {
+        let l = (0..8).find(|i| x & (mask >> (4 * i)) != 0).unwrap_or(8);
+        half_bytes.push(l as u8);
+        for i in l..8 {
+            half_bytes.push(((x >> (4 * (i - l))) & 0xf) as u8);
+        }
+    } else if init == mask {
+        let l = (0..8).find(|i| x & (mask >> (4 * i)) != (mask >> (4 * i))).unwrap_or(7);
+        half_bytes.push(l as u8 + 8);
+        for i in l..8 {
+            half_bytes.push(((x >> (4 * (i - l))) & 0xf) as u8);
+        }
+    } else {
+        half_bytes.push(0);
+        for i in 0..8 {
+            half_bytes.push(((x >> (4 * i)) & 0xf) as u8);
+        }
+    }
+}
+
+fn pack_half_bytes(half_bytes: &[u8], out: &mut Vec<u8>) {
+    for pair in half_bytes.chunks(2) {
+        let high = pair[0] << 4;
+        out.push(if pair.len() == 2 { high | (pair[1] & 0xf) } else { high });
+    }
+}
+
+fn optimal_linear_fixed_point(data: &[f64]) -> f64 {
+    match data.len() {
+        0 => 0.0,
+        1 => (0xFFFF_FFFFu32 as f64 / data[0]).floor(),
+        _ => {
+            let mut max_double = data[0].max(data[1]);
+            for i in 2..data.len() {
+                let extrapol = data[i - 1] + (data[i - 1] - data[i - 2]);
+                let diff = data[i] - extrapol;
+                max_double = max_double.max((diff.abs() + 1.0).ceil());
+            }
+            (0x7FFF_FFFF as f64 / max_double).floor()
+        }
+    }
+}
+
+fn numpress_linear(data: &[f64]) -> Vec<u8> {
+    let fixed_point = optimal_linear_fixed_point(data);
+    let mut out = fixed_point.to_be_bytes().to_vec();
+    let to_int = |x: f64| (x * fixed_point + 0.5) as i64;
+
+    let mut ints = [0i64; 3];
+    if let Some(&first) = data.first() {
+        ints[1] = to_int(first);
+        out.extend_from_slice(&(ints[1] as u32).to_le_bytes());
+    }
+    if let Some(&second) = data.get(1) {
+        ints[2] = to_int(second);
+        out.extend_from_slice(&(ints[2] as u32).to_le_bytes());
+    }
+
+    let mut half_bytes = Vec::with_capacity(data.len() * 2);
+    for &value in data.iter().skip(2) {
+        ints[0] = ints[1];
+        ints[1] = ints[2];
+        ints[2] = to_int(value);
+        let extrapol = ints[1] + (ints[1] - ints[0]);
+        encode_int((ints[2] - extrapol) as i32 as u32, &mut half_bytes);
+    }
+    pack_half_bytes(&half_bytes, &mut out);
+    out
+}
+
+fn numpress_pic(data: &[f64]) -> Vec<u8> {
+    let mut half_bytes = Vec::with_capacity(data.len() * 3);
+    for &value in data {
+        encode_int((value + 0.5) as u32, &mut half_bytes);
+    }
+    let mut out = Vec::with_capacity(half_bytes.len() / 2 + 1);
+    pack_half_bytes(&half_bytes, &mut out);
+    out
+}
+
+// ---- Binary data arrays ----
+
+enum ArrayKind {
+    Mz,
+    Intensity,
+    Mobility,
+}
+
+fn zlib(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+// Returns (base64 payload, cvParams describing dtype + compression + array type)
+fn encode_array(values: &[f64], kind: ArrayKind, compression: ArrayCompression) -> io::Result<(String, String)> {
+    let array_param = match kind {
+        ArrayKind::Mz => r#"<cvParam cvRef="MS" accession="MS:1000514" name="m/z array" value="" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#,
+        ArrayKind::Intensity => r#"<cvParam cvRef="MS" accession="MS:1000515" name="intensity array" value="" unitCvRef="MS" unitAccession="MS:1000131" unitName="number of detector counts"/>"#,
+        ArrayKind::Mobility => r#"<cvParam cvRef="MS" accession="MS:1003006" name="mean inverse reduced ion mobility array" value="" unitCvRef="MS" unitAccession="MS:1002814" unitName="volt-second per square centimeter"/>"#,
+    };
+    let float64 = r#"<cvParam cvRef="MS" accession="MS:1000523" name="64-bit float" value=""/>"#;
+    let float32 = r#"<cvParam cvRef="MS" accession="MS:1000521" name="32-bit float" value=""/>"#;
+    let no_compression = r#"<cvParam cvRef="MS" accession="MS:1000576" name="no compression" value=""/>"#;
+    let zlib_compression = r#"<cvParam cvRef="MS" accession="MS:1000574" name="zlib compression" value=""/>"#;
+
+    // m/z needs double precision in the file; intensity and mobility fit in 32 bits
+    let (bytes, dtype, compression_param) = match (compression, &kind) {
+        (ArrayCompression::Numpress, ArrayKind::Mz) => (
+            numpress_linear(values),
+            float64,
+            r#"<cvParam cvRef="MS" accession="MS:1002312" name="MS-Numpress linear prediction compression" value=""/>"#,
+        ),
+        (ArrayCompression::Numpress, ArrayKind::Intensity) => (
+            numpress_pic(values),
+            float32,
+            r#"<cvParam cvRef="MS" accession="MS:1002313" name="MS-Numpress positive integer compression" value=""/>"#,
+        ),
+        _ => {
+            let raw: Vec<u8> = match kind {
+                ArrayKind::Mz => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                _ => values.iter().flat_map(|v| (*v as f32).to_le_bytes()).collect(),
+            };
+            let dtype = if matches!(kind, ArrayKind::Mz) { float64 } else { float32 };
+            match compression {
+                ArrayCompression::None => (raw, dtype, no_compression),
+                // Numpress has no good fit for mobility values, so they fall back to zlib
+                _ => (zlib(&raw)?, dtype, zlib_compression),
+            }
+        }
+    };
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    Ok((encoded, format!("{}{}{}", dtype, compression_param, array_param)))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn write_spectrum<W: Write>(out: &mut W, index: usize, spectrum: &SpectrumRows, options: &MzmlOptions) -> io::Result<()> {
+    let data = spectrum.data;
+    let mz: Vec<f64> = spectrum.rows.iter().map(|&i| data.mz_values[i] as f64).collect();
+    let intensity: Vec<f64> = spectrum.rows.iter().map(|&i| data.intensity_values[i] as f64).collect();
+    let total_ion_current: f64 = intensity.iter().sum();
+    let rt_minutes = data.rt_values_min[spectrum.rows[0]];
+    let ms_level = if spectrum.window.is_some() { 2 } else { 1 };
+
+    writeln!(out, r#"        <spectrum index="{}" id="frame={} scan={}" defaultArrayLength="{}">"#, index, spectrum.frame, spectrum.scan, mz.len())?;
+    writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000511" name="ms level" value="{}"/>"#, ms_level)?;
+    if ms_level == 1 {
+        writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000579" name="MS1 spectrum" value=""/>"#)?;
+    } else {
+        writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000580" name="MSn spectrum" value=""/>"#)?;
+    }
+    writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000127" name="centroid spectrum" value=""/>"#)?;
+    writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000285" name="total ion current" value="{}"/>"#, total_ion_current)?;
+    if let (Some(first), Some(last)) = (mz.first(), mz.last()) {
+        writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000528" name="lowest observed m/z" value="{}" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#, first)?;
+        writeln!(out, r#"          <cvParam cvRef="MS" accession="MS:1000527" name="highest observed m/z" value="{}" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#, last)?;
+    }
+
+    writeln!(out, r#"          <scanList count="1">"#)?;
+    writeln!(out, r#"            <cvParam cvRef="MS" accession="MS:1000795" name="no combination" value=""/>"#)?;
+    writeln!(out, r#"            <scan>"#)?;
+    writeln!(out, r#"              <cvParam cvRef="MS" accession="MS:1000016" name="scan start time" value="{}" unitCvRef="UO" unitAccession="UO:0000031" unitName="minute"/>"#, rt_minutes)?;
+    if options.grouping == SpectrumGrouping::FrameScan {
+        let mobility = data.mobility_values[spectrum.rows[0]];
+        writeln!(out, r#"              <cvParam cvRef="MS" accession="MS:1002815" name="inverse reduced ion mobility" value="{}" unitCvRef="MS" unitAccession="MS:1002814" unitName="volt-second per square centimeter"/>"#, mobility)?;
+    }
+    writeln!(out, r#"            </scan>"#)?;
+    writeln!(out, r#"          </scanList>"#)?;
+
+    if let Some((low, high)) = spectrum.window {
+        let target = (low + high) / 2.0;
+        writeln!(out, r#"          <precursorList count="1">"#)?;
+        writeln!(out, r#"            <precursor>"#)?;
+        writeln!(out, r#"              <isolationWindow>"#)?;
+        writeln!(out, r#"                <cvParam cvRef="MS" accession="MS:1000827" name="isolation window target m/z" value="{}" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#, target)?;
+        writeln!(out, r#"                <cvParam cvRef="MS" accession="MS:1000828" name="isolation window lower offset" value="{}" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#, target - low)?;
+        writeln!(out, r#"                <cvParam cvRef="MS" accession="MS:1000829" name="isolation window upper offset" value="{}" unitCvRef="MS" unitAccession="MS:1000040" unitName="m/z"/>"#, high - target)?;
+        writeln!(out, r#"              </isolationWindow>"#)?;
+        writeln!(out, r#"              <activation>"#)?;
+        writeln!(out, r#"                <cvParam cvRef="MS" accession="MS:1000133" name="collision-induced dissociation" value=""/>"#)?;
+        writeln!(out, r#"              </activation>"#)?;
+        writeln!(out, r#"            </precursor>"#)?;
+        writeln!(out, r#"          </precursorList>"#)?;
+    }
+
+    let mut arrays = vec![
+        encode_array(&mz, ArrayKind::Mz, options.compression)?,
+        encode_array(&intensity, ArrayKind::Intensity, options.compression)?,
+    ];
+    if options.include_mobility && options.grouping == SpectrumGrouping::Frame {
+        let mobility: Vec<f64> = spectrum.rows.iter().map(|&i| data.mobility_values[i] as f64).collect();
+        arrays.push(encode_array(&mobility, ArrayKind::Mobility, options.compression)?);
+    }
+
+    writeln!(out, r#"          <binaryDataArrayList count="{}">"#, arrays.len())?;
+    for (encoded, params) in arrays {
+        writeln!(out, r#"            <binaryDataArray encodedLength="{}">"#, encoded.len())?;
+        writeln!(out, "              {}", params)?;
+        writeln!(out, "              <binary>{}</binary>", encoded)?;
+        writeln!(out, r#"            </binaryDataArray>"#)?;
+    }
+    writeln!(out, r#"          </binaryDataArrayList>"#)?;
+    writeln!(out, r#"        </spectrum>"#)
+}
+
+// Write all spectra of one source, ordered by frame (i.e. acquisition time). Returns the
+// number of spectra written.
+pub fn write_mzml(
+    out_path: &Path,
+    source_name: &str,
+    ms1: &IndexedTimsTOFData,
+    ms2_windows: &[(IsolationWindow, IndexedTimsTOFData)],
+    options: &MzmlOptions,
+) -> io::Result<usize> {
+    let mut spectra = group_spectra(ms1, None, options.grouping);
+    for (window, data) in ms2_windows {
+        spectra.extend(group_spectra(data, Some(window.bounds()), options.grouping));
+    }
+    spectra.sort_by_key(|s| (s.frame, s.window.is_some(), s.scan));
+
+    let partial_path = out_path.with_extension("partial");
+    let mut out = BufWriter::with_capacity(1024 * 1024 * 8, fdlimit::create(&partial_path)?);
+    let run_id = xml_escape(source_name.trim_end_matches(".d"));
+    let source_name = xml_escape(source_name);
+
+    writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(out, r#"<mzML xmlns="http://psi.hupo.org/ms/mzml" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://psi.hupo.org/ms/mzml http://psidev.info/files/ms/mzML/xsd/mzML1.1.0.xsd" id="{}" version="1.1.0">"#, run_id)?;
+    writeln!(out, r#"  <cvList count="2">"#)?;
+    writeln!(out, r#"    <cv id="MS" fullName="Proteomics Standards Initiative Mass Spectrometry Ontology" version="4.1.0" URI="https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo"/>"#)?;
+    writeln!(out, r#"    <cv id="UO" fullName="Unit Ontology" version="09:04:2014" URI="https://raw.githubusercontent.com/bio-ontology-research-group/unit-ontology/master/unit.obo"/>"#)?;
+    writeln!(out, r#"  </cvList>"#)?;
+    writeln!(out, r#"  <fileDescription>"#)?;
+    writeln!(out, r#"    <fileContent>"#)?;
+    writeln!(out, r#"      <cvParam cvRef="MS" accession="MS:1000579" name="MS1 spectrum" value=""/>"#)?;
+    writeln!(out, r#"      <cvParam cvRef="MS" accession="MS:1000580" name="MSn spectrum" value=""/>"#)?;
+    writeln!(out, r#"    </fileContent>"#)?;
+    writeln!(out, r#"    <sourceFileList count="1">"#)?;
+    writeln!(out, r#"      <sourceFile id="SF1" name="{}" location="file:///">"#, source_name)?;
+    writeln!(out, r#"        <cvParam cvRef="MS" accession="MS:1002818" name="Bruker TDF nativeID format" value=""/>"#)?;
+    writeln!(out, r#"        <cvParam cvRef="MS" accession="MS:1002817" name="Bruker TDF format" value=""/>"#)?;
+    writeln!(out, r#"      </sourceFile>"#)?;
+    writeln!(out, r#"    </sourceFileList>"#)?;
+    writeln!(out, r#"  </fileDescription>"#)?;
+    writeln!(out, r#"  <softwareList count="1">"#)?;
+    writeln!(out, r#"    <software id="timstof_cache" version="{}">"#, env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, r#"      <cvParam cvRef="MS" accession="MS:1000799" name="custom unreleased software tool" value="timstof-cache"/>"#)?;
+    writeln!(out, r#"    </software>"#)?;
+    writeln!(out, r#"  </softwareList>"#)?;
+    writeln!(out, r#"  <instrumentConfigurationList count="1">"#)?;
+    writeln!(out, r#"    <instrumentConfiguration id="IC1">"#)?;
+    writeln!(out, r#"      <cvParam cvRef="MS" accession="MS:1000031" name="instrument model" value=""/>"#)?;
+    writeln!(out, r#"    </instrumentConfiguration>"#)?;
+    writeln!(out, r#"  </instrumentConfigurationList>"#)?;
+    writeln!(out, r#"  <dataProcessingList count="1">"#)?;
+    writeln!(out, r#"    <dataProcessing id="timstof_cache_export">"#)?;
+    writeln!(out, r#"      <processingMethod order="0" softwareRef="timstof_cache">"#)?;
+    writeln!(out, r#"        <cvParam cvRef="MS" accession="MS:1000544" name="Conversion to mzML" value=""/>"#)?;
+    writeln!(out, r#"      </processingMethod>"#)?;
+    writeln!(out, r#"    </dataProcessing>"#)?;
+    writeln!(out, r#"  </dataProcessingList>"#)?;
+    writeln!(out, r#"  <run id="{}" defaultInstrumentConfigurationRef="IC1" defaultSourceFileRef="SF1">"#, run_id)?;
+    writeln!(out, r#"    <spectrumList count="{}" defaultDataProcessingRef="timstof_cache_export">"#, spectra.len())?;
+    for (index, spectrum) in spectra.iter().enumerate() {
+        write_spectrum(&mut out, index, spectrum, options)?;
+    }
+    writeln!(out, r#"    </spectrumList>"#)?;
+    writeln!(out, r#"  </run>"#)?;
+    writeln!(out, r#"</mzML>"#)?;
+
+    out.into_inner().map_err(|e| e.into_error())?;
+    fs::rename(&partial_path, out_path)?;
+    Ok(spectra.len())
+}
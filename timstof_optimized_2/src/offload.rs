@@ -0,0 +1,54 @@
+// File: src/offload.rs
+//
+// Runtime-agnostic bridge for async callers: CPU-heavy cache work (decompression,
+// decryption, column decoding) runs on a dedicated rayon pool and the caller gets a
+// future that completes when it is done. Executor threads of tokio/async-std/smol are
+// never blocked, and parallel iterators inside the work stay on the dedicated pool.
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct OffloadState<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+pub struct Offloaded<T> {
+    state: Arc<Mutex<OffloadState<T>>>,
+}
+
+impl<T> Future for Offloaded<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            // A panic in the work resurfaces in the awaiting task, like a joined thread
+            Some(result) => Poll::Ready(result.unwrap_or_else(|payload| panic::resume_unwind(payload))),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+pub fn offload<T, F>(pool: &rayon::ThreadPool, work: F) -> Offloaded<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(OffloadState { result: None, waker: None }));
+    let shared = Arc::clone(&state);
+    pool.spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(work));
+        let mut state = shared.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    Offloaded { state }
+}
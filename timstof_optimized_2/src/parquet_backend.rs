@@ -0,0 +1,184 @@
+// File: src/parquet_backend.rs
+//
+// Parquet alternative to the columnar container. MS1 is written in row groups of
+// `row_group_size` rows (MS1 is m/z-sorted, so each group covers a narrow m/z band);
+// MS2 gets one row group per isolation window. Row groups carry min/max statistics,
+// so filtered scans (ours, DuckDB, Spark, polars) skip groups outside the m/z / RT range.
+// A window without points is kept as a single row with null data columns, since a
+// window with no rows would otherwise vanish from the file.
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use polars::prelude::*;
+
+use crate::fdlimit;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+use crate::compression::CompressionType;
+
+// Same column names as the Arrow export
+const DATA_COLUMNS: [&str; 6] = ["rt", "mobility", "mz", "intensity", "frame", "scan"];
+
+pub struct ParquetBackend {
+    pub row_group_size: usize,
+    pub compression: Option<CompressionType>, // None writes uncompressed pages
+}
+
+// Optional bounds pushed down to the row-group statistics, both inclusive
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegionFilter {
+    pub mz: Option<(f32, f32)>,
+    pub rt: Option<(f32, f32)>,
+}
+
+fn to_io(e: PolarsError) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+fn data_frame(data: &IndexedTimsTOFData) -> PolarsResult<DataFrame> {
+    DataFrame::new(vec![
+        Series::new("rt", &data.rt_values_min),
+        Series::new("mobility", &data.mobility_values),
+        Series::new("mz", &data.mz_values),
+        Series::new("intensity", &data.intensity_values),
+        Series::new("frame", &data.frame_indices),
+        Series::new("scan", &data.scan_indices),
+    ])
+}
+
+// The placeholder row of an empty MS2 window
+fn empty_window_frame() -> PolarsResult<DataFrame> {
+    DataFrame::new(vec![
+        Series::new("rt", &[None::<f32>]),
+        Series::new("mobility", &[None::<f32>]),
+        Series::new("mz", &[None::<f32>]),
+        Series::new("intensity", &[None::<u32>]),
+        Series::new("frame", &[None::<u32>]),
+        Series::new("scan", &[None::<u32>]),
+    ])
+}
+
+fn f32_column(df: &DataFrame, name: &str) -> PolarsResult<Vec<f32>> {
+    Ok(df.column(name)?.f32()?.into_no_null_iter().collect())
+}
+
+fn u32_column(df: &DataFrame, name: &str) -> PolarsResult<Vec<u32>> {
+    Ok(df.column(name)?.u32()?.into_no_null_iter().collect())
+}
+
+fn indexed_data(df: &DataFrame) -> PolarsResult<IndexedTimsTOFData> {
+    Ok(IndexedTimsTOFData {
+        rt_values_min: f32_column(df, "rt")?,
+        mobility_values: f32_column(df, "mobility")?,
+        mz_values: f32_column(df, "mz")?,
+        intensity_values: u32_column(df, "intensity")?,
+        frame_indices: u32_column(df, "frame")?,
+        scan_indices: u32_column(df, "scan")?,
+    })
+}
+
+impl ParquetBackend {
+    fn writer(&self, file: File) -> ParquetWriter<File> {
+        // Parquet has no zstd long mode; the level still applies
+        let compression = match self.compression {
+            None => ParquetCompression::Uncompressed,
+            Some(CompressionType::Lz4) => ParquetCompression::Lz4Raw,
+            Some(CompressionType::Zstd { level, .. }) => ParquetCompression::Zstd(ZstdLevel::try_new(level).ok()),
+            // Refused before a backend is made (CacheManager::parquet_backend)
+            Some(CompressionType::Custom { .. }) => ParquetCompression::Uncompressed,
+        };
+        ParquetWriter::new(file)
+            .with_compression(compression)
+            .with_statistics(true)
+    }
+
+    // Every call to write_batch with a single-chunk frame becomes exactly one row group
+    fn write_row_groups(&self, path: &Path, frames: impl Iterator<Item = PolarsResult<DataFrame>>, schema: &Schema) -> io::Result<()> {
+        let partial_path = path.with_extension("partial");
+        let (file, _permit) = fdlimit::create(&partial_path)?.into_parts();
+        let mut writer = self.writer(file).batched(schema).map_err(to_io)?;
+        for df in frames {
+            let mut df = df.map_err(to_io)?;
+            df.as_single_chunk_par();
+            writer.write_batch(&df).map_err(to_io)?;
+        }
+        writer.finish().map_err(to_io)?;
+        fs::rename(&partial_path, path)
+    }
+
+    pub fn write_ms1(&self, path: &Path, data: &IndexedTimsTOFData) -> io::Result<()> {
+        let df = data_frame(data).map_err(to_io)?;
+        let schema = df.schema();
+        let rows = df.height();
+        let group_size = self.row_group_size.max(1);
+        let frames = (0..rows.max(1)).step_by(group_size)
+            .map(|offset| Ok(df.slice(offset as i64, group_size)));
+        self.write_row_groups(path, frames, &schema)
+    }
+
+    pub fn write_ms2(&self, path: &Path, windows: &[(IsolationWindow, IndexedTimsTOFData)]) -> io::Result<()> {
+        let window_frame = |(window, data): &(IsolationWindow, IndexedTimsTOFData)| -> PolarsResult<DataFrame> {
+            let mut df = match data.mz_values.is_empty() {
+                true => empty_window_frame()?,
+                false => data_frame(data)?,
+            };
+            let rows = df.height();
+            df.with_column(Series::new("isolation_low", vec![window.mz_low; rows]))?;
+            df.with_column(Series::new("isolation_high", vec![window.mz_high; rows]))?;
+            Ok(df)
+        };
+        let empty = (IsolationWindow::new(0.0, 0.0, 0), IndexedTimsTOFData::new());
+        let schema = window_frame(windows.first().unwrap_or(&empty)).map_err(to_io)?.schema();
+        self.write_row_groups(path, windows.iter().map(window_frame), &schema)
+    }
+
+    pub fn read_ms1(path: &Path) -> io::Result<IndexedTimsTOFData> {
+        let (file, _permit) = fdlimit::open(path)?.into_parts();
+        let df = ParquetReader::new(file).finish().map_err(to_io)?;
+        indexed_data(&df).map_err(to_io)
+    }
+
+    // Windows are stored contiguously, in write order
+    pub fn read_ms2(path: &Path) -> io::Result<Vec<(IsolationWindow, IndexedTimsTOFData)>> {
+        let (file, _permit) = fdlimit::open(path)?.into_parts();
+        let df = ParquetReader::new(file).finish().map_err(to_io)?;
+        let low = f32_column(&df, "isolation_low").map_err(to_io)?;
+        let high = f32_column(&df, "isolation_high").map_err(to_io)?;
+
+        let mut windows = Vec::new();
+        let mut start = 0;
+        while start < low.len() {
+            let mut end = start + 1;
+            while end < low.len() && low[end] == low[start] && high[end] == high[start] {
+                end += 1;
+            }
+            let slice = df.slice(start as i64, end - start);
+            let data = match slice.column("mz").map_err(to_io)?.null_count() == slice.height() {
+                true => IndexedTimsTOFData::new(), // Placeholder of an empty window
+                false => indexed_data(&slice).map_err(to_io)?,
+            };
+            windows.push((IsolationWindow::new(low[start], high[start], windows.len()), data));
+            start = end;
+        }
+        Ok(windows)
+    }
+
+    // Rows inside the filter; row groups whose statistics fall outside it are never read
+    pub fn scan(path: &Path, filter: RegionFilter) -> io::Result<IndexedTimsTOFData> {
+        let mut lazy = LazyFrame::scan_parquet(path, ScanArgsParquet::default())
+            .map_err(to_io)?
+            .select(DATA_COLUMNS.iter().map(|name| col(name)).collect::<Vec<_>>());
+        if let Some((min, max)) = filter.mz {
+            lazy = lazy.filter(col("mz").gt_eq(lit(min)).and(col("mz").lt_eq(lit(max))));
+        }
+        if let Some((min, max)) = filter.rt {
+            lazy = lazy.filter(col("rt").gt_eq(lit(min)).and(col("rt").lt_eq(lit(max))));
+        }
+        let df = lazy.collect().map_err(to_io)?;
+        indexed_data(&df).map_err(to_io)
+    }
+}
+
+pub fn is_parquet_path(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("parquet")
+}
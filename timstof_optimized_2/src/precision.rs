@@ -0,0 +1,134 @@
+// File: src/precision.rs
+//
+// Width of the float columns (rt, mobility, m/z). Caches store them as f32 unless
+// configured for f64 (CacheConfig::precision), recorded per column as its dtype and for
+// the cache as "precision" in the metadata. Data in memory is IndexedTimsTOFData<F> for
+// either width: saves take both, and loads convert to the width the caller asks for, so
+// an f32 load of an f64 cache narrows and an f64 load of an f32 cache widens.
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use crate::utils::IndexedTimsTOFData;
+use crate::window::Ms2Window as Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    #[default]
+    F32,
+    F64,
+}
+
+impl Precision {
+    // Column dtype and "precision" metadata value
+    pub fn dtype(&self) -> &'static str {
+        match self {
+            Precision::F32 => "f32",
+            Precision::F64 => "f64",
+        }
+    }
+
+    pub fn from_dtype(dtype: &str) -> Option<Self> {
+        match dtype {
+            "f32" => Some(Precision::F32),
+            "f64" => Some(Precision::F64),
+            _ => None,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        match self {
+            Precision::F32 => 4,
+            Precision::F64 => 8,
+        }
+    }
+}
+
+// The float types IndexedTimsTOFData columns come in
+pub trait Float: Copy + PartialOrd + Debug + Send + Sync + 'static {
+    const PRECISION: Precision;
+
+    fn from_f32(value: f32) -> Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f32(self) -> f32;
+    fn to_f64(self) -> f64;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+
+    // The data at f32, borrowed when it already is
+    fn as_f32_data(data: &IndexedTimsTOFData<Self>) -> Cow<'_, IndexedTimsTOFData>;
+    fn as_f32_windows(windows: &[Window<Self>]) -> Cow<'_, [Window]>;
+    fn from_f32_data(data: IndexedTimsTOFData) -> IndexedTimsTOFData<Self>;
+}
+
+impl Float for f32 {
+    const PRECISION: Precision = Precision::F32;
+
+    fn from_f32(value: f32) -> Self { value }
+    fn from_f64(value: f64) -> Self { value as f32 }
+    fn to_f32(self) -> f32 { self }
+    fn to_f64(self) -> f64 { self as f64 }
+    fn total_cmp(&self, other: &Self) -> Ordering { f32::total_cmp(self, other) }
+
+    fn as_f32_data(data: &IndexedTimsTOFData) -> Cow<'_, IndexedTimsTOFData> {
+        Cow::Borrowed(data)
+    }
+
+    fn as_f32_windows(windows: &[Window]) -> Cow<'_, [Window]> {
+        Cow::Borrowed(windows)
+    }
+
+    fn from_f32_data(data: IndexedTimsTOFData) -> IndexedTimsTOFData {
+        data
+    }
+}
+
+impl Float for f64 {
+    const PRECISION: Precision = Precision::F64;
+
+    fn from_f32(value: f32) -> Self { value as f64 }
+    fn from_f64(value: f64) -> Self { value }
+    fn to_f32(self) -> f32 { self as f32 }
+    fn to_f64(self) -> f64 { self }
+    fn total_cmp(&self, other: &Self) -> Ordering { f64::total_cmp(self, other) }
+
+    fn as_f32_data(data: &IndexedTimsTOFData<f64>) -> Cow<'_, IndexedTimsTOFData> {
+        Cow::Owned(convert(data))
+    }
+
+    fn as_f32_windows(windows: &[Window<f64>]) -> Cow<'_, [Window]> {
+        Cow::Owned(windows.iter().map(|(window, data)| (*window, convert(data))).collect())
+    }
+
+    fn from_f32_data(data: IndexedTimsTOFData) -> IndexedTimsTOFData<f64> {
+        convert(&data)
+    }
+}
+
+// The same points with float columns of another width
+pub fn convert<A: Float, B: Float>(data: &IndexedTimsTOFData<A>) -> IndexedTimsTOFData<B> {
+    let floats = |values: &[A]| values.iter().map(|value| B::from_f64(value.to_f64())).collect();
+    IndexedTimsTOFData {
+        rt_values_min: floats(&data.rt_values_min),
+        mobility_values: floats(&data.mobility_values),
+        mz_values: floats(&data.mz_values),
+        intensity_values: data.intensity_values.clone(),
+        frame_indices: data.frame_indices.clone(),
+        scan_indices: data.scan_indices.clone(),
+    }
+}
+
+// Little-endian values of `stored` width back to floats of type F
+pub fn floats_from_le<F: Float>(raw: &[u8], stored: Precision) -> Vec<F> {
+    match stored {
+        Precision::F32 => raw.chunks_exact(4).map(|c| F::from_f32(f32::from_le_bytes(c.try_into().unwrap()))).collect(),
+        Precision::F64 => raw.chunks_exact(8).map(|c| F::from_f64(f64::from_le_bytes(c.try_into().unwrap()))).collect(),
+    }
+}
+
+// Floats as little-endian values of `stored` width
+pub fn floats_to_le<F: Float>(values: &[F], stored: Precision) -> Vec<u8> {
+    match stored {
+        Precision::F32 => values.iter().flat_map(|value| value.to_f32().to_le_bytes()).collect(),
+        Precision::F64 => values.iter().flat_map(|value| value.to_f64().to_le_bytes()).collect(),
+    }
+}
@@ -0,0 +1,68 @@
+// File: src/prefetch.rs
+//
+// Read-ahead hints for cold loads (CacheConfig::prefetch). Parallel decoding reads many
+// shard columns at scattered offsets, and on a cold page cache each worker blocks on its
+// own read in turn. Telling the kernel up front which byte ranges are about to be read
+// (posix_fadvise WILLNEED for files, madvise WILLNEED for mappings) lets it fetch them
+// all in the background while the first shards decode. Hints are advisory: failures and
+// platforms without them are ignored, and nothing waits for the data to arrive.
+use std::fs::File;
+use memmap2::Mmap;
+
+use crate::columnar::SegmentDescriptor;
+
+// Ranges closer than this are hinted as one, to keep the number of syscalls down
+const MERGE_GAP: u64 = 64 * 1024;
+
+// (offset, len) of the stored columns of `segments`, sorted and merged
+pub fn column_ranges<'a>(segments: impl IntoIterator<Item = &'a SegmentDescriptor>) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = segments.into_iter()
+        .flat_map(|segment| segment.columns.iter())
+        .filter(|column| column.stored_len > 0)
+        .map(|column| (column.offset, column.stored_len))
+        .collect();
+    merged(&mut ranges)
+}
+
+fn merged(ranges: &mut [(u64, u64)]) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut out: Vec<(u64, u64)> = Vec::new();
+    for &(offset, len) in ranges.iter() {
+        match out.last_mut() {
+            Some((start, merged_len)) if offset <= *start + *merged_len + MERGE_GAP => {
+                *merged_len = (*merged_len).max(offset + len - *start);
+            }
+            _ => out.push((offset, len)),
+        }
+    }
+    out
+}
+
+// Ask the kernel to start reading `ranges` of `file` into the page cache
+#[cfg(target_os = "linux")]
+pub fn file_ranges(file: &File, ranges: &[(u64, u64)]) {
+    use std::os::unix::io::AsRawFd;
+
+    for &(offset, len) in ranges {
+        let _ = unsafe { libc::posix_fadvise(file.as_raw_fd(), offset as libc::off_t, len as libc::off_t, libc::POSIX_FADV_WILLNEED) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn file_ranges(_file: &File, _ranges: &[(u64, u64)]) {}
+
+// The same for ranges of a mapping; offsets are relative to its start
+#[cfg(unix)]
+pub fn mapped(mmap: &Mmap, ranges: &[(u64, u64)]) {
+    let mut ranges = ranges.to_vec();
+    for (offset, len) in merged(&mut ranges) {
+        let offset = offset as usize;
+        if offset < mmap.len() {
+            let len = (len as usize).min(mmap.len() - offset);
+            let _ = mmap.advise_range(memmap2::Advice::WillNeed, offset, len);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn mapped(_mmap: &Mmap, _ranges: &[(u64, u64)]) {}
@@ -1,14 +1,11 @@
-use bitvec::prelude::*;
-use crate::utils::{
-    TimsTOFRawData, IndexedTimsTOFData, find_scan_for_index, 
-    library_records_to_dataframe, merge_library_and_report, get_unique_precursor_ids, 
-    process_library_fast, create_rt_im_dicts, build_lib_matrix, build_precursors_matrix_step1, 
-    build_precursors_matrix_step2, build_range_matrix_step3, build_precursors_matrix_step3, 
-    build_frag_info, get_rt_list, LibCols, quantize, FrameSplit, MergeFrom, PrecursorLibData,
+use timstof_cache_ffi::utils::{
+    IndexedTimsTOFData, build_precursors_matrix_step1, build_precursors_matrix_step2,
+    build_range_matrix_step3, build_precursors_matrix_step3, build_frag_info, get_rt_list,
+    PrecursorLibData,
 };
-use rayon::prelude::*;
-use std::{collections::HashMap, error::Error, cmp::Ordering, sync::Arc, time::Instant};
-use ndarray::{Array1, Array2, Array3, Array4, s, Axis, concatenate};
+use timstof_cache_ffi::window::IsolationWindow;
+use std::error::Error;
+use ndarray::{Array2, Array3, Array4, s, Axis};
 use polars::prelude::*;
 use std::fs::File;
 
@@ -29,8 +26,8 @@ pub fn process_single_precursor(
     
     // Step 1: Build tensor representations
     let (ms1_data_tensor, ms2_data_tensor) = build_precursors_matrix_step1(
-        &[precursor_data.ms1_data.clone()],
-        &[precursor_data.ms2_data.clone()],
+        std::slice::from_ref(&precursor_data.ms1_data),
+        std::slice::from_ref(&precursor_data.ms2_data),
         device,
     )?;
     
@@ -47,7 +44,7 @@ pub fn process_single_precursor(
         device,
     )?;
     
-    let (re_ms1_data_tensor, re_ms2_data_tensor, ms1_extract_width_range_list, ms2_extract_width_range_list) = 
+    let (_, _, ms1_extract_width_range_list, ms2_extract_width_range_list) = 
         build_precursors_matrix_step3(
             &ms1_data_tensor,
             &ms2_data_tensor_processed,
@@ -75,7 +72,7 @@ pub fn process_single_precursor(
         .for_each(|mz| *mz = (*mz * 1000.0).ceil());
     
     // Step 5: Extract MS2 data
-    let mut frag_result_filtered = extract_ms2_data(
+    let frag_result_filtered = extract_ms2_data(
         finder,
         precursor_mz,
         &ms2_range_list,
@@ -168,16 +165,16 @@ pub struct FastChunkFinder {
 }
 
 impl FastChunkFinder {
-    pub fn new(mut pairs: Vec<((f32, f32), IndexedTimsTOFData)>) -> Result<Self, Box<dyn Error>> {
+    pub fn new(mut pairs: Vec<(IsolationWindow, IndexedTimsTOFData)>) -> Result<Self, Box<dyn Error>> {
         if pairs.is_empty() { return Err("no MS2 windows collected".into()); }
-        pairs.sort_by(|a, b| a.0 .0.partial_cmp(&b.0 .0).unwrap());
+        pairs.sort_by(|a, b| a.0.mz_low.partial_cmp(&b.0.mz_low).unwrap());
         
         let n = pairs.len();
         let mut low = Vec::with_capacity(n);
         let mut high = Vec::with_capacity(n);
-        for ((l, h), _) in &pairs {
-            low.push(*l);
-            high.push(*h);
+        for (window, _) in &pairs {
+            low.push(window.mz_low);
+            high.push(window.mz_high);
         }
         
         let chunks: Vec<IndexedTimsTOFData> = pairs.into_iter().map(|(_, data)| data).collect();
@@ -200,7 +197,7 @@ impl FastChunkFinder {
 pub fn build_intensity_matrix_optimized(
     // 提前建立时间→索引的映射，避免重复计算。
     // 这就是整个优化的核心原理：从"查找所有可能"变成"只处理存在的数据"。
-    data: &crate::utils::TimsTOFData,
+    data: &timstof_cache_ffi::utils::TimsTOFData,
     extract_width_range: &Array2<f32>,
     frag_moz_matrix: &Array2<f32>,
     all_rt: &[f32],
@@ -232,7 +229,7 @@ pub fn build_intensity_matrix_optimized(
 
         if let Some(&rt_idx) = rt2idx.get(&rt_key) {
             mz_table.entry(mz_key)
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push((rt_idx, inten_f as f32));
         }
     }
@@ -259,42 +256,6 @@ pub fn build_intensity_matrix_optimized(
 
 // Helper function implementations
 
-pub fn prepare_precursor_features(
-    precursors_list: &[Vec<String>],
-    precursor_info_list: &[Vec<f32>],
-    assay_rt_kept_dict: &std::collections::HashMap<String, f32>,
-    assay_im_kept_dict: &std::collections::HashMap<String, f32>,
-) -> Result<Array2<f32>, Box<dyn Error>> {
-    let n_precursors = precursors_list.len();
-    let n_cols = 8; // 5 info columns + im + rt + delta_rt
-    
-    let mut precursor_feat = Array2::<f32>::zeros((n_precursors, n_cols));
-    
-    for i in 0..n_precursors {
-        // Copy first 5 columns from precursor_info
-        let info_len = precursor_info_list[i].len().min(5);
-        for j in 0..info_len {
-            precursor_feat[[i, j]] = precursor_info_list[i][j];
-        }
-        
-        // Add assay IM and RT
-        precursor_feat[[i, 5]] = assay_im_kept_dict
-            .get(&precursors_list[i][0])
-            .copied()
-            .unwrap_or(0.0);
-        
-        precursor_feat[[i, 6]] = assay_rt_kept_dict
-            .get(&precursors_list[i][0])
-            .copied()
-            .unwrap_or(0.0);
-        
-        // Delta RT is 0 for all
-        precursor_feat[[i, 7]] = 0.0;
-    }
-    
-    Ok(precursor_feat)
-}
-
 pub fn calculate_mz_range(ms1_range_list: &Array3<f32>, i: usize) -> (f32, f32) {
     let ms1_range_slice = ms1_range_list.slice(s![i, .., ..]);
     
@@ -320,11 +281,10 @@ pub fn extract_ms2_data(
     i: usize,
     im_min: f32,
     im_max: f32,
-) -> Result<crate::utils::TimsTOFData, Box<dyn Error>> {
+) -> Result<timstof_cache_ffi::utils::TimsTOFData, Box<dyn Error>> {
     let mut result = if let Some(ms2_indexed) = finder.find(precursor_mz) {
         // Process all 66 MS2 ranges in parallel
-        let frag_results: Vec<crate::utils::TimsTOFData> = (0..66)
-            .into_iter()
+        let frag_results: Vec<timstof_cache_ffi::utils::TimsTOFData> = (0..66)
             .map(|j| {
                 let ms2_range_min_val = ms2_range_list[[i, j, 0]];
                 let ms2_range_max_val = ms2_range_list[[i, j, 1]];
@@ -333,7 +293,7 @@ pub fn extract_ms2_data(
                 let ms2_range_max = (ms2_range_max_val + 1.0) / 1000.0;
                 
                 if ms2_range_min <= 0.0 || ms2_range_max <= 0.0 || ms2_range_min >= ms2_range_max {
-                    crate::utils::TimsTOFData::new()
+                    timstof_cache_ffi::utils::TimsTOFData::new()
                 } else {
                     ms2_indexed.slice_by_mz_im_range(
                         ms2_range_min, ms2_range_max, im_min, im_max
@@ -342,10 +302,10 @@ pub fn extract_ms2_data(
             })
             .collect();
         
-        crate::utils::TimsTOFData::merge(frag_results)
+        timstof_cache_ffi::utils::TimsTOFData::merge(frag_results)
     } else {
         println!("  Warning: No MS2 data found for precursor m/z {:.4}", precursor_mz);
-        crate::utils::TimsTOFData::new()
+        timstof_cache_ffi::utils::TimsTOFData::new()
     };
     
     // Convert m/z values to integers
@@ -356,8 +316,8 @@ pub fn extract_ms2_data(
 }
 
 pub fn build_mask_matrices(
-    precursor_result_filtered: &crate::utils::TimsTOFData,
-    frag_result_filtered: &crate::utils::TimsTOFData,
+    precursor_result_filtered: &timstof_cache_ffi::utils::TimsTOFData,
+    frag_result_filtered: &timstof_cache_ffi::utils::TimsTOFData,
     ms1_extract_width_range_list: &Array3<f32>,
     ms2_extract_width_range_list: &Array3<f32>,
     i: usize,
@@ -409,8 +369,8 @@ pub fn build_mask_matrices(
 }
 
 pub fn extract_aligned_rt_values(
-    precursor_result_filtered: &crate::utils::TimsTOFData,
-    frag_result_filtered: &crate::utils::TimsTOFData,
+    precursor_result_filtered: &timstof_cache_ffi::utils::TimsTOFData,
+    frag_result_filtered: &timstof_cache_ffi::utils::TimsTOFData,
     target_rt: f32,
 ) -> Vec<f32> {
     use std::collections::HashSet;
@@ -0,0 +1,149 @@
+// File: src/profile.rs
+//
+// Read amplification bookkeeping: for every cache query, how much stored data was read
+// versus how many points came back. A ratio far above 1 means the layout (segments,
+// encoding, compression) doesn't suit the query pattern, e.g. lz4 columns forcing
+// whole-column reads for narrow m/z ranges.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+use crate::fdlimit;
+use crate::telemetry;
+
+// Bytes per decoded point: six 4-byte columns
+pub const ROW_BYTES: u64 = 24;
+const RECENT_LIMIT: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct QueryProfile {
+    pub source: String,
+    pub query: String,        // e.g. "ms1 mz [400.0, 410.0]"
+    pub strategy: String,     // How the cache was read, e.g. "columnar range read (raw)"
+    pub segments_read: usize,
+    pub bytes_read: u64,
+    pub rows_returned: u64,
+    pub elapsed: Duration,
+    pub io_fallback: Option<String>, // Why a mapping was given up for positional reads (iomode.rs)
+}
+
+impl QueryProfile {
+    // Stored bytes read per byte of returned data
+    pub fn amplification(&self) -> f64 {
+        self.bytes_read as f64 / (self.rows_returned * ROW_BYTES).max(1) as f64
+    }
+
+    pub fn explain(&self) -> String {
+        let strategy = match &self.io_fallback {
+            Some(fallback) => format!("{} ({})", self.strategy, fallback),
+            None => self.strategy.clone(),
+        };
+        format!(
+            "{} on {}\n   ├── Strategy: {}\n   ├── Segments read: {}\n   ├── Bytes read: {:.2} MB\n   ├── Points returned: {} ({:.2} MB)\n   ├── Read amplification: {:.1}x\n   └── Time: {:.3}s",
+            self.query,
+            self.source,
+            strategy,
+            self.segments_read,
+            self.bytes_read as f64 / 1024.0 / 1024.0,
+            self.rows_returned,
+            (self.rows_returned * ROW_BYTES) as f64 / 1024.0 / 1024.0,
+            self.amplification(),
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+// Running totals for one dataset, persisted across runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetProfile {
+    pub queries: u64,
+    pub segments_read: u64,
+    pub bytes_read: u64,
+    pub rows_returned: u64,
+    pub seconds: f64,
+    pub by_strategy: BTreeMap<String, u64>, // Query count per read strategy
+}
+
+impl DatasetProfile {
+    pub fn amplification(&self) -> f64 {
+        self.bytes_read as f64 / (self.rows_returned * ROW_BYTES).max(1) as f64
+    }
+}
+
+pub struct QueryProfiler {
+    path: Option<PathBuf>, // None keeps the totals of this process only, in `totals`
+    totals: Mutex<BTreeMap<String, DatasetProfile>>,
+    recent: Mutex<Vec<QueryProfile>>,
+}
+
+impl QueryProfiler {
+    const FILE_NAME: &'static str = "query_profile.json";
+
+    pub fn new(cache_dir: &Path) -> Self {
+        Self { path: Some(cache_dir.join(Self::FILE_NAME)), totals: Mutex::default(), recent: Mutex::new(Vec::new()) }
+    }
+
+    // For read-only cache directories
+    pub fn in_memory() -> Self {
+        Self { path: None, totals: Mutex::default(), recent: Mutex::new(Vec::new()) }
+    }
+
+    // Every load served from a cache passes through here, so it also counts the hit
+    pub fn record(&self, profile: QueryProfile) {
+        telemetry::record_hit();
+        let mut totals = self.report();
+        let entry = totals.entry(profile.source.clone()).or_default();
+        entry.queries += 1;
+        entry.segments_read += profile.segments_read as u64;
+        entry.bytes_read += profile.bytes_read;
+        entry.rows_returned += profile.rows_returned;
+        entry.seconds += profile.elapsed.as_secs_f64();
+        *entry.by_strategy.entry(profile.strategy.clone()).or_default() += 1;
+
+        // Profiling must never fail a query
+        match &self.path {
+            Some(path) => if let Ok(text) = serde_json::to_string_pretty(&totals) {
+                let _ = fdlimit::write(path, text);
+            },
+            None => *self.totals.lock().unwrap() = totals,
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() >= RECENT_LIMIT {
+            recent.remove(0);
+        }
+        recent.push(profile);
+    }
+
+    // Profile of the most recent query made through this cache manager
+    pub fn last(&self) -> Option<QueryProfile> {
+        self.recent.lock().unwrap().last().cloned()
+    }
+
+    pub fn recent(&self) -> Vec<QueryProfile> {
+        self.recent.lock().unwrap().clone()
+    }
+
+    // Per-dataset totals over all runs
+    pub fn report(&self) -> BTreeMap<String, DatasetProfile> {
+        match &self.path {
+            Some(path) => fdlimit::read_to_string(path)
+                .ok()
+                .and_then(|text| serde_json::from_str(&text).ok())
+                .unwrap_or_default(),
+            None => self.totals.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn reset(&self) -> std::io::Result<()> {
+        self.recent.lock().unwrap().clear();
+        self.totals.lock().unwrap().clear();
+        match self.path.as_ref().map(fs::remove_file) {
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
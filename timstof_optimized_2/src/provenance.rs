@@ -0,0 +1,89 @@
+// File: src/provenance.rs
+//
+// How a cache was produced, recorded in its metadata ("provenance" line, JSON) by every save
+// and update, so an audit can tell which build of this crate wrote an intermediate file,
+// with which settings, on which machine and when (CacheManager::provenance). The git commit
+// is the one build.rs found at compile time (TIMSTOF_CACHE_GIT_COMMIT), None for builds
+// outside a checkout that didn't set it. The configuration snapshot lists the settings that
+// shape the cache files, under their CacheSettings names; keys appear by id only. In
+// deterministic mode the hostname and the save's duration are left out and the time is the
+// recorded one (SOURCE_DATE_EPOCH or zero), so the record doesn't break byte-identity.
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+use serde::{Serialize, Deserialize};
+use sysinfo::{System, SystemExt};
+
+use crate::cache::{CacheConfig, CacheFormat};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub crate_version: String,            // Version of the crate that wrote the cache
+    pub git_commit: Option<String>,       // Commit it was built from
+    pub config: BTreeMap<String, String>, // Settings shaping the cache files
+    pub hostname: Option<String>,         // None in deterministic mode or if unknown
+    pub written_at: u64,                  // Seconds since the Unix epoch, as "cached at" / "updated at"
+    pub wall_seconds: Option<f64>,        // Duration of the save or update; None in deterministic mode
+}
+
+impl Provenance {
+    // `recorded_time` is the time the commit records, `elapsed` how long it took to write
+    pub fn capture(config: &CacheConfig, recorded_time: SystemTime, elapsed: Duration) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: option_env!("TIMSTOF_CACHE_GIT_COMMIT").map(str::to_string),
+            config: config_snapshot(config),
+            hostname: if config.deterministic { None } else { System::new().host_name() },
+            written_at: recorded_time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            wall_seconds: (!config.deterministic).then_some(elapsed.as_secs_f64()),
+        }
+    }
+
+    // "0.1.0 (1a2b3c4d) on host-7"
+    pub fn describe(&self) -> String {
+        let commit = self.git_commit.as_deref()
+            .map(|commit| format!(" ({})", &commit[..commit.len().min(8)]))
+            .unwrap_or_default();
+        let host = self.hostname.as_deref().map(|host| format!(" on {}", host)).unwrap_or_default();
+        format!("{}{}{}", self.crate_version, commit, host)
+    }
+}
+
+// Thread counts, buffer sizes and other settings that only affect speed are left out, so
+// deterministic saves on different machines record the same snapshot
+fn config_snapshot(config: &CacheConfig) -> BTreeMap<String, String> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let mut snapshot = BTreeMap::new();
+    let mut set = |key: &str, value: String| {
+        snapshot.insert(key.to_string(), value);
+    };
+    set("format", match config.format {
+        CacheFormat::Columnar => "columnar".to_string(),
+        CacheFormat::SingleFile => "single-file".to_string(),
+        CacheFormat::Parquet { row_group_size } => format!("parquet (row groups of {})", row_group_size),
+    });
+    set("enable_compression", config.enable_compression.to_string());
+    set("auto_compression", config.auto_compression.to_string());
+    set("compression", format!("{:?}", config.compression));
+    set("adaptive_compression", config.adaptive_compression.to_string());
+    set("byte_shuffle", config.byte_shuffle.to_string());
+    set("compression_block_bytes", optional(config.compression_block_bytes.map(|bytes| bytes.to_string())));
+    set("window_compression_min_bytes", config.window_compression_min_bytes.to_string());
+    set("column_order", config.column_order.join(","));
+    set("encryption", optional(config.encryption_key.as_ref().map(|key| format!("aes256gcm (key id: {})", key.id))));
+    set("signing", optional(config.signing_key.as_ref().map(|key| format!("key id: {}", key.id))));
+    set("target_shard_bytes", optional(config.target_shard_bytes.map(|bytes| bytes.to_string())));
+    set("target_shard_points", optional(config.target_shard_points.map(|points| points.to_string())));
+    set("validation", format!("{:?}", config.validation));
+    set("lossy", optional(config.lossy.map(|lossy| lossy.describe())));
+    set("precision", config.precision.dtype().to_string());
+    set("frame_index", config.frame_index.to_string());
+    set("rt_major", config.rt_major.to_string());
+    set("heatmap", config.heatmap.to_string());
+    set("params_fingerprint", optional(config.params_fingerprint.map(|fingerprint| format!("{:016x}", fingerprint))));
+    set("content_addressed", config.content_addressed.to_string());
+    set("sort", format!("{:?}", config.sort));
+    set("non_finite", config.non_finite.describe());
+    set("durability", format!("{:?}", config.durability));
+    set("deterministic", config.deterministic.to_string());
+    snapshot
+}
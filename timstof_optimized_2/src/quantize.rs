@@ -0,0 +1,127 @@
+// File: src/quantize.rs
+//
+// Opt-in lossy storage for QC and visualization caches: intensities as log-scaled u16,
+// m/z as u32 fixed-point steps small enough to stay within a ppm error bound. Together
+// with a codec this cuts cache size several-fold. A quantized column is stored with the
+// dtype of its stored values ("u16", "u32") and the parameters to undo it, so readers that
+// don't know quantization reject it instead of misreading it; readers that do hand back
+// the usual f32 / u32 values. Which columns are quantized is fixed: every other column is
+// stored exactly.
+use serde::{Serialize, Deserialize};
+
+use crate::precision::Float;
+use crate::simd;
+
+// Lossy settings of a save (CacheConfig::lossy)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LossyOptions {
+    pub mz_error_ppm: f64, // Largest relative m/z error, in ppm
+}
+
+impl Default for LossyOptions {
+    fn default() -> Self {
+        Self { mz_error_ppm: 2.0 }
+    }
+}
+
+impl LossyOptions {
+    // The "lossy" metadata value
+    pub fn describe(&self) -> String {
+        format!("mz<={}ppm,intensity=log-u16", self.mz_error_ppm)
+    }
+}
+
+// How a stored column maps back to its values
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Quantization {
+    // u16 q = round(ln(1 + intensity) * scale); about 0.02% relative error at full u32 range
+    LogU16 { scale: f64 },
+    // u32 q = round((mz - offset) / step), u32::MAX for NaN, each stored as the (wrapping)
+    // difference to the previous row's: sorted m/z makes those small and compressible
+    FixedU32 { offset: f64, step: f64 },
+}
+
+impl Quantization {
+    // Dtype of the stored values
+    pub fn stored_dtype(&self) -> &'static str {
+        match self {
+            Quantization::LogU16 { .. } => "u16",
+            Quantization::FixedU32 { .. } => "u32",
+        }
+    }
+
+    // Dtype of the values it stands for
+    pub fn value_dtype(&self) -> &'static str {
+        match self {
+            Quantization::LogU16 { .. } => "u32",
+            Quantization::FixedU32 { .. } => "f32",
+        }
+    }
+
+    pub fn stored_width(&self) -> usize {
+        match self {
+            Quantization::LogU16 { .. } => 2,
+            Quantization::FixedU32 { .. } => 4,
+        }
+    }
+
+    // What an m/z value decodes to once quantized (unchanged for other quantizations)
+    pub fn round_trip_mz(&self, mz: f32) -> f32 {
+        match *self {
+            Quantization::FixedU32 { offset, step } if !mz.is_nan() => {
+                (offset + ((mz as f64 - offset) / step).round() * step) as f32
+            }
+            _ => mz,
+        }
+    }
+
+    // Little-endian stored bytes back to little-endian values of the column's own dtype
+    pub fn dequantize(&self, stored: &[u8]) -> Vec<u8> {
+        match *self {
+            Quantization::LogU16 { scale } => simd::log_u16_to_u32_bytes(stored, scale),
+            Quantization::FixedU32 { offset, step } => simd::fixed_to_f32_bytes(&simd::prefix_sum_u32(stored), offset, step),
+        }
+    }
+}
+
+// Intensities as log-scaled u16, scaled so the largest one uses the full range
+pub fn quantize_intensity(values: &[u32]) -> (Quantization, Vec<u8>) {
+    let max = values.iter().copied().max().unwrap_or(0);
+    let scale = match max {
+        0 => 1.0,
+        max => (u16::MAX - 1) as f64 / (max as f64).ln_1p(),
+    };
+    let stored = values.iter()
+        .map(|&value| ((value as f64).ln_1p() * scale).round() as u16)
+        .flat_map(|q| q.to_le_bytes())
+        .collect();
+    (Quantization::LogU16 { scale }, stored)
+}
+
+// m/z as fixed-point u32 above the smallest value, with a step of twice the error bound
+// at that value, so rounding stays within `error_ppm` for every larger one. None (store
+// exactly) if there is no positive m/z, a value is infinite, or the steps don't fit u32.
+pub fn quantize_mz<F: Float>(values: &[F], error_ppm: f64) -> Option<(Quantization, Vec<u8>)> {
+    let values: Vec<f64> = values.iter().map(|mz| mz.to_f64()).collect();
+    if values.iter().any(|mz| mz.is_infinite()) {
+        return None;
+    }
+    let finite = || values.iter().copied().filter(|mz| !mz.is_nan());
+    let min = finite().fold(f64::INFINITY, f64::min);
+    let max = finite().fold(f64::NEG_INFINITY, f64::max);
+    // 90% of the bound, leaving room for the f32 rounding of the decoded value
+    let step = 2.0 * error_ppm * 0.9e-6 * min;
+    if !(min > 0.0 && step > 0.0) || (max - min) / step >= (u32::MAX - 1) as f64 {
+        return None;
+    }
+    let stored = values.iter()
+        .map(|&mz| match mz.is_nan() {
+            true => u32::MAX,
+            false => ((mz - min) / step).round() as u32,
+        })
+        .scan(0u32, |previous, q| Some(q.wrapping_sub(std::mem::replace(previous, q))))
+        .flat_map(|delta| delta.to_le_bytes())
+        .collect();
+    Some((Quantization::FixedU32 { offset: min, step }, stored))
+}
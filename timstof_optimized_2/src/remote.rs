@@ -0,0 +1,216 @@
+// File: src/remote.rs
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::fdlimit;
+
+// Primary cache storage that lives somewhere other than the local cache dir
+pub trait RemoteStore: Send + Sync {
+    fn describe(&self) -> String;
+    fn contains(&self, file_name: &str) -> bool;
+    // Download one cache file into dest, returning the number of bytes transferred
+    fn fetch(&self, file_name: &str, dest: &Path) -> io::Result<u64>;
+    fn put(&self, file_name: &str, src: &Path) -> io::Result<()>;
+    // Read `len` bytes starting at `offset` without transferring the whole file
+    fn read_range(&self, file_name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+    fn len(&self, file_name: &str) -> io::Result<u64>;
+    // A small file (metadata) whole, into memory rather than the local mirror
+    fn read(&self, file_name: &str) -> io::Result<Vec<u8>> {
+        self.read_range(file_name, 0, self.len(file_name)? as usize)
+    }
+}
+
+// Remote cache exposed as a mounted directory (s3fs/goofys bucket mount, NFS export, ...)
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl RemoteStore for DirectoryStore {
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn contains(&self, file_name: &str) -> bool {
+        self.root.join(file_name).is_file()
+    }
+
+    fn fetch(&self, file_name: &str, dest: &Path) -> io::Result<u64> {
+        fs::copy(self.root.join(file_name), dest)
+    }
+
+    fn put(&self, file_name: &str, src: &Path) -> io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        // Copy under a temporary name first so readers never see a half-written file
+        let tmp = self.root.join(format!("{}.partial", file_name));
+        fs::copy(src, &tmp)?;
+        fs::rename(tmp, self.root.join(file_name))
+    }
+
+    fn read_range(&self, file_name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = fdlimit::open(self.root.join(file_name))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = vec![0u8; len];
+        file.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn len(&self, file_name: &str) -> io::Result<u64> {
+        Ok(fs::metadata(self.root.join(file_name))?.len())
+    }
+}
+
+// Read-only cache served by a plain HTTP(S) server; partial loads use Range requests
+pub struct HttpStore {
+    base_url: String,
+}
+
+impl HttpStore {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self { base_url: base_url.into().trim_end_matches('/').to_string() }
+    }
+
+    fn url(&self, file_name: &str) -> String {
+        format!("{}/{}", self.base_url, file_name)
+    }
+}
+
+fn http_error(e: ureq::Error) -> io::Error {
+    match e {
+        ureq::Error::Status(404, _) => io::Error::new(io::ErrorKind::NotFound, e.to_string()),
+        _ => io::Error::other(e.to_string()),
+    }
+}
+
+impl RemoteStore for HttpStore {
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn contains(&self, file_name: &str) -> bool {
+        ureq::head(&self.url(file_name)).call().is_ok()
+    }
+
+    fn fetch(&self, file_name: &str, dest: &Path) -> io::Result<u64> {
+        let response = ureq::get(&self.url(file_name)).call().map_err(http_error)?;
+        let mut file = fdlimit::create(dest)?;
+        io::copy(&mut response.into_reader(), &mut file)
+    }
+
+    fn put(&self, file_name: &str, _src: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("HTTP cache {} is read-only, cannot upload {}", self.base_url, file_name),
+        ))
+    }
+
+    fn read_range(&self, file_name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let range = format!("bytes={}-{}", offset, offset + len as u64 - 1);
+        let response = ureq::get(&self.url(file_name))
+            .set("Range", &range)
+            .call()
+            .map_err(http_error)?;
+
+        // A 200 means the server ignored the Range header and is sending the whole file
+        if response.status() != 206 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} does not support HTTP range requests", self.base_url),
+            ));
+        }
+
+        let mut buffer = Vec::with_capacity(len);
+        response.into_reader().take(len as u64).read_to_end(&mut buffer)?;
+        if buffer.len() != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("short range read from {}", file_name)));
+        }
+        Ok(buffer)
+    }
+
+    // A plain GET: servers without range support serve whole files too
+    fn read(&self, file_name: &str) -> io::Result<Vec<u8>> {
+        let response = ureq::get(&self.url(file_name)).call().map_err(http_error)?;
+        let mut buffer = Vec::new();
+        response.into_reader().read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn len(&self, file_name: &str) -> io::Result<u64> {
+        let response = ureq::head(&self.url(file_name)).call().map_err(http_error)?;
+        response.header("Content-Length")
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| io::Error::other(format!("no Content-Length for {}", file_name)))
+    }
+}
+
+// The sources the local mirror fetched from the remote store, with their last access,
+// persisted next to the cache files. Only these are evicted to bound the mirror: caches
+// built locally may exist nowhere else. File mtimes can't be used for the access times
+// because they drive cache validity.
+pub struct MirrorIndex {
+    path: PathBuf,
+    last_access: HashMap<String, u64>,
+}
+
+impl MirrorIndex {
+    // Not mirror_index.json, which listed every source accessed with a remote configured,
+    // locally built ones included
+    const FILE_NAME: &'static str = "mirror_manifest.json";
+
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(Self::FILE_NAME);
+        let last_access = fdlimit::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, last_access }
+    }
+
+    // A source whose files the mirror just fetched, accessed now
+    pub fn record(&mut self, source_name: &str) {
+        self.last_access.insert(source_name.to_string(), now_secs());
+    }
+
+    // An access to a mirrored source; other sources are not tracked
+    pub fn touch(&mut self, source_name: &str) {
+        if let Some(last_access) = self.last_access.get_mut(source_name) {
+            *last_access = now_secs();
+        }
+    }
+
+    pub fn contains(&self, source_name: &str) -> bool {
+        self.last_access.contains_key(source_name)
+    }
+
+    // E.g. once the source is saved locally
+    pub fn remove(&mut self, source_name: &str) {
+        self.last_access.remove(source_name);
+    }
+
+    pub fn last_access(&self, source_name: &str) -> u64 {
+        self.last_access.get(source_name).copied().unwrap_or(0)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = serde_json::to_string(&self.last_access)
+            .map_err(io::Error::other)?;
+        fdlimit::write(&self.path, text)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,79 @@
+// File: src/report.rs
+//
+// End-of-operation summaries as JSON, for orchestration systems that ingest them rather
+// than scrape stdout. With a report writer (CacheManager::with_json_reports) every save,
+// update, raw tier save and load writes its summary as one JSON object per line (JSON
+// Lines) to the writer, in place of the text it would print; progress lines printed while
+// the operation runs are unchanged. A report that can't be written is noted on stderr and
+// never fails the operation it describes.
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::Serialize;
+
+use crate::profile::QueryProfile;
+use crate::telemetry;
+
+// Shared by clones of the writer's owner; reports from concurrent operations never interleave
+pub type ReportWriter = Arc<Mutex<dyn Write + Send>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationReport {
+    pub operation: String,           // "save", "update", "save_raw", "load", "load_raw"
+    pub source: String,
+    pub bytes: u64,                  // Written by saves and updates, read by loads
+    pub points: u64,
+    pub seconds: f64,
+    pub mb_per_second: f64,          // Of `bytes`, MB as in the text summaries (2^20 bytes)
+    pub ms1_shards: Option<usize>,
+    pub ms2_windows: Option<usize>,
+    pub query: Option<String>,       // Loads: what was asked for and how the cache was read (profile.rs)
+    pub strategy: Option<String>,
+    pub io_fallback: Option<String>, // Loads that couldn't map a file and read it instead
+}
+
+impl OperationReport {
+    pub fn new(operation: &str, source_path: &Path, bytes: u64, points: u64, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        Self {
+            operation: operation.to_string(),
+            source: source_path.display().to_string(),
+            bytes,
+            points,
+            seconds,
+            mb_per_second: if seconds > 0.0 { bytes as f64 / 1024.0 / 1024.0 / seconds } else { 0.0 },
+            ms1_shards: None,
+            ms2_windows: None,
+            query: None,
+            strategy: None,
+            io_fallback: None,
+        }
+    }
+
+    pub fn from_profile(source_path: &Path, profile: &QueryProfile) -> Self {
+        Self {
+            query: Some(profile.query.clone()),
+            strategy: Some(profile.strategy.clone()),
+            io_fallback: profile.io_fallback.clone(),
+            ..Self::new("load", source_path, profile.bytes_read, profile.rows_returned, profile.elapsed)
+        }
+    }
+
+    pub fn with_layout(mut self, ms1_shards: Option<usize>, ms2_windows: usize) -> Self {
+        self.ms1_shards = ms1_shards;
+        self.ms2_windows = Some(ms2_windows);
+        self
+    }
+
+    pub fn write_to(&self, writer: &ReportWriter) {
+        let written = serde_json::to_string(self).map_err(std::io::Error::other).and_then(|line| {
+            let mut writer = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            writeln!(writer, "{}", line)?;
+            writer.flush()
+        });
+        if let Err(e) = written {
+            telemetry::warn(&format!("⚠️  Could not write the {} report of {}: {}", self.operation, self.source, e));
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// File: src/retry.rs
+//
+// Retries for transient I/O failures. Network filesystems report sporadic EIO / ESTALE
+// under parallel reads that succeed when repeated, and without a retry one such error
+// aborts a whole load. Retries happen per shard read (RetryingFile, remote range reads)
+// and per container write; container writes go to a .partial file that is renamed at
+// the end, so repeating one from the start is safe.
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::columnar;
+use crate::fdlimit::{self, FilePermit};
+use crate::prefetch;
+use crate::telemetry;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub attempts: u32,                        // Tries per operation, the first included; 1 disables retries
+    pub initial_backoff: Duration,            // Wait before the first retry, doubled for each further one
+    pub max_backoff: Duration,                // Cap on the wait between tries
+    pub retryable_kinds: Vec<io::ErrorKind>,  // Errors worth repeating, by kind
+    pub retryable_os_errors: Vec<i32>,        // ... and by raw OS code, for errors std has no kind for (EIO)
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            // Not Interrupted: that is what a cancelled operation returns (cancel.rs)
+            retryable_kinds: vec![
+                io::ErrorKind::TimedOut,
+                io::ErrorKind::ConnectionReset,
+                io::ErrorKind::ConnectionAborted,
+                io::ErrorKind::StaleNetworkFileHandle,
+            ],
+            retryable_os_errors: transient_os_errors(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn transient_os_errors() -> Vec<i32> {
+    vec![libc::EIO, libc::ESTALE]
+}
+
+// ERROR_UNEXP_NET_ERR, ERROR_NETNAME_DELETED, ERROR_SEM_TIMEOUT
+#[cfg(windows)]
+fn transient_os_errors() -> Vec<i32> {
+    vec![59, 64, 121]
+}
+
+#[cfg(not(any(unix, windows)))]
+fn transient_os_errors() -> Vec<i32> {
+    Vec::new()
+}
+
+impl RetryPolicy {
+    // Every error is final
+    pub fn none() -> Self {
+        Self { attempts: 1, ..Self::default() }
+    }
+
+    pub fn is_retryable(&self, error: &io::Error) -> bool {
+        self.retryable_kinds.contains(&error.kind())
+            || error.raw_os_error().is_some_and(|code| self.retryable_os_errors.contains(&code))
+    }
+
+    // Wait before try `attempt` (1 is the first retry)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    // Run `operation` until it succeeds, fails with an error that is not retryable, or
+    // runs out of attempts; `what` names it in the retry message. The operation gets the
+    // index of the try, so it can reset state before repeating (reopen a file, say).
+    pub fn run<T>(&self, what: impl Fn() -> String, mut operation: impl FnMut(u32) -> io::Result<T>) -> io::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match operation(attempt) {
+                Err(e) if attempt + 1 < self.attempts && self.is_retryable(&e) => {
+                    attempt += 1;
+                    let wait = self.backoff(attempt);
+                    telemetry::warn(&format!("⚠️  {} failed ({}), retry {}/{} in {:?}", what(), e, attempt, self.attempts - 1, wait));
+                    std::thread::sleep(wait);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// Positional reads of a file that retry transient failures. The file is reopened before
+// each retry: a handle NFS has declared stale stays stale. Holds one of the cache's file
+// handles (fdlimit.rs) while open.
+pub struct RetryingFile {
+    path: PathBuf,
+    file: RwLock<Arc<File>>,
+    policy: RetryPolicy,
+    _permit: FilePermit,
+}
+
+impl RetryingFile {
+    pub fn open(path: &Path, policy: &RetryPolicy) -> io::Result<Self> {
+        let permit = fdlimit::acquire(1)?;
+        let file = policy.run(|| format!("opening {}", path.display()), |_| File::open(path)).map_err(fdlimit::explain)?;
+        Ok(Self { path: path.to_path_buf(), file: RwLock::new(Arc::new(file)), policy: policy.clone(), _permit: permit })
+    }
+
+    pub fn file_len(&self) -> io::Result<u64> {
+        self.policy.run(|| format!("reading the size of {}", self.path.display()), |attempt| {
+            Ok(self.current(attempt)?.metadata()?.len())
+        })
+    }
+
+    pub fn read_at(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.policy.run(|| format!("reading {} bytes at {} of {}", len, offset, self.path.display()), |attempt| {
+            let mut buffer = vec![0u8; len];
+            columnar::read_exact_at(&*self.current(attempt)?, &mut buffer, offset)?;
+            Ok(buffer)
+        })
+    }
+
+    // Read-ahead hint for `ranges` (prefetch.rs); reads still go through read_at
+    pub fn prefetch(&self, ranges: &[(u64, u64)]) {
+        if let Ok(file) = self.current(0) {
+            prefetch::file_ranges(&file, ranges);
+        }
+    }
+
+    // The open handle, replaced by a fresh one on retries
+    fn current(&self, attempt: u32) -> io::Result<Arc<File>> {
+        if attempt > 0 {
+            let reopened = Arc::new(File::open(&self.path).map_err(fdlimit::explain)?);
+            *self.file.write().unwrap_or_else(|e| e.into_inner()) = reopened.clone();
+            return Ok(reopened);
+        }
+        Ok(self.file.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
@@ -0,0 +1,95 @@
+// File: src/scheme.rs
+//
+// DIA isolation window scheme of a cache, recorded in its metadata at save time
+// ("window_scheme" line, JSON), so callers no longer keep an external window table in step
+// with what the cache holds. Windows are listed in cycle order: the order of the first
+// frame holding their points, which is the order the instrument steps through them. Schemes
+// with overlapping windows are kept as they are, and windows_covering() returns every
+// window isolating a precursor m/z rather than the first match.
+use serde::{Serialize, Deserialize};
+
+use crate::cache::WindowKey;
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WindowScheme {
+    pub windows: Vec<SchemeWindow>, // Cycle order; windows without points last, in save order
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemeWindow {
+    pub low: f32,
+    pub high: f32,
+    pub first_frame: Option<u32>, // First frame holding its points, None if it holds none
+}
+
+impl SchemeWindow {
+    fn key(&self) -> WindowKey {
+        WindowKey::new(self.low, self.high)
+    }
+
+    // Bounds inclusive, as WindowFilter::Containing
+    pub fn contains(&self, mz: f32) -> bool {
+        self.low <= mz && mz <= self.high
+    }
+
+    // Shares more than an edge with `other`
+    pub fn overlaps(&self, other: &SchemeWindow) -> bool {
+        self.low < other.high && other.low < self.high
+    }
+}
+
+impl WindowScheme {
+    pub fn from_windows<F: Float>(ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)]) -> Self {
+        let mut scheme = Self::default();
+        scheme.add(ms2_indexed_pairs);
+        scheme
+    }
+
+    // Windows of an incremental update: known ones may start earlier, new ones are added,
+    // then the cycle order is worked out again
+    pub fn add<F: Float>(&mut self, ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)]) {
+        for (window, data) in ms2_indexed_pairs {
+            let first_frame = data.frame_indices.iter().min().copied();
+            let key = window.key();
+            match self.windows.iter_mut().find(|window| window.key() == key) {
+                Some(window) => {
+                    window.first_frame = match (window.first_frame, first_frame) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => self.windows.push(SchemeWindow { low: window.mz_low, high: window.mz_high, first_frame }),
+            }
+        }
+        // Stable: windows first seen in the same frame keep their save order
+        self.windows.sort_by_key(|window| (window.first_frame.is_none(), window.first_frame));
+    }
+
+    // Every window whose isolation range holds `mz`, in cycle order; more than one where
+    // windows overlap, none outside the scheme
+    pub fn windows_covering(&self, mz: f32) -> Vec<&SchemeWindow> {
+        self.windows.iter().filter(|window| window.contains(mz)).collect()
+    }
+
+    // Position of the window with these bounds (matched to 0.001 Th) in the cycle
+    pub fn cycle_position(&self, low: f32, high: f32) -> Option<usize> {
+        let key = WindowKey::new(low, high);
+        self.windows.iter().position(|window| window.key() == key)
+    }
+
+    // Pairs of cycle positions whose windows overlap
+    pub fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for (i, a) in self.windows.iter().enumerate() {
+            for (j, b) in self.windows.iter().enumerate().skip(i + 1) {
+                if a.overlaps(b) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+}
@@ -0,0 +1,218 @@
+// File: src/scrub.rs
+//
+// Incremental integrity checking of the cache dir. Each run verifies at most `budget.bytes`
+// of stored data and records where it stopped, so a large cache is covered over many runs
+// (e.g. a nightly cron job) without ever reading everything at once.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use polars::prelude::{ParquetReader, SerReader};
+
+use crate::columnar::{self, ColumnDescriptor};
+use crate::fdlimit;
+use crate::parquet_backend;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScrubBudget {
+    pub bytes: u64,                   // Stop after verifying this much stored data
+    pub bytes_per_sec: Option<u64>,   // Throttle reads so foreground jobs keep their bandwidth
+}
+
+#[derive(Debug, Default)]
+pub struct ScrubReport {
+    pub bytes_verified: u64,
+    pub units_verified: usize,
+    pub corrupt: Vec<String>,
+    pub skipped: Vec<String>,   // Legacy files without checksums or structure to check
+    pub pass_completed: bool,   // The cursor wrapped around: every file has been checked
+}
+
+// Position of the scrubber, persisted between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScrubCursor {
+    file: Option<String>,
+    unit: usize,
+    last_full_pass: Option<u64>, // Unix seconds
+}
+
+impl ScrubCursor {
+    const FILE_NAME: &'static str = "scrub_cursor.json";
+
+    fn load(cache_dir: &Path) -> Self {
+        fdlimit::read_to_string(cache_dir.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, cache_dir: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        fdlimit::write(cache_dir.join(Self::FILE_NAME), text)
+    }
+}
+
+// Smallest piece of work: one container column, or a whole file without a column table
+enum ScrubUnit {
+    Column(ColumnDescriptor),
+    ParquetFile(u64),
+    Unverifiable,
+}
+
+impl ScrubUnit {
+    fn len(&self) -> u64 {
+        match self {
+            ScrubUnit::Column(column) => column.stored_len,
+            ScrubUnit::ParquetFile(len) => *len,
+            ScrubUnit::Unverifiable => 0,
+        }
+    }
+}
+
+// Data files in a stable order; partial writes and bookkeeping files are ignored
+fn cache_files(cache_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if name.contains(".cache.") && !name.ends_with(".partial") {
+                files.push((name.to_string(), path.clone()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn units_of(path: &Path) -> io::Result<Vec<ScrubUnit>> {
+    if parquet_backend::is_parquet_path(path) {
+        return Ok(vec![ScrubUnit::ParquetFile(fs::metadata(path)?.len())]);
+    }
+    if !columnar::is_container(path) {
+        return Ok(vec![ScrubUnit::Unverifiable]);
+    }
+    let file = fdlimit::open(path)?;
+    let header = columnar::read_header_with(&columnar::file_reader(&file), file.metadata()?.len())?;
+    Ok(header.segments.into_iter()
+        .flat_map(|segment| segment.columns)
+        .map(ScrubUnit::Column)
+        .collect())
+}
+
+fn verify_unit(path: &Path, unit: &ScrubUnit) -> io::Result<()> {
+    match unit {
+        ScrubUnit::Column(column) => {
+            let file = fdlimit::open(path)?;
+            let read_at = columnar::file_reader(&file);
+            columnar::verify_stored_column(&read_at, column)
+        }
+        // Parquet pages are checked by decoding the whole file
+        ScrubUnit::ParquetFile(_) => {
+            let (file, _permit) = fdlimit::open(path)?.into_parts();
+            ParquetReader::new(file)
+                .finish()
+                .map(|_| ())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+        }
+        ScrubUnit::Unverifiable => Ok(()),
+    }
+}
+
+// Verify every unit of one file in one go, adding to `report`; used for explicit checks
+// of a single source rather than the budgeted background pass
+pub fn verify_file(path: &Path, report: &mut ScrubReport) -> io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    for unit in units_of(path)? {
+        match unit {
+            ScrubUnit::Unverifiable => report.skipped.push(name.clone()),
+            _ => {
+                if let Err(e) = verify_unit(path, &unit) {
+                    report.corrupt.push(format!("{}: {}", name, e));
+                }
+                report.units_verified += 1;
+            }
+        }
+        report.bytes_verified += unit.len();
+    }
+    Ok(())
+}
+
+pub fn scrub(cache_dir: &Path, budget: ScrubBudget) -> io::Result<ScrubReport> {
+    let mut cursor = ScrubCursor::load(cache_dir);
+    let mut report = ScrubReport::default();
+    let start = Instant::now();
+
+    let files = cache_files(cache_dir)?;
+    // Resume at the cursor's file, or the next one if it has been deleted meanwhile
+    let mut file_index = match &cursor.file {
+        Some(name) => files.iter().position(|(f, _)| f >= name).unwrap_or(files.len()),
+        None => 0,
+    };
+    if files.get(file_index).map(|(f, _)| Some(f) != cursor.file.as_ref()).unwrap_or(true) {
+        cursor.unit = 0;
+    }
+
+    'files: loop {
+        let (name, path) = match files.get(file_index) {
+            Some(file) => file,
+            None => {
+                // End of the directory: one full pass done, start over next run
+                report.pass_completed = true;
+                cursor.file = None;
+                cursor.unit = 0;
+                cursor.last_full_pass = SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+                break;
+            }
+        };
+        if report.bytes_verified >= budget.bytes {
+            break;
+        }
+
+        let units = match units_of(path) {
+            Ok(units) => units,
+            Err(e) => {
+                report.corrupt.push(format!("{}: {}", name, e));
+                Vec::new()
+            }
+        };
+
+        while cursor.unit < units.len() {
+            if report.bytes_verified >= budget.bytes {
+                cursor.file = Some(name.clone());
+                break 'files;
+            }
+            let unit = &units[cursor.unit];
+            match unit {
+                ScrubUnit::Unverifiable => report.skipped.push(name.clone()),
+                _ => {
+                    if let Err(e) = verify_unit(path, unit) {
+                        report.corrupt.push(format!("{}: {}", name, e));
+                    }
+                    report.units_verified += 1;
+                }
+            }
+            report.bytes_verified += unit.len();
+            cursor.unit += 1;
+
+            if let Some(rate) = budget.bytes_per_sec {
+                let target = Duration::from_secs_f64(report.bytes_verified as f64 / rate.max(1) as f64);
+                if let Some(wait) = target.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+
+        file_index += 1;
+        cursor.unit = 0;
+        cursor.file = files.get(file_index).map(|(f, _)| f.clone());
+    }
+
+    cursor.save(cache_dir)?;
+    Ok(report)
+}
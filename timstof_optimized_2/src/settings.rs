@@ -0,0 +1,235 @@
+// File: src/settings.rs
+//
+// CacheConfig from a TOML file (CacheConfig::from_file) or TIMSTOF_CACHE_* environment
+// variables (CacheConfig::from_env), so deployments can tune the cache without code
+// changes. Every setting is optional and overrides the default; keys are the CacheSettings
+// field names, and in the environment the same names upper-cased after the prefix
+// (TIMSTOF_CACHE_COMPRESSION=zstd-9). Keys, encryption and signing stay with their own
+// variables (encryption.rs, signing.rs), never in a file.
+//
+//     cache_dir = "/scratch/timstof_cache"
+//     format = "single-file"
+//     compression = "zstd-9"
+//     parallel_threads = 16
+//     local_mirror_bytes = 500_000_000_000
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::Deserialize;
+
+use crate::cache::{CacheConfig, CacheFormat};
+use crate::compression::{self, CompressionType};
+use crate::freshness::ValidationPolicy;
+use crate::iomode::IoMode;
+use crate::durability::Durability;
+use crate::input::{NonFinitePolicy, SortPolicy};
+use crate::precision::Precision;
+use crate::space::LowSpacePolicy;
+
+pub const ENV_PREFIX: &str = "TIMSTOF_CACHE_";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheSettings {
+    pub cache_dir: Option<PathBuf>,
+    pub format: Option<String>, // columnar | single-file | parquet
+    pub parquet_row_group_size: Option<usize>,
+    pub enable_compression: Option<bool>,
+    pub auto_compression: Option<bool>,
+    pub compression: Option<String>, // lz4 | zstd | zstd-<level> | zstd-<level>-long | archival
+    pub zstd_workers: Option<u32>,
+    pub zstd_window_log: Option<u32>,
+    pub adaptive_compression: Option<bool>,
+    pub byte_shuffle: Option<bool>,
+    pub buffer_size: Option<usize>,
+    pub async_threads: Option<usize>,
+    pub parallel_threads: Option<usize>,
+    pub hot_cache_entries: Option<usize>,
+    pub stream_buffer_points: Option<usize>,
+    pub history_generations: Option<usize>,
+    pub local_mirror_bytes: Option<u64>,
+    pub trash_retention_hours: Option<u64>,
+    pub on_low_space: Option<String>, // fail | evict-oldest | ignore
+    pub target_shard_bytes: Option<u64>,
+    pub target_shard_points: Option<usize>,
+    pub window_compression_min_bytes: Option<u64>,
+    pub validation: Option<String>, // dir-mtime | deep-mtime | content-hash
+    pub precision: Option<String>,  // f32 | f64
+    pub read_only: Option<bool>,
+    pub content_addressed: Option<bool>,
+    pub shared_memory_dir: Option<PathBuf>,
+    pub frame_index: Option<bool>,
+    pub rt_major: Option<bool>,
+    pub heatmap: Option<bool>,
+    pub prefetch: Option<bool>,
+    pub io_mode: Option<String>, // auto | buffered | mmap
+    pub compression_block_bytes: Option<usize>, // 0 compresses whole columns
+    pub durability: Option<String>, // none | flush | fsync
+    pub non_finite: Option<String>, // keep | reject | drop-rows | replace:<value>
+    pub sort: Option<String>,       // verify | sort
+    pub deterministic: Option<bool>,
+    pub io_attempts: Option<u32>,   // Tries per I/O operation (retry.rs); 1 disables retries
+}
+
+impl CacheSettings {
+    // Settings read from the environment. Keep in step with the fields; content_addressed
+    // and shared_memory_dir already have variables of their own (see CacheConfig::from_env).
+    const ENV_KEYS: [&'static str; 36] = [
+        "cache_dir", "format", "parquet_row_group_size", "enable_compression", "auto_compression", "compression",
+        "zstd_workers", "zstd_window_log",
+        "adaptive_compression", "byte_shuffle", "buffer_size", "async_threads", "parallel_threads", "hot_cache_entries", "history_generations",
+        "local_mirror_bytes", "trash_retention_hours", "on_low_space", "target_shard_bytes", "target_shard_points", "stream_buffer_points",
+        "window_compression_min_bytes", "validation", "precision", "read_only", "frame_index", "rt_major", "heatmap",
+        "prefetch", "io_mode", "compression_block_bytes", "durability", "non_finite", "sort", "deterministic",
+        "io_attempts",
+    ];
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    // Values are read as TOML (numbers, booleans), anything else as a string
+    pub fn from_env() -> Result<Self, String> {
+        let mut table = toml::value::Table::new();
+        for key in Self::ENV_KEYS {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Ok(raw) = std::env::var(&name) {
+                let value = toml::from_str::<toml::value::Table>(&format!("value = {}", raw))
+                    .ok()
+                    .and_then(|mut parsed| parsed.remove("value"))
+                    .unwrap_or(toml::Value::String(raw));
+                table.insert(key.to_string(), value);
+            }
+        }
+        toml::Value::Table(table).try_into().map_err(|e: toml::de::Error| format!("{}*: {}", ENV_PREFIX, e))
+    }
+
+    pub fn apply(&self, config: &mut CacheConfig) -> Result<(), String> {
+        if let Some(format) = &self.format {
+            config.format = match format.as_str() {
+                "columnar" => CacheFormat::Columnar,
+                "single-file" => CacheFormat::SingleFile,
+                "parquet" => CacheFormat::Parquet { row_group_size: 1024 * 1024 },
+                other => return Err(format!("unknown format {} (columnar, single-file, parquet)", other)),
+            };
+        }
+        if let (Some(size), CacheFormat::Parquet { row_group_size }) = (self.parquet_row_group_size, &mut config.format) {
+            *row_group_size = size;
+        }
+        if let Some(compression) = &self.compression {
+            config.compression = parse_compression(compression)?;
+        }
+        if self.zstd_workers.is_some() || self.zstd_window_log.is_some() {
+            let CompressionType::Zstd { workers, window_log, .. } = &mut config.compression else {
+                return Err("zstd_workers and zstd_window_log need zstd compression".to_string());
+            };
+            *workers = self.zstd_workers.unwrap_or(*workers);
+            if let Some(log) = self.zstd_window_log {
+                if !(10..=compression::MAX_WINDOW_LOG).contains(&log) {
+                    return Err(format!("zstd_window_log must be 10 to {}", compression::MAX_WINDOW_LOG));
+                }
+                *window_log = Some(log);
+            }
+        }
+        if let Some(policy) = &self.on_low_space {
+            config.on_low_space = match policy.as_str() {
+                "fail" => LowSpacePolicy::Fail,
+                "evict-oldest" => LowSpacePolicy::EvictOldest,
+                "ignore" => LowSpacePolicy::Ignore,
+                other => return Err(format!("unknown low-space policy {} (fail, evict-oldest, ignore)", other)),
+            };
+        }
+        if let Some(validation) = &self.validation {
+            config.validation = ValidationPolicy::parse(validation)?;
+        }
+        if let Some(bytes) = self.compression_block_bytes {
+            config.compression_block_bytes = (bytes > 0).then_some(bytes);
+        }
+        if let Some(io_mode) = &self.io_mode {
+            config.io_mode = IoMode::parse(io_mode)?;
+        }
+        if let Some(durability) = &self.durability {
+            config.durability = Durability::parse(durability)?;
+        }
+        if let Some(non_finite) = &self.non_finite {
+            config.non_finite = NonFinitePolicy::parse(non_finite)?;
+        }
+        if let Some(sort) = &self.sort {
+            config.sort = SortPolicy::parse(sort)?;
+        }
+        if let Some(precision) = &self.precision {
+            config.precision = Precision::from_dtype(precision)
+                .ok_or_else(|| format!("unknown precision {} (f32, f64)", precision))?;
+        }
+        if let Some(attempts) = self.io_attempts {
+            config.retry.attempts = attempts.max(1);
+        }
+        if let Some(hours) = self.trash_retention_hours {
+            config.trash_retention = Duration::from_secs(hours * 3600);
+        }
+        if self.parallel_threads == Some(0) || self.async_threads == Some(0) {
+            return Err("thread counts must be at least 1".to_string());
+        }
+        if self.cache_dir.is_some() {
+            config.cache_dir = self.cache_dir.clone();
+        }
+        if self.shared_memory_dir.is_some() {
+            config.shared_memory_dir = self.shared_memory_dir.clone();
+        }
+        if self.local_mirror_bytes.is_some() {
+            config.local_mirror_bytes = self.local_mirror_bytes;
+        }
+        if self.target_shard_bytes.is_some() {
+            config.target_shard_bytes = self.target_shard_bytes;
+        }
+        if self.target_shard_points.is_some() {
+            config.target_shard_points = self.target_shard_points;
+        }
+        if self.parallel_threads.is_some() {
+            config.parallel_threads = self.parallel_threads;
+        }
+        let set = |target: &mut bool, value: Option<bool>| if let Some(value) = value { *target = value };
+        set(&mut config.enable_compression, self.enable_compression);
+        set(&mut config.auto_compression, self.auto_compression);
+        set(&mut config.adaptive_compression, self.adaptive_compression);
+        set(&mut config.byte_shuffle, self.byte_shuffle);
+        set(&mut config.read_only, self.read_only);
+        set(&mut config.content_addressed, self.content_addressed);
+        set(&mut config.frame_index, self.frame_index);
+        set(&mut config.rt_major, self.rt_major);
+        set(&mut config.heatmap, self.heatmap);
+        set(&mut config.prefetch, self.prefetch);
+        set(&mut config.deterministic, self.deterministic);
+        config.buffer_size = self.buffer_size.unwrap_or(config.buffer_size);
+        config.async_threads = self.async_threads.unwrap_or(config.async_threads);
+        config.hot_cache_entries = self.hot_cache_entries.unwrap_or(config.hot_cache_entries);
+        config.stream_buffer_points = self.stream_buffer_points.unwrap_or(config.stream_buffer_points);
+        config.history_generations = self.history_generations.unwrap_or(config.history_generations);
+        config.window_compression_min_bytes = self.window_compression_min_bytes.unwrap_or(config.window_compression_min_bytes);
+        Ok(())
+    }
+}
+
+fn parse_compression(name: &str) -> Result<CompressionType, String> {
+    let invalid = || format!("unknown compression {} (lz4, zstd, zstd-<1..22>, zstd-<1..22>-long, archival, custom-<id>)", name);
+    match name {
+        "lz4" => Ok(CompressionType::Lz4),
+        "zstd" => Ok(CompressionType::zstd(3)),
+        "archival" => Ok(CompressionType::archival()),
+        _ if name.starts_with("custom-") => {
+            let id = name["custom-".len()..].parse().map_err(|_| invalid())?;
+            Ok(CompressionType::Custom { id })
+        }
+        _ => {
+            let spec = name.strip_prefix("zstd-").ok_or_else(invalid)?;
+            let (level, long_mode) = match spec.strip_suffix("-long") {
+                Some(level) => (level, true),
+                None => (spec, false),
+            };
+            let level: i32 = level.parse().map_err(|_| invalid())?;
+            match (1..=22).contains(&level) {
+                true => Ok(CompressionType::Zstd { level, long_mode, workers: 0, window_log: None }),
+                false => Err(invalid()),
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// File: src/shm.rs
+//
+// Decoded cache data in a shared-memory file, for worker processes that all need the same
+// source. The file holds every column uncompressed and is mapped read-only, so the page
+// cache backs all mappings with one physical copy: N workers cost the RAM of one load
+// instead of N. Files are written once and published by rename, never changed in place.
+//
+// Layout: MAGIC, the header length (u64 LE), the JSON header, then each dataset's six
+// columns (INDEXED_COLUMNS order) as little-endian values, every column 8-byte aligned.
+// Files written with a signing key end in an HMAC-SHA256 of everything before it, so a
+// file planted by another user is rejected even though the fingerprint is no secret.
+// Files are created readable by their owner only.
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use memmap2::Mmap;
+use serde::{Serialize, Deserialize};
+
+use crate::fdlimit;
+use crate::prefetch;
+use crate::signing::{self, SigningKey};
+use crate::utils::{IndexedTimsTOFData, TimsTOFData};
+use crate::window::IsolationWindow;
+
+const MAGIC: &[u8; 8] = b"TSHM0001";
+const TAG_LEN: usize = 32;
+
+// Where one dataset's columns start; each column is `rows` 4-byte values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DatasetEntry {
+    isolation: Option<(f32, f32)>, // None for MS1
+    rows: u64,
+    offsets: [u64; 6],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SharedHeader {
+    fingerprint: String, // Of the cache metadata the data was decoded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key_id: Option<String>, // Set when the file ends in a tag (see above)
+    datasets: Vec<DatasetEntry>,
+}
+
+// A read-only mapping of a shared file; cheap to clone
+#[derive(Clone)]
+pub struct SharedIndexedData {
+    mmap: Arc<Mmap>,
+    header: Arc<SharedHeader>,
+    path: PathBuf,
+}
+
+// Borrowed columns of one dataset, laid out like IndexedTimsTOFData
+#[derive(Debug, Clone, Copy)]
+pub struct SharedColumns<'a> {
+    pub rt_values_min: &'a [f32],
+    pub mobility_values: &'a [f32],
+    pub mz_values: &'a [f32],
+    pub intensity_values: &'a [u32],
+    pub frame_indices: &'a [u32],
+    pub scan_indices: &'a [u32],
+}
+
+impl SharedColumns<'_> {
+    // Peaks with m/z in [mz_min, mz_max], as IndexedTimsTOFData::slice_by_mz_range
+    pub fn slice_by_mz_range(&self, mz_min: f32, mz_max: f32) -> TimsTOFData {
+        let start = self.mz_values.partition_point(|&x| x < mz_min);
+        let end = self.mz_values.partition_point(|&x| x <= mz_max).max(start);
+        TimsTOFData {
+            rt_values_min: self.rt_values_min[start..end].to_vec(),
+            mobility_values: self.mobility_values[start..end].to_vec(),
+            mz_values: self.mz_values[start..end].to_vec(),
+            intensity_values: self.intensity_values[start..end].to_vec(),
+            frame_indices: self.frame_indices[start..end].to_vec(),
+            scan_indices: self.scan_indices[start..end].to_vec(),
+        }
+    }
+
+    // Private copy, for APIs that take owned data
+    pub fn to_indexed(self) -> IndexedTimsTOFData {
+        IndexedTimsTOFData {
+            rt_values_min: self.rt_values_min.to_vec(),
+            mobility_values: self.mobility_values.to_vec(),
+            mz_values: self.mz_values.to_vec(),
+            intensity_values: self.intensity_values.to_vec(),
+            frame_indices: self.frame_indices.to_vec(),
+            scan_indices: self.scan_indices.to_vec(),
+        }
+    }
+}
+
+impl SharedIndexedData {
+    // Map an existing shared file, checking it was made from the cache `fingerprint` names
+    // and, with a signing key, that it was written with that key
+    pub fn open(path: &Path, fingerprint: &str, signing_key: Option<&SigningKey>) -> io::Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "shared cache files are little-endian"));
+        }
+        let file = fdlimit::open(path)?;
+        // Safety: shared files are published by rename and never modified afterwards
+        let mmap = unsafe { Mmap::map(&*file)? };
+        if mmap.len() < 16 || &mmap[..8] != MAGIC {
+            return Err(invalid_data(format!("{} is not a shared cache file", path.display())));
+        }
+        // The header is read from the file, so lengths that overflow are as damaged as
+        // ones past the end
+        let header_len = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
+        let header: SharedHeader = usize::try_from(header_len).ok()
+            .and_then(|len| 16usize.checked_add(len))
+            .and_then(|end| mmap.get(16..end))
+            .and_then(|json| serde_json::from_slice(json).ok())
+            .ok_or_else(|| invalid_data(format!("{} has a damaged header", path.display())))?;
+        if header.fingerprint != fingerprint {
+            return Err(invalid_data(format!("{} was made from another version of the cache", path.display())));
+        }
+        let mut payload_end = mmap.len();
+        if let Some(key) = signing_key {
+            if header.signing_key_id.as_deref() != Some(key.id.as_str()) || payload_end < 16 + TAG_LEN {
+                return Err(signing::tampered(format!("{} is not signed with key '{}'", path.display(), key.id)));
+            }
+            payload_end -= TAG_LEN;
+            key.verify_tag(&mmap[..payload_end], &mmap[payload_end..])?;
+        }
+        if header.datasets.iter().filter(|dataset| dataset.isolation.is_none()).count() != 1 {
+            return Err(invalid_data(format!("{} has no MS1 data", path.display())));
+        }
+        for dataset in &header.datasets {
+            let fits = dataset.offsets.iter().all(|&offset| {
+                let end = dataset.rows.checked_mul(4).and_then(|len| offset.checked_add(len));
+                offset.is_multiple_of(4) && end.is_some_and(|end| end <= payload_end as u64)
+            });
+            if !fits {
+                return Err(invalid_data(format!("{} is truncated", path.display())));
+            }
+        }
+        Ok(Self { mmap: Arc::new(mmap), header: Arc::new(header), path: path.to_path_buf() })
+    }
+
+    // Start paging in every dataset's columns
+    pub fn prefetch(&self) {
+        let ranges: Vec<(u64, u64)> = self.header.datasets.iter()
+            .flat_map(|dataset| dataset.offsets.iter().map(move |&offset| (offset, dataset.rows * 4)))
+            .collect();
+        prefetch::mapped(&self.mmap, &ranges);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn ms1(&self) -> SharedColumns<'_> {
+        let dataset = self.header.datasets.iter().find(|dataset| dataset.isolation.is_none()).unwrap();
+        self.columns(dataset)
+    }
+
+    pub fn ms2_windows(&self) -> Vec<(IsolationWindow, SharedColumns<'_>)> {
+        self.header.datasets.iter()
+            .filter_map(|dataset| Some((dataset.isolation?, self.columns(dataset))))
+            .enumerate()
+            .map(|(index, ((low, high), columns))| (IsolationWindow::new(low, high, index), columns))
+            .collect()
+    }
+
+    fn columns(&self, dataset: &DatasetEntry) -> SharedColumns<'_> {
+        let rows = dataset.rows as usize;
+        let f32s = |i: usize| -> &[f32] {
+            // Safety: bounds and 4-byte alignment were checked in open(); the mapping is
+            // page-aligned and lives as long as `self`
+            unsafe { std::slice::from_raw_parts(self.mmap.as_ptr().add(dataset.offsets[i] as usize) as *const f32, rows) }
+        };
+        let u32s = |i: usize| -> &[u32] {
+            unsafe { std::slice::from_raw_parts(self.mmap.as_ptr().add(dataset.offsets[i] as usize) as *const u32, rows) }
+        };
+        SharedColumns {
+            rt_values_min: f32s(0),
+            mobility_values: f32s(1),
+            mz_values: f32s(2),
+            intensity_values: u32s(3),
+            frame_indices: u32s(4),
+            scan_indices: u32s(5),
+        }
+    }
+}
+
+// Write a shared file for `fingerprint` and publish it at `path`, tagged with
+// `signing_key` if given. Processes racing to create the same file each write their own
+// partial file; whichever rename lands last wins, and both are complete.
+pub fn write(
+    path: &Path,
+    fingerprint: &str,
+    signing_key: Option<&SigningKey>,
+    ms1_indexed: &IndexedTimsTOFData,
+    ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+) -> io::Result<()> {
+    let datasets: Vec<(Option<(f32, f32)>, &IndexedTimsTOFData)> = std::iter::once((None, ms1_indexed))
+        .chain(ms2_indexed_pairs.iter().map(|(window, data)| (Some(window.bounds()), data)))
+        .collect();
+
+    // Offsets depend on the header length, which depends on the offsets: lay out with
+    // the header length guessed from a first pass, then pad the header to it
+    let layout = |data_start: u64| {
+        let mut offset = data_start;
+        datasets.iter()
+            .map(|(isolation, data)| {
+                let rows = data.mz_values.len() as u64;
+                let mut offsets = [0u64; 6];
+                for column_offset in offsets.iter_mut() {
+                    *column_offset = offset;
+                    offset = align8(offset + rows * 4);
+                }
+                DatasetEntry { isolation: *isolation, rows, offsets }
+            })
+            .collect::<Vec<_>>()
+    };
+    let header_json = |data_start: u64| {
+        let header = SharedHeader {
+            fingerprint: fingerprint.to_string(),
+            signing_key_id: signing_key.map(|key| key.id.clone()),
+            datasets: layout(data_start),
+        };
+        serde_json::to_vec(&header)
+            .map_err(io::Error::other)
+    };
+    // Offsets grow by at most a few digits each, so reserve room for that
+    let reserved = header_json(0)?.len() as u64 + 32 * (datasets.len() as u64 * 6 + 1);
+    let data_start = align8(16 + reserved);
+    let mut header = header_json(data_start)?;
+    header.resize(reserved as usize, b' ');
+
+    let partial = path.with_extension(format!("partial.{}", std::process::id()));
+    // A fresh file, so the mode applies even over one left by a crashed writer
+    let _ = fs::remove_file(&partial);
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut writer = BufWriter::with_capacity(8 * 1024 * 1024, fdlimit::open_with(&options, &partial)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&reserved.to_le_bytes())?;
+    writer.write_all(&header)?;
+    let mut written = 16 + reserved;
+    for (_, data) in &datasets {
+        let columns: [Vec<u8>; 6] = [
+            data.rt_values_min.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            data.mobility_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            data.mz_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            data.intensity_values.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            data.frame_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            data.scan_indices.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        ];
+        for bytes in columns {
+            let padding = align8(written) - written;
+            writer.write_all(&vec![0u8; padding as usize])?;
+            writer.write_all(&bytes)?;
+            written = align8(written) + bytes.len() as u64;
+        }
+    }
+    let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+    if let Some(key) = signing_key {
+        file.flush()?;
+        // Safety: the partial file is private to this process until the rename
+        let tag = key.tag(&unsafe { Mmap::map(&*file)? });
+        file.write_all(&tag)?;
+    }
+    file.sync_all()?;
+    fs::rename(partial, path)
+}
+
+fn align8(offset: u64) -> u64 {
+    offset.div_ceil(8) * 8
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,80 @@
+// File: src/sidecar.rs
+//
+// Small summary files saved next to a cache's data files (chromatograms, heatmaps): one
+// bincode value, zstd-compressed, then encrypted with the cache's key when there is one.
+// The file's magic, with the envelope version, is the AAD, so one sidecar can't be passed
+// off as another. Written to a .partial file and renamed, like the manifest.
+//
+// The envelope starts with its version. Version 1 envelopes had none and start with the
+// Option tag of key_id (0 or 1), which is how they are told apart; they are still read.
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::compression::ColumnCodec;
+use crate::encryption::EncryptionKey;
+use crate::fdlimit;
+
+const ZSTD_LEVEL: i32 = 3;
+
+pub const ENVELOPE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    key_id: Option<String>,
+    nonce: Vec<u8>,
+    payload: Vec<u8>, // zstd bincode value, encrypted if key_id is set
+}
+
+pub fn write<T: Serialize>(path: &Path, magic: &[u8; 8], value: &T, key: Option<&EncryptionKey>) -> io::Result<()> {
+    // Raw frame tiers run to gigabytes; compressed on every core, as one ordinary frame
+    let serialized = bincode::serialize(value).map_err(io::Error::other)?;
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let payload = ColumnCodec::zstd(ZSTD_LEVEL).with_spare_workers(serialized.len(), threads).compress(serialized)?;
+    let envelope = match key {
+        Some(key) => {
+            let (nonce, ciphertext) = key.encrypt(&payload, &aad(magic, ENVELOPE_VERSION))?;
+            Envelope { version: ENVELOPE_VERSION, key_id: Some(key.id.clone()), nonce, payload: ciphertext }
+        }
+        None => Envelope { version: ENVELOPE_VERSION, key_id: None, nonce: Vec::new(), payload },
+    };
+    let mut bytes = magic.to_vec();
+    bincode::serialize_into(&mut bytes, &envelope).map_err(io::Error::other)?;
+    let partial = path.with_extension("partial");
+    fdlimit::write(&partial, bytes)?;
+    fs::rename(partial, path)
+}
+
+pub fn read<T: DeserializeOwned>(path: &Path, magic: &[u8; 8], key: Option<&EncryptionKey>) -> io::Result<T> {
+    let bytes = fdlimit::read(path)?;
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let envelope: Envelope = match bytes.strip_prefix(magic) {
+        Some(body @ [0 | 1, ..]) => {
+            let (key_id, nonce, payload) = bincode::deserialize(body).map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+            Envelope { version: 1, key_id, nonce, payload }
+        }
+        Some(body) => bincode::deserialize(body).map_err(|e| invalid(format!("{}: {}", path.display(), e)))?,
+        None => return Err(invalid(format!("{} is not a {} file", path.display(), String::from_utf8_lossy(magic)))),
+    };
+    if envelope.version > ENVELOPE_VERSION {
+        return Err(invalid(format!("{} has envelope version {}, newer than this build reads ({})", path.display(), envelope.version, ENVELOPE_VERSION)));
+    }
+    let payload = match (&envelope.key_id, key) {
+        (None, _) => envelope.payload,
+        (Some(_), Some(key)) => key.decrypt(&envelope.nonce, &envelope.payload, &aad(magic, envelope.version))?,
+        (Some(id), None) => return Err(invalid(format!("{} is encrypted (key id: {}); no key configured", path.display(), id))),
+    };
+    let mut decoder = zstd::stream::read::Decoder::new(payload.as_slice())?;
+    bincode::deserialize_from(&mut decoder).map_err(|e| invalid(format!("{}: {}", path.display(), e)))
+}
+
+// The magic, and from version 2 on the envelope version, so neither can be swapped
+fn aad(magic: &[u8; 8], version: u32) -> Vec<u8> {
+    match version {
+        1 => magic.to_vec(),
+        _ => [magic.as_slice(), &version.to_le_bytes()].concat(),
+    }
+}
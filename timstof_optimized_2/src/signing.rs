@@ -0,0 +1,98 @@
+// File: src/signing.rs
+use std::io::{self, BufReader};
+use std::path::Path;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use crate::fdlimit;
+use crate::telemetry;
+
+pub const KEY_ENV: &str = "TIMSTOF_CACHE_SIGNING_KEY";       // Hex-encoded secret, any length
+pub const KEY_ID_ENV: &str = "TIMSTOF_CACHE_SIGNING_KEY_ID"; // Optional, defaults to "default"
+pub const SIGNATURE_PREFIX: &str = "signature: hmac-sha256:";
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Secret used to sign cache metadata; the id is recorded so verifiers know which key to use
+#[derive(Clone)]
+pub struct SigningKey {
+    pub id: String,
+    secret: Vec<u8>,
+}
+
+impl SigningKey {
+    pub fn new(id: impl Into<String>, secret: Vec<u8>) -> Self {
+        Self { id: id.into(), secret }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let secret_hex = std::env::var(KEY_ENV).ok()?;
+        let id = std::env::var(KEY_ID_ENV).unwrap_or_else(|_| "default".to_string());
+        match hex::decode(secret_hex.trim()) {
+            Ok(secret) if !secret.is_empty() => Some(Self::new(id, secret)),
+            Ok(_) => {
+                telemetry::warn(&format!("Ignoring {}: key is empty", KEY_ENV));
+                None
+            }
+            Err(e) => {
+                telemetry::warn(&format!("Ignoring {}: invalid key hex: {}", KEY_ENV, e));
+                None
+            }
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    // Append a signature line covering every byte of `metadata` (which already lists the checksums)
+    pub fn sign(&self, metadata: &str) -> String {
+        let mut mac = self.mac();
+        mac.update(metadata.as_bytes());
+        format!("{}{}{}\n", metadata, SIGNATURE_PREFIX, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    // Check the trailing signature line; returns the signed part of the metadata
+    pub fn verify<'a>(&self, signed_metadata: &'a str) -> io::Result<&'a str> {
+        let (body, signature_hex) = split_signature(signed_metadata)
+            .ok_or_else(|| tampered("metadata is not signed".to_string()))?;
+        let signature = hex::decode(signature_hex)
+            .map_err(|e| tampered(format!("malformed signature: {}", e)))?;
+
+        let mut mac = self.mac();
+        mac.update(body.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| tampered(format!("metadata signature does not match key '{}'", self.id)))?;
+        Ok(body)
+    }
+
+    // HMAC-SHA256 of raw bytes, for files the metadata doesn't list (shm.rs)
+    pub fn tag(&self, data: &[u8]) -> [u8; 32] {
+        let mut mac = self.mac();
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    pub fn verify_tag(&self, data: &[u8], tag: &[u8]) -> io::Result<()> {
+        let mut mac = self.mac();
+        mac.update(data);
+        mac.verify_slice(tag)
+            .map_err(|_| tampered(format!("data signature does not match key '{}'", self.id)))
+    }
+}
+
+// ("everything before the signature line", "hex signature")
+pub fn split_signature(signed_metadata: &str) -> Option<(&str, &str)> {
+    let start = signed_metadata.rfind(SIGNATURE_PREFIX)?;
+    let signature = signed_metadata[start + SIGNATURE_PREFIX.len()..].trim();
+    Some((&signed_metadata[..start], signature))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(fdlimit::open(path)?), &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn tampered(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("cache integrity check failed: {}", message))
+}
@@ -0,0 +1,75 @@
+// File: src/snapshot.rs
+//
+// Consistent reads of a cache while it is being updated, e.g. real-time DIA processing of
+// a run still being acquired while update_cache appends the frames acquired since. Every
+// commit of a cache's files (save, update, repair, compaction) ends by writing
+// "<name>.generation": a generation number and the manifest description (size and
+// checksum, footer-based for containers) of each data file and the metadata as committed.
+// Files are only ever replaced by rename, so a load that finds them matching the same
+// committed generation before and after reading saw exactly that generation, and one
+// overlapping a commit finds a mismatch and is retried. Updates write their new files
+// under temporary names while the last generation stays in place and rename them only at
+// the end, so readers retry for the length of the renames, not of the update.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+use crate::durability::{self, Durability};
+use crate::fdlimit;
+use crate::hot::LoadedData;
+use crate::manifest::{self, ManifestFile};
+
+// Pause between attempts while a commit is under way
+pub const RETRY_DELAY: Duration = Duration::from_millis(20);
+// Longest a reader waits for files matching a committed generation
+pub const COMMIT_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    pub number: u64,               // 1 for the first commit, incremented by every later one
+    pub files: Vec<ManifestFile>,  // Data files and the metadata file, as committed
+}
+
+impl Generation {
+    // The last committed generation, None for caches committed before generations
+    pub fn read(path: &Path) -> Option<Self> {
+        let text = fdlimit::read_to_string(path).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    // The generation after `previous` for the files as they are now
+    pub fn next(previous: Option<&Generation>, files: &[PathBuf]) -> io::Result<Self> {
+        Ok(Self {
+            number: previous.map_or(1, |previous| previous.number + 1),
+            files: files.iter().map(|path| manifest::describe_file(path)).collect::<io::Result<_>>()?,
+        })
+    }
+
+    // Always through a temporary file, which write_file skips under Durability::None:
+    // readers poll this file and must never find it half-written
+    pub fn write(&self, path: &Path, durability: Durability) -> io::Result<()> {
+        let text = serde_json::to_string(self).map_err(io::Error::other)?;
+        if durability != Durability::None {
+            return durability::write_file(path, text, durability);
+        }
+        let partial_path = path.with_extension("partial");
+        fdlimit::write(&partial_path, text)?;
+        fs::rename(partial_path, path)
+    }
+
+    // Whether the files in `cache_dir` are still the ones committed; false while a commit
+    // is replacing them or after they were changed without one
+    pub fn is_current(&self, cache_dir: &Path) -> bool {
+        self.files.iter().all(|file| {
+            manifest::describe_file(&cache_dir.join(&file.name)).is_ok_and(|now| now == *file)
+        })
+    }
+}
+
+// A load of one committed generation (CacheManager::load_snapshot)
+pub struct Snapshot {
+    pub generation: u64, // 0 for caches committed before generations
+    pub data: LoadedData,
+}
@@ -0,0 +1,67 @@
+// File: src/space.rs
+//
+// Free-space preflight for saves. A multi-GB save that runs out of space fails with
+// ENOSPC somewhere in the middle, after minutes of encoding; checking the estimated size
+// against the free space first fails (or makes room) before any work is done.
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// What a save does when the estimate does not fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LowSpacePolicy {
+    #[default]
+    Fail,        // Return InsufficientSpace
+    EvictOldest, // Remove the least recently cached other sources until it fits
+    Ignore,      // No preflight; the write fails when the disk is full
+}
+
+// Returned (boxed) by saves that would not fit; downcast to tell it from other errors
+#[derive(Debug, Clone)]
+pub struct InsufficientSpace {
+    pub dir: PathBuf,
+    pub required: u64,  // Estimated bytes the save writes
+    pub available: u64, // Free bytes for unprivileged users, after any eviction
+}
+
+impl fmt::Display for InsufficientSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not enough space in {}: the save needs about {:.2} MB, {:.2} MB are free",
+               self.dir.display(), self.required as f64 / 1024.0 / 1024.0, self.available as f64 / 1024.0 / 1024.0)
+    }
+}
+
+impl std::error::Error for InsufficientSpace {}
+
+// Bytes an unprivileged user can still write on the filesystem holding `dir`
+#[cfg(unix)]
+pub fn available_bytes(dir: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+pub fn available_bytes(dir: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let mut available = 0u64;
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn available_bytes(_dir: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "free space is only known on Unix and Windows"))
+}
@@ -0,0 +1,226 @@
+// File: src/stream.rs
+//
+// Saves from a stream of points (CacheManager::save_from_stream), for machines that can't
+// hold the whole indexed dataset in memory before caching it. Points are collected in a
+// StreamBuffer of at most CacheConfig::stream_buffer_points; once it is full its MS1
+// points and those of every MS2 window are sorted by m/z and spilled to disk as a run
+// (SpillDir), and the buffer starts over. When the stream ends, the runs of each dataset
+// are merged (MergedRuns, an external merge sort) into the cache files: MS1 a shard at a
+// time, so the cache holds a single m/z-ordered run of shards as after an in-memory save,
+// and each MS2 window in consecutive segments of at most a buffer's points, which loads
+// merge back into one window as they do the segments updates append.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+
+use crate::cache::WindowKey;
+use crate::columnar;
+use crate::fdlimit::{self, FilePermit};
+use crate::hot::Ms2Windows;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+
+// One peak as a converter produces it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimsTOFPoint {
+    pub rt_min: f32,
+    pub mobility: f32,
+    pub mz: f32,
+    pub intensity: u32,
+    pub frame: u32,
+    pub scan: u32,
+}
+
+// Points of the stream not yet written: MS1, and each MS2 window in order of first appearance
+#[derive(Default)]
+pub struct StreamBuffer {
+    ms1: IndexedTimsTOFData,
+    windows: Ms2Windows,
+    window_positions: HashMap<WindowKey, usize>,
+    len: usize,
+}
+
+impl StreamBuffer {
+    // `window` is the isolation window of an MS2 point, None for MS1
+    pub fn push(&mut self, point: TimsTOFPoint, window: Option<IsolationWindow>) {
+        let data = match window {
+            None => &mut self.ms1,
+            Some(window) => {
+                let windows = &mut self.windows;
+                let position = *self.window_positions.entry(window.key()).or_insert_with(|| {
+                    windows.push((window.at(windows.len()), IndexedTimsTOFData::new()));
+                    windows.len() - 1
+                });
+                &mut self.windows[position].1
+            }
+        };
+        data.rt_values_min.push(point.rt_min);
+        data.mobility_values.push(point.mobility);
+        data.mz_values.push(point.mz);
+        data.intensity_values.push(point.intensity);
+        data.frame_indices.push(point.frame);
+        data.scan_indices.push(point.scan);
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The buffered points, MS1 and each window sorted by m/z, leaving the buffer empty
+    pub fn take_sorted(&mut self) -> (IndexedTimsTOFData, Ms2Windows) {
+        let buffer = std::mem::take(self);
+        let windows = buffer.windows.into_par_iter()
+            .map(|(window, data)| (window, columnar::sort_by_mz(&data)))
+            .collect();
+        (columnar::sort_by_mz(&buffer.ms1), windows)
+    }
+}
+
+// Bytes of a point in a spill file: its six 32-bit fields, little-endian
+const SPILL_POINT_BYTES: usize = 24;
+
+// Directory of the spilled runs of one save, removed with everything in it when dropped
+pub struct SpillDir {
+    path: PathBuf,
+    runs: usize,
+}
+
+impl SpillDir {
+    // Runs left behind by an interrupted save are removed first
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        fs::create_dir_all(path)?;
+        Ok(Self { path: path.to_path_buf(), runs: 0 })
+    }
+
+    // Write m/z-sorted points as a run, returning its file
+    pub fn write_run(&mut self, data: &IndexedTimsTOFData) -> io::Result<PathBuf> {
+        let path = self.path.join(format!("run-{}.bin", self.runs));
+        self.runs += 1;
+        let mut writer = BufWriter::new(fdlimit::create(&path)?);
+        for row in 0..data.mz_values.len() {
+            for field in [
+                data.rt_values_min[row].to_bits(),
+                data.mobility_values[row].to_bits(),
+                data.mz_values[row].to_bits(),
+                data.intensity_values[row],
+                data.frame_indices[row],
+                data.scan_indices[row],
+            ] {
+                writer.write_all(&field.to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+struct RunReader {
+    reader: BufReader<File>,
+    remaining: u64,
+}
+
+impl RunReader {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path).map_err(fdlimit::explain)?;
+        let remaining = file.metadata()?.len() / SPILL_POINT_BYTES as u64;
+        Ok(Self { reader: BufReader::new(file), remaining })
+    }
+
+    fn next(&mut self) -> io::Result<Option<TimsTOFPoint>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let mut bytes = [0u8; SPILL_POINT_BYTES];
+        self.reader.read_exact(&mut bytes)?;
+        let field = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+        Ok(Some(TimsTOFPoint {
+            rt_min: f32::from_bits(field(0)),
+            mobility: f32::from_bits(field(1)),
+            mz: f32::from_bits(field(2)),
+            intensity: field(3),
+            frame: field(4),
+            scan: field(5),
+        }))
+    }
+}
+
+// Next point of a run, ordered for a min-heap: lowest m/z first, then earliest run, so
+// points of equal m/z keep the order they arrived in
+struct Head {
+    point: TimsTOFPoint,
+    run: usize,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.point.mz.total_cmp(&self.point.mz).then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+// k-way merge of spilled runs of one dataset, holding one point per run in memory and
+// every run open; more runs than the cache may hold open (fdlimit.rs) is an error, which a
+// larger CacheConfig::stream_buffer_points avoids
+pub struct MergedRuns {
+    runs: Vec<RunReader>,
+    heads: BinaryHeap<Head>,
+    _permit: FilePermit,
+}
+
+impl MergedRuns {
+    pub fn open(paths: &[PathBuf]) -> io::Result<Self> {
+        let permit = fdlimit::acquire(paths.len())?;
+        let mut runs = paths.iter().map(|path| RunReader::open(path)).collect::<io::Result<Vec<_>>>()?;
+        let mut heads = BinaryHeap::with_capacity(runs.len());
+        for (run, reader) in runs.iter_mut().enumerate() {
+            if let Some(point) = reader.next()? {
+                heads.push(Head { point, run });
+            }
+        }
+        Ok(Self { runs, heads, _permit: permit })
+    }
+
+    // Up to `max_points` next points in m/z order; empty once every run is exhausted
+    pub fn next_chunk(&mut self, max_points: usize) -> io::Result<IndexedTimsTOFData> {
+        let mut buffer = StreamBuffer::default();
+        while buffer.len() < max_points {
+            let Some(Head { point, run }) = self.heads.pop() else { break };
+            buffer.push(point, None);
+            if let Some(next) = self.runs[run].next()? {
+                self.heads.push(Head { point: next, run });
+            }
+        }
+        Ok(buffer.ms1)
+    }
+}
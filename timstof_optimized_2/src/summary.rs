@@ -0,0 +1,178 @@
+// File: src/summary.rs
+//
+// Dataset statistics recorded in the metadata at save time ("summary" line, JSON), so tools
+// can pick extraction parameters (m/z and mobility ranges, intensity thresholds) without
+// loading any data. Ranges are over finite values of all points, MS1 and MS2. Intensity
+// percentiles come from a log-scale histogram, which incremental updates can merge exactly;
+// they are accurate to within one bucket (about 9%).
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::precision::Float;
+use crate::utils::IndexedTimsTOFData;
+use crate::window::IsolationWindow;
+
+const BUCKETS_PER_OCTAVE: f64 = 8.0;
+const BUCKETS: usize = 8 * 32 + 1; // Bucket 0 holds zero intensities
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetSummary {
+    pub ms1_points: u64,
+    pub ms2_points: u64,
+    pub rt_range: Option<(f32, f32)>, // None without points
+    pub mz_range: Option<(f32, f32)>,
+    pub mobility_range: Option<(f32, f32)>,
+    pub intensity_range: Option<(u32, u32)>,
+    pub windows: Vec<WindowSummary>,
+    intensity_histogram: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowSummary {
+    pub isolation: (f32, f32),
+    pub points: u64,
+}
+
+// Ranges and histogram of one dataset
+#[derive(Clone)]
+struct Stats {
+    points: u64,
+    rt: Option<(f32, f32)>,
+    mz: Option<(f32, f32)>,
+    mobility: Option<(f32, f32)>,
+    intensity: Option<(u32, u32)>,
+    histogram: Vec<u64>,
+}
+
+impl DatasetSummary {
+    pub fn compute<F: Float>(ms1: &IndexedTimsTOFData<F>, ms2_pairs: &[(IsolationWindow, IndexedTimsTOFData<F>)]) -> Self {
+        let ms1_stats = stats(ms1);
+        let window_stats: Vec<Stats> = ms2_pairs.iter().map(|(_, data)| stats(data)).collect();
+        let all = window_stats.iter().fold(ms1_stats.clone(), |all, window| all.merge(window));
+        Self {
+            ms1_points: ms1_stats.points,
+            ms2_points: all.points - ms1_stats.points,
+            rt_range: all.rt,
+            mz_range: all.mz,
+            mobility_range: all.mobility,
+            intensity_range: all.intensity,
+            windows: ms2_pairs.iter().zip(&window_stats)
+                .map(|((window, _), stats)| WindowSummary { isolation: window.bounds(), points: stats.points })
+                .collect(),
+            intensity_histogram: all.histogram,
+        }
+    }
+
+    // Add points appended by an incremental update; their windows are matched by isolation range
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut windows = self.windows.clone();
+        for window in &other.windows {
+            match windows.iter_mut().find(|w| w.isolation == window.isolation) {
+                Some(existing) => existing.points += window.points,
+                None => windows.push(window.clone()),
+            }
+        }
+        Self {
+            ms1_points: self.ms1_points + other.ms1_points,
+            ms2_points: self.ms2_points + other.ms2_points,
+            rt_range: merge_range(self.rt_range, other.rt_range),
+            mz_range: merge_range(self.mz_range, other.mz_range),
+            mobility_range: merge_range(self.mobility_range, other.mobility_range),
+            intensity_range: merge_range(self.intensity_range, other.intensity_range),
+            windows,
+            intensity_histogram: add_histograms(&self.intensity_histogram, &other.intensity_histogram),
+        }
+    }
+
+    pub fn total_points(&self) -> u64 {
+        self.ms1_points + self.ms2_points
+    }
+
+    // Approximate intensity below which `percent` of all points fall (0 to 100); None
+    // without points
+    pub fn intensity_percentile(&self, percent: f64) -> Option<u32> {
+        let total: u64 = self.intensity_histogram.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((percent.clamp(0.0, 100.0) / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        let bucket = self.intensity_histogram.iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(BUCKETS - 1);
+        let (min, max) = self.intensity_range.unwrap_or((0, u32::MAX));
+        Some(bucket_value(bucket).clamp(min, max))
+    }
+}
+
+fn stats<F: Float>(data: &IndexedTimsTOFData<F>) -> Stats {
+    let histogram = data.intensity_values.par_iter()
+        .with_min_len(1 << 16)
+        .fold(|| vec![0u64; BUCKETS], |mut histogram, &intensity| {
+            histogram[bucket_of(intensity)] += 1;
+            histogram
+        })
+        .reduce(|| vec![0u64; BUCKETS], |a, b| add_histograms(&a, &b));
+    Stats {
+        points: data.mz_values.len() as u64,
+        rt: float_range(&data.rt_values_min),
+        mz: float_range(&data.mz_values),
+        mobility: float_range(&data.mobility_values),
+        intensity: data.intensity_values.par_iter().copied().min().zip(data.intensity_values.par_iter().copied().max()),
+        histogram,
+    }
+}
+
+impl Stats {
+    fn merge(&self, other: &Self) -> Self {
+        Self {
+            points: self.points + other.points,
+            rt: merge_range(self.rt, other.rt),
+            mz: merge_range(self.mz, other.mz),
+            mobility: merge_range(self.mobility, other.mobility),
+            intensity: merge_range(self.intensity, other.intensity),
+            histogram: add_histograms(&self.histogram, &other.histogram),
+        }
+    }
+}
+
+// Min / max of the finite values, None if there are none
+fn float_range<F: Float>(values: &[F]) -> Option<(f32, f32)> {
+    values.par_iter()
+        .map(|value| value.to_f32())
+        .filter(|value| value.is_finite())
+        .fold(|| None, |range, value| merge_range(range, Some((value, value))))
+        .reduce(|| None, merge_range)
+}
+
+fn merge_range<T: PartialOrd + Copy>(a: Option<(T, T)>, b: Option<(T, T)>) -> Option<(T, T)> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some((if b.0 < a.0 { b.0 } else { a.0 }, if b.1 > a.1 { b.1 } else { a.1 })),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn add_histograms(a: &[u64], b: &[u64]) -> Vec<u64> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).copied().unwrap_or(0) + b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+fn bucket_of(intensity: u32) -> usize {
+    match intensity {
+        0 => 0,
+        intensity => (1 + ((intensity as f64).log2() * BUCKETS_PER_OCTAVE) as usize).min(BUCKETS - 1),
+    }
+}
+
+// Upper edge of a bucket
+fn bucket_value(bucket: usize) -> u32 {
+    match bucket {
+        0 => 0,
+        bucket => 2f64.powf(bucket as f64 / BUCKETS_PER_OCTAVE).min(u32::MAX as f64) as u32,
+    }
+}
@@ -0,0 +1,133 @@
+// File: src/telemetry.rs
+//
+// Metrics of the cache layer for services embedding it, exported through OpenTelemetry
+// behind the `otel` feature, so caches show up in the dashboards the service already has:
+//
+//     cache_hits, cache_misses      loads served from a cache; is_cache_valid() finding none
+//     bytes_written                 data files written by saves and updates
+//     save_duration_seconds         saves and updates ("operation" attribute), seconds
+//     shard_load_duration           decoding of one shard or window ("segment" attribute), seconds
+//
+// The crate only records into the global MeterProvider; the service installs the exporter
+// (Prometheus, OTLP) as for its own metrics, before the first cache operation, since the
+// instruments are created once, from the provider installed at that point. Without the
+// feature every function here is a no-op.
+//
+// Messages (progress of saves and loads, fallbacks taken, errors recovered from) go to the
+// hook installed with set_log_hook, e.g. the service's logger, and nowhere without one: the
+// library itself never writes to stdout or stderr. The command-line tools install
+// print_to_terminal, C hosts a callback (cabi.rs).
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+#[cfg(feature = "otel")]
+struct Instruments {
+    hits: opentelemetry::metrics::Counter<u64>,
+    misses: opentelemetry::metrics::Counter<u64>,
+    bytes_written: opentelemetry::metrics::Counter<u64>,
+    save_duration: opentelemetry::metrics::Histogram<f64>,
+    shard_load_duration: opentelemetry::metrics::Histogram<f64>,
+}
+
+#[cfg(feature = "otel")]
+fn instruments() -> &'static Instruments {
+    static INSTRUMENTS: std::sync::OnceLock<Instruments> = std::sync::OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = opentelemetry::global::meter("timstof_cache");
+        Instruments {
+            hits: meter.u64_counter("cache_hits")
+                .with_description("Loads served from a cache")
+                .build(),
+            misses: meter.u64_counter("cache_misses")
+                .with_description("Validity checks that found no valid cache")
+                .build(),
+            bytes_written: meter.u64_counter("bytes_written")
+                .with_description("Bytes of cache data files written by saves and updates")
+                .with_unit("By")
+                .build(),
+            save_duration: meter.f64_histogram("save_duration_seconds")
+                .with_description("Duration of cache saves and updates")
+                .with_unit("s")
+                .build(),
+            shard_load_duration: meter.f64_histogram("shard_load_duration")
+                .with_description("Decoding time of one shard or MS2 window")
+                .with_unit("s")
+                .build(),
+        }
+    })
+}
+
+pub fn record_hit() {
+    #[cfg(feature = "otel")]
+    instruments().hits.add(1, &[]);
+}
+
+pub fn record_miss() {
+    #[cfg(feature = "otel")]
+    instruments().misses.add(1, &[]);
+}
+
+// A committed save or update ("save", "update") and the size of the files it wrote
+pub fn record_write(operation: &'static str, bytes: u64, elapsed: Duration) {
+    #[cfg(feature = "otel")]
+    {
+        let attributes = [opentelemetry::KeyValue::new("operation", operation)];
+        instruments().bytes_written.add(bytes, &attributes);
+        instruments().save_duration.record(elapsed.as_secs_f64(), &attributes);
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = (operation, bytes, elapsed);
+}
+
+// `segment` is the segment name, "ms1" or "ms2_window"
+pub fn record_shard_load(segment: &str, elapsed: Duration) {
+    #[cfg(feature = "otel")]
+    instruments().shard_load_duration.record(
+        elapsed.as_secs_f64(),
+        &[opentelemetry::KeyValue::new("segment", segment.to_string())],
+    );
+    #[cfg(not(feature = "otel"))]
+    let _ = (segment, elapsed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+type LogHook = Box<dyn Fn(Level, &str) + Send + Sync>;
+
+static LOG_HOOK: RwLock<Option<LogHook>> = RwLock::new(None);
+
+// Replaces the previous hook; messages logged before the first one are dropped
+pub fn set_log_hook(hook: impl Fn(Level, &str) + Send + Sync + 'static) {
+    *LOG_HOOK.write().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(hook));
+}
+
+// Info to stdout, warnings and errors to stderr
+pub fn print_to_terminal(level: Level, message: &str) {
+    match level {
+        Level::Info => println!("{}", message),
+        Level::Warn | Level::Error => eprintln!("{}", message),
+    }
+}
+
+pub fn info(message: &str) {
+    log(Level::Info, message);
+}
+
+pub fn warn(message: &str) {
+    log(Level::Warn, message);
+}
+
+pub fn error(message: &str) {
+    log(Level::Error, message);
+}
+
+fn log(level: Level, message: &str) {
+    if let Some(hook) = LOG_HOOK.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        hook(level, message);
+    }
+}
@@ -0,0 +1,333 @@
+// File: src/testing.rs
+//
+// Generators for the data shapes that break serialization (arbitrary sizes, NaNs, empty
+// MS2 windows, unsorted input) and a save/load round trip through every backend and
+// codec, so property tests can catch format bugs on small inputs instead of on 20 GB
+// runs. Generators are deterministic in their seed, which makes failures reproducible
+// with any property-testing driver (tests/roundtrip.rs uses proptest).
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bench::{BenchCodec, SplitMix};
+use crate::cache::{CacheConfig, CacheFormat, CacheManager};
+use crate::compression::CompressionType;
+pub use crate::encryption::EncryptionKey;
+use crate::heatmap::{HeatmapAxis, Viewport};
+pub use crate::hot::LoadedData;
+use crate::layout::Layout;
+pub use crate::signing::SigningKey;
+// Decode kernels (simd.rs), to check the SSE2 path against the scalar one (tests/simd.rs)
+pub use crate::simd::{fixed_to_f32_bytes, log_u16_to_u32_bytes, prefix_sum_u32, scalar_prefix_sum_u32};
+pub use crate::utils::{IndexedTimsTOFData, TimsTOFData, TimsTOFRawData};
+pub use crate::window::IsolationWindow;
+
+// What indexed_data() generates
+#[derive(Debug, Clone)]
+pub struct DataShape {
+    pub points: usize,
+    pub nan_fraction: f32, // Rows with a NaN in rt, mobility or m/z
+    pub sorted: bool,      // By m/z, as indexing produces; false leaves rows in random order
+    pub seed: u64,
+}
+
+impl Default for DataShape {
+    fn default() -> Self {
+        Self { points: 1000, nan_fraction: 0.0, sorted: true, seed: 0 }
+    }
+}
+
+pub fn indexed_data(shape: &DataShape) -> IndexedTimsTOFData {
+    let mut rng = SplitMix::new(shape.seed);
+    let mut data = IndexedTimsTOFData::new();
+    for _ in 0..shape.points {
+        let frame = (rng.uniform() * 60_000.0) as u32;
+        let scan = (rng.uniform() * 927.0) as u32;
+        let mut values = [frame as f32 * 0.001, 0.6 + rng.uniform(), 100.0 + rng.uniform() * 1600.0];
+        if rng.uniform() < shape.nan_fraction {
+            values[(rng.next_u64() % 3) as usize] = f32::NAN;
+        }
+        data.rt_values_min.push(values[0]);
+        data.mobility_values.push(values[1]);
+        data.mz_values.push(values[2]);
+        // Full u32 range, including 0 and u32::MAX
+        data.intensity_values.push(match rng.next_u64() % 16 {
+            0 => 0,
+            1 => u32::MAX,
+            _ => rng.next_u64() as u32,
+        });
+        data.frame_indices.push(frame);
+        data.scan_indices.push(scan);
+    }
+    if shape.sorted {
+        data = sorted_by_mz(&data);
+    }
+    data
+}
+
+// `count` windows with distinct isolation ranges tiling 400 m/z upwards; each is empty
+// with probability `empty_fraction`, otherwise has up to `max_points` sorted points
+pub fn ms2_windows(count: usize, max_points: usize, empty_fraction: f32, seed: u64) -> Vec<(IsolationWindow, IndexedTimsTOFData)> {
+    let mut rng = SplitMix::new(seed ^ 0x5eed);
+    (0..count)
+        .map(|i| {
+            let low = 400.0 + 25.0 * i as f32;
+            let points = match rng.uniform() < empty_fraction {
+                true => 0,
+                false => 1 + (rng.next_u64() as usize) % max_points.max(1),
+            };
+            let shape = DataShape { points, seed: rng.next_u64(), ..DataShape::default() };
+            (IsolationWindow::new(low, low + 25.0, i), indexed_data(&shape))
+        })
+        .collect()
+}
+
+// Whether the data is in the order the loaders assume: m/z ascending, no NaN m/z
+pub fn is_loadable_order(data: &IndexedTimsTOFData) -> bool {
+    data.mz_values.windows(2).all(|pair| pair[0] <= pair[1]) && !data.mz_values.iter().any(|mz| mz.is_nan())
+}
+
+// Equality with NaN equal to NaN (bitwise), as a round trip must preserve
+pub fn same_data(a: &IndexedTimsTOFData, b: &IndexedTimsTOFData) -> bool {
+    let bits = |values: &[f32]| values.iter().map(|x| x.to_bits()).collect::<Vec<u32>>();
+    bits(&a.rt_values_min) == bits(&b.rt_values_min)
+        && bits(&a.mobility_values) == bits(&b.mobility_values)
+        && bits(&a.mz_values) == bits(&b.mz_values)
+        && a.intensity_values == b.intensity_values
+        && a.frame_indices == b.frame_indices
+        && a.scan_indices == b.scan_indices
+}
+
+// Same windows with the same data, in any order
+pub fn same_windows(a: &[(IsolationWindow, IndexedTimsTOFData)], b: &[(IsolationWindow, IndexedTimsTOFData)]) -> bool {
+    fn ordered(windows: &[(IsolationWindow, IndexedTimsTOFData)]) -> Vec<&(IsolationWindow, IndexedTimsTOFData)> {
+        let mut refs: Vec<&(IsolationWindow, IndexedTimsTOFData)> = windows.iter().collect();
+        refs.sort_by_key(|(window, _)| window.key());
+        refs
+    }
+    a.len() == b.len()
+        && ordered(a).into_iter().zip(ordered(b)).all(|(x, y)| x.0 == y.0 && same_data(&x.1, &y.1))
+}
+
+// One backend/codec combination to round-trip through
+#[derive(Clone)]
+pub struct Backend {
+    label: String,
+    config: CacheConfig,
+    raw_tier: bool, // Also save and read the raw frame tier
+}
+
+impl Backend {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    // The same backend with signed metadata, and the frame index, RT-major copy, heatmap and
+    // raw frame tier written too, so the signature covers a checksum of every kind of cache file
+    pub fn signed(&self, key: SigningKey) -> Backend {
+        let mut backend = self.clone();
+        backend.raw_tier = true;
+        backend.config.signing_key = Some(key);
+        backend.config.frame_index = true;
+        backend.config.rt_major = true;
+        backend.config.heatmap = true;
+        backend
+    }
+
+    // The same backend with other settings, e.g. a single-threaded pool
+    pub fn configured(&self, edit: impl FnOnce(&mut CacheConfig)) -> Backend {
+        let mut backend = self.clone();
+        edit(&mut backend.config);
+        backend
+    }
+
+    // The same backend saving and loading with `key`, or without encryption if None
+    pub fn encrypted(&self, key: Option<EncryptionKey>) -> Backend {
+        let mut backend = self.clone();
+        backend.config.encryption_key = key;
+        backend
+    }
+
+    // Save into a scratch cache directory, load back, remove the directory
+    pub fn round_trip(
+        &self,
+        ms1_indexed: &IndexedTimsTOFData,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    ) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        self.round_trip_in(&std::env::temp_dir(), ms1_indexed, ms2_indexed_pairs)
+    }
+
+    // The same with the scratch directory under `parent`, e.g. one deep enough that cache
+    // paths pass Windows' MAX_PATH
+    pub fn round_trip_in(
+        &self,
+        parent: &Path,
+        ms1_indexed: &IndexedTimsTOFData,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    ) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        let saved = self.save_in(parent, ms1_indexed, ms2_indexed_pairs)?;
+        saved.manager(self).load_indexed_data(&saved.source_path)
+    }
+
+    // Save into a scratch cache directory under `parent` that stays until the SavedCache is
+    // dropped, for tests that change the cache files or the config before loading
+    pub fn save_in(
+        &self,
+        parent: &Path,
+        ms1_indexed: &IndexedTimsTOFData,
+        ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)],
+    ) -> Result<SavedCache, Box<dyn std::error::Error>> {
+        let saved = Self::scratch_in(parent)?;
+        let manager = saved.manager(self);
+        manager.save_indexed_data(&saved.source_path, ms1_indexed, ms2_indexed_pairs)?;
+        if self.raw_tier {
+            manager.save_raw_data(&saved.source_path, &raw_data(ms1_indexed, ms2_indexed_pairs))?;
+        }
+        Ok(saved)
+    }
+
+    // A scratch source directory under `parent` with nothing saved yet, for tests that save
+    // through the manager themselves
+    pub fn scratch_in(parent: &Path) -> std::io::Result<SavedCache> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let dir = parent.join(format!(
+            "timstof-roundtrip-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let saved = SavedCache { source_path: dir.join("source.d"), dir };
+        fs::create_dir_all(&saved.source_path)?;
+        // Sidecar loaders check freshness, and a source folder created in the same timestamp
+        // tick as the cache files would make it stale; opening a folder fails on Windows
+        if let Ok(source) = fs::File::open(&saved.source_path) {
+            let _ = source.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(60));
+        }
+        Ok(saved)
+    }
+}
+
+// A saved cache and its (empty) source directory, removed on drop
+pub struct SavedCache {
+    dir: PathBuf,
+    source_path: PathBuf,
+}
+
+impl SavedCache {
+    // A manager of the scratch cache directory with the backend's config
+    pub fn manager(&self, backend: &Backend) -> CacheManager {
+        CacheManager::with_cache_dir(self.cache_dir(), backend.config.clone())
+    }
+
+    pub fn source_path(&self) -> &Path {
+        &self.source_path
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.dir.join("cache")
+    }
+
+    // Every file in the cache directory: data, metadata, sidecars and bookkeeping
+    pub fn files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = fs::read_dir(self.cache_dir())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+        files.retain(|path| path.is_file());
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn load(&self, backend: &Backend) -> Result<LoadedData, Box<dyn std::error::Error>> {
+        self.manager(backend).load_indexed_data(&self.source_path)
+    }
+
+    // Each reader of the cache in turn, stopping at the first error: the data in both
+    // layouts, one frame, the TIC, a heatmap and the raw frame tier if the backend saves
+    // one, so that every file the save wrote is read
+    pub fn read_all(&self, backend: &Backend) -> Result<(), Box<dyn std::error::Error>> {
+        let manager = self.manager(backend);
+        let (ms1_loaded, _) = manager.load_indexed_data(&self.source_path)?;
+        manager.load_indexed_data_with_layout(&self.source_path, Layout::RtMajor)?;
+        if let Some(&frame) = ms1_loaded.frame_indices.first() {
+            manager.load_frame(&self.source_path, frame)?;
+        }
+        manager.load_tic(&self.source_path)?;
+        let viewport = Viewport { axis: HeatmapAxis::Mz, rt: (0.0, 60.0), y: (100.0, 1700.0) };
+        manager.load_heatmap(&self.source_path, 0, &viewport)?;
+        if backend.raw_tier {
+            manager.load_raw_data(&self.source_path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SavedCache {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// Every backend with every codec, small shards so multi-shard paths are exercised
+pub fn backends() -> Vec<Backend> {
+    let formats = [
+        ("columnar", CacheFormat::Columnar),
+        ("single-file", CacheFormat::SingleFile),
+        ("parquet", CacheFormat::Parquet { row_group_size: 256 }),
+    ];
+    let codecs = [BenchCodec::Raw, BenchCodec::Lz4, BenchCodec::Zstd { level: 3 }];
+    let mut backends = Vec::new();
+    for (name, format) in formats {
+        for codec in codecs {
+            backends.push(Backend {
+                label: format!("{}-{}", name, codec.label()),
+                config: CacheConfig {
+                    format,
+                    auto_compression: false,
+                    enable_compression: codec != BenchCodec::Raw,
+                    compression: match codec {
+                        BenchCodec::Zstd { level } => CompressionType::zstd(level),
+                        _ => CompressionType::Lz4,
+                    },
+                    target_shard_points: Some(257),
+                    encryption_key: None,
+                    signing_key: None,
+                    content_addressed: false,
+                    hot_cache_entries: 0,
+                    ..CacheConfig::default()
+                },
+                raw_tier: false,
+            });
+        }
+    }
+    backends
+}
+
+// The raw frame tier holding the same points, MS2 windows by isolation range
+pub fn raw_data(ms1_indexed: &IndexedTimsTOFData, ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)]) -> TimsTOFRawData {
+    let unindexed = |data: &IndexedTimsTOFData| TimsTOFData {
+        rt_values_min: data.rt_values_min.clone(),
+        mobility_values: data.mobility_values.clone(),
+        mz_values: data.mz_values.clone(),
+        intensity_values: data.intensity_values.clone(),
+        frame_indices: data.frame_indices.clone(),
+        scan_indices: data.scan_indices.clone(),
+    };
+    TimsTOFRawData {
+        ms1_data: unindexed(ms1_indexed),
+        ms2_windows: ms2_indexed_pairs.iter().map(|(window, data)| ((window.mz_low, window.mz_high), unindexed(data))).collect(),
+    }
+}
+
+fn sorted_by_mz(data: &IndexedTimsTOFData) -> IndexedTimsTOFData {
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.sort_by(|&a, &b| data.mz_values[a].total_cmp(&data.mz_values[b]));
+    IndexedTimsTOFData {
+        rt_values_min: order.iter().map(|&i| data.rt_values_min[i]).collect(),
+        mobility_values: order.iter().map(|&i| data.mobility_values[i]).collect(),
+        mz_values: order.iter().map(|&i| data.mz_values[i]).collect(),
+        intensity_values: order.iter().map(|&i| data.intensity_values[i]).collect(),
+        frame_indices: order.iter().map(|&i| data.frame_indices[i]).collect(),
+        scan_indices: order.iter().map(|&i| data.scan_indices[i]).collect(),
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use ndarray::{Array2, Array3, s};
 use std::cmp::Ordering;
 use std::error::Error;
+use crate::hot::LoadedData;
+use crate::window::{self, IsolationWindow};
 use polars::prelude::*;
 use std::fs::File;
 use rayon::prelude::*;
@@ -171,7 +173,7 @@ pub fn read_timstof_data(d_folder: &Path) -> Result<TimsTOFRawData, Box<dyn Erro
         global_ms1.scan_indices.extend(split.ms1.scan_indices);
         
         for (key, mut td) in split.ms2 {
-            ms2_hash.entry(key).or_insert_with(TimsTOFData::new).merge_from(&mut td);
+            ms2_hash.entry(key).or_default().merge_from(&mut td);
         }
     }
     
@@ -192,16 +194,23 @@ pub fn read_timstof_data(d_folder: &Path) -> Result<TimsTOFRawData, Box<dyn Erro
 // Optimized IndexedTimsTOFData with all u32 indices
 // ============================================================================
 
+// Float columns are f32 unless asked for otherwise (precision.rs)
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IndexedTimsTOFData {
-    pub rt_values_min: Vec<f32>,
-    pub mobility_values: Vec<f32>,
-    pub mz_values: Vec<f32>,
+pub struct IndexedTimsTOFData<F = f32> {
+    pub rt_values_min: Vec<F>,
+    pub mobility_values: Vec<F>,
+    pub mz_values: Vec<F>,
     pub intensity_values: Vec<u32>,
     pub frame_indices: Vec<u32>,
     pub scan_indices: Vec<u32>,
 }
 
+impl Default for IndexedTimsTOFData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IndexedTimsTOFData {
     /// Empty constructor
     pub fn new() -> Self {
@@ -307,15 +316,15 @@ impl IndexedTimsTOFData {
 }
 
 /// 构建索引数据
-pub fn build_indexed_data(raw_data: TimsTOFRawData) -> Result<(IndexedTimsTOFData, Vec<((f32, f32), IndexedTimsTOFData)>), Box<dyn Error>> {
+pub fn build_indexed_data(raw_data: TimsTOFRawData) -> Result<LoadedData, Box<dyn Error>> {
     // 为 MS1 数据构建索引
     let ms1_indexed = IndexedTimsTOFData::from_timstof_data(raw_data.ms1_data);
     
     // 为 MS2 窗口构建索引
-    let ms2_indexed_pairs: Vec<((f32, f32), IndexedTimsTOFData)> = raw_data.ms2_windows
+    let ms2_indexed_pairs: Vec<(IsolationWindow, IndexedTimsTOFData)> = window::from_bounds(raw_data.ms2_windows
         .into_par_iter()
-        .map(|((low, high), data)| ((low, high), IndexedTimsTOFData::from_timstof_data(data)))
-        .collect();
+        .map(|(range, data)| (range, IndexedTimsTOFData::from_timstof_data(data)))
+        .collect());
     
     Ok((ms1_indexed, ms2_indexed_pairs))
 }
@@ -360,6 +369,12 @@ pub struct TimsTOFData {
     pub scan_indices: Vec<u32>,      // Changed from Vec<usize> to Vec<u32>
 }
 
+impl Default for TimsTOFData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TimsTOFData {
     pub fn new() -> Self {
         TimsTOFData {
@@ -448,6 +463,12 @@ impl Default for LibCols {
 
 pub type MSDataArray = Vec<Vec<f32>>;
 
+// Precursor ids, MS1 data, MS2 data and precursor info of a library (build_lib_matrix)
+pub type LibMatrix = (Vec<Vec<String>>, Vec<MSDataArray>, Vec<MSDataArray>, Vec<Vec<f32>>);
+
+// Repeated MS1/MS2 tensors and their extract width ranges (build_precursors_matrix_step3)
+pub type PrecursorMatrices = (Array3<f32>, Array3<f32>, Array3<f32>, Array3<f32>);
+
 #[derive(Debug, Clone)]
 pub struct LibraryRecord {
     pub transition_group_id: String,
@@ -581,25 +602,15 @@ pub fn build_ms2_data(fragment_list: &[Vec<f32>], max_fragment_num: usize) -> MS
     let total_rows = fragment_num * FRAGMENT_VARIANTS;
     
     let mut type_column = vec![0.0; total_rows];
-    for i in fragment_num..(fragment_num * 2) {
-        type_column[i] = -1.0;
-    }
-    for i in (fragment_num * 2)..total_rows {
-        type_column[i] = 1.0;
-    }
+    type_column[fragment_num..(fragment_num * 2)].fill(-1.0);
+    type_column[(fragment_num * 2)..total_rows].fill(1.0);
     
     let window_id_column = vec![0.0; total_rows];
     
     let mut variant_type_column = vec![0.0; total_rows];
-    for i in 0..fragment_num {
-        variant_type_column[i] = VARIANT_ORIGINAL;
-    }
-    for i in fragment_num..(fragment_num * 2) {
-        variant_type_column[i] = VARIANT_LIGHT;
-    }
-    for i in (fragment_num * 2)..total_rows {
-        variant_type_column[i] = VARIANT_HEAVY;
-    }
+    variant_type_column[..fragment_num].fill(VARIANT_ORIGINAL);
+    variant_type_column[fragment_num..(fragment_num * 2)].fill(VARIANT_LIGHT);
+    variant_type_column[(fragment_num * 2)..total_rows].fill(VARIANT_HEAVY);
     
     let mut complete_data = Vec::new();
     for i in 0..total_rows {
@@ -664,11 +675,11 @@ pub fn format_ms_data(
 
 pub fn build_lib_matrix(
     lib_data: &[LibraryRecord],
-    lib_cols: &LibCols,
+    _lib_cols: &LibCols,
     iso_range: f32,
     mz_max: f32,
     max_fragment: usize,
-) -> Result<(Vec<Vec<String>>, Vec<MSDataArray>, Vec<MSDataArray>, Vec<Vec<f32>>), Box<dyn Error>> {
+) -> Result<LibMatrix, Box<dyn Error>> {
     let precursor_ids: Vec<String> = lib_data.iter()
         .map(|record| record.transition_group_id.clone())
         .collect();
@@ -680,7 +691,7 @@ pub fn build_lib_matrix(
     let mut all_ms2_data = Vec::new();
     let mut all_precursor_info = Vec::new();
     
-    for (group_idx, indices) in precursor_groups.iter().enumerate() {
+    for indices in precursor_groups.iter() {
         if indices.is_empty() {
             continue;
         }
@@ -726,7 +737,7 @@ pub fn build_lib_matrix(
 pub fn build_precursors_matrix_step1(
     ms1_data_list: &[MSDataArray], 
     ms2_data_list: &[MSDataArray], 
-    device: &str
+    _device: &str
 ) -> Result<(Array3<f32>, Array3<f32>), Box<dyn Error>> {
     if ms1_data_list.is_empty() || ms2_data_list.is_empty() {
         return Err("MS1或MS2数据列表为空".into());
@@ -795,10 +806,10 @@ pub fn extract_width_2(
     mz_to_extract: &Array3<f32>,
     mz_unit: &str,
     mz_tol: f32,
-    max_extract_len: usize,
+    _max_extract_len: usize,
     frag_repeat_num: usize,
     max_moz_num: f32,
-    device: &str
+    _device: &str
 ) -> Result<Array3<f32>, Box<dyn Error>> {
     let shape = mz_to_extract.shape();
     let (batch, rows, _) = (shape[0], shape[1], shape[2]);
@@ -919,10 +930,10 @@ pub fn extract_width(
     mz_to_extract: &Array3<f32>,
     mz_unit: &str,
     mz_tol: f32,
-    max_extract_len: usize,
+    _max_extract_len: usize,
     frag_repeat_num: usize,
     max_moz_num: f32,
-    device: &str
+    _device: &str
 ) -> Result<Array3<f32>, Box<dyn Error>> {
     let shape = mz_to_extract.shape();
     let (batch, rows, _) = (shape[0], shape[1], shape[2]);
@@ -1058,7 +1069,7 @@ pub fn build_precursors_matrix_step3(
     mz_tol_ms1: f32,
     mz_tol_ms2: f32,
     device: &str
-) -> Result<(Array3<f32>, Array3<f32>, Array3<f32>, Array3<f32>), Box<dyn Error>> {
+) -> Result<PrecursorMatrices, Box<dyn Error>> {
     let shape1 = ms1_data_tensor.shape();
     let shape2 = ms2_data_tensor.shape();
     
@@ -1352,7 +1363,7 @@ pub fn get_rt_list(mut lst: Vec<f32>, target: f32) -> Vec<f32> {
     lst[start..start + 48].to_vec()
 }
 
-pub fn build_ext_ms1_matrix(ms1_data_tensor: &Array3<f32>, device: &str) -> Array3<f32> {
+pub fn build_ext_ms1_matrix(ms1_data_tensor: &Array3<f32>, _device: &str) -> Array3<f32> {
     let shape = ms1_data_tensor.shape();
     let (batch, rows, _) = (shape[0], shape[1], shape[2]);
     
@@ -1376,7 +1387,7 @@ pub fn build_ext_ms1_matrix(ms1_data_tensor: &Array3<f32>, device: &str) -> Arra
     ext_matrix
 }
 
-pub fn build_ext_ms2_matrix(ms2_data_tensor: &Array3<f32>, device: &str) -> Array3<f32> {
+pub fn build_ext_ms2_matrix(ms2_data_tensor: &Array3<f32>, _device: &str) -> Array3<f32> {
     let shape = ms2_data_tensor.shape();
     let (batch, rows, _) = (shape[0], shape[1], shape[2]);
     
@@ -1403,18 +1414,15 @@ pub fn build_ext_ms2_matrix(ms2_data_tensor: &Array3<f32>, device: &str) -> Arra
 pub fn build_frag_info(
     ms1_data_tensor: &Array3<f32>,
     ms2_data_tensor: &Array3<f32>,
-    frag_repeat_num: usize,
+    _frag_repeat_num: usize,
     device: &str
 ) -> Array3<f32> {
     let ext_ms1_precursors_frag_rt_matrix = build_ext_ms1_matrix(ms1_data_tensor, device);
     let ext_ms2_precursors_frag_rt_matrix = build_ext_ms2_matrix(ms2_data_tensor, device);
     
     let ms1_shape = ext_ms1_precursors_frag_rt_matrix.shape().to_vec();
-    let ms2_shape = ext_ms2_precursors_frag_rt_matrix.shape().to_vec();
     
     let batch = ms1_shape[0];
-    let ms1_rows = ms1_shape[1];
-    let ms2_rows = ms2_shape[1];
     
     let orig_ms1_shape = ms1_data_tensor.shape();
     let orig_ms2_shape = ms2_data_tensor.shape();
@@ -0,0 +1,84 @@
+// File: src/window.rs
+//
+// Typed key of an MS2 isolation window, paired with the window's data in save and load
+// signatures (Ms2Window). Two windows are the same window when their bounds match to
+// 0.001 Th (WindowKey), so Eq and Hash follow the quantized bounds, and f32/f64 round trips
+// or float noise in a caller's window table never split or merge windows. `index` is the
+// position of the window in the dataset: the order a save was given the windows in, which
+// is also the order the cache stores them in and loads return them in.
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::cache::WindowKey;
+use crate::utils::IndexedTimsTOFData;
+
+// Not serialized itself: legacy cache files store windows as bare (low, high) bounds, which
+// loads read as such and number with from_bounds()
+#[derive(Debug, Clone, Copy)]
+pub struct IsolationWindow {
+    pub mz_low: f32,
+    pub mz_high: f32,
+    pub index: usize, // Position in the dataset; not part of the window's identity
+}
+
+// An MS2 window with its points, as saves take them and loads return them
+pub type Ms2Window<F = f32> = (IsolationWindow, IndexedTimsTOFData<F>);
+
+impl IsolationWindow {
+    pub fn new(mz_low: f32, mz_high: f32, index: usize) -> Self {
+        Self { mz_low, mz_high, index }
+    }
+
+    pub fn key(&self) -> WindowKey {
+        WindowKey::new(self.mz_low, self.mz_high)
+    }
+
+    pub fn bounds(&self) -> (f32, f32) {
+        (self.mz_low, self.mz_high)
+    }
+
+    // The same window at another position, e.g. once a load has filtered or merged windows
+    pub fn at(self, index: usize) -> Self {
+        Self { index, ..self }
+    }
+
+    // Bounds inclusive, as WindowFilter::Containing
+    pub fn contains(&self, mz: f32) -> bool {
+        self.mz_low <= mz && mz <= self.mz_high
+    }
+}
+
+impl PartialEq for IsolationWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for IsolationWindow {}
+
+impl Hash for IsolationWindow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl From<IsolationWindow> for (f32, f32) {
+    fn from(window: IsolationWindow) -> Self {
+        window.bounds()
+    }
+}
+
+// As WindowKey: "400.000-425.000"
+impl fmt::Display for IsolationWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.key().fmt(f)
+    }
+}
+
+// Windows of a (low, high) table, e.g. from a raw data reader, numbered in table order
+pub fn from_bounds<D>(pairs: Vec<((f32, f32), D)>) -> Vec<(IsolationWindow, D)> {
+    pairs.into_iter()
+        .enumerate()
+        .map(|(index, ((low, high), data))| (IsolationWindow::new(low, high, index), data))
+        .collect()
+}
@@ -0,0 +1,107 @@
+// The C ABI (src/cabi.rs) called as a C caller would, and the committed header checked
+// against what cbindgen generates from it
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+use timstof_cache_ffi::cabi::{self, CacheRange};
+use timstof_cache_ffi::testing::{self, DataShape, IndexedTimsTOFData};
+
+fn last_error() -> String {
+    let message = cabi::cache_last_error();
+    assert!(!message.is_null(), "a call failed without setting the last error");
+    unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+}
+
+// Copies the points out of a range and frees it
+unsafe fn take_range(range: *mut CacheRange) -> IndexedTimsTOFData {
+    assert!(!range.is_null(), "{}", last_error());
+    let r = &*range;
+    let copied = |values: *mut f32| std::slice::from_raw_parts(values, r.len).to_vec();
+    let data = IndexedTimsTOFData {
+        rt_values_min: copied(r.rt),
+        mobility_values: copied(r.mobility),
+        mz_values: copied(r.mz),
+        intensity_values: std::slice::from_raw_parts(r.intensity, r.len).to_vec(),
+        frame_indices: std::slice::from_raw_parts(r.frame, r.len).to_vec(),
+        scan_indices: std::slice::from_raw_parts(r.scan, r.len).to_vec(),
+    };
+    cabi::cache_free_range(range);
+    data
+}
+
+static MESSAGES: Mutex<Vec<(c_int, String)>> = Mutex::new(Vec::new());
+
+extern "C" fn record_message(level: c_int, message: *const c_char) {
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+    MESSAGES.lock().unwrap().push((level, message));
+}
+
+#[test]
+fn the_c_abi_loads_ranges_and_reports_errors() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 800, seed: 67, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 200, 0.0, 67);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap();
+    cabi::cache_set_log_callback(Some(record_message));
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    // cache_open configures itself from the environment; no other test here reads it
+    std::env::set_var("TIMSTOF_CACHE_CACHE_DIR", saved.cache_dir());
+    let source_path = CString::new(saved.source_path().as_os_str().as_encoded_bytes()).unwrap();
+    let missing = CString::new(saved.source_path().with_file_name("missing.d").as_os_str().as_encoded_bytes()).unwrap();
+
+    unsafe {
+        assert!(cabi::cache_open(ptr::null()).is_null());
+        assert!(last_error().contains("NULL"));
+        assert!(cabi::cache_open(missing.as_ptr()).is_null());
+        assert!(last_error().contains("no valid cache"), "{}", last_error());
+        assert!(cabi::cache_load_range(ptr::null(), 0.0, 2000.0).is_null());
+        assert!(cabi::cache_load_ms2_window(ptr::null(), 400.0, 425.0).is_null());
+        assert!(last_error().contains("NULL"));
+        // Freeing NULL is allowed
+        cabi::cache_free_range(ptr::null_mut());
+        cabi::cache_free(ptr::null_mut());
+
+        let cache = cabi::cache_open(source_path.as_ptr());
+        assert!(!cache.is_null(), "{}", last_error());
+        let all = take_range(cabi::cache_load_range(cache, 0.0, 2000.0));
+        assert!(testing::same_data(&all, &ms1_indexed));
+        let some = take_range(cabi::cache_load_range(cache, 500.0, 600.0));
+        assert!(!some.mz_values.is_empty() && some.mz_values.len() < all.mz_values.len());
+        assert!(some.mz_values.iter().all(|mz| (500.0..=600.0).contains(mz)));
+        assert!(take_range(cabi::cache_load_range(cache, 5000.0, 6000.0)).mz_values.is_empty(), "an empty range failed");
+
+        let (window, window_data) = &ms2_indexed_pairs[1];
+        let loaded = take_range(cabi::cache_load_ms2_window(cache, window.mz_low, window.mz_high));
+        assert!(testing::same_data(&loaded, window_data));
+        assert!(cabi::cache_load_ms2_window(cache, 10.0, 20.0).is_null());
+        assert!(last_error().contains("no MS2 window"), "{}", last_error());
+        cabi::cache_free(cache);
+    }
+
+    // The save's progress messages reached the callback, and none once it is cleared
+    let messages = std::mem::take(&mut *MESSAGES.lock().unwrap());
+    assert!(messages.iter().any(|(level, _)| *level == 0), "{:?}", messages);
+    assert!(messages.iter().all(|(level, _)| (0..=2).contains(level)));
+    cabi::cache_set_log_callback(None);
+    let _resaved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    assert!(MESSAGES.lock().unwrap().is_empty());
+}
+
+#[test]
+fn the_committed_header_matches_cbindgen() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/cabi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+    let committed = std::fs::read(crate_dir.join("include/timstof_cache.h")).unwrap();
+    assert!(
+        generated == committed,
+        "include/timstof_cache.h is out of date; rebuild with TIMSTOF_CACHE_REGENERATE_HEADER=1"
+    );
+}
@@ -0,0 +1,103 @@
+// mzML export (src/mzml.rs) read back with decoders ported from the reference
+// MSNumpress.cpp: Numpress arrays decode to the cached points within the format's error
+// bound, half a unit of the fixed point for m/z and exactly for integer intensities.
+use std::collections::BTreeMap;
+use base64::Engine;
+use timstof_cache_ffi::mzml::{ArrayCompression, MzmlOptions, SpectrumGrouping};
+use timstof_cache_ffi::testing::{self, DataShape};
+
+// Half-bytes of a Numpress payload, high nibble first
+fn half_bytes(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0xf])
+}
+
+// Inverse of encode_int: a count of dropped leading nibbles (zeros, or ones above 8),
+// then the remaining nibbles low first
+fn decode_int(nibbles: &mut impl Iterator<Item = u8>) -> u32 {
+    let head = nibbles.next().expect("truncated Numpress payload") as u32;
+    let (dropped, fill) = if head <= 8 { (head, 0) } else { (head - 8, 0xf) };
+    let mut value = 0u32;
+    for i in 0..8 {
+        let nibble = match i < 8 - dropped {
+            true => nibbles.next().expect("truncated Numpress payload") as u32,
+            false => fill,
+        };
+        value |= nibble << (4 * i);
+    }
+    value
+}
+
+// Returns the values and the fixed point they were scaled by
+fn decode_linear(bytes: &[u8], len: usize) -> (Vec<f64>, f64) {
+    let fixed_point = f64::from_be_bytes(bytes[..8].try_into().unwrap());
+    let mut ints: Vec<i64> = bytes[8..].chunks(4).take(len.min(2))
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()) as i64)
+        .collect();
+    let mut nibbles = half_bytes(&bytes[8 + 4 * ints.len()..]);
+    while ints.len() < len {
+        let (previous, last) = (ints[ints.len() - 2], ints[ints.len() - 1]);
+        ints.push(decode_int(&mut nibbles) as i32 as i64 + last + (last - previous));
+    }
+    (ints.into_iter().map(|value| value as f64 / fixed_point).collect(), fixed_point)
+}
+
+fn decode_pic(bytes: &[u8], len: usize) -> Vec<f64> {
+    let mut nibbles = half_bytes(bytes);
+    (0..len).map(|_| decode_int(&mut nibbles) as f64).collect()
+}
+
+// Text between `open` and `close` after each `open` in `text`
+fn sections<'a>(text: &'a str, open: &'a str, close: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    text.split(open).skip(1).map(move |rest| rest.split(close).next().unwrap())
+}
+
+fn attribute<'a>(text: &'a str, name: &str) -> &'a str {
+    text.split(&format!("{}=\"", name)).nth(1).unwrap().split('"').next().unwrap()
+}
+
+#[test]
+fn numpress_arrays_decode_within_the_error_bound() {
+    let mut ms1_indexed = testing::indexed_data(&DataShape { points: 2000, seed: 71, ..DataShape::default() });
+    // Few frames, so spectra hold many points and linear prediction is exercised
+    for frame in &mut ms1_indexed.frame_indices {
+        *frame %= 20;
+    }
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-raw").unwrap();
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+    let out_path = saved.cache_dir().join("export.mzML");
+    let options = MzmlOptions { grouping: SpectrumGrouping::Frame, compression: ArrayCompression::Numpress, include_mobility: true };
+    let spectra = saved.manager(&backend).export_mzml(saved.source_path(), &out_path, &options).unwrap();
+
+    let mut expected: BTreeMap<u32, Vec<(f32, u32)>> = BTreeMap::new();
+    for row in 0..ms1_indexed.mz_values.len() {
+        expected.entry(ms1_indexed.frame_indices[row]).or_default().push((ms1_indexed.mz_values[row], ms1_indexed.intensity_values[row]));
+    }
+    for points in expected.values_mut() {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    assert_eq!(spectra, expected.len());
+
+    let mzml = std::fs::read_to_string(&out_path).unwrap();
+    let mut decoded_spectra = 0;
+    for spectrum in sections(&mzml, "<spectrum ", "</spectrum>") {
+        let frame: u32 = attribute(spectrum, "id").trim_start_matches("frame=").split(' ').next().unwrap().parse().unwrap();
+        let len: usize = attribute(spectrum, "defaultArrayLength").parse().unwrap();
+        let arrays: Vec<Vec<u8>> = sections(spectrum, "<binary>", "</binary>")
+            .map(|encoded| base64::engine::general_purpose::STANDARD.decode(encoded).unwrap())
+            .collect();
+        assert!(spectrum.contains("MS-Numpress linear prediction compression") && spectrum.contains("MS-Numpress positive integer compression"));
+        let (mz, fixed_point) = decode_linear(&arrays[0], len);
+        let intensity = decode_pic(&arrays[1], len);
+
+        let points = &expected[&frame];
+        assert_eq!(len, points.len(), "frame {}", frame);
+        let bound = 0.5 / fixed_point + 1e-9;
+        assert!(bound < 1e-5, "frame {}: fixed point {} loses m/z precision", frame, fixed_point);
+        for ((decoded_mz, decoded_intensity), (cached_mz, cached_intensity)) in mz.iter().zip(&intensity).zip(points) {
+            assert!((decoded_mz - *cached_mz as f64).abs() <= bound, "frame {}: m/z {} decoded as {}", frame, cached_mz, decoded_mz);
+            assert_eq!(*decoded_intensity, *cached_intensity as f64, "frame {}", frame);
+        }
+        decoded_spectra += 1;
+    }
+    assert_eq!(decoded_spectra, spectra);
+}
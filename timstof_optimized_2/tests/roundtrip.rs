@@ -0,0 +1,858 @@
+// Property-based save/load round trips through every backend and codec
+// (testing::backends()): whatever the input, a save either fails or loads back exactly
+// what was saved, and inputs in the order indexing produces always succeed.
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use timstof_cache_ffi::shm;
+use timstof_cache_ffi::testing::{self, DataShape, EncryptionKey, IndexedTimsTOFData, IsolationWindow, SigningKey};
+
+fn round_trips(ms1_indexed: &IndexedTimsTOFData, ms2_indexed_pairs: &[(IsolationWindow, IndexedTimsTOFData)]) -> Result<(), TestCaseError> {
+    for backend in testing::backends() {
+        match backend.round_trip(ms1_indexed, ms2_indexed_pairs) {
+            Ok((ms1_loaded, ms2_loaded)) => {
+                prop_assert!(testing::same_data(&ms1_loaded, ms1_indexed), "{}: MS1 changed in the round trip", backend.label());
+                prop_assert!(testing::same_windows(&ms2_loaded, ms2_indexed_pairs), "{}: MS2 changed in the round trip", backend.label());
+            }
+            Err(e) => prop_assert!(
+                !testing::is_loadable_order(ms1_indexed),
+                "{}: saving indexed data failed: {}", backend.label(), e
+            ),
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn indexed_data_round_trips(points in 1usize..3000, windows in 1usize..5, seed: u64) {
+        let ms1_indexed = testing::indexed_data(&DataShape { points, seed, ..DataShape::default() });
+        let ms2_indexed_pairs = testing::ms2_windows(windows, 500, 0.0, seed);
+        round_trips(&ms1_indexed, &ms2_indexed_pairs)?;
+    }
+
+    #[test]
+    fn empty_and_sparse_data_round_trips(points in 0usize..3, windows in 0usize..5, empty_fraction in 0.0f32..=1.0, seed: u64) {
+        let ms1_indexed = testing::indexed_data(&DataShape { points, seed, ..DataShape::default() });
+        let ms2_indexed_pairs = testing::ms2_windows(windows, 3, empty_fraction, seed);
+        round_trips(&ms1_indexed, &ms2_indexed_pairs)?;
+    }
+
+    #[test]
+    fn nans_round_trip_bitwise_or_are_rejected(points in 1usize..2000, nan_fraction in 0.0f32..0.3, seed: u64) {
+        let ms1_indexed = testing::indexed_data(&DataShape { points, nan_fraction, seed, ..DataShape::default() });
+        let ms2_indexed_pairs = testing::ms2_windows(2, 300, 0.0, seed);
+        round_trips(&ms1_indexed, &ms2_indexed_pairs)?;
+    }
+
+    #[test]
+    fn unsorted_input_never_loads_different_data(points in 2usize..2000, seed: u64) {
+        let ms1_indexed = testing::indexed_data(&DataShape { points, sorted: false, seed, ..DataShape::default() });
+        let ms2_indexed_pairs = testing::ms2_windows(2, 300, 0.0, seed);
+        round_trips(&ms1_indexed, &ms2_indexed_pairs)?;
+    }
+}
+
+#[test]
+fn empty_source_round_trips() {
+    for backend in testing::backends() {
+        let (ms1_loaded, ms2_loaded) = backend.round_trip(&IndexedTimsTOFData::new(), &[])
+            .unwrap_or_else(|e| panic!("{}: empty source failed: {}", backend.label(), e));
+        assert!(ms1_loaded.mz_values.is_empty() && ms2_loaded.is_empty(), "{}", backend.label());
+    }
+}
+
+#[test]
+fn cache_paths_past_max_path_round_trip() {
+    // Components under the 255-character limit, together well past Windows' 260
+    let top = std::env::temp_dir().join(format!("timstof-longpath-{}", std::process::id()));
+    let deep = (0..6).fold(top.clone(), |dir, level| dir.join(format!("{}-deep-project-directory-{}", "nested".repeat(6), level)));
+    assert!(deep.as_os_str().len() > 300);
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 500, seed: 7, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(3, 200, 0.0, 7);
+    for backend in testing::backends() {
+        let loaded = backend.round_trip_in(&deep, &ms1_indexed, &ms2_indexed_pairs);
+        let (ms1_loaded, ms2_loaded) = loaded.unwrap_or_else(|e| panic!("{}: long cache path failed: {}", backend.label(), e));
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}", backend.label());
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "{}", backend.label());
+    }
+    let _ = std::fs::remove_dir_all(&top);
+}
+
+#[test]
+fn tampered_cache_files_are_rejected_when_signed() {
+    let key = SigningKey::new("roundtrip", b"roundtrip signing secret".to_vec());
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 800, seed: 11, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 200, 0.0, 11);
+    for backend in testing::backends() {
+        let backend = backend.signed(key.clone());
+        let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs)
+            .unwrap_or_else(|e| panic!("{}: signed save failed: {}", backend.label(), e));
+        saved.read_all(&backend).unwrap_or_else(|e| panic!("{}: untampered cache rejected: {}", backend.label(), e));
+        let files = saved.files().unwrap();
+        let signed: Vec<_> = files.iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bin" || ext == "lz4" || ext == "parquet" || ext == "meta"))
+            .collect();
+        assert!(signed.len() >= 5, "{}: expected data, metadata and sidecars, found {:?}", backend.label(), files);
+        for path in signed {
+            let original = std::fs::read(path).unwrap();
+            let mut tampered = original.clone();
+            let middle = tampered.len() / 2;
+            tampered[middle] ^= 0x01;
+            std::fs::write(path, &tampered).unwrap();
+            assert!(saved.read_all(&backend).is_err(), "{}: tampered {} was accepted", backend.label(), path.display());
+            std::fs::write(path, &original).unwrap();
+        }
+        let wrong_key = backend.signed(SigningKey::new("roundtrip", b"another secret".to_vec()));
+        assert!(saved.load(&wrong_key).is_err(), "{}: metadata signed with another key was accepted", backend.label());
+    }
+}
+
+#[test]
+fn encrypted_caches_need_the_key_they_were_saved_with() {
+    let key = EncryptionKey::new("roundtrip", [7; 32]);
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 800, seed: 13, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 200, 0.0, 13);
+    // Parquet files aren't encrypted: saves with a key are refused
+    for backend in testing::backends().into_iter().filter(|backend| !backend.label().starts_with("parquet")) {
+        let encrypted = backend.encrypted(Some(key.clone()));
+        let saved = encrypted.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs)
+            .unwrap_or_else(|e| panic!("{}: encrypted save failed: {}", backend.label(), e));
+        let (ms1_loaded, ms2_loaded) = saved.load(&encrypted)
+            .unwrap_or_else(|e| panic!("{}: load with the key failed: {}", backend.label(), e));
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}", backend.label());
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "{}", backend.label());
+        let wrong_key = backend.encrypted(Some(EncryptionKey::new("roundtrip", [8; 32])));
+        assert!(saved.load(&wrong_key).is_err(), "{}: loaded with the wrong key", backend.label());
+        assert!(saved.load(&backend.encrypted(None)).is_err(), "{}: loaded without a key", backend.label());
+    }
+}
+
+// The JSON column table of a container and where it starts, after the column payloads
+fn container_header(bytes: &[u8]) -> (usize, serde_json::Value) {
+    let trailer = bytes.len() - 12;
+    let header_len = u64::from_le_bytes(bytes[trailer..trailer + 8].try_into().unwrap()) as usize;
+    let header_start = trailer - header_len;
+    (header_start, serde_json::from_slice(&bytes[header_start..trailer]).unwrap())
+}
+
+// Replaces the JSON column table of the container at `path` with `edit` applied to it
+fn edit_container_header(path: &std::path::Path, edit: impl FnOnce(&mut serde_json::Value)) {
+    let bytes = std::fs::read(path).unwrap();
+    let (header_start, mut header) = container_header(&bytes);
+    edit(&mut header);
+    let header = serde_json::to_vec(&header).unwrap();
+    let mut edited = bytes[..header_start].to_vec();
+    edited.extend_from_slice(&header);
+    edited.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    edited.extend_from_slice(b"TTFC");
+    std::fs::write(path, edited).unwrap();
+}
+
+// Rows `rows` of `data`
+fn rows_of(data: &IndexedTimsTOFData, rows: std::ops::Range<usize>) -> IndexedTimsTOFData {
+    IndexedTimsTOFData {
+        rt_values_min: data.rt_values_min[rows.clone()].to_vec(),
+        mobility_values: data.mobility_values[rows.clone()].to_vec(),
+        mz_values: data.mz_values[rows.clone()].to_vec(),
+        intensity_values: data.intensity_values[rows.clone()].to_vec(),
+        frame_indices: data.frame_indices[rows.clone()].to_vec(),
+        scan_indices: data.scan_indices[rows].to_vec(),
+    }
+}
+
+#[test]
+fn zero_copy_shards_are_rkyv_archives_checked_on_open() {
+    use timstof_cache_ffi::mapped::{self, DataShard, MappedShard};
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 600, seed: 19, ..DataShape::default() });
+    // At most 257 points a shard (testing::backends), split evenly: three of 200
+    let second_shard = rows_of(&ms1_indexed, 200..400);
+    for backend in testing::backends() {
+        let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+        let manager = saved.manager(&backend);
+        if backend.label().starts_with("parquet") {
+            assert!(manager.load_shard_mmap(saved.source_path(), 1).is_err(), "{}: a Parquet shard was mapped", backend.label());
+            continue;
+        }
+        let mapped = manager.load_shard_mmap(saved.source_path(), 1)
+            .unwrap_or_else(|e| panic!("{}: mapping failed: {}", backend.label(), e));
+        assert!(testing::same_data(&mapped.to_indexed(), &second_shard), "{}: mapped shard differs", backend.label());
+        let (mz_min, mz_max) = mapped.mz_bounds().unwrap();
+        assert_eq!(mapped.slice_by_mz_range(mz_min, mz_max).mz_values, second_shard.mz_values);
+        let again = manager.load_shard_mmap(saved.source_path(), 1).unwrap();
+        assert_eq!(again.path(), mapped.path());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |shard: &MappedShard| std::fs::metadata(shard.path()).unwrap().ino();
+            assert_eq!(inode(&again), inode(&mapped), "{}: the archive was written again", backend.label());
+        }
+        assert!(manager.load_shard_mmap(saved.source_path(), 99).is_err(), "{}: a missing shard was mapped", backend.label());
+    }
+
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap();
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+    let manager = saved.manager(&backend);
+    let mapped = manager.load_shard_mmap(saved.source_path(), 1).unwrap();
+    let path = mapped.path().to_path_buf();
+    let fingerprint = path.file_name().unwrap().to_string_lossy().split('.').rev().nth(2).unwrap().to_string();
+    assert!(MappedShard::open(&path, &fingerprint, 0, None).is_err(), "shard 1 was mapped as shard 0");
+    assert!(MappedShard::open(&path, "0123456789abcdef", 1, None).is_err(), "another version's shard was mapped");
+    let intact = std::fs::read(&path).unwrap();
+    drop(mapped);
+
+    // Damaged archives are rewritten, not mapped: the root (last in the archive) pointing
+    // past the end, a truncated file, and garbage
+    let mut bad_root = intact.clone();
+    let root = bad_root.len() - 8;
+    bad_root[root..].fill(0xff);
+    for bytes in [bad_root, intact[..intact.len() / 2].to_vec(), b"garbage, not an archive".to_vec()] {
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(MappedShard::open(&path, &fingerprint, 1, None).is_err(), "a damaged archive was mapped");
+        let shard = manager.load_shard_mmap(saved.source_path(), 1).unwrap();
+        assert!(testing::same_data(&shard.to_indexed(), &second_shard));
+    }
+
+    // A rebuilt cache gets archives of its own; clearing the cache removes them
+    let rebuilt = testing::indexed_data(&DataShape { points: 600, seed: 20, ..DataShape::default() });
+    manager.save_indexed_data(saved.source_path(), &rebuilt, &[]).unwrap();
+    let current = manager.load_shard_mmap(saved.source_path(), 1).unwrap();
+    assert!(testing::same_data(&current.to_indexed(), &rows_of(&rebuilt, 200..400)));
+    assert!(!path.exists(), "the stale archive was kept");
+    let zero_copy_dir = current.path().parent().unwrap().to_path_buf();
+    drop(current);
+    manager.clear_cache_for(saved.source_path()).unwrap();
+    assert_eq!(std::fs::read_dir(&zero_copy_dir).unwrap().count(), 0, "archives outlived their cache");
+
+    // With a signing key, unsigned or foreign archives are replaced
+    let key = SigningKey::new("roundtrip", b"roundtrip signing secret".to_vec());
+    let signed = backend.configured(|config| config.signing_key = Some(key.clone()));
+    let saved = signed.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+    let manager = saved.manager(&signed);
+    let path = manager.load_shard_mmap(saved.source_path(), 1).unwrap().path().to_path_buf();
+    let fingerprint = path.file_name().unwrap().to_string_lossy().split('.').rev().nth(2).unwrap().to_string();
+    let planted = DataShard::new(&fingerprint, 1, rows_of(&ms1_indexed, 0..10));
+    for planted_key in [None, Some(&SigningKey::new("roundtrip", b"another secret".to_vec()))] {
+        mapped::write(&path, &planted, planted_key).unwrap();
+        assert!(MappedShard::open(&path, &fingerprint, 1, Some(&key)).is_err());
+        let shard = manager.load_shard_mmap(saved.source_path(), 1).unwrap();
+        assert!(testing::same_data(&shard.to_indexed(), &second_shard), "a planted archive was mapped");
+    }
+
+    // An archive would hold an encrypted cache decrypted
+    let encrypted = backend.encrypted(Some(EncryptionKey::new("roundtrip", [7; 32])));
+    let saved = encrypted.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+    assert!(saved.manager(&encrypted).load_shard_mmap(saved.source_path(), 1).is_err());
+}
+
+// Segments of each unit an interrupted save recorded in its .progress file
+fn committed_segments(saved: &testing::SavedCache) -> usize {
+    let Ok(files) = saved.files() else { return 0 };
+    files.iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "progress"))
+        .filter_map(|path| serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path).ok()?).ok())
+        .filter_map(|progress| progress["segments"].as_object().cloned())
+        .flat_map(|units| units.into_values())
+        .map(|header| header["segments"].as_array().map_or(0, Vec::len))
+        .sum()
+}
+
+#[test]
+fn interrupted_saves_resume_after_the_committed_shards() {
+    use std::sync::{Arc, Mutex};
+    use timstof_cache_ffi::cancel::{self, CancellationToken};
+    use timstof_cache_ffi::telemetry;
+
+    let resumed = Arc::new(Mutex::new(Vec::new()));
+    let log = resumed.clone();
+    telemetry::set_log_hook(move |_, message| {
+        if message.contains("resuming after them") {
+            log.lock().unwrap().push(message.to_string());
+        }
+    });
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 20_000, seed: 23, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(8, 2000, 0.0, 23);
+    for label in ["columnar-zstd-3", "single-file-lz4"] {
+        // One segment per batch, so the save notices the cancellation after each shard
+        let backend = testing::backends().into_iter().find(|backend| backend.label() == label).unwrap()
+            .configured(|config| config.parallel_threads = Some(1));
+        let saved = testing::Backend::scratch_in(&std::env::temp_dir()).unwrap();
+        let manager = saved.manager(&backend);
+
+        // Cancelled once the first shards are recorded as committed
+        let token = CancellationToken::new();
+        let result = std::thread::scope(|scope| {
+            let save = scope.spawn(|| manager.save_indexed_data_cancellable(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs, &token).map_err(|e| e.to_string()));
+            while !save.is_finished() && committed_segments(&saved) == 0 {
+                std::thread::yield_now();
+            }
+            token.cancel();
+            save.join().unwrap()
+        });
+        let error = result.expect_err("the save finished before it was cancelled");
+        assert!(error.contains("cancelled"), "{}: {}", label, error);
+        let committed = committed_segments(&saved);
+        assert!(committed > 0, "{}: no shards committed", label);
+        assert!(saved.load(&backend).is_err(), "{}: an interrupted save loaded", label);
+
+        resumed.lock().unwrap().clear();
+        manager.save_indexed_data(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+        assert_eq!(resumed.lock().unwrap().len(), 1, "{}: the save started over", label);
+        let (ms1_loaded, ms2_loaded) = saved.load(&backend).unwrap();
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}: resumed MS1 differs", label);
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "{}: resumed MS2 differs", label);
+        assert_eq!(committed_segments(&saved), 0, "{}: progress left behind", label);
+
+        // Progress recorded under other settings describes other files and is not resumed
+        let token = CancellationToken::new();
+        token.cancel();
+        let error = manager.save_indexed_data_cancellable(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs, &token).unwrap_err();
+        assert!(cancel::is_cancelled_error(error.as_ref()), "{}: {}", label, error);
+        let other = backend.configured(|config| config.compression_block_bytes = Some(4096));
+        resumed.lock().unwrap().clear();
+        saved.manager(&other).save_indexed_data(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+        assert!(resumed.lock().unwrap().is_empty(), "{}: resumed progress of other settings", label);
+        let (ms1_loaded, _) = saved.load(&other).unwrap();
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}: MS1 differs after a fresh save", label);
+    }
+}
+
+#[test]
+fn the_mirror_evicts_only_what_it_fetched() {
+    use std::sync::Arc;
+    use timstof_cache_ffi::{CacheManager, DirectoryStore};
+
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 300, seed: 29, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 100, 0.0, 29);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-raw").unwrap();
+    let temp_dir = std::env::temp_dir();
+    // Two sources cached in the remote store only, and one built locally
+    let store = testing::Backend::scratch_in(&temp_dir).unwrap();
+    let (a, b, local) = (
+        testing::Backend::scratch_in(&temp_dir).unwrap(),
+        testing::Backend::scratch_in(&temp_dir).unwrap(),
+        testing::Backend::scratch_in(&temp_dir).unwrap(),
+    );
+    let remote_manager = CacheManager::with_cache_dir(store.cache_dir(), backend.config().clone());
+    for source in [&a, &b] {
+        remote_manager.save_indexed_data(source.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    }
+    let mirror = || {
+        let config = backend.configured(|config| config.local_mirror_bytes = Some(1)).config().clone();
+        CacheManager::with_cache_dir(local.cache_dir(), config).with_remote(Arc::new(DirectoryStore::new(store.cache_dir())))
+    };
+    mirror().save_indexed_data(local.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let metadata_files = || local.files().unwrap().into_iter().filter(|path| path.extension().is_some_and(|ext| ext == "meta")).count();
+    assert_eq!(metadata_files(), 1);
+
+    // Checking validity downloads nothing
+    let files = local.files().unwrap();
+    assert!(mirror().is_cache_valid(a.source_path()));
+    assert_eq!(local.files().unwrap(), files);
+
+    // Over the bound, the mirror drops its least recently used copy, never the local cache
+    mirror().load_indexed_data(a.source_path()).unwrap();
+    assert_eq!(metadata_files(), 2, "the copy in use was evicted");
+    mirror().load_indexed_data(b.source_path()).unwrap();
+    assert_eq!(metadata_files(), 2);
+    let unmirrored = local.manager(&backend);
+    assert!(unmirrored.is_cache_valid(local.source_path()), "the locally built cache was evicted");
+    assert!(!unmirrored.is_cache_valid(a.source_path()), "the older mirrored copy was kept");
+    assert!(unmirrored.is_cache_valid(b.source_path()));
+}
+
+#[test]
+fn range_reads_of_ms1_files_without_shards_fail() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 200, seed: 31, ..DataShape::default() });
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-raw").unwrap();
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &[]).unwrap();
+    let manager = saved.manager(&backend);
+    let in_range = manager.load_ms1_mz_range(saved.source_path(), 0.0, 2000.0).unwrap();
+    assert!(testing::same_data(&in_range, &ms1_indexed));
+
+    let ms1_path = saved.files().unwrap().into_iter()
+        .find(|path| path.to_string_lossy().ends_with("ms1_indexed.cache.bin"))
+        .unwrap();
+    let original = std::fs::read(&ms1_path).unwrap();
+    for (label, pointer) in [("no shards", "/segments"), ("a shard without columns", "/segments/0/columns")] {
+        edit_container_header(&ms1_path, |header| *header.pointer_mut(pointer).unwrap() = serde_json::json!([]));
+        assert!(manager.load_ms1_mz_range(saved.source_path(), 0.0, 2000.0).is_err(), "{} was range-read", label);
+        std::fs::write(&ms1_path, &original).unwrap();
+    }
+}
+
+#[test]
+fn the_format_setting_is_read_from_files_and_the_environment() {
+    use timstof_cache_ffi::{CacheConfig, CacheFormat, CacheManager};
+
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 400, seed: 37, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 100, 0.0, 37);
+    let temp_dir = std::env::temp_dir();
+    let formats = [
+        ("columnar", CacheFormat::Columnar, "container-v"),
+        ("single-file", CacheFormat::SingleFile, "container-v"),
+        ("parquet", CacheFormat::Parquet { row_group_size: 256 }, "parquet"),
+    ];
+    for (name, format, serialization) in formats {
+        let saved = testing::Backend::scratch_in(&temp_dir).unwrap();
+        let settings_path = saved.cache_dir().with_file_name("cache.toml");
+        std::fs::write(&settings_path, format!("format = \"{}\"\nparquet_row_group_size = 256\n", name)).unwrap();
+        let from_file = CacheConfig::from_file(&settings_path).unwrap();
+        assert_eq!(from_file.format, format, "{} from the settings file", name);
+        // No other test in this binary reads the environment
+        std::env::set_var("TIMSTOF_CACHE_FORMAT", name);
+        std::env::set_var("TIMSTOF_CACHE_PARQUET_ROW_GROUP_SIZE", "256");
+        let from_env = CacheConfig::from_env();
+        std::env::remove_var("TIMSTOF_CACHE_FORMAT");
+        std::env::remove_var("TIMSTOF_CACHE_PARQUET_ROW_GROUP_SIZE");
+        assert_eq!(from_env.unwrap().format, format, "{} from the environment", name);
+
+        // The save writes that format and records it, and loads back what was saved
+        let manager = CacheManager::with_cache_dir(saved.cache_dir(), from_file);
+        manager.save_indexed_data(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+        let meta_path = saved.files().unwrap().into_iter()
+            .find(|path| path.extension().is_some_and(|ext| ext == "meta"))
+            .unwrap();
+        let metadata = std::fs::read_to_string(meta_path).unwrap();
+        assert!(metadata.contains(&format!("\nformat: {}\n", name)), "{}: {}", name, metadata);
+        assert!(metadata.contains(&format!("\nserialization: {}", serialization)), "{}: {}", name, metadata);
+        let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(saved.source_path()).unwrap();
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}: MS1 changed in the round trip", name);
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "{}: MS2 changed in the round trip", name);
+    }
+    let saved = testing::Backend::scratch_in(&temp_dir).unwrap();
+    let settings_path = saved.cache_dir().with_file_name("cache.toml");
+    std::fs::write(&settings_path, "format = \"bincode\"\n").unwrap();
+    assert!(CacheConfig::from_file(&settings_path).is_err(), "an unknown format was accepted");
+}
+
+// The points of frames up to `last_frame`, as a cache of an acquisition still running holds
+fn frames_up_to(data: &IndexedTimsTOFData, last_frame: u32) -> IndexedTimsTOFData {
+    let mut kept = IndexedTimsTOFData::new();
+    for row in (0..data.mz_values.len()).filter(|&row| data.frame_indices[row] <= last_frame) {
+        kept.rt_values_min.push(data.rt_values_min[row]);
+        kept.mobility_values.push(data.mobility_values[row]);
+        kept.mz_values.push(data.mz_values[row]);
+        kept.intensity_values.push(data.intensity_values[row]);
+        kept.frame_indices.push(data.frame_indices[row]);
+        kept.scan_indices.push(data.scan_indices[row]);
+    }
+    kept
+}
+
+#[test]
+fn updates_append_only_the_new_frames() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 3000, seed: 41, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(3, 400, 0.0, 41);
+    let ms2_cached: Vec<_> = ms2_indexed_pairs.iter().map(|(window, data)| (*window, frames_up_to(data, 30_000))).collect();
+    let appended_points = |cached: &[(IsolationWindow, IndexedTimsTOFData)]| -> usize {
+        ms2_indexed_pairs.iter().zip(cached).map(|((_, all), (_, old))| all.mz_values.len() - old.mz_values.len()).sum()
+    };
+    for label in ["columnar-lz4", "single-file-zstd-3"] {
+        let backend = testing::backends().into_iter().find(|backend| backend.label() == label).unwrap();
+        let saved = backend.save_in(&std::env::temp_dir(), &frames_up_to(&ms1_indexed, 30_000), &ms2_cached).unwrap();
+        let data_files = || saved.files().unwrap().into_iter()
+            .filter(|path| path.to_string_lossy().contains("indexed.cache."))
+            .collect::<Vec<_>>();
+        let before: Vec<Vec<u8>> = data_files().iter().map(|path| std::fs::read(path).unwrap()).collect();
+        assert_eq!(before.len(), if label.starts_with("columnar") { 2 } else { 1 }, "{}", label);
+
+        let manager = saved.manager(&backend);
+        let appended = manager.update_cache(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+        let ms1_appended = ms1_indexed.mz_values.len() - frames_up_to(&ms1_indexed, 30_000).mz_values.len();
+        assert_eq!(appended, ms1_appended + appended_points(&ms2_cached), "{}", label);
+
+        // The shards already cached keep their bytes and descriptors; new ones follow them
+        let after: Vec<Vec<u8>> = data_files().iter().map(|path| std::fs::read(path).unwrap()).collect();
+        assert_eq!(after.len(), before.len(), "{}", label);
+        for (old, new) in before.iter().zip(&after) {
+            let (old_end, old_header) = container_header(old);
+            let (_, new_header) = container_header(new);
+            let (old_segments, new_segments) = (old_header["segments"].as_array().unwrap(), new_header["segments"].as_array().unwrap());
+            assert!(new_segments.len() > old_segments.len(), "{}: nothing was appended", label);
+            assert_eq!(&new_segments[..old_segments.len()], old_segments.as_slice(), "{}: cached shards were rewritten", label);
+            assert_eq!(&new[..old_end], &old[..old_end], "{}: cached shard data changed", label);
+        }
+
+        let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(saved.source_path()).unwrap();
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "{}: MS1 differs after the update", label);
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "{}: MS2 differs after the update", label);
+        assert_eq!(manager.update_cache(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap(), 0, "{}", label);
+    }
+}
+
+#[test]
+fn gc_and_the_trash_keep_live_caches_and_their_history() {
+    use std::time::Duration;
+    use timstof_cache_ffi::GcOptions;
+
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 500, seed: 43, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 100, 0.0, 43);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap()
+        .configured(|config| config.history_generations = 2);
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let manager = saved.manager(&backend);
+    // A second generation of the live cache, so its history holds one to roll back to
+    manager.save_indexed_data(saved.source_path(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let other = saved.source_path().with_file_name("other.d");
+    std::fs::create_dir_all(&other).unwrap();
+    manager.save_indexed_data(&other, &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    manager.clear_cache_for(&other).unwrap();
+    assert!(!manager.is_cache_valid(&other));
+    assert_eq!(manager.trash_entries().unwrap().len(), 1);
+
+    // What interrupted saves and removed sources leave behind, next to a file not the cache's
+    let cache_dir = saved.cache_dir();
+    let mut strays: Vec<_> = ["orphan.ms1_indexed.cache.bin", "orphan.meta", "interrupted.indexed.cache.partial"].iter()
+        .map(|name| cache_dir.join(name))
+        .collect();
+    strays.sort();
+    for path in &strays {
+        std::fs::write(path, b"left behind").unwrap();
+    }
+    std::fs::write(cache_dir.join("notes.txt"), b"kept").unwrap();
+    let loads_unchanged = |what: &str| {
+        let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(saved.source_path()).unwrap();
+        assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "MS1 differs after {}", what);
+        assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "MS2 differs after {}", what);
+        assert_eq!(manager.history(saved.source_path()).unwrap().len(), 2, "history lost by {}", what);
+    };
+
+    // Too young by default: they could belong to a save in progress
+    assert!(manager.gc(GcOptions::default()).unwrap().orphans.is_empty());
+    let collect_all = GcOptions { min_age: Duration::ZERO, ..GcOptions::default() };
+    let dry_run = manager.gc(GcOptions { dry_run: true, ..collect_all }).unwrap();
+    assert_eq!(dry_run.orphans.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), strays);
+    assert!(strays.iter().all(|path| path.exists()), "the dry run removed files");
+    let report = manager.gc(collect_all).unwrap();
+    assert_eq!(report.orphans.len(), strays.len());
+    assert!(strays.iter().all(|path| !path.exists()), "orphans were kept");
+    assert!(cache_dir.join("notes.txt").exists());
+    assert_eq!(manager.trash_entries().unwrap().len(), 1, "gc touched the trash");
+    loads_unchanged("gc");
+
+    // Emptying the trash removes only what was trashed
+    let files = saved.files().unwrap();
+    manager.empty_trash().unwrap();
+    assert!(manager.trash_entries().unwrap().is_empty());
+    assert!(manager.restore(&other).is_err());
+    assert_eq!(saved.files().unwrap(), files);
+    loads_unchanged("emptying the trash");
+}
+
+#[test]
+fn rollback_restores_an_earlier_generation() {
+    let first = testing::indexed_data(&DataShape { points: 600, seed: 47, ..DataShape::default() });
+    let first_ms2 = testing::ms2_windows(2, 150, 0.0, 47);
+    let second = testing::indexed_data(&DataShape { points: 900, seed: 53, ..DataShape::default() });
+    let second_ms2 = testing::ms2_windows(3, 150, 0.0, 53);
+    for label in ["columnar-zstd-3", "single-file-lz4"] {
+        let backend = testing::backends().into_iter().find(|backend| backend.label() == label).unwrap()
+            .configured(|config| config.history_generations = 2);
+        let saved = backend.save_in(&std::env::temp_dir(), &first, &first_ms2).unwrap();
+        let manager = saved.manager(&backend);
+        manager.save_indexed_data(saved.source_path(), &second, &second_ms2).unwrap();
+        let generations: Vec<u64> = manager.history(saved.source_path()).unwrap().iter().map(|entry| entry.generation).collect();
+        assert_eq!(generations.len(), 2, "{}", label);
+        let (newest, oldest) = (generations[0], generations[1]);
+        let loads = |ms1_expected: &IndexedTimsTOFData, ms2_expected: &[(IsolationWindow, IndexedTimsTOFData)], what: &str| {
+            let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(saved.source_path()).unwrap();
+            assert!(testing::same_data(&ms1_loaded, ms1_expected), "{}: MS1 of {}", label, what);
+            assert!(testing::same_windows(&ms2_loaded, ms2_expected), "{}: MS2 of {}", label, what);
+        };
+        loads(&second, &second_ms2, "the rebuild");
+
+        let committed = manager.rollback(saved.source_path(), oldest).unwrap();
+        assert!(committed > newest, "{}: the rollback was not committed as a new generation", label);
+        loads(&first, &first_ms2, "the first generation");
+        // The rollback can be rolled back in turn
+        manager.rollback(saved.source_path(), newest).unwrap();
+        loads(&second, &second_ms2, "the second generation");
+        assert!(manager.rollback(saved.source_path(), oldest).is_err(), "{}: a generation past history_generations was kept", label);
+    }
+}
+
+#[test]
+fn repair_rebuilds_only_the_damaged_shards() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 1000, seed: 59, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(3, 200, 0.0, 59);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap();
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let manager = saved.manager(&backend);
+    let data_file = |cache_type: &str| saved.files().unwrap().into_iter()
+        .find(|path| path.to_string_lossy().contains(cache_type))
+        .unwrap();
+    let (ms1_path, ms2_path) = (data_file(".ms1_indexed.cache."), data_file(".ms2_indexed.cache."));
+
+    // A flipped byte in the second MS1 shard, and the MS2 file gone
+    let mut bytes = std::fs::read(&ms1_path).unwrap();
+    let (_, header) = container_header(&bytes);
+    assert!(header["segments"].as_array().unwrap().len() > 2);
+    let offset = header["segments"][1]["columns"][0]["offset"].as_u64().unwrap() as usize;
+    bytes[offset] ^= 0xff;
+    std::fs::write(&ms1_path, &bytes).unwrap();
+    std::fs::remove_file(&ms2_path).unwrap();
+
+    let (ms1_partial, ms2_partial, errors) = manager.load_indexed_data_tolerant(saved.source_path()).unwrap();
+    let mut damaged: Vec<_> = errors.iter().map(|error| (error.file.clone(), error.segment)).collect();
+    damaged.sort();
+    assert_eq!(damaged, vec![(ms1_path.clone(), Some(1)), (ms2_path.clone(), None)]);
+    assert!(ms1_partial.mz_values.len() < ms1_indexed.mz_values.len() && !ms1_partial.mz_values.is_empty());
+    assert!(ms2_partial.is_empty());
+
+    let repaired = manager.repair_cache(saved.source_path(), |_| Ok((ms1_indexed.clone(), ms2_indexed_pairs.clone()))).unwrap();
+    assert_eq!(repaired, 2);
+    // The intact shards were copied as stored
+    let repaired_bytes = std::fs::read(&ms1_path).unwrap();
+    let (_, repaired_header) = container_header(&repaired_bytes);
+    assert_eq!(repaired_header["segments"][0], header["segments"][0]);
+    let (_, _, errors) = manager.load_indexed_data_tolerant(saved.source_path()).unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(manager.validate_layout(saved.source_path()).unwrap().is_valid());
+    let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(saved.source_path()).unwrap();
+    assert!(testing::same_data(&ms1_loaded, &ms1_indexed), "MS1 differs after the repair");
+    assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs), "MS2 differs after the repair");
+    assert_eq!(manager.repair_cache(saved.source_path(), |_| Err("nothing to repair".into())).unwrap(), 0);
+}
+
+#[test]
+fn identical_sources_share_one_content_addressed_cache() {
+    use std::time::{Duration, SystemTime};
+
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 500, seed: 61, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 100, 0.0, 61);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-raw").unwrap()
+        .configured(|config| config.content_addressed = true);
+    let saved = testing::Backend::scratch_in(&std::env::temp_dir()).unwrap();
+    let original = saved.source_path().to_path_buf();
+    let copy = original.with_file_name("copy.d");
+    // The same acquisition under two paths, older than the cache about to be written
+    for source in [&original, &copy] {
+        std::fs::create_dir_all(source).unwrap();
+        std::fs::write(source.join("analysis.tdf"), b"the same acquisition").unwrap();
+        for path in [source.join("analysis.tdf"), source.clone()] {
+            std::fs::File::open(path).unwrap().set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        }
+    }
+    let manager = saved.manager(&backend);
+    manager.save_indexed_data(&original, &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let files = saved.files().unwrap();
+    assert!(manager.is_cache_valid(&copy), "the copy doesn't find the cache of the original");
+    manager.save_indexed_data(&copy, &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let data_files = |files: &[std::path::PathBuf]| files.iter().filter(|path| path.to_string_lossy().contains(".cache.")).count();
+    assert_eq!(data_files(&saved.files().unwrap()), data_files(&files), "the copy got data files of its own");
+
+    // Clearing one path keeps the cache the other still uses
+    manager.clear_cache_for(&original).unwrap();
+    assert!(manager.trash_entries().unwrap().is_empty());
+    let (ms1_loaded, ms2_loaded) = manager.load_indexed_data(&copy).unwrap();
+    assert!(testing::same_data(&ms1_loaded, &ms1_indexed));
+    assert!(testing::same_windows(&ms2_loaded, &ms2_indexed_pairs));
+    manager.clear_cache_for(&copy).unwrap();
+    assert_eq!(manager.trash_entries().unwrap().len(), 1);
+    assert!(!manager.is_cache_valid(&copy));
+}
+
+fn shared_data(shared: &shm::SharedIndexedData) -> (IndexedTimsTOFData, Vec<(IsolationWindow, IndexedTimsTOFData)>) {
+    let windows = shared.ms2_windows().into_iter().map(|(window, columns)| (window, columns.to_indexed())).collect();
+    (shared.ms1().to_indexed(), windows)
+}
+
+// The fingerprint in a shared file's name, <source>.<fingerprint>.shm
+fn shared_fingerprint(shared: &shm::SharedIndexedData) -> String {
+    let name = shared.path().file_stem().unwrap().to_string_lossy().into_owned();
+    name.rsplit('.').next().unwrap().to_string()
+}
+
+#[test]
+fn load_shared_maps_one_checked_file_per_cache_version() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 800, seed: 67, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(3, 200, 0.0, 67);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap();
+    let saved = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let shm_dir = saved.cache_dir().join("shm");
+    let backend = backend.configured(|config| config.shared_memory_dir = Some(shm_dir.clone()));
+    let manager = saved.manager(&backend);
+
+    let first = manager.load_shared(saved.source_path()).unwrap();
+    let second = manager.load_shared(saved.source_path()).unwrap();
+    assert_eq!(first.path(), second.path());
+    assert_eq!(std::fs::read_dir(&shm_dir).unwrap().count(), 1);
+    let (ms1_shared, ms2_shared) = shared_data(&second);
+    assert!(testing::same_data(&ms1_shared, &ms1_indexed), "shared MS1 differs");
+    assert!(testing::same_windows(&ms2_shared, &ms2_indexed_pairs), "shared MS2 differs");
+
+    // A rebuilt cache gets a file of its own and the stale one is removed
+    let rebuilt = testing::indexed_data(&DataShape { points: 600, seed: 68, ..DataShape::default() });
+    manager.save_indexed_data(saved.source_path(), &rebuilt, &ms2_indexed_pairs).unwrap();
+    let current = manager.load_shared(saved.source_path()).unwrap();
+    assert_ne!(current.path(), first.path());
+    assert!(!first.path().exists(), "the stale shared file was kept");
+    assert!(testing::same_data(&shared_data(&current).0, &rebuilt));
+
+    // Damaged files are rewritten, not mapped
+    let path = current.path().to_path_buf();
+    let intact = std::fs::read(&path).unwrap();
+    drop((first, second, current));
+    let damaged: [Vec<u8>; 3] = [intact[..intact.len() / 2].to_vec(), intact[..10].to_vec(), b"garbage, not a shared file".to_vec()];
+    for bytes in damaged {
+        std::fs::write(&path, &bytes).unwrap();
+        let shared = manager.load_shared(saved.source_path()).unwrap();
+        assert!(testing::same_data(&shared_data(&shared).0, &rebuilt));
+        assert_eq!(std::fs::read(&path).unwrap(), intact);
+    }
+
+    // Header lengths and column ends that overflow are rejected: 2^62 rows of 4 bytes wrap to 0
+    let fingerprint = "0123456789abcdef";
+    let crafted = shm_dir.join("crafted.shm");
+    let header = serde_json::json!({
+        "fingerprint": fingerprint,
+        "datasets": [{ "isolation": null, "rows": 1u64 << 62, "offsets": [16, 16, 16, 16, 16, 16] }],
+    }).to_string();
+    for (header_len, header) in [(header.len() as u64, header.as_bytes()), (u64::MAX, header.as_bytes())] {
+        let mut bytes = b"TSHM0001".to_vec();
+        bytes.extend_from_slice(&header_len.to_le_bytes());
+        bytes.extend_from_slice(header);
+        bytes.resize(bytes.len().div_ceil(8) * 8 + 64, 0);
+        std::fs::write(&crafted, &bytes).unwrap();
+        assert!(shm::SharedIndexedData::open(&crafted, fingerprint, None).is_err(), "header length {} was mapped", header_len);
+    }
+}
+
+#[test]
+fn shared_files_are_signed_and_private() {
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 500, seed: 71, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 100, 0.0, 71);
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-raw").unwrap();
+    let key = SigningKey::new("roundtrip", b"roundtrip signing secret".to_vec());
+    let signed = backend.configured(|config| config.signing_key = Some(key.clone()));
+    let saved = signed.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let signed = signed.configured(|config| config.shared_memory_dir = Some(saved.cache_dir().join("shm")));
+    let manager = saved.manager(&signed);
+    let shared = manager.load_shared(saved.source_path()).unwrap();
+    let (path, fingerprint) = (shared.path().to_path_buf(), shared_fingerprint(&shared));
+    drop(shared);
+
+    // A file planted under the expected name, unsigned or signed with another key, is replaced
+    let planted = testing::indexed_data(&DataShape { points: 50, seed: 72, ..DataShape::default() });
+    let other_key = SigningKey::new("roundtrip", b"another secret".to_vec());
+    for planted_key in [None, Some(&other_key)] {
+        shm::write(&path, &fingerprint, planted_key, &planted, &[]).unwrap();
+        assert!(shm::SharedIndexedData::open(&path, &fingerprint, Some(&key)).is_err());
+        let shared = manager.load_shared(saved.source_path()).unwrap();
+        assert!(testing::same_data(&shared_data(&shared).0, &ms1_indexed), "a planted shared file was mapped");
+    }
+
+    // Signed caches whose files were modified are refused before any shared file is used
+    let metadata = saved.files().unwrap().into_iter().find(|path| path.extension().is_some_and(|ext| ext == "meta")).unwrap();
+    let original = std::fs::read_to_string(&metadata).unwrap();
+    std::fs::write(&metadata, original.replacen("compression", "compressi0n", 1)).unwrap();
+    assert!(manager.load_shared(saved.source_path()).is_err(), "tampered metadata was accepted");
+    std::fs::write(&metadata, original).unwrap();
+
+    // Decrypted copies of encrypted caches only go to a directory the caller chose
+    let encrypted = backend.encrypted(Some(EncryptionKey::new("roundtrip", [7; 32])));
+    let saved = encrypted.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    assert!(saved.manager(&encrypted).load_shared(saved.source_path()).is_err(), "decrypted data went to the default directory");
+    let encrypted = encrypted.configured(|config| config.shared_memory_dir = Some(saved.cache_dir().join("shm")));
+    let shared = saved.manager(&encrypted).load_shared(saved.source_path()).unwrap();
+    assert!(testing::same_data(&shared_data(&shared).0, &ms1_indexed));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(shared.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "shared file is readable by others");
+    }
+}
+
+// Points of `data` in m/z order, stable, so ties keep the order of `data`
+fn stable_by_mz(data: &IndexedTimsTOFData) -> IndexedTimsTOFData {
+    let mut order: Vec<usize> = (0..data.mz_values.len()).collect();
+    order.sort_by(|&a, &b| data.mz_values[a].total_cmp(&data.mz_values[b]));
+    let mut sorted = IndexedTimsTOFData::new();
+    for i in order {
+        sorted.rt_values_min.push(data.rt_values_min[i]);
+        sorted.mobility_values.push(data.mobility_values[i]);
+        sorted.mz_values.push(data.mz_values[i]);
+        sorted.intensity_values.push(data.intensity_values[i]);
+        sorted.frame_indices.push(data.frame_indices[i]);
+        sorted.scan_indices.push(data.scan_indices[i]);
+    }
+    sorted
+}
+
+#[test]
+fn streamed_saves_load_as_in_memory_saves_do() {
+    use timstof_cache_ffi::stream::TimsTOFPoint;
+
+    // One window takes most MS2 points, as in DIA with a wide window
+    let window_of = |point: &TimsTOFPoint| match point.scan % 10 {
+        0..=2 => None,
+        3 => Some(IsolationWindow::new(400.0, 425.0, 0)),
+        4 => Some(IsolationWindow::new(425.0, 450.0, 1)),
+        _ => Some(IsolationWindow::new(450.0, 1200.0, 2)),
+    };
+    // In frame order as a converter produces them, m/z rounded so many points tie
+    let generated = testing::indexed_data(&DataShape { points: 3000, seed: 73, ..DataShape::default() });
+    let mut points: Vec<TimsTOFPoint> = (0..generated.mz_values.len())
+        .map(|i| TimsTOFPoint {
+            rt_min: generated.rt_values_min[i],
+            mobility: generated.mobility_values[i],
+            mz: (generated.mz_values[i] * 2.0).round() / 2.0,
+            intensity: generated.intensity_values[i],
+            frame: generated.frame_indices[i],
+            scan: generated.scan_indices[i],
+        })
+        .collect();
+    points.sort_by_key(|point| point.frame);
+
+    // The same points in memory: each dataset in arrival order, windows in order of first appearance
+    let mut ms1_arrived = IndexedTimsTOFData::new();
+    let mut windows_arrived: Vec<(IsolationWindow, IndexedTimsTOFData)> = Vec::new();
+    for point in &points {
+        let data = match window_of(point) {
+            None => &mut ms1_arrived,
+            Some(window) => match windows_arrived.iter().position(|(known, _)| *known == window) {
+                Some(position) => &mut windows_arrived[position].1,
+                None => {
+                    windows_arrived.push((window.at(windows_arrived.len()), IndexedTimsTOFData::new()));
+                    &mut windows_arrived.last_mut().unwrap().1
+                }
+            },
+        };
+        data.rt_values_min.push(point.rt_min);
+        data.mobility_values.push(point.mobility);
+        data.mz_values.push(point.mz);
+        data.intensity_values.push(point.intensity);
+        data.frame_indices.push(point.frame);
+        data.scan_indices.push(point.scan);
+    }
+    let ms1_indexed = stable_by_mz(&ms1_arrived);
+    let ms2_indexed_pairs: Vec<_> = windows_arrived.iter().map(|(window, data)| (*window, stable_by_mz(data))).collect();
+    assert!(ms1_indexed.mz_values.windows(2).any(|pair| pair[0] == pair[1]), "no m/z ties to keep in order");
+
+    // A buffer of 50 points: dozens of spilled runs, and the big window in many segments
+    let backend = testing::backends().into_iter().find(|backend| backend.label() == "columnar-lz4").unwrap()
+        .configured(|config| config.stream_buffer_points = 50);
+    let in_memory = backend.save_in(&std::env::temp_dir(), &ms1_indexed, &ms2_indexed_pairs).unwrap();
+    let streamed = testing::Backend::scratch_in(&std::env::temp_dir()).unwrap();
+    let manager = streamed.manager(&backend);
+    manager.save_from_stream(streamed.source_path(), points.iter().copied(), window_of).unwrap();
+
+    let ms2_file = streamed.files().unwrap().into_iter().find(|path| path.to_string_lossy().contains(".ms2_indexed.cache.")).unwrap();
+    let (_, header) = container_header(&std::fs::read(ms2_file).unwrap());
+    let largest = ms2_indexed_pairs.iter().map(|(_, data)| data.mz_values.len()).max().unwrap();
+    assert!(header["segments"].as_array().unwrap().len() >= largest / 50, "MS2 windows were written whole");
+    let (ms1_streamed, ms2_streamed) = streamed.load(&backend).unwrap();
+    let (ms1_loaded, ms2_loaded) = in_memory.load(&backend).unwrap();
+    assert!(testing::same_data(&ms1_loaded, &ms1_indexed));
+    assert!(testing::same_data(&ms1_streamed, &ms1_loaded), "streamed MS1 differs");
+    assert!(testing::same_windows(&ms2_streamed, &ms2_loaded), "streamed MS2 differs");
+
+    // An empty stream saves an empty cache
+    let empty = testing::Backend::scratch_in(&std::env::temp_dir()).unwrap();
+    empty.manager(&backend).save_from_stream(empty.source_path(), std::iter::empty(), |_| None).unwrap();
+    let (ms1_empty, ms2_empty) = empty.load(&backend).unwrap();
+    assert!(ms1_empty.mz_values.is_empty() && ms2_empty.is_empty());
+}
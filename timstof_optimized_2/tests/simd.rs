@@ -0,0 +1,73 @@
+// The decode kernels of simd.rs against their scalar forms, bit for bit. Run with
+// --features simd to check the SSE2 prefix sum (CI does); without it both sides are
+// scalar. Lengths around the 4-lane width exercise the scalar remainder.
+use proptest::prelude::*;
+use timstof_cache_ffi::testing;
+
+// 0, a partial lane, exact lanes and lanes plus a remainder
+const LENGTHS: [usize; 14] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 15, 16, 17, 1027];
+
+fn le_bytes(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+// Fixed-point steps dequantized one value at a time
+fn fixed_to_f32_bytes_each(steps: &[u32], offset: f64, step: f64) -> Vec<u8> {
+    steps.iter().flat_map(|&q| testing::fixed_to_f32_bytes(&[q], offset, step)).collect()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // Deltas over the full u32 range, so the running sums wrap
+    #[test]
+    fn prefix_sums_match_the_scalar_scan(
+        deltas in prop::sample::select(LENGTHS.to_vec()).prop_flat_map(|len| prop::collection::vec(any::<u32>(), len)),
+    ) {
+        let stored = le_bytes(&deltas);
+        let sums = testing::prefix_sum_u32(&stored);
+        prop_assert_eq!(&sums, &testing::scalar_prefix_sum_u32(&stored));
+        prop_assert_eq!(
+            testing::fixed_to_f32_bytes(&sums, 100.0, 1e-5),
+            fixed_to_f32_bytes_each(&sums, 100.0, 1e-5)
+        );
+    }
+}
+
+#[test]
+fn prefix_sums_match_the_scalar_scan_at_every_short_length() {
+    for len in LENGTHS {
+        // Small deltas with u32::MAX in every lane position, carried across lanes
+        let deltas: Vec<u32> = (0..len as u32).map(|i| if i % 5 == 3 { u32::MAX } else { i * 7 + 1 }).collect();
+        let stored = le_bytes(&deltas);
+        let sums = testing::prefix_sum_u32(&stored);
+        assert_eq!(sums, testing::scalar_prefix_sum_u32(&stored), "{} values", len);
+        assert_eq!(sums.len(), len);
+        // u32::MAX steps decode to NaN, compared by bits
+        let mut steps = sums.clone();
+        if let Some(last) = steps.last_mut() {
+            *last = u32::MAX;
+        }
+        assert_eq!(testing::fixed_to_f32_bytes(&steps, 100.0, 1e-5), fixed_to_f32_bytes_each(&steps, 100.0, 1e-5), "{} values", len);
+    }
+    // A trailing partial value is ignored, not read past
+    assert_eq!(testing::prefix_sum_u32(&[1, 0, 0, 0, 9]), vec![1]);
+}
+
+#[test]
+fn log_intensities_decode_the_same_through_the_table() {
+    // Long enough for the table, with a remainder past the last full lane
+    let stored: Vec<u8> = (0..(1u32 << 16) + 5)
+        .flat_map(|i| (i.wrapping_mul(2_654_435_761) as u16).to_le_bytes())
+        .collect();
+    let scale = 4096.0;
+    let by_table = testing::log_u16_to_u32_bytes(&stored, scale);
+    // Pieces below the table threshold take the exp() per row path
+    let by_row: Vec<u8> = stored.chunks(2 * 1000).flat_map(|piece| testing::log_u16_to_u32_bytes(piece, scale)).collect();
+    assert_eq!(by_table.len(), stored.len() * 2);
+    assert!(by_table == by_row, "the table and per-row decodes differ");
+    for len in LENGTHS {
+        let piece = &stored[..len * 2];
+        assert_eq!(testing::log_u16_to_u32_bytes(piece, scale), by_row[..len * 4], "{} values", len);
+    }
+}
@@ -0,0 +1,76 @@
+// Metrics recorded through src/telemetry.rs reach the MeterProvider the host installed:
+// cargo test --features otel --test telemetry
+#![cfg(feature = "otel")]
+use std::sync::{Arc, Weak};
+
+use opentelemetry_sdk::metrics::data::{Histogram, ResourceMetrics, Sum};
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use opentelemetry_sdk::metrics::{InstrumentKind, ManualReader, MetricResult, Pipeline, SdkMeterProvider, Temporality};
+use opentelemetry_sdk::Resource;
+use timstof_cache_ffi::telemetry;
+use timstof_cache_ffi::testing::{self, DataShape};
+
+// The provider owns its reader; the test keeps a second handle to collect from
+#[derive(Debug, Clone)]
+struct SharedReader(Arc<ManualReader>);
+
+impl MetricReader for SharedReader {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.0.register_pipeline(pipeline)
+    }
+    fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+        self.0.collect(rm)
+    }
+    fn force_flush(&self) -> MetricResult<()> {
+        self.0.force_flush()
+    }
+    fn shutdown(&self) -> MetricResult<()> {
+        self.0.shutdown()
+    }
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.0.temporality(kind)
+    }
+}
+
+fn sum(metrics: &ResourceMetrics, name: &str) -> u64 {
+    metrics.scope_metrics.iter()
+        .flat_map(|scope| &scope.metrics)
+        .filter(|metric| metric.name == name)
+        .filter_map(|metric| metric.data.as_any().downcast_ref::<Sum<u64>>())
+        .flat_map(|sum| &sum.data_points)
+        .map(|point| point.value)
+        .sum()
+}
+
+fn histogram_count(metrics: &ResourceMetrics, name: &str) -> u64 {
+    metrics.scope_metrics.iter()
+        .flat_map(|scope| &scope.metrics)
+        .filter(|metric| metric.name == name)
+        .filter_map(|metric| metric.data.as_any().downcast_ref::<Histogram<f64>>())
+        .flat_map(|histogram| &histogram.data_points)
+        .map(|point| point.count)
+        .sum()
+}
+
+#[test]
+fn cache_operations_record_metrics() {
+    // Installed before the first cache operation, as the instruments are created once
+    let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+    let provider = SdkMeterProvider::builder().with_reader(reader.clone()).build();
+    opentelemetry::global::set_meter_provider(provider);
+
+    let ms1_indexed = testing::indexed_data(&DataShape { points: 600, seed: 17, ..DataShape::default() });
+    let ms2_indexed_pairs = testing::ms2_windows(2, 200, 0.0, 17);
+    let backend = &testing::backends()[0];
+    backend.round_trip(&ms1_indexed, &ms2_indexed_pairs)
+        .unwrap_or_else(|e| panic!("{}: round trip failed: {}", backend.label(), e));
+    telemetry::record_miss();
+
+    let mut metrics = ResourceMetrics { resource: Resource::empty(), scope_metrics: Vec::new() };
+    reader.collect(&mut metrics).unwrap();
+    assert!(sum(&metrics, "bytes_written") > 0, "{:?}", metrics);
+    assert_eq!(histogram_count(&metrics, "save_duration_seconds"), 1);
+    assert!(histogram_count(&metrics, "shard_load_duration") >= 1);
+    assert_eq!(sum(&metrics, "cache_hits"), 1);
+    assert_eq!(sum(&metrics, "cache_misses"), 1);
+}